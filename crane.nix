@@ -14,7 +14,8 @@ let
   nightlyVersion = "2023-05-01";
   rustNightly = pkgs.rust-bin.nightly.${nightlyVersion}.default.override {
     extensions = [ "rust-src" "rust-analyzer-preview" ];
-    targets = cargoTargets;
+    # The library also builds for browsers and WASI
+    targets = cargoTargets ++ [ "wasm32-unknown-unknown" "wasm32-wasi" ];
   };
 
   # For easy cross-compilation in devShells
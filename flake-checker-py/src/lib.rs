@@ -0,0 +1,244 @@
+//! Python bindings for flake-checker, as the `flake_checker` module: parse lockfiles and check
+//! their Nixpkgs inputs from Python.
+//!
+//! ```python
+//! import flake_checker
+//!
+//! lock = flake_checker.FlakeLock.load("flake.lock")
+//! report = flake_checker.check(lock, policy='{"max_days": 60}')
+//! for issue in report.issues:
+//!     print(issue.severity, issue.input, issue.message)
+//! ```
+
+use std::path::PathBuf;
+
+use flake_checker::{FlakeChecker, Lang, Node, Policy};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use serde_json::json;
+
+create_exception!(
+    flake_checker,
+    FlakeCheckerError,
+    PyException,
+    "A lockfile or policy that couldn't be read or checked."
+);
+
+fn error(err: impl std::fmt::Display) -> PyErr {
+    FlakeCheckerError::new_err(err.to_string())
+}
+
+// The equivalent of JSON in Python's own types.
+fn to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<Py<PyAny>> {
+    Ok(py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+/// A parsed flake.lock.
+#[pyclass(frozen)]
+struct FlakeLock(flake_checker::FlakeLock);
+
+#[pymethods]
+impl FlakeLock {
+    /// Parse a lockfile's contents.
+    #[new]
+    fn new(source: &str) -> PyResult<Self> {
+        source.parse().map(Self).map_err(error)
+    }
+
+    /// Read and parse a lockfile.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        flake_checker::FlakeLock::new(&path)
+            .map(Self)
+            .map_err(error)
+    }
+
+    /// The lockfile's format version.
+    #[getter]
+    fn version(&self) -> usize {
+        self.0.version
+    }
+
+    /// The root inputs by name, as dicts with their `type` and, for repositories, their `owner`
+    /// and `repo` (or, for Git and Mercurial inputs, their `url`), `ref`, `rev`, and
+    /// `last_modified` time.
+    fn inputs(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let inputs: serde_json::Map<String, serde_json::Value> = self
+            .0
+            .root
+            .iter()
+            .map(|(name, node)| (name.clone(), input(node)))
+            .collect();
+        to_python(py, &inputs.into())
+    }
+}
+
+fn input(node: &Node) -> serde_json::Value {
+    match node {
+        Node::Repo(repo) => json!({
+            "type": repo.locked.node_type,
+            "owner": repo.original.owner,
+            "repo": repo.original.repo,
+            "ref": repo.original.git_ref,
+            "rev": repo.locked.rev,
+            "last_modified": repo.locked.last_modified,
+        }),
+        Node::Indirect(indirect) => json!({ "type": indirect.locked.node_type }),
+        Node::Path(path) => json!({ "type": path.locked.node_type }),
+        Node::Tarball(tarball) => json!({ "type": tarball.locked.node_type }),
+        Node::Git(git) => json!({
+            "type": git.locked.node_type,
+            "url": git.original.url,
+            "ref": git.original.git_ref,
+            "rev": git.locked.rev,
+            "last_modified": git.locked.last_modified,
+        }),
+        Node::Mercurial(mercurial) => json!({
+            "type": mercurial.locked.node_type,
+            "url": mercurial.original.url,
+            "ref": mercurial.original.git_ref,
+            "rev": mercurial.locked.rev,
+            "last_modified": mercurial.locked.last_modified,
+        }),
+        Node::Root(_) => json!({ "type": "root" }),
+        Node::Fallthrough(value) => json!({ "type": value.pointer("/locked/type") }),
+    }
+}
+
+/// An issue found with a root input.
+#[pyclass(frozen, get_all)]
+struct Issue {
+    /// The name of the root input.
+    input: String,
+    /// The kind of issue, like `outdated`.
+    kind: String,
+    /// `warning` or `error`.
+    severity: String,
+    /// A description of the issue, in English.
+    message: String,
+    /// The issue's details, as written by `--format json`.
+    details: Py<PyAny>,
+    /// For inputs of other inputs, the names of the inputs along the way from the root.
+    provenance: Option<Vec<String>>,
+}
+
+#[pymethods]
+impl Issue {
+    fn __repr__(&self) -> String {
+        format!(
+            "Issue(input={:?}, kind={:?}, severity={:?})",
+            self.input, self.kind, self.severity
+        )
+    }
+}
+
+/// The outcome of checking a lockfile.
+#[pyclass(frozen, get_all)]
+struct CheckReport {
+    /// The issues found, sorted by input.
+    issues: Vec<Py<Issue>>,
+    /// Each way in which the errors exceed the policy's budget.
+    violations: Vec<String>,
+    /// Whether the lockfile passes: its errors (if any) are within the budget.
+    passed: bool,
+    /// The run's totals, as a dict of `inputs_checked`, `issues_by_kind`, `errors`, `warnings`,
+    /// and the `skipped` checks with the `reason` they didn't run.
+    stats: Py<PyAny>,
+}
+
+/// Check a lockfile's Nixpkgs inputs, with the overrides of a policy in the JSON format of
+/// policy files, if given. Plugins aren't run.
+#[pyfunction]
+#[pyo3(signature = (flake_lock, policy = None))]
+fn check(py: Python<'_>, flake_lock: &FlakeLock, policy: Option<&str>) -> PyResult<CheckReport> {
+    let mut builder = FlakeChecker::builder();
+    if let Some(policy) = policy {
+        builder = builder.with_policy(policy.parse::<Policy>().map_err(error)?);
+    }
+    let report = builder.run(&flake_lock.0).map_err(error)?;
+
+    let passed = report.passed();
+    let stats = to_python(py, &serde_json::to_value(&report.stats).map_err(error)?)?;
+    let issues = report
+        .issues
+        .into_iter()
+        .map(|issue| {
+            let details = serde_json::to_value(&issue.kind).map_err(error)?;
+            Py::new(
+                py,
+                Issue {
+                    kind: issue.kind.name().to_string(),
+                    severity: issue.severity.as_str().to_string(),
+                    message: issue.message(Lang::En),
+                    details: to_python(py, &details)?,
+                    provenance: issue.provenance,
+                    input: issue.input,
+                },
+            )
+        })
+        .collect::<PyResult<_>>()?;
+    Ok(CheckReport {
+        issues,
+        violations: report.violations,
+        passed,
+        stats,
+    })
+}
+
+#[pymodule]
+#[pyo3(name = "flake_checker")]
+fn flake_checker_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<FlakeLock>()?;
+    module.add_class::<Issue>()?;
+    module.add_class::<CheckReport>()?;
+    module.add_function(wrap_pyfunction!(check, module)?)?;
+    module.add(
+        "FlakeCheckerError",
+        module.py().get_type::<FlakeCheckerError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake_checker_py;
+
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_module() {
+        Python::attach(|py| {
+            let module = PyModule::new(py, "flake_checker").unwrap();
+            flake_checker_py(&module).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("flake_checker", module).unwrap();
+            py.run(
+                cr#"
+lock = flake_checker.FlakeLock.load("../tests/flake.dirty.0.lock")
+assert lock.inputs()["nixpkgs"]["owner"] == "bitcoin-miner-org"
+
+report = flake_checker.check(lock, policy='{"check_outdated": false, "fail_mode": true}')
+assert [issue.kind for issue in report.issues] == ["disallowed", "non-upstream"]
+assert report.issues[0].details["reference"] == "this-should-fail"
+assert report.issues[0].severity == "error" and not report.passed
+assert report.issues[0].provenance is None
+assert report.stats["errors"] == 2 and report.stats["skipped"] == [{"check": "max-age", "reason": "disabled"}]
+
+try:
+    flake_checker.check(lock, policy='{"unknown": true}')
+    assert False
+except flake_checker.FlakeCheckerError:
+    pass
+"#,
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+    }
+}
@@ -10,14 +10,18 @@
 //! [detsys]: https://determinate.systems
 //! [lock]: https://zero-to-nix.com/concepts/flakes#lockfile
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use serde::de::{self, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer};
 
+pub mod parse;
+pub mod source;
+
 /// A custom error type for the `parse-flake-lock` crate.
 #[derive(Debug, thiserror::Error)]
 pub enum FlakeLockParseError {
@@ -30,6 +34,48 @@ pub enum FlakeLockParseError {
     /// The specified `flake.lock` file couldn't be parsed as JSON.
     #[error("couldn't parse the flake.lock file as json: {0}")]
     Json(#[from] serde_json::Error),
+    /// The `flake.lock` contains merge conflict markers, on the given (1-based) lines.
+    #[error(
+        "the flake.lock file has unresolved merge conflicts (conflict markers on line{} {}); resolve them and re-run `nix flake lock` to make the lockfile consistent again",
+        if .0.len() == 1 { "" } else { "s" },
+        .0.iter().map(|line| line.to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    Conflict(Vec<usize>),
+    /// The `flake.lock` has a version that can't be parsed.
+    #[error(
+        "the flake.lock file has version {0}, but only versions {min} through {max} are supported",
+        min = parse::MIN_VERSION,
+        max = parse::MAX_VERSION
+    )]
+    UnsupportedVersion(u64),
+    /// The `flake.lock` couldn't be parsed at a position in its source.
+    #[error("{0}")]
+    Syntax(Box<parse::SyntaxError>),
+}
+
+impl FlakeLockParseError {
+    /// Name the lockfile that the error is in, for errors with a position in its source.
+    pub fn with_path(self, path: &Path) -> Self {
+        match self {
+            Self::Syntax(mut err) => {
+                err.path = Some(path.to_path_buf());
+                Self::Syntax(err)
+            }
+            err => err,
+        }
+    }
+}
+
+/// The (1-based) numbers of the lines in a `flake.lock` that are merge conflict markers, as left by
+/// Git when both sides of a merge change the lockfile.
+pub fn conflict_marker_lines(s: &str) -> Vec<usize> {
+    // Lines of valid JSON can't start with these, as strings can't span lines
+    const MARKERS: [&str; 4] = ["<<<<<<<", "|||||||", "=======", ">>>>>>>"];
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| MARKERS.iter().any(|marker| line.starts_with(marker)))
+        .map(|(i, _)| i + 1)
+        .collect()
 }
 
 /// A Rust representation of a Nix [`flake.lock`
@@ -41,6 +87,8 @@ pub struct FlakeLock {
     /// The `root` of the `flake.lock` with all input references resolved into the corresponding
     /// [Node]s represented by the `nodes` field.
     pub root: HashMap<String, Node>,
+    /// The names of the entries in `nodes` that each of the `root` inputs resolves to.
+    pub root_node_names: HashMap<String, String>,
     /// The version of the `flake.lock` (incremented whenever the `flake.nix` dependencies are
     /// updated).
     pub version: usize,
@@ -103,11 +151,18 @@ impl<'de> Deserialize<'de> for FlakeLock {
                     nodes.ok_or_else(|| de::Error::missing_field("nodes"))?;
                 let root: String = root.ok_or_else(|| de::Error::missing_field("root"))?;
                 let version: usize = version.ok_or_else(|| de::Error::missing_field("version"))?;
+                parse::check_version(version as u64).map_err(de::Error::custom)?;
 
                 let mut root_nodes = HashMap::new();
-                let root_node = &nodes[&root];
+                let mut root_node_names = HashMap::new();
+                let root_node = nodes
+                    .get(&root)
+                    .ok_or_else(|| de::Error::custom(format!("no root node named {root}")))?;
                 let Node::Root(root_node) = root_node else {
-                    return Err(de::Error::custom(format!("root node was not a Root node, but was a {} node", root_node.variant())));
+                    return Err(de::Error::custom(format!(
+                        "root node was not a Root node, but was a {} node",
+                        root_node.variant()
+                    )));
                 };
 
                 for (root_name, root_input) in root_node.inputs.iter() {
@@ -116,15 +171,21 @@ impl<'de> Deserialize<'de> for FlakeLock {
                         Input::List(keys) => keys.into(),
                     };
 
-                    let real_node = chase_input_node(&nodes, inputs).map_err(|e| {
-                        de::Error::custom(format!("failed to chase input {}: {:?}", root_name, e))
-                    })?;
+                    let (real_node_name, real_node) = chase_input_node(&nodes, &root, inputs)
+                        .map_err(|e| {
+                            de::Error::custom(format!(
+                                "failed to chase input {}: {:?}",
+                                root_name, e
+                            ))
+                        })?;
                     root_nodes.insert(root_name.clone(), real_node.clone());
+                    root_node_names.insert(root_name.clone(), real_node_name.to_string());
                 }
 
                 Ok(FlakeLock {
                     nodes,
                     root: root_nodes,
+                    root_node_names,
                     version,
                 })
             }
@@ -134,15 +195,26 @@ impl<'de> Deserialize<'de> for FlakeLock {
     }
 }
 
-fn chase_input_node(
-    nodes: &HashMap<String, Node>,
+// Follow a path of inputs from the root to the node that it refers to. An empty path (which Nix
+// writes for `follows = ""`) refers to the root itself.
+fn chase_input_node<'a>(
+    nodes: &'a HashMap<String, Node>,
+    root: &str,
     mut inputs: VecDeque<String>,
-) -> Result<&Node, FlakeLockParseError> {
+) -> Result<(&'a str, &'a Node), FlakeLockParseError> {
     let Some(next_input) = inputs.pop_front() else {
-        unreachable!("there should always be at least one input");
+        return nodes
+            .get_key_value(root)
+            .map(|(name, node)| (name.as_str(), node))
+            .ok_or_else(|| FlakeLockParseError::Invalid(format!("no root node named {root}")));
     };
 
-    let mut node = &nodes[&next_input];
+    let (mut node_name, mut node) = nodes
+        .get_key_value(&next_input)
+        .map(|(name, node)| (name.as_str(), node))
+        .ok_or_else(|| {
+            FlakeLockParseError::Invalid(format!("no node named {next_input} in the lock"))
+        })?;
     for input in inputs {
         let maybe_node_inputs = match node {
             Node::Root(_) => None,
@@ -150,6 +222,8 @@ fn chase_input_node(
             Node::Indirect(node) => node.inputs.to_owned(),
             Node::Path(node) => node.inputs.to_owned(),
             Node::Tarball(node) => node.inputs.to_owned(),
+            Node::Git(node) => node.inputs.to_owned(),
+            Node::Mercurial(node) => node.inputs.to_owned(),
             Node::Fallthrough(node) => match node.get("inputs") {
                 Some(node_inputs) => serde_json::from_value(node_inputs.clone())
                     .map_err(FlakeLockParseError::Json)?,
@@ -167,50 +241,137 @@ fn chase_input_node(
             }
         };
 
-        let next_inputs = &node_inputs[&input];
-        node = match next_inputs {
-            Input::String(s) => &nodes[s],
-            Input::List(inputs) => chase_input_node(nodes, inputs.to_owned().into())?,
+        let next_inputs = node_inputs.get(&input).ok_or_else(|| {
+            FlakeLockParseError::Invalid(format!("no input named {input} in node {node_name}"))
+        })?;
+        (node_name, node) = match next_inputs {
+            Input::String(s) => nodes
+                .get_key_value(s)
+                .map(|(name, node)| (name.as_str(), node))
+                .ok_or_else(|| {
+                    FlakeLockParseError::Invalid(format!("no node named {s} in the lock"))
+                })?,
+            Input::List(inputs) => chase_input_node(nodes, root, inputs.to_owned().into())?,
         };
     }
 
-    Ok(node)
+    Ok((node_name, node))
 }
 
 impl FlakeLock {
     /// Instantiate a new [FlakeLock] from the provided [Path].
     pub fn new(path: &Path) -> Result<Self, FlakeLockParseError> {
         let flake_lock_file = read_to_string(path)?;
-        let flake_lock: FlakeLock = serde_json::from_str(&flake_lock_file)?;
-        Ok(flake_lock)
+        flake_lock_file
+            .parse()
+            .map_err(|err: FlakeLockParseError| err.with_path(path))
+    }
+}
+
+impl FromStr for FlakeLock {
+    type Err = FlakeLockParseError;
+
+    /// Parse a [FlakeLock] from the contents of a `flake.lock` file of any
+    /// [supported](parse::check_version) version.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let conflicts = conflict_marker_lines(s);
+        if !conflicts.is_empty() {
+            return Err(FlakeLockParseError::Conflict(conflicts));
+        }
+        parse::parse(s)
     }
 }
 
-/// A flake input [node]. This enum represents two concrete node types, [RepoNode] and [RootNode],
-/// and uses the `Fallthrough` variant to capture node types that don't have explicitly defined
-/// structs in this library, representing them as raw [Value][serde_json::value::Value]s.
+/// A flake input [node], with a variant for each type of input by the `type` of its `original`
+/// attributes. Nodes of other types, and nodes that don't have the fields that their type should,
+/// are captured by the `Fallthrough` variant as raw [Value][serde_json::value::Value]s.
 ///
 /// [node]: https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-flake.html#lock-files
-#[derive(Clone, Debug, Deserialize)]
-#[serde(untagged)]
+#[derive(Clone, Debug)]
 pub enum Node {
     /// A [RootNode] specifying an [Input] map.
     Root(RootNode),
-    /// A [RepoNode] flake input for a [Git](https://git-scm.com) repository (or another version
-    /// control system).
+    /// A [RepoNode] flake input for a repository on a [Forge], like GitHub.
     Repo(Box<RepoNode>),
     /// An [IndirectNode] flake input stemming from an indirect flake reference like `inputs.nixpkgs.url =
     /// "nixpkgs";`.
     Indirect(IndirectNode),
     /// A [PathNode] flake input stemming from a filesystem path.
     Path(PathNode),
-    /// TODO
+    /// A [TarballNode] flake input stemming from a tarball (or file) URL.
     Tarball(TarballNode),
+    /// A [GitNode] flake input stemming from a [Git](https://git-scm.com) repository URL, like
+    /// `git+https://git.example.com/repo`.
+    Git(GitNode),
+    /// A [MercurialNode] flake input stemming from a [Mercurial](https://www.mercurial-scm.org)
+    /// repository URL, like `hg+https://hg.example.com/repo`.
+    Mercurial(MercurialNode),
     /// A "catch-all" variant for node types that don't (yet) have explicit struct definitions in
     /// this crate.
     Fallthrough(serde_json::value::Value), // Covers all other node types
 }
 
+impl Node {
+    /// Whether the input is a flake, which it is unless it's declared with `flake = false` (like
+    /// a source tree that's used as is). Non-flake inputs don't have inputs of their own.
+    pub fn is_flake(&self) -> bool {
+        let flake = match self {
+            Node::Root(_) => None,
+            Node::Repo(node) => Some(node.is_flake()),
+            Node::Indirect(node) => node.flake,
+            Node::Path(node) => node.flake,
+            Node::Tarball(node) => node.flake,
+            Node::Git(node) => node.flake,
+            Node::Mercurial(node) => node.flake,
+            Node::Fallthrough(value) => value.get("flake").and_then(|flake| flake.as_bool()),
+        };
+        flake.unwrap_or(true)
+    }
+
+    /// The fields of the node that this crate doesn't recognize, as paths like `locked.treeHash`,
+    /// in order. Fallthrough nodes keep all of their fields, so none of them count.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let sections: Vec<(&str, &BTreeMap<String, serde_json::Value>)> = match self {
+            Node::Root(node) => vec![("", &node.unknown)],
+            Node::Repo(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Indirect(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Path(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Tarball(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Git(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Mercurial(node) => vec![
+                ("", &node.unknown),
+                ("locked.", &node.locked.unknown),
+                ("original.", &node.original.unknown),
+            ],
+            Node::Fallthrough(_) => Vec::new(),
+        };
+        sections
+            .into_iter()
+            .flat_map(|(prefix, unknown)| unknown.keys().map(move |key| format!("{prefix}{key}")))
+            .collect()
+    }
+}
+
 // A string representation of the node variant (for logging).
 impl Node {
     fn variant(&self) -> &'static str {
@@ -220,11 +381,37 @@ impl Node {
             Node::Indirect(_) => "Indirect",
             Node::Path(_) => "Path",
             Node::Tarball(_) => "Tarball",
+            Node::Git(_) => "Git",
+            Node::Mercurial(_) => "Mercurial",
             Node::Fallthrough(_) => "Fallthrough", // Covers all other node types
         }
     }
 }
 
+/// Nodes are told apart by the `type` of their `original` attributes (as indirect inputs are locked
+/// to other types), and the root by its lack of them.
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let node = match value.pointer("/original/type").and_then(|t| t.as_str()) {
+            None if value.get("locked").is_none() => RootNode::deserialize(&value).map(Node::Root),
+            Some(node_type) if Forge::from_type(node_type).is_some() => {
+                RepoNode::deserialize(&value).map(|repo| Node::Repo(Box::new(repo)))
+            }
+            Some("indirect") => IndirectNode::deserialize(&value).map(Node::Indirect),
+            Some("path") => PathNode::deserialize(&value).map(Node::Path),
+            Some("tarball" | "file") => TarballNode::deserialize(&value).map(Node::Tarball),
+            Some("git") => GitNode::deserialize(&value).map(Node::Git),
+            Some("hg") => MercurialNode::deserialize(&value).map(Node::Mercurial),
+            _ => return Ok(Node::Fallthrough(value)),
+        };
+        Ok(node.unwrap_or(Node::Fallthrough(value)))
+    }
+}
+
 /// An enum type representing node input references.
 #[derive(Clone, Debug, Deserialize)]
 #[serde(untagged)]
@@ -237,16 +424,48 @@ pub enum Input {
 
 /// A flake [Node] representing a raw mapping of strings to [Input]s.
 #[derive(Clone, Debug, Deserialize)]
-#[serde(deny_unknown_fields)]
 pub struct RootNode {
     /// A mapping of the flake's input [Node]s.
     pub inputs: HashMap<String, Input>,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// The forges that [RepoNode]s can come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Forge {
+    /// [GitHub](https://github.com) (`github:owner/repo`).
+    GitHub,
+    /// [GitLab](https://gitlab.com) (`gitlab:owner/repo`).
+    GitLab,
+    /// [SourceHut](https://sr.ht) (`sourcehut:~owner/repo`).
+    SourceHut,
+}
+
+impl Forge {
+    /// The forge for a node `type`, like `github`, if it's one.
+    pub fn from_type(node_type: &str) -> Option<Self> {
+        match node_type {
+            "github" => Some(Self::GitHub),
+            "gitlab" => Some(Self::GitLab),
+            "sourcehut" => Some(Self::SourceHut),
+            _ => None,
+        }
+    }
+
+    /// The forge's default host, which inputs can override.
+    pub fn host(&self) -> &'static str {
+        match self {
+            Self::GitHub => "github.com",
+            Self::GitLab => "gitlab.com",
+            Self::SourceHut => "git.sr.ht",
+        }
+    }
 }
 
-/// A [Node] representing a [Git](https://git-scm.com) repository (or another version control
-/// system).
+/// A [Node] representing a repository on a [Forge].
 #[derive(Clone, Debug, Deserialize)]
-#[serde(deny_unknown_fields)]
 pub struct RepoNode {
     /// Whether the input is itself a flake.
     pub flake: Option<bool>,
@@ -256,6 +475,45 @@ pub struct RepoNode {
     pub locked: RepoLocked,
     /// The "original" (user-supplied) attributes of the repository input.
     pub original: RepoOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+impl RepoNode {
+    /// The forge that the repository is on.
+    pub fn forge(&self) -> Option<Forge> {
+        Forge::from_type(&self.original.node_type)
+    }
+
+    /// The host that the repository is on: the input's own `host`, or else the forge's default.
+    pub fn host(&self) -> &str {
+        self.original
+            .host
+            .as_deref()
+            .or(self.locked.host.as_deref())
+            .unwrap_or_else(|| self.forge().unwrap_or(Forge::GitHub).host())
+    }
+
+    /// The web URL of the repository, like `https://github.com/NixOS/nixpkgs`.
+    pub fn url(&self) -> String {
+        format!(
+            "https://{}/{}/{}",
+            self.host(),
+            self.original.owner,
+            self.original.repo
+        )
+    }
+
+    /// The subdirectory of the repository that the flake is in, if it isn't at the root.
+    pub fn dir(&self) -> Option<&str> {
+        self.original.dir.as_deref().or(self.locked.dir.as_deref())
+    }
+
+    /// Whether the repository is a flake, rather than an input with `flake = false`.
+    pub fn is_flake(&self) -> bool {
+        self.flake.unwrap_or(true)
+    }
 }
 
 /// Information about the repository input that's "locked" because it's supplied by Nix.
@@ -267,20 +525,31 @@ pub struct RepoLocked {
     /// The NAR hash of the input.
     #[serde(alias = "narHash")]
     pub nar_hash: String,
+    /// The subdirectory of the repository that the flake is in.
+    pub dir: Option<String>,
+    /// The host of the forge, if it isn't the forge's default.
+    pub host: Option<String>,
     /// The repository owner.
     pub owner: String,
     /// The repository.
     pub repo: String,
     /// The Git revision.
     pub rev: String,
-    /// The type of the node (either `"repo"` or `"indirect"`).
+    /// The type of the node (like `"github"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// The `original` field of a [Repo][Node::Repo] node.
 #[derive(Clone, Debug, Deserialize)]
 pub struct RepoOriginal {
+    /// The subdirectory of the repository that the flake is in.
+    pub dir: Option<String>,
+    /// The host of the forge, if it isn't the forge's default.
+    pub host: Option<String>,
     /// The repository owner.
     pub owner: String,
     /// The repository.
@@ -288,22 +557,31 @@ pub struct RepoOriginal {
     /// The Git reference of the input.
     #[serde(alias = "ref")]
     pub git_ref: Option<String>,
-    /// The type of the node (always `"repo"`).
+    /// The revision, if the input is pinned to one.
+    pub rev: Option<String>,
+    /// The type of the node (like `"github"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// An indirect flake input (using the [flake
 /// registry](https://nixos.org/manual/nix/stable/command-ref/conf-file.html#conf-flake-registry)).
 #[derive(Clone, Debug, Deserialize)]
-#[serde(deny_unknown_fields)]
 pub struct IndirectNode {
+    /// Whether the input is itself a flake.
+    pub flake: Option<bool>,
     /// The "locked" attributes of the input (set by Nix).
     pub locked: RepoLocked,
     /// The node's inputs.
     pub inputs: Option<HashMap<String, Input>>,
     /// The "original" (user-supplied) attributes of the indirect flake registry input.
     pub original: IndirectOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// The `original` field of an [Indirect][Node::Indirect] node.
@@ -312,21 +590,33 @@ pub struct IndirectOriginal {
     /// The ID of the input (recognized by the [flake
     /// registry]((https://nixos.org/manual/nix/stable/command-ref/conf-file.html#conf-flake-registry))).
     pub id: String,
+    /// The Git reference of the input.
+    #[serde(alias = "ref")]
+    pub git_ref: Option<String>,
+    /// The revision, if the input is pinned to one.
+    pub rev: Option<String>,
     /// The type of the node (always `"indirect"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// A flake input as a filesystem path, e.g. `inputs.local.url = "path:./subdir";`.
 #[derive(Clone, Debug, Deserialize)]
-#[serde(deny_unknown_fields)]
 pub struct PathNode {
+    /// Whether the input is itself a flake.
+    pub flake: Option<bool>,
     /// The "locked" attributes of the input (set by Nix).
     pub locked: PathLocked,
     /// The node's inputs.
     pub inputs: Option<HashMap<String, Input>>,
     /// The "original" (user-supplied) attributes of the path input.
     pub original: PathOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// Information about the path input that's "locked" because it's supplied by Nix.
@@ -343,6 +633,9 @@ pub struct PathLocked {
     /// The type of the node (always `"path"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// The user-supplied path input info.
@@ -356,30 +649,49 @@ pub struct PathOriginal {
     /// The type of the node (always `"path"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// A flake input as a tarball URL.
 #[derive(Clone, Debug, Deserialize)]
 pub struct TarballNode {
+    /// Whether the input is itself a flake.
+    pub flake: Option<bool>,
     /// The "locked" attributes of the input (set by Nix).
     pub locked: TarballLocked,
     /// The node's inputs.
     pub inputs: Option<HashMap<String, Input>>,
     /// The "original" (user-supplied) attributes of the tarball input.
     pub original: TarballOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// Information about the tarball input that's "locked" because it's supplied by Nix.
 #[derive(Clone, Debug, Deserialize)]
 pub struct TarballLocked {
+    /// The timestamp for when the input was last modified.
+    #[serde(alias = "lastModified")]
+    pub last_modified: Option<i64>,
     /// The NAR hash of the input.
     #[serde(alias = "narHash")]
     pub nar_hash: String,
+    /// The revision, for tarballs of repositories.
+    pub rev: Option<String>,
+    /// The number of ancestors of the revision, for tarballs of repositories.
+    #[serde(alias = "revCount")]
+    pub rev_count: Option<u64>,
     /// The type of the node (always `"tarball"`).
     #[serde(alias = "type")]
     pub node_type: String,
     /// The URL used to fetch the tarball.
     pub url: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
 
 /// The user-supplied tarball input info.
@@ -387,7 +699,100 @@ pub struct TarballLocked {
 pub struct TarballOriginal {
     /// The URL for the tarball input.
     pub url: String,
-    /// The type of the node (always `"tarball"`).
+    /// The type of the node (`"tarball"` or `"file"`).
+    #[serde(alias = "type")]
+    pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// A flake input as a Git repository URL.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitNode {
+    /// Whether the input is itself a flake.
+    pub flake: Option<bool>,
+    /// The node's inputs.
+    pub inputs: Option<HashMap<String, Input>>,
+    /// The "locked" attributes of the input (set by Nix).
+    pub locked: VcsLocked,
+    /// The "original" (user-supplied) attributes of the Git input.
+    pub original: VcsOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// A flake input as a Mercurial repository URL.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MercurialNode {
+    /// Whether the input is itself a flake.
+    pub flake: Option<bool>,
+    /// The node's inputs.
+    pub inputs: Option<HashMap<String, Input>>,
+    /// The "locked" attributes of the input (set by Nix).
+    pub locked: VcsLocked,
+    /// The "original" (user-supplied) attributes of the Mercurial input.
+    pub original: VcsOriginal,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// Information about a [Git][Node::Git] or [Mercurial][Node::Mercurial] input that's "locked"
+/// because it's supplied by Nix.
+#[derive(Clone, Debug, Deserialize)]
+pub struct VcsLocked {
+    /// The timestamp for when the input was last modified.
+    #[serde(alias = "lastModified")]
+    pub last_modified: i64,
+    /// The NAR hash of the input.
+    #[serde(alias = "narHash")]
+    pub nar_hash: String,
+    /// The branch (or Mercurial bookmark) of the input.
+    #[serde(alias = "ref")]
+    pub git_ref: Option<String>,
+    /// The revision, unless the input is a working tree with uncommitted changes.
+    pub rev: Option<String>,
+    /// The number of ancestors of the revision.
+    #[serde(alias = "revCount")]
+    pub rev_count: Option<u64>,
+    /// Whether the input includes the repository's Git submodules.
+    pub submodules: Option<bool>,
+    /// The subdirectory of the repository that the flake is in.
+    pub dir: Option<String>,
+    /// The URL of the repository.
+    pub url: String,
+    /// Whether the input is a shallow clone.
+    pub shallow: Option<bool>,
+    /// The type of the node (`"git"` or `"hg"`).
+    #[serde(alias = "type")]
+    pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+/// The user-supplied [Git][Node::Git] or [Mercurial][Node::Mercurial] input info.
+#[derive(Clone, Debug, Deserialize)]
+pub struct VcsOriginal {
+    /// The URL of the repository.
+    pub url: String,
+    /// The branch (or Mercurial bookmark) of the input.
+    #[serde(alias = "ref")]
+    pub git_ref: Option<String>,
+    /// The revision, if the input is pinned to one.
+    pub rev: Option<String>,
+    /// Whether the input includes the repository's Git submodules.
+    pub submodules: Option<bool>,
+    /// Whether the input is a shallow clone.
+    pub shallow: Option<bool>,
+    /// The subdirectory of the repository that the flake is in.
+    pub dir: Option<String>,
+    /// The type of the node (`"git"` or `"hg"`).
     #[serde(alias = "type")]
     pub node_type: String,
+    /// Fields that this crate doesn't recognize, like attributes added by newer versions of Nix.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
 }
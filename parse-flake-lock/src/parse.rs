@@ -0,0 +1,140 @@
+//! Reading each supported version of `flake.lock` into the current shape.
+//!
+//! Nix has written lockfiles as a graph of `nodes` since version 5, and reads versions
+//! [MIN_VERSION] through [MAX_VERSION]. Version 5 lockfiles can keep some of a node's locked
+//! attributes (like `lastModified` and `narHash`) in a separate `info` attribute, which is merged
+//! into `locked` as Nix does; versions 6 and 7 have the same shape. Older lockfiles have a
+//! different structure entirely, and newer ones may, so they're rejected rather than misread.
+
+use crate::{FlakeLock, FlakeLockParseError};
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+/// The oldest lockfile version that can be parsed.
+pub const MIN_VERSION: u64 = 5;
+/// The newest lockfile version that can be parsed.
+pub const MAX_VERSION: u64 = 7;
+
+/// Check that a lockfile version can be parsed.
+pub fn check_version(version: u64) -> Result<(), FlakeLockParseError> {
+    if (MIN_VERSION..=MAX_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(FlakeLockParseError::UnsupportedVersion(version))
+    }
+}
+
+/// A `flake.lock` that couldn't be parsed at a position in its source, which is shown like
+/// `flake.lock:142:7 expected value` followed by the lines around it.
+#[derive(Debug)]
+pub struct SyntaxError {
+    /// The lockfile's path, if it's known.
+    pub path: Option<PathBuf>,
+    /// The 1-based line.
+    pub line: usize,
+    /// The 1-based column.
+    pub column: usize,
+    /// What's wrong, without the position.
+    pub message: String,
+    /// A few numbered lines of the source around the position, which is marked.
+    pub snippet: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = match &self.path {
+            Some(path) => path.display().to_string(),
+            None => String::from("flake.lock"),
+        };
+        write!(
+            f,
+            "{path}:{}:{} {}\n{}",
+            self.line, self.column, self.message, self.snippet
+        )
+    }
+}
+
+impl SyntaxError {
+    // The error's position and the source around it, if serde_json knows where it is.
+    fn new(source: &str, err: serde_json::Error) -> Result<Self, serde_json::Error> {
+        let (line, column) = (err.line(), err.column());
+        if line == 0 {
+            return Err(err);
+        }
+        let message = err.to_string();
+        let message = message
+            .strip_suffix(&format!(" at line {line} column {column}"))
+            .unwrap_or(&message)
+            .to_string();
+        Ok(Self {
+            path: None,
+            line,
+            column,
+            message,
+            snippet: snippet(source, line, column),
+        })
+    }
+}
+
+// The lines around a position, numbered, with a `>` marker on its line and a caret under its
+// column.
+fn snippet(source: &str, line: usize, column: usize) -> String {
+    const CONTEXT: usize = 2;
+    let first = line.saturating_sub(CONTEXT).max(1);
+    let width = (line + CONTEXT).to_string().len();
+    let mut lines = Vec::new();
+    for (number, text) in source.lines().enumerate().map(|(i, text)| (i + 1, text)) {
+        if number < first || number > line + CONTEXT {
+            continue;
+        }
+        let marker = if number == line { '>' } else { ' ' };
+        lines.push(format!("{marker} {number:>width$} | {text}"));
+        if number == line {
+            let padding = " ".repeat(column.saturating_sub(1));
+            lines.push(format!("  {:>width$} | {padding}^", ""));
+        }
+    }
+    lines.join("\n")
+}
+
+// Errors that serde_json can place in the source carry their position and the lines around it.
+fn positioned(source: &str, err: serde_json::Error) -> FlakeLockParseError {
+    match SyntaxError::new(source, err) {
+        Ok(err) => FlakeLockParseError::Syntax(Box::new(err)),
+        Err(err) => FlakeLockParseError::Json(err),
+    }
+}
+
+/// Parse the contents of a `flake.lock` file of any supported version.
+pub(crate) fn parse(s: &str) -> Result<FlakeLock, FlakeLockParseError> {
+    let mut lock: Value = serde_json::from_str(s).map_err(|err| positioned(s, err))?;
+    let version = lock
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| FlakeLockParseError::Invalid(String::from("the lockfile has no version")))?;
+    check_version(version)?;
+    if version == 5 {
+        merge_info(&mut lock);
+        return Ok(serde_json::from_value(lock)?);
+    }
+    // Other versions are read from the source, so that errors have positions
+    serde_json::from_str(s).map_err(|err| positioned(s, err))
+}
+
+// Move the attributes of each node's `info` into its `locked` attributes, taking precedence.
+fn merge_info(lock: &mut Value) {
+    let Some(nodes) = lock.get_mut("nodes").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for node in nodes.values_mut().filter_map(Value::as_object_mut) {
+        let Some(Value::Object(info)) = node.remove("info") else {
+            continue;
+        };
+        if let Some(Value::Object(locked)) = node.get_mut("locked") {
+            locked.extend(info);
+        }
+    }
+}
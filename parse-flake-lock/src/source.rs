@@ -0,0 +1,203 @@
+//! Source locations for the values in a `flake.lock` file.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::FlakeLockParseError;
+
+/// A map from [JSON Pointers](https://www.rfc-editor.org/rfc/rfc6901) (like
+/// `/nodes/nixpkgs/original/ref`) to the byte ranges of the corresponding values in the source
+/// text of a `flake.lock` file.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    spans: HashMap<String, Range<usize>>,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    /// Scan the provided JSON source text and record the span of every value in it.
+    pub fn new(source: &str) -> Result<Self, FlakeLockParseError> {
+        let mut scanner = Scanner {
+            bytes: source.as_bytes(),
+            pos: 0,
+            spans: HashMap::new(),
+        };
+        scanner.skip_whitespace();
+        scanner.value(String::new())?;
+
+        let line_starts = std::iter::once(0)
+            .chain(
+                source
+                    .bytes()
+                    .enumerate()
+                    .filter(|(_, b)| *b == b'\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+
+        Ok(Self {
+            spans: scanner.spans,
+            line_starts,
+        })
+    }
+
+    /// The byte range of the value at the given JSON Pointer, if present.
+    pub fn span(&self, pointer: &str) -> Option<Range<usize>> {
+        self.spans.get(pointer).cloned()
+    }
+
+    /// The 1-based line and column of the given byte offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    /// The 1-based line range covered by the value at the given JSON Pointer, if present.
+    pub fn lines(&self, pointer: &str) -> Option<Range<usize>> {
+        let span = self.span(pointer)?;
+        let (start, _) = self.line_col(span.start);
+        let (end, _) = self.line_col(span.end.saturating_sub(1).max(span.start));
+        Some(start..end + 1)
+    }
+}
+
+/// Escape a key for use as a JSON Pointer reference token.
+pub fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    spans: HashMap<String, Range<usize>>,
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, expected: &str) -> FlakeLockParseError {
+        FlakeLockParseError::Invalid(format!(
+            "expected {expected} at byte offset {} while mapping source locations",
+            self.pos
+        ))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\n' | b'\r' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), FlakeLockParseError> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", byte as char)))
+        }
+    }
+
+    fn value(&mut self, pointer: String) -> Result<(), FlakeLockParseError> {
+        let start = self.pos;
+        match self.peek() {
+            Some(b'{') => self.object(&pointer)?,
+            Some(b'[') => self.array(&pointer)?,
+            Some(b'"') => {
+                self.string()?;
+            }
+            Some(_) => self.literal()?,
+            None => return Err(self.error("a value")),
+        }
+        self.spans.insert(pointer, start..self.pos);
+        Ok(())
+    }
+
+    fn object(&mut self, pointer: &str) -> Result<(), FlakeLockParseError> {
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            self.value(format!("{pointer}/{}", escape_pointer_token(&key)))?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error("`,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self, pointer: &str) -> Result<(), FlakeLockParseError> {
+        self.expect(b'[')?;
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(());
+        }
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            self.value(format!("{pointer}/{index}"))?;
+            index += 1;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error("`,` or `]`")),
+            }
+        }
+    }
+
+    // Scans a string and returns its contents, decoded well enough to be used as a key.
+    fn string(&mut self) -> Result<String, FlakeLockParseError> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("`\"`")),
+            }
+        }
+        let raw = std::str::from_utf8(&self.bytes[start..self.pos])
+            .map_err(|_| self.error("a UTF-8 string"))?;
+        Ok(serde_json::from_str(raw)?)
+    }
+
+    fn literal(&mut self) -> Result<(), FlakeLockParseError> {
+        let start = self.pos;
+        while matches!(
+            self.peek(),
+            Some(b'a'..=b'z' | b'0'..=b'9' | b'-' | b'+' | b'.' | b'E')
+        ) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("a value"));
+        }
+        Ok(())
+    }
+}
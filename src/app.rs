@@ -0,0 +1,1213 @@
+//! The command-line interface, which parses the arguments and runs the requested command.
+
+use crate::baseline::Baseline;
+use crate::budget::Budget;
+use crate::cli::{
+    AuditLogAction, BaselineAction, CheckArgs, Cli, Command, HookAction, PolicyAction,
+};
+use crate::error::FlakeCheckerError;
+use crate::filter::IssueFilter;
+use crate::flake::{check_flake_lock_with, sort_issues, FlakeCheckConfig};
+use crate::http::NetworkConfig;
+use crate::i18n::Lang;
+use crate::issue::{Issue, IssueKind, Missing, Severity};
+use crate::lockfile::{InputFormat, Lockfile, MissingMode, Remote, EMPTY_FLAKE_LOCK};
+use crate::summary::{issue_json_line, OutputFormat, Report, Summary, SCHEMA};
+use crate::template_dir::TemplateDir;
+use crate::tui;
+use crate::{
+    audit, bitbucket, canonical, checks, compare, conflict, daemon, diff, discover, doctor,
+    exemption, explain, fields, fix, flake_nix, forecast, gitea, github_app, grace, hook, list,
+    lsp, org, otel, plugin, policy, pull_request, registry, serve, stats, teams, telemetry, watch,
+    why,
+};
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use parse_flake_lock::source::SourceMap;
+use parse_flake_lock::{FlakeLock, FlakeLockParseError};
+
+/// Run the command that the command-line arguments select, printing any error.
+pub fn main() -> ExitCode {
+    // Exit quietly when stdout is closed early, like when piping into `head`, rather than
+    // panicking on the failed write
+    #[cfg(unix)]
+    // SAFETY: this runs first thing in `main`, before any other threads exist, and SIG_DFL is a
+    // valid disposition for SIGPIPE
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+
+    match run() {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            if let FlakeCheckerError::FlakeLock(FlakeLockParseError::Conflict(_)) = err {
+                eprintln!(
+                    "(flake-checker --resolve ours|theirs|regenerate can resolve them for you)"
+                );
+            }
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, FlakeCheckerError> {
+    let Cli { command, check } = Cli::parse();
+
+    match command {
+        None => run_check(check),
+        Some(Command::Check(check)) => run_check(*check),
+        Some(Command::Report {
+            report_format,
+            mut check,
+        }) => {
+            check.format = Some(report_format);
+            run_check(*check)
+        }
+        Some(Command::Fix { mut check }) => {
+            check.fix = true;
+            run_check(*check)
+        }
+        Some(Command::Diff {
+            old,
+            new,
+            policy,
+            lang,
+        }) => run_diff(old, new, &policy.config()?, lang),
+        Some(Command::Hook {
+            action: HookAction::Install { force },
+        }) => {
+            let hook_path = hook::install(force)?;
+            println!("installed pre-commit hook at {:?}", hook_path);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Baseline {
+            action: BaselineAction::Create { output, mut check },
+        }) => {
+            check.baseline = Some(output);
+            check.write_baseline = true;
+            run_check(*check)
+        }
+        Some(Command::Policy {
+            action:
+                PolicyAction::FromLock {
+                    flake_lock_paths,
+                    output,
+                    policy,
+                },
+        }) => {
+            let flake_locks = flake_lock_paths
+                .into_iter()
+                .map(read_flake_lock)
+                .collect::<Result<Vec<_>, _>>()?;
+            let generated = policy::from_locks(&flake_locks, &policy.config()?)?;
+            let generated = serde_json::to_string_pretty(&generated)? + "\n";
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, generated)?;
+                    println!("wrote the policy to {:?}", path);
+                }
+                None => print!("{generated}"),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Policy {
+            action:
+                PolicyAction::Show {
+                    flake_lock_path,
+                    recursive,
+                    policy,
+                },
+        }) => {
+            let shown = show_policy(&flake_lock_path, recursive.as_deref(), &policy.config()?)?;
+            println!("{}", serde_json::to_string_pretty(&shown)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::AuditLog {
+            action: AuditLogAction::Verify { path },
+        }) => match audit::verify(&std::fs::read_to_string(&path)?) {
+            Ok(records) => {
+                println!(
+                    "{}: {records} record(s), none edited or removed",
+                    path.display()
+                );
+                Ok(ExitCode::SUCCESS)
+            }
+            Err(problem) => {
+                println!("{}: {problem}", path.display());
+                Ok(ExitCode::FAILURE)
+            }
+        },
+        Some(Command::Forecast {
+            flake_lock_path,
+            policy,
+            format,
+        }) => {
+            let forecasts =
+                forecast::forecast(&read_flake_lock(flake_lock_path)?, &policy.config()?)?;
+            print!("{}", forecast::generate(&forecasts, format)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Stats {
+            flake_lock_path,
+            format,
+        }) => {
+            let stats = stats::stats(&read_flake_lock(flake_lock_path)?);
+            print!("{}", stats::generate(&stats, format)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Schema) => {
+            print!("{SCHEMA}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Why {
+            node,
+            flake_lock_path,
+        }) => {
+            let flake_lock = read_flake_lock(flake_lock_path)?;
+            let chains = why::why(&flake_lock, &node)?;
+            print!("{}", why::explain(&flake_lock, &node, &chains));
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::List {
+            flake_lock_path,
+            node_type,
+            owner,
+            older_than,
+            format,
+        }) => {
+            let query = list::Query {
+                node_type,
+                owner,
+                older_than_days: older_than,
+            };
+            let nodes = list::list(&read_flake_lock(flake_lock_path)?, &query);
+            print!("{}", list::generate(&nodes, format)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Registry {
+            paths,
+            allowed_owners,
+            allowed_types,
+            allowed_hosts,
+            fail_mode,
+        }) => {
+            let paths = if paths.is_empty() {
+                registry::default_paths()
+            } else {
+                paths
+            };
+            if paths.is_empty() {
+                println!("no flake registries found");
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let policy = registry::RegistryPolicy {
+                allowed_owners,
+                allowed_types,
+                allowed_hosts,
+            };
+            let mut num_issues = 0;
+            for path in paths {
+                let issues = registry::check(&std::fs::read_to_string(&path)?, &policy)?;
+                if issues.is_empty() {
+                    println!("{}: no issues found", path.display());
+                }
+                for issue in issues.iter() {
+                    println!("{}: {issue}", path.display());
+                }
+                num_issues += issues.len();
+            }
+
+            Ok(if fail_mode && num_issues > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            })
+        }
+        Some(Command::Doctor {
+            flake_lock_path,
+            policy,
+            no_telemetry,
+            offline,
+        }) => {
+            let config = policy.config()?;
+            let diagnoses = doctor::diagnose(&doctor::Environment {
+                flake_lock_path: &flake_lock_path,
+                network: &config.network,
+                telemetry: !no_telemetry,
+                offline,
+            });
+            for diagnosis in diagnoses.iter() {
+                println!("{diagnosis}");
+            }
+
+            let failed = diagnoses
+                .iter()
+                .any(|diagnosis| diagnosis.status == doctor::Status::Failure);
+            Ok(if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            })
+        }
+        Some(Command::Daemon {
+            mut flake_lock_paths,
+            remotes,
+            interval,
+            webhook_url,
+            metrics_file,
+            policy,
+            lang,
+        }) => {
+            if flake_lock_paths.is_empty() && remotes.is_empty() {
+                flake_lock_paths.push(PathBuf::from("flake.lock"));
+            }
+            let mut lockfiles: Vec<Lockfile> = flake_lock_paths
+                .into_iter()
+                .map(Lockfile::from_path)
+                .collect();
+            for remote in remotes.iter() {
+                lockfiles.push(Lockfile::Remote(Remote::parse(remote)?));
+            }
+
+            let sinks = daemon::Sinks {
+                webhook_url,
+                metrics_file,
+            };
+            daemon::run(lockfiles, policy.config()?, lang, interval, sinks)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Serve {
+            listen,
+            policy,
+            lang,
+        }) => {
+            serve::serve(&listen, policy.config()?, lang)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::GithubApp {
+            listen,
+            app_id,
+            private_key,
+            webhook_secret,
+            policy,
+            lang,
+        }) => {
+            github_app::GitHubApp::new(
+                app_id,
+                &private_key,
+                &webhook_secret,
+                policy.config()?,
+                lang,
+            )?
+            .serve(&listen)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Lsp { policy, lang }) => {
+            lsp::serve(policy.config()?, lang)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Org {
+            org,
+            policy,
+            lang,
+            format,
+            max_issues,
+        }) => {
+            let report = org::scan(&org, &policy.config()?, lang, max_issues)?;
+            match format {
+                Some(format) => report.generate(format)?,
+                None => org::print_ranking(&report),
+            }
+            if report.num_issues() > 0 && report.severity() == Severity::Error {
+                return Ok(ExitCode::FAILURE);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn run_check(args: CheckArgs) -> Result<ExitCode, FlakeCheckerError> {
+    let mut flake_check_config = args.policy.base_config();
+    args.policy.select(&mut flake_check_config);
+    let networked = networked_options(&args);
+    let CheckArgs {
+        policy,
+        mut no_telemetry,
+        offline,
+        ignore_missing_flake_lock,
+        ignore_missing,
+        mut flake_lock_paths,
+        format_in,
+        recursive,
+        glob_patterns,
+        remotes,
+        resolve,
+        fix,
+        fix_engine,
+        fix_flake_nix,
+        create_pr,
+        pr_branch,
+        suggest,
+        fix_format,
+        git_ref,
+        new_input_grace_days,
+        previous_lock,
+        resolve_indirect,
+        registry_paths,
+        watch,
+        tui,
+        explain,
+        verbose,
+        list_checks,
+        baseline,
+        write_baseline,
+        markdown_summary,
+        format,
+        lang,
+        bitbucket_report,
+        bitbucket_token,
+        set_commit_status,
+        gitea_token,
+        commit_status_target_url,
+        teams_webhook_url,
+        teams_min_severity,
+        only_kind,
+        min_severity,
+        input_patterns,
+        max_issues,
+        top,
+        group_by_input,
+        template_dir,
+        max_allowed_issues,
+        max_allowed_issues_per_kind,
+        audit_log,
+        compare_to,
+    } = args;
+
+    // The nearest policy file governing each discovered lockfile
+    let mut policy_paths: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+    if let Some(dir) = recursive {
+        let found = discover::find_flake_locks(&dir)?;
+        if found.is_empty() {
+            println!("no flake lockfiles found under {:?}", dir);
+        }
+        for path in found.iter() {
+            if let Some(policy_path) = policy::governing(path, Some(&dir)) {
+                policy_paths.insert(path.clone(), policy_path);
+            }
+        }
+        flake_lock_paths.extend(found);
+    } else if !glob_patterns.is_empty() {
+        let found = discover::find_flake_locks_by_glob(&glob_patterns)?;
+        if found.is_empty() {
+            println!("no flake lockfiles match {}", glob_patterns.join(", "));
+        }
+        flake_lock_paths.extend(found);
+    } else if flake_lock_paths.is_empty() && remotes.is_empty() {
+        flake_lock_paths.push(PathBuf::from(format_in.default_path()));
+    }
+
+    // Policy files can register checks of their own, which --only and --skip can name too
+    let mut known_checks = flake_check_config.checks.clone();
+    for policy_path in policy_paths.values().collect::<BTreeSet<_>>() {
+        let policy_config = policy::Policy::load(policy_path)?.apply(&flake_check_config);
+        known_checks.merge(&policy_config.checks);
+    }
+    if list_checks {
+        let listed = FlakeCheckConfig {
+            checks: known_checks,
+            ..flake_check_config
+        };
+        print!("{}", checks::list(&listed));
+        return Ok(ExitCode::SUCCESS);
+    }
+    checks::require_known(&known_checks, &policy.only, &policy.skip)?;
+
+    flake_check_config.budget = Budget {
+        max_issues: max_allowed_issues,
+        per_kind: max_allowed_issues_per_kind.into_iter().collect(),
+    };
+    if let Some(dir) = template_dir {
+        flake_check_config.template_dir = TemplateDir::load(&dir)?;
+    }
+
+    if offline {
+        if !networked.is_empty() {
+            return Err(FlakeCheckerError::Offline(networked.join(", ")));
+        }
+        checks::require_offline(&flake_check_config)?;
+        no_telemetry = true;
+    }
+
+    let registries = if resolve_indirect {
+        let paths = if registry_paths.is_empty() {
+            registry::default_paths()
+        } else {
+            registry_paths
+        };
+        let network = (!offline).then_some(&flake_check_config.network);
+        Some(registry::Registries::load(&paths, network)?)
+    } else {
+        None
+    };
+
+    let issue_filter = IssueFilter {
+        kinds: only_kind,
+        min_severity,
+        input_patterns,
+    };
+
+    let mut lockfiles: Vec<Lockfile> = flake_lock_paths
+        .into_iter()
+        .map(Lockfile::from_path)
+        .map(|lockfile| match &git_ref {
+            Some(rev) => lockfile.at_git_rev(rev),
+            None => lockfile,
+        })
+        .collect();
+    for remote in remotes.iter() {
+        lockfiles.push(Lockfile::Remote(Remote::parse(remote)?));
+    }
+
+    if watch {
+        let paths: Vec<PathBuf> = lockfiles
+            .iter()
+            .filter_map(|lockfile| match lockfile {
+                Lockfile::Path(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+        if paths.is_empty() {
+            println!("no local lockfiles to watch");
+            return Ok(ExitCode::FAILURE);
+        }
+        return watch::watch(paths);
+    }
+
+    let known = match &baseline {
+        Some(path) if !write_baseline => Baseline::load(path)?,
+        _ => Baseline::default(),
+    };
+    let mut new_baseline = Baseline::default();
+    let previous_results = match &compare_to {
+        Some(path) => Some(compare::PreviousResults::load(path)?),
+        None => None,
+    };
+
+    let stream_jsonl = matches!(format, Some(OutputFormat::Jsonl));
+    let otel_exporter = otel::OtelExporter::from_env();
+
+    let mut summaries = Vec::new();
+    let mut all_issues = Vec::new();
+    let mut missing_flake_lock = false;
+    let mut audited = Vec::new();
+    let mut fixed_lockfiles = Vec::new();
+
+    for lockfile in lockfiles {
+        let flake_lock_path = lockfile.display_path();
+        let policy_path = match &lockfile {
+            Lockfile::Path(path) => policy_paths.get(path),
+            _ => None,
+        };
+        let format_in = format_in.detect(&lockfile);
+        if let (Some(resolution), Lockfile::Path(path)) = (resolve, &lockfile) {
+            if path.exists() && conflict::resolve(path, resolution)? {
+                println!("resolved the merge conflicts in {:?}", path);
+            }
+        }
+        if let (true, Lockfile::Path(path)) = (fix_format, &lockfile) {
+            if format_in.is_flake_lock() && path.exists() && canonical::fix(path)? {
+                println!("reformatted {:?} canonically", path);
+            }
+        }
+        let mut flake_check_config = match policy_path {
+            Some(policy_path) => policy::Policy::load(policy_path)?.apply(&flake_check_config),
+            None => flake_check_config.clone(),
+        };
+        policy.select(&mut flake_check_config);
+        flake_check_config.devenv = format_in == InputFormat::Devenv;
+        if offline {
+            checks::require_offline(&flake_check_config)?;
+        }
+        let Some(source) = lockfile.read(&flake_check_config.network)? else {
+            match ignore_missing {
+                Some(MissingMode::Error) => {
+                    println!("no flake lockfile found at {:?}", flake_lock_path);
+                    missing_flake_lock = true;
+                }
+                Some(MissingMode::Warning) => {
+                    let mut issues = vec![Issue {
+                        input: String::new(),
+                        kind: IssueKind::Missing(Missing {}),
+                        location: None,
+                        provenance: None,
+                        severity: Severity::Warning,
+                        message: None,
+                    }];
+                    issues[0].severity = flake_check_config.issue_severity(&issues[0]);
+                    if stream_jsonl {
+                        println!(
+                            "{}",
+                            issue_json_line(&issues[0], &flake_lock_path, lang, false, None)?
+                        );
+                    }
+                    summaries.push(Summary::new(
+                        &issues,
+                        &EMPTY_FLAKE_LOCK.parse()?,
+                        "",
+                        flake_lock_path,
+                        flake_check_config,
+                        lang,
+                        max_issues,
+                    ));
+                }
+                Some(MissingMode::Skip) => {}
+                // Without --ignore-missing, --ignore-missing-flake-lock decides
+                None if ignore_missing_flake_lock => {
+                    println!("no flake lockfile found at {:?}; ignoring", flake_lock_path);
+                }
+                None => {
+                    println!("no flake lockfile found at {:?}", flake_lock_path);
+                    missing_flake_lock = true;
+                }
+            }
+            continue;
+        };
+        if let (true, Lockfile::Path(path)) = (fix_flake_nix, &lockfile) {
+            if format_in == InputFormat::Flake {
+                flake_nix::fix(path, &source, &flake_check_config)?;
+            }
+        }
+        let source = match &lockfile {
+            Lockfile::Path(path) if fix && format_in.is_flake_lock() => {
+                let (source, fixed) =
+                    fix::fix(path, source, &flake_check_config, fix_engine, lang)?;
+                if !fixed.is_empty() {
+                    fixed_lockfiles.push(pull_request::FixedLockfile {
+                        path: flake_lock_path.clone(),
+                        source: source.clone(),
+                        fixed,
+                    });
+                }
+                source
+            }
+            _ => source,
+        };
+        let source = format_in.to_flake_lock(source, &flake_check_config.network)?;
+        let mut flake_lock: FlakeLock = source
+            .parse()
+            .map_err(|err: FlakeLockParseError| err.with_path(&flake_lock_path))?;
+        if let Some(registries) = &registries {
+            for node in registries.resolve(&mut flake_lock) {
+                eprintln!(
+                    "{}: the indirect `{node}` input doesn't resolve to a repository in the flake registries, so its source isn't checked",
+                    flake_lock_path.display()
+                );
+            }
+        }
+        let non_canonical = if flake_check_config.check_format && format_in.is_flake_lock() {
+            canonical::check(&source)?.map(|mut issue| {
+                issue.severity = flake_check_config.issue_severity(&issue);
+                issue
+            })
+        } else {
+            None
+        };
+        if verbose {
+            for (input, fields) in fields::unknown_fields(&flake_lock) {
+                eprintln!(
+                    "{}: the `{input}` input has fields that flake-checker doesn't recognize: {}",
+                    flake_lock_path.display(),
+                    fields.join(", ")
+                );
+            }
+        }
+
+        if explain {
+            println!(
+                "{}",
+                explain::explain(&flake_lock, &flake_lock_path, &flake_check_config)
+            );
+            continue;
+        }
+
+        let exemptions = match &lockfile {
+            Lockfile::Path(path) if format_in == InputFormat::Flake => {
+                exemption::Exemptions::load(path)?
+            }
+            _ => exemption::Exemptions::default(),
+        };
+        let today = flake_check_config.now().date_naive();
+        for expired in exemptions.exemptions.iter().filter(|e| e.is_expired(today)) {
+            eprintln!(
+                "{}: the exemption for the `{}` input on line {} of flake.nix expired on {}",
+                flake_lock_path.display(),
+                expired.input,
+                expired.line,
+                expired.until.unwrap_or_default()
+            );
+        }
+
+        let new_inputs = match new_input_grace_days {
+            Some(days) => {
+                let previous = match (&previous_lock, &lockfile) {
+                    (Some(path), _) => Some(std::fs::read_to_string(path)?),
+                    (None, Lockfile::Path(path)) => grace::lockfile_days_ago(path, "HEAD", days)?,
+                    (None, Lockfile::GitRevision { rev, path }) => {
+                        grace::lockfile_days_ago(path, rev, days)?
+                    }
+                    // Without any history, no input counts as new
+                    (None, _) => Some(source.clone()),
+                };
+                let previous: Option<FlakeLock> = previous
+                    .map(|previous| format_in.to_flake_lock(previous, &flake_check_config.network))
+                    .transpose()?
+                    .map(|previous| previous.parse())
+                    .transpose()?;
+                grace::NewInputs::new(&flake_lock, previous.as_ref(), days)
+            }
+            None => grace::NewInputs::default(),
+        };
+
+        // Issues are located in the source that they were found in, if it's JSON
+        let source_map = SourceMap::new(&source).ok();
+        let locate = |issue: &mut Issue| {
+            if let Some(source_map) = &source_map {
+                issue.locate(&flake_lock_path, &flake_lock, source_map);
+            }
+        };
+
+        let run_start = otel::now_nanos();
+        let mut stream = |issue: &Issue| {
+            let exempt = exemptions.exempting(issue, today).is_some() || new_inputs.covers(issue);
+            if stream_jsonl && !exempt && issue_filter.matches(issue) {
+                let is_known = known.contains(&flake_lock_path, &flake_lock, issue);
+                let suggestion = suggest
+                    .then(|| fix::suggestion(&flake_lock, issue))
+                    .flatten();
+                let mut issue = issue.clone();
+                locate(&mut issue);
+                if let Ok(line) = issue_json_line(
+                    &issue,
+                    &flake_lock_path,
+                    lang,
+                    is_known,
+                    suggestion.as_ref(),
+                ) {
+                    let mut stdout = std::io::stdout().lock();
+                    let _ = writeln!(stdout, "{line}");
+                    let _ = stdout.flush();
+                }
+            }
+        };
+        let mut file_issues = check_lockfile(
+            &flake_lock,
+            &flake_lock_path,
+            &source,
+            &flake_check_config,
+            flake_check_config.strict_fields && format_in.is_flake_lock(),
+            &mut stream,
+        )?;
+        // Formatting issues aren't about an input, so they sort first
+        if let Some(issue) = non_canonical {
+            stream(&issue);
+            file_issues.insert(0, issue);
+        }
+        file_issues.iter_mut().for_each(locate);
+        let run_end = otel::now_nanos();
+        let (exempted, file_issues): (Vec<_>, Vec<_>) = file_issues
+            .into_iter()
+            .partition(|issue| exemptions.exempting(issue, today).is_some());
+        let (in_grace, file_issues): (Vec<_>, Vec<_>) = file_issues
+            .into_iter()
+            .partition(|issue| new_inputs.covers(issue));
+
+        let (known_issues, issues): (Vec<_>, Vec<_>) = file_issues
+            .iter()
+            .filter(|issue| issue_filter.matches(issue))
+            .cloned()
+            .partition(|issue| known.contains(&flake_lock_path, &flake_lock, issue));
+        for issue in issues.iter() {
+            new_baseline.add(&flake_lock_path, &flake_lock, issue);
+        }
+        all_issues.extend(file_issues);
+
+        if tui {
+            tui::browse(
+                &flake_lock,
+                &flake_lock_path,
+                &issues,
+                lang,
+                flake_check_config.now(),
+            )?;
+            continue;
+        }
+
+        let mut summary = Summary::new(
+            &issues,
+            &flake_lock,
+            &source,
+            flake_lock_path,
+            flake_check_config,
+            lang,
+            max_issues,
+        );
+        summary.set_known_issues(&known_issues);
+        let exempted: Vec<_> = exempted
+            .iter()
+            .filter_map(|issue| Some((issue, exemptions.exempting(issue, today)?)))
+            .collect();
+        summary.set_exempted(&exempted);
+        summary.set_in_grace(&in_grace, new_inputs.days);
+        if let Some(policy_path) = policy_path {
+            summary.set_policy_path(policy_path);
+        }
+        if let Some(top) = top {
+            summary.set_oldest_inputs(top);
+        }
+        if group_by_input {
+            summary.group_by_input();
+        }
+        if suggest {
+            let suggestions: Vec<_> = issues
+                .iter()
+                .filter_map(|issue| Some((issue, fix::suggestion(&flake_lock, issue)?)))
+                .collect();
+            summary.set_suggestions(&suggestions);
+        }
+        if let Some(previous_results) = &previous_results {
+            // Known issues were found by the previous run too, if it used the same baseline
+            let all: Vec<Issue> = issues.iter().chain(&known_issues).cloned().collect();
+            summary.set_comparison(&previous_results.compare(&summary.flake_lock_path, &all));
+        }
+        if audit_log.is_some() {
+            audited.push(audit::AuditedLockfile::new(
+                &summary.flake_lock_path,
+                &source,
+                summary.policy(),
+                &summary.issues,
+            ));
+        }
+
+        if let Some(exporter) = &otel_exporter {
+            let timing = otel::RunTiming {
+                start: run_start,
+                end: run_end,
+            };
+            exporter.export(
+                &summary.flake_check_config.network,
+                &timing,
+                &summary.issues,
+                &summary.inputs,
+            );
+        }
+
+        summaries.push(summary);
+    }
+
+    if explain || tui {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !no_telemetry {
+        telemetry::TelemetryReport::make_and_send(&all_issues, &flake_check_config.network);
+    }
+
+    if let Some(path) = &audit_log {
+        audit::append(path, audited)?;
+    }
+
+    if let Some(path) = baseline.filter(|_| write_baseline) {
+        new_baseline.save(&path)?;
+        println!(
+            "wrote {} issue(s) to the baseline at {:?}",
+            new_baseline.issues.len(),
+            path
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if summaries.is_empty() {
+        return Ok(if missing_flake_lock {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let report = Report::new(summaries);
+
+    if bitbucket_report {
+        bitbucket::BitbucketReporter::from_env(bitbucket_token, &flake_check_config.network)?
+            .report(&report)?;
+    }
+
+    if set_commit_status {
+        if let Some(token) = gitea_token {
+            gitea::GiteaStatusReporter::from_env(
+                token,
+                commit_status_target_url,
+                flake_check_config.network.clone(),
+            )?
+            .report(&report)?;
+        }
+    }
+
+    if create_pr {
+        if fixed_lockfiles.is_empty() {
+            println!("--fix didn't fix any issues, so no pull request was opened");
+        } else {
+            let url = pull_request::PullRequestCreator::from_env(
+                pr_branch,
+                flake_check_config.network.clone(),
+            )?
+            .create(&fixed_lockfiles, &report, lang)?;
+            println!("the fixed lockfiles are up for review at {url}");
+        }
+    }
+
+    if let Some(webhook_url) = teams_webhook_url {
+        let notifier = teams::TeamsNotifier::new(
+            webhook_url,
+            teams_min_severity,
+            flake_check_config.network.clone(),
+        );
+        for summary in report.summaries.iter() {
+            notifier.notify(summary)?;
+        }
+    }
+
+    if let Some(format) = format {
+        report.generate(format)?;
+    } else if std::env::var("GITHUB_ACTIONS").is_ok() {
+        if markdown_summary {
+            report.generate_markdown()?;
+        }
+        report.console_log_errors()?;
+    } else if std::env::var("TF_BUILD").is_ok() {
+        // Azure Pipelines
+        report.generate_text()?;
+        report.azure_log_issues()?;
+    } else {
+        report.generate_text()?;
+    }
+
+    // Each lockfile fails the run if its errors (all of its issues in fail mode, or those that
+    // have escalated) exceed its budget
+    let mut failed = false;
+    for summary in report.summaries.iter() {
+        let config = &summary.flake_check_config;
+        let budget = &config.budget;
+
+        // Missing lockfiles are only warnings
+        let issues: Vec<Issue> = summary
+            .issues
+            .iter()
+            .filter(|issue| !issue.kind.is_missing())
+            .filter(|issue| issue.severity == Severity::Error)
+            .cloned()
+            .collect();
+        let violations = budget.violations(&issues);
+        let path = summary.flake_lock_path.display();
+        if budget.has_limits() {
+            if violations.is_empty() && !issues.is_empty() {
+                eprintln!(
+                    "{path}: {} issues found, within the allowed budget",
+                    issues.len()
+                );
+            }
+            for violation in violations.iter() {
+                eprintln!("{path}: issue budget exceeded: {violation}");
+            }
+        }
+        failed |= !violations.is_empty();
+    }
+    if missing_flake_lock || failed {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Run the built-in checks, the plugins, and (with `strict_fields`) the check for unknown fields
+// against a lockfile, streaming each issue as it's found, and sort the issues together.
+fn check_lockfile(
+    flake_lock: &FlakeLock,
+    flake_lock_path: &Path,
+    source: &str,
+    config: &FlakeCheckConfig,
+    strict_fields: bool,
+    mut stream: impl FnMut(&Issue),
+) -> Result<Vec<Issue>, FlakeCheckerError> {
+    let mut issues = check_flake_lock_with(flake_lock, config, &mut stream)?;
+    for path in config.plugins.iter() {
+        for issue in plugin::run(path, flake_lock_path, flake_lock, source, config) {
+            stream(&issue);
+            issues.push(issue);
+        }
+    }
+    if strict_fields {
+        for mut issue in fields::check(flake_lock) {
+            issue.severity = config.issue_severity(&issue);
+            stream(&issue);
+            issues.push(issue);
+        }
+    }
+    sort_issues(&mut issues);
+    Ok(issues)
+}
+
+// The policy that a lockfile is checked against, with the policy file it came from as `source`
+fn show_policy(
+    flake_lock_path: &Path,
+    recursive: Option<&Path>,
+    config: &FlakeCheckConfig,
+) -> Result<serde_json::Value, FlakeCheckerError> {
+    let source = match recursive {
+        Some(root) => policy::governing(
+            &std::path::absolute(flake_lock_path)?,
+            Some(&std::path::absolute(root)?),
+        ),
+        None => None,
+    };
+    let config = match &source {
+        Some(path) => policy::Policy::load(path)?.apply(config),
+        None => config.clone(),
+    };
+    let mut shown = policy::describe(&config);
+    shown["source"] = serde_json::json!(source);
+    Ok(shown)
+}
+
+// The options of a check run that access the network, which --offline rules out
+fn networked_options(args: &CheckArgs) -> Vec<&'static str> {
+    [
+        (!args.remotes.is_empty(), "--remote"),
+        (
+            args.fix,
+            "--fix (to fetch the updated inputs, with either engine)",
+        ),
+        (args.create_pr, "--create-pr"),
+        (args.bitbucket_report, "--bitbucket-report"),
+        (args.set_commit_status, "--set-commit-status"),
+        (args.teams_webhook_url.is_some(), "--teams-webhook-url"),
+        (
+            args.format_in != InputFormat::Flake,
+            "--format-in (to look up when each pinned revision was committed)",
+        ),
+        (
+            otel::OtelExporter::from_env().is_some(),
+            "OpenTelemetry export (OTEL_EXPORTER_OTLP_ENDPOINT)",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(used, name)| used.then_some(name))
+    .collect()
+}
+
+fn run_diff(
+    old: PathBuf,
+    new: PathBuf,
+    flake_check_config: &FlakeCheckConfig,
+    lang: Lang,
+) -> Result<ExitCode, FlakeCheckerError> {
+    let lock_diff = diff::LockDiff::new(
+        &read_flake_lock(old.clone())?,
+        &read_flake_lock(new.clone())?,
+        flake_check_config,
+    )?;
+    print!(
+        "{}",
+        lock_diff.markdown(&old.to_string_lossy(), &new.to_string_lossy(), lang)?
+    );
+
+    if lock_diff
+        .introduced
+        .iter()
+        .any(|issue| issue.severity == Severity::Error)
+    {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Reads and parses a single local lockfile (`-` for stdin), which must exist.
+fn read_flake_lock(path: PathBuf) -> Result<FlakeLock, FlakeCheckerError> {
+    let lockfile = Lockfile::from_path(path);
+    // Local lockfiles are read without network access
+    match lockfile.read(&NetworkConfig::default())? {
+        Some(source) => Ok(source.parse()?),
+        None => Err(FlakeCheckerError::Invalid(format!(
+            "no flake lockfile found at {:?}",
+            lockfile.display_path()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::{check_lockfile, networked_options, run_check, show_policy};
+    use crate::cli::{Cli, Command, PolicyAction};
+    use crate::flake::FlakeCheckConfig;
+    use crate::summary::OutputFormat;
+
+    use std::path::PathBuf;
+    use std::process::ExitCode;
+
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn test_subcommands() {
+        Cli::command().debug_assert();
+        let parse = |args: &[&str]| Cli::try_parse_from([&["flake-checker"], args].concat());
+
+        let Some(Command::Report {
+            report_format,
+            check,
+        }) = parse(&["report", "html", "sub/flake.lock"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected the report subcommand");
+        };
+        assert!(matches!(report_format, OutputFormat::Html));
+        assert_eq!(check.flake_lock_paths, [PathBuf::from("sub/flake.lock")]);
+
+        let Some(Command::Fix { check }) =
+            parse(&["fix", "--fix-engine", "in-place"]).unwrap().command
+        else {
+            panic!("expected the fix subcommand");
+        };
+        assert!(!check.fix);
+
+        assert!(matches!(
+            parse(&["policy", "show"]).unwrap().command,
+            Some(Command::Policy {
+                action: PolicyAction::Show { .. }
+            })
+        ));
+        // The flags of the subcommands still work without them
+        assert!(parse(&["--fix", "--format", "csv"])
+            .unwrap()
+            .command
+            .is_none());
+    }
+
+    #[test]
+    fn test_networked_options() {
+        let networked = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["flake-checker"], args].concat()).unwrap();
+            networked_options(&cli.check)
+        };
+        assert!(networked(&["--offline"]).is_empty());
+        assert_eq!(
+            networked(&["--offline", "--fix", "--fix-engine", "in-place"]),
+            ["--fix (to fetch the updated inputs, with either engine)"]
+        );
+        assert_eq!(networked(&["--fix", "--create-pr"]).len(), 2);
+    }
+
+    #[test]
+    fn test_policy_show_matches_check() {
+        let dir = std::env::temp_dir().join(format!("flake-checker-policy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let flake_lock = dir.join("flake.lock");
+        std::fs::copy("tests/flake.clean.0.lock", &flake_lock).unwrap();
+        std::fs::write(dir.join("flake-checker.json"), r#"{ "max_days": 100000 }"#).unwrap();
+
+        let flake_lock = flake_lock.to_str().unwrap();
+        let dir = dir.to_str().unwrap();
+        let args = ["--only", "max-age", "--offline", "--no-telemetry"];
+        let check = |extra: &[&str]| {
+            let cli = Cli::try_parse_from(
+                [
+                    &["flake-checker", "--fail-mode", "--format", "csv"],
+                    &args,
+                    extra,
+                ]
+                .concat(),
+            )
+            .unwrap();
+            run_check(cli.check).unwrap()
+        };
+        let show = |recursive: Option<&str>| {
+            let cli = Cli::try_parse_from([&["flake-checker"], &args[..2]].concat()).unwrap();
+            let config = cli.check.policy.config().unwrap();
+            show_policy(flake_lock.as_ref(), recursive.map(AsRef::as_ref), &config).unwrap()
+        };
+
+        // An explicit path is checked against the flags alone
+        let explicit = check(&[flake_lock]);
+        let shown_explicit = show(None);
+        // A discovered one is checked against the nearest policy file
+        let recursive = check(&["--recursive", dir]);
+        let shown_recursive = show(Some(dir));
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(explicit, ExitCode::FAILURE);
+        assert_eq!(shown_explicit["max_days"], 30);
+        assert!(shown_explicit["source"].is_null());
+        assert_eq!(recursive, ExitCode::SUCCESS);
+        assert_eq!(shown_recursive["max_days"], 100000);
+        assert!(shown_recursive["source"].is_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_appended_issues_are_sorted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("flake-checker-sorted-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join("plugin");
+        std::fs::write(
+            &plugin,
+            "#!/bin/sh\ncat > /dev/null\n\
+             echo '{\"issues\": [{\"input\": \"nixpkgs\", \"code\": \"unpinned\", \"message\": \"not pinned\"}]}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.clean.3.lock").unwrap())
+                .unwrap();
+        lock["nodes"]["nixpkgs"]["attrs"] = serde_json::json!({});
+        let source = lock.to_string();
+        let config = FlakeCheckConfig {
+            plugins: vec![plugin],
+            ..FlakeCheckConfig::default()
+        };
+        let issues = check_lockfile(
+            &source.parse().unwrap(),
+            "flake.lock".as_ref(),
+            &source,
+            &config,
+            true,
+            |_| {},
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let keys: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.input.as_str(), issue.kind.name()))
+            .collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        // The plugin's and the unknown fields' issues are with the other issues for nixpkgs, before
+        // those of other inputs
+        assert!(keys.contains(&("nixpkgs", "unpinned")));
+        assert!(keys.contains(&("nixpkgs", "unknown-fields")));
+        assert!(keys.iter().any(|(input, _)| *input > "nixpkgs"));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::iso8601;
+use crate::issue::Issue;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// One check run, as recorded in the audit log (one JSON record per line). Each record includes
+/// the hash of the line before it, so editing or removing an earlier record breaks the chain.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct AuditRecord {
+    pub timestamp: String,
+    pub version: String,
+    pub lockfiles: Vec<AuditedLockfile>,
+    /// The SHA-256 hash of the previous line of the log, if there is one.
+    pub previous: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct AuditedLockfile {
+    pub path: String,
+    /// The SHA-256 hash of the lockfile's contents.
+    pub sha256: String,
+    /// The SHA-256 hash of the policy that the lockfile was checked against.
+    pub policy_sha256: String,
+    pub issues: Vec<serde_json::Value>,
+}
+
+impl AuditedLockfile {
+    pub(crate) fn new(
+        path: &Path,
+        source: &str,
+        policy: &serde_json::Value,
+        issues: &[Issue],
+    ) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            sha256: sha256(source),
+            policy_sha256: sha256(&policy.to_string()),
+            issues: issues
+                .iter()
+                .map(|issue| {
+                    serde_json::json!({
+                        "input": issue.input,
+                        "kind": issue.kind.name(),
+                        "details": issue.kind,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+fn sha256(data: &str) -> String {
+    format!("{:x}", Sha256::digest(data.as_bytes()))
+}
+
+/// Append a record of a run to the audit log, creating the log if it doesn't exist.
+pub(crate) fn append(
+    path: &Path,
+    lockfiles: Vec<AuditedLockfile>,
+) -> Result<(), FlakeCheckerError> {
+    let previous = match std::fs::read_to_string(path) {
+        Ok(log) => log.lines().last().map(sha256),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    let record = AuditRecord {
+        timestamp: iso8601(Utc::now().timestamp()),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        lockfiles,
+        previous,
+    };
+
+    let mut log = OpenOptions::new().append(true).create(true).open(path)?;
+    writeln!(log, "{}", serde_json::to_string(&record)?)?;
+    Ok(())
+}
+
+/// Verify an audit log's chain of hashes, returning the number of records, or a description of
+/// the first line that breaks the chain.
+pub(crate) fn verify(log: &str) -> Result<usize, String> {
+    let mut previous: Option<&str> = None;
+    for (index, line) in log.lines().enumerate() {
+        let number = index + 1;
+        let record: AuditRecord = serde_json::from_str(line)
+            .map_err(|err| format!("line {number} isn't an audit record: {err}"))?;
+        if record.previous != previous.map(sha256) {
+            return Err(format!(
+                "line {number} doesn't follow line {index}; a record has been edited or removed"
+            ));
+        }
+        previous = Some(line);
+    }
+    Ok(log.lines().count())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::{append, verify, AuditedLockfile};
+
+    use std::path::Path;
+
+    #[test]
+    fn test_audit_log() {
+        let path = std::env::temp_dir().join(format!("flake-checker-audit-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let source = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let policy = serde_json::json!({ "fail_mode": true });
+        for _ in 0..3 {
+            let lockfile = AuditedLockfile::new(Path::new("flake.lock"), &source, &policy, &[]);
+            append(&path, vec![lockfile]).unwrap();
+        }
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(verify(&log), Ok(3));
+
+        // Removing or editing a record breaks the chain
+        let lines: Vec<&str> = log.lines().collect();
+        assert!(verify(&[lines[0], lines[2]].join("\n")).is_err());
+        let edited = log.replacen("flake.lock", "other.lock", 1);
+        assert!(verify(&edited).unwrap_err().starts_with("line 2"));
+    }
+}
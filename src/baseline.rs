@@ -0,0 +1,157 @@
+use crate::error::FlakeCheckerError;
+use crate::issue::Issue;
+
+use std::path::Path;
+
+use parse_flake_lock::{FlakeLock, Node};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A set of known issues that are reported but don't affect the exit code, for adopting the
+/// checker on repos with existing violations.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Baseline {
+    pub issues: Vec<BaselineEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct BaselineEntry {
+    /// The lockfile the issue was found in (any lockfile if absent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub input: String,
+    pub kind: String,
+    /// Where the input came from when the issue was recorded (see `fingerprint`), so that the
+    /// entry stops matching if the input is moved elsewhere (any source if absent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+impl Baseline {
+    pub(crate) fn load(path: &Path) -> Result<Self, FlakeCheckerError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), FlakeCheckerError> {
+        let mut serialized = serde_json::to_string_pretty(self)?;
+        serialized.push('\n');
+        Ok(std::fs::write(path, serialized)?)
+    }
+
+    pub(crate) fn add(&mut self, flake_lock_path: &Path, flake_lock: &FlakeLock, issue: &Issue) {
+        self.issues.push(BaselineEntry {
+            path: Some(flake_lock_path.to_string_lossy().to_string()),
+            input: issue.input.clone(),
+            kind: issue.kind.name().to_string(),
+            fingerprint: fingerprint(flake_lock, &issue.input),
+        });
+    }
+
+    pub(crate) fn contains(
+        &self,
+        flake_lock_path: &Path,
+        flake_lock: &FlakeLock,
+        issue: &Issue,
+    ) -> bool {
+        self.issues.iter().any(|entry| {
+            entry.input == issue.input
+                && entry.kind == issue.kind.name()
+                && entry
+                    .path
+                    .as_deref()
+                    .is_none_or(|path| Path::new(path) == flake_lock_path)
+                && entry.fingerprint.as_deref().is_none_or(|expected| {
+                    fingerprint(flake_lock, &issue.input).as_deref() == Some(expected)
+                })
+        })
+    }
+}
+
+/// A short hash of where a root input comes from (its type, owner, repo, ref, and subdirectory as
+/// written in flake.nix), which stays the same as the input is updated but changes if it's moved
+/// elsewhere.
+pub(crate) fn fingerprint(flake_lock: &FlakeLock, input: &str) -> Option<String> {
+    let Some(Node::Repo(repo)) = flake_lock.root.get(input) else {
+        return None;
+    };
+    let original = &repo.original;
+    let mut source = format!(
+        "{}:{}/{}/{}",
+        original.node_type,
+        original.owner.to_lowercase(),
+        original.repo.to_lowercase(),
+        original.git_ref.as_deref().unwrap_or_default()
+    );
+    // Flakes in different subdirectories of a repository are different inputs (and inputs without
+    // one keep the fingerprints they had before subdirectories were taken into account)
+    if let Some(dir) = repo.dir() {
+        source.push_str(&format!("?dir={dir}"));
+    }
+    let digest = format!("{:x}", Sha256::digest(source.as_bytes()));
+    Some(digest[..16].to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::baseline::{fingerprint, Baseline};
+    use crate::issue::{Disallowed, Issue, IssueKind, Severity};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_baseline_matching() {
+        let baseline: Baseline = serde_json::from_str(
+            r#"{
+                "issues": [
+                    { "path": "flake.lock", "input": "nixpkgs", "kind": "disallowed" },
+                    { "input": "nixpkgs-old", "kind": "disallowed" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let issue = |input: &str| Issue {
+            input: String::from(input),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("this-should-fail"),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let contains = |path: &str, input: &str| {
+            baseline.contains(Path::new(path), &flake_lock, &issue(input))
+        };
+        assert!(contains("flake.lock", "nixpkgs"));
+        assert!(!contains("nix/flake.lock", "nixpkgs"));
+        assert!(contains("nix/flake.lock", "nixpkgs-old"));
+        assert!(!contains("flake.lock", "nixpkgs-new"));
+
+        // Entries with a fingerprint only match while the input comes from the same place
+        let mut fingerprinted = Baseline::default();
+        fingerprinted.add(Path::new("flake.lock"), &flake_lock, &issue("nixpkgs"));
+        assert!(fingerprinted.contains(Path::new("flake.lock"), &flake_lock, &issue("nixpkgs")));
+        fingerprinted.issues[0].fingerprint = Some(String::from("0123456789abcdef"));
+        assert!(!fingerprinted.contains(Path::new("flake.lock"), &flake_lock, &issue("nixpkgs")));
+        assert_eq!(fingerprint(&flake_lock, "nixpkgs-new"), None);
+
+        // A flake in a subdirectory of the same repository is another input
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap())
+                .unwrap();
+        lock["nodes"]["nixpkgs"]["original"]["dir"] = "lib".into();
+        let in_dir: FlakeLock = lock.to_string().parse().unwrap();
+        assert_ne!(
+            fingerprint(&in_dir, "nixpkgs"),
+            fingerprint(&flake_lock, "nixpkgs")
+        );
+    }
+}
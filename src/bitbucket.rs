@@ -0,0 +1,135 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::issue::Severity;
+use crate::summary::Report;
+
+use std::env;
+
+use serde_json::json;
+
+const REPORT_ID: &str = "flake-checker";
+
+// Inside Bitbucket Pipelines, requests to the API sent through this proxy are authenticated
+// automatically on behalf of the pipeline.
+const PIPELINES_PROXY: &str = "http://localhost:29418";
+
+/// Publishes the checker's findings as a Bitbucket [Code Insights] report on the current commit.
+///
+/// [Code Insights]: https://support.atlassian.com/bitbucket-cloud/docs/code-insights/
+pub(crate) struct BitbucketReporter {
+    client: reqwest::blocking::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl BitbucketReporter {
+    /// Create a reporter for the commit identified by the `BITBUCKET_REPO_FULL_NAME` and
+    /// `BITBUCKET_COMMIT` environment variables. Without an access token, requests are sent through
+    /// the Bitbucket Pipelines authentication proxy.
+    pub(crate) fn from_env(
+        token: Option<String>,
+        network: &NetworkConfig,
+    ) -> Result<Self, FlakeCheckerError> {
+        let repo = env::var("BITBUCKET_REPO_FULL_NAME")?;
+        let commit = env::var("BITBUCKET_COMMIT")?;
+
+        let (client, scheme) = match token {
+            Some(_) => (http::client(network)?, "https"),
+            None => (
+                reqwest::blocking::Client::builder()
+                    .timeout(network.timeout)
+                    .proxy(reqwest::Proxy::http(PIPELINES_PROXY)?)
+                    .build()?,
+                "http",
+            ),
+        };
+
+        Ok(Self {
+            client,
+            base_url: format!(
+                "{scheme}://api.bitbucket.org/2.0/repositories/{repo}/commit/{commit}/reports/{REPORT_ID}"
+            ),
+            token,
+        })
+    }
+
+    pub(crate) fn report(&self, report: &Report) -> Result<(), FlakeCheckerError> {
+        let num_issues = report.num_issues();
+        let num_inputs: usize = report.summaries.iter().map(|s| s.inputs.len()).sum();
+        let failed = report.severity() == Severity::Error && num_issues > 0;
+        let paths: Vec<_> = report
+            .summaries
+            .iter()
+            .map(|s| s.flake_lock_path.to_string_lossy())
+            .collect();
+        let paths = paths.join(", ");
+
+        let insights_report = json!({
+            "title": "Nix Flake Checker",
+            "details": if num_issues == 0 {
+                format!("No issues found in {paths}")
+            } else {
+                format!("Found {num_issues} issue(s) in {paths}")
+            },
+            "report_type": "BUG",
+            "reporter": "Determinate Systems",
+            "link": "https://github.com/DeterminateSystems/flake-checker",
+            "result": if failed { "FAILED" } else { "PASSED" },
+            "data": [
+                { "title": "Issues", "type": "NUMBER", "value": num_issues },
+                { "title": "Inputs", "type": "NUMBER", "value": num_inputs },
+            ],
+        });
+
+        self.request(self.client.put(&self.base_url))
+            .body(serde_json::to_string(&insights_report)?)
+            .send()?
+            .error_for_status()?;
+
+        if num_issues == 0 {
+            return Ok(());
+        }
+
+        let annotations: Vec<serde_json::Value> = report
+            .summaries
+            .iter()
+            .flat_map(|summary| {
+                let path = summary.flake_lock_path.to_string_lossy();
+                summary.issues.iter().map(move |issue| {
+                    let mut annotation = json!({
+                        "external_id": format!("{path}-{}-{}", issue.input, issue.kind.name()),
+                        "annotation_type": "BUG",
+                        "summary": issue.message(summary.lang),
+                        "severity": match issue.severity {
+                            Severity::Error => "HIGH",
+                            Severity::Warning => "MEDIUM",
+                        },
+                        "path": path,
+                    });
+                    if let Some(location) = &issue.location {
+                        annotation["line"] = json!(location.line);
+                    }
+                    annotation
+                })
+            })
+            .collect();
+
+        self.request(self.client.post(format!("{}/annotations", self.base_url)))
+            .body(serde_json::to_string(&annotations)?)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn request(
+        &self,
+        builder: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        let builder = builder.header("Content-Type", "application/json");
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+}
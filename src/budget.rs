@@ -0,0 +1,144 @@
+use crate::issue::{Issue, KIND_NAMES};
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// The number of issues a lockfile tolerates before it fails in fail mode, overall and per issue
+/// kind. Kinds without their own budget are only limited by the overall one.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    /// The number of issues tolerated overall.
+    pub max_issues: Option<usize>,
+    /// The number of issues tolerated of each kind, by [kind name](crate::IssueKind::name).
+    pub per_kind: BTreeMap<String, usize>,
+}
+
+/// How much of one of a budget's limits a set of issues consumes.
+#[derive(Debug, Serialize)]
+pub(crate) struct BudgetUsage {
+    /// The kind of issue limited, or `None` for the overall limit.
+    pub kind: Option<String>,
+    pub count: usize,
+    pub max: usize,
+    pub exceeded: bool,
+}
+
+impl Budget {
+    pub(crate) fn has_limits(&self) -> bool {
+        self.max_issues.is_some() || !self.per_kind.is_empty()
+    }
+
+    /// Describe each way in which the issues exceed the budget. Without any budget, any issue
+    /// exceeds it.
+    pub(crate) fn violations(&self, issues: &[Issue]) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if !self.has_limits() {
+            if !issues.is_empty() {
+                violations.push(format!("{} issues found (none allowed)", issues.len()));
+            }
+            return violations;
+        }
+
+        for usage in self.usage(issues).iter().filter(|usage| usage.exceeded) {
+            let BudgetUsage { count, max, .. } = usage;
+            violations.push(match &usage.kind {
+                Some(kind) => format!("{count} {kind} issues found ({max} allowed)"),
+                None => format!("{count} issues found ({max} allowed)"),
+            });
+        }
+
+        violations
+    }
+
+    /// How much of each of the budget's limits the issues consume, overall first.
+    pub(crate) fn usage(&self, issues: &[Issue]) -> Vec<BudgetUsage> {
+        let overall = self.max_issues.map(|max| (None, issues.len(), max));
+        let per_kind = self.per_kind.iter().map(|(kind, max)| {
+            let count = issues.iter().filter(|i| i.kind.name() == kind).count();
+            (Some(kind.clone()), count, *max)
+        });
+
+        overall
+            .into_iter()
+            .chain(per_kind)
+            .map(|(kind, count, max)| BudgetUsage {
+                kind,
+                count,
+                max,
+                exceeded: count > max,
+            })
+            .collect()
+    }
+}
+
+/// Parse a `kind=N` per-kind budget from the command line.
+pub(crate) fn parse_kind_budget(value: &str) -> Result<(String, usize), String> {
+    let (kind, max) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected kind=N, got `{value}`"))?;
+    check_kind(kind)?;
+    let max = max
+        .parse()
+        .map_err(|_| format!("invalid budget `{max}` for {kind} issues"))?;
+    Ok((kind.to_string(), max))
+}
+
+/// Check that a budget is for a known kind of issue.
+pub(crate) fn check_kind(kind: &str) -> Result<(), String> {
+    if KIND_NAMES.contains(&kind) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown issue kind `{kind}` (expected one of {})",
+            KIND_NAMES.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::budget::{parse_kind_budget, Budget};
+    use crate::issue::{Disallowed, Issue, IssueKind, Severity};
+
+    #[test]
+    fn test_budget_violations() {
+        let disallowed = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("this-should-fail"),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        let issues = vec![disallowed.clone(), disallowed];
+
+        assert_eq!(Budget::default().violations(&issues).len(), 1);
+        assert!(Budget::default().violations(&[]).is_empty());
+
+        let budget = Budget {
+            max_issues: Some(2),
+            per_kind: [(String::from("outdated"), 0)].into(),
+        };
+        assert!(budget.violations(&issues).is_empty());
+
+        let budget = Budget {
+            max_issues: None,
+            per_kind: [(String::from("disallowed"), 1)].into(),
+        };
+        assert_eq!(
+            budget.violations(&issues),
+            ["2 disallowed issues found (1 allowed)"]
+        );
+
+        assert_eq!(
+            parse_kind_budget("outdated=3"),
+            Ok((String::from("outdated"), 3))
+        );
+        assert!(parse_kind_budget("stale=3").is_err());
+        assert!(parse_kind_budget("outdated").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::FlakeCheckerError;
+use crate::issue::{Issue, IssueKind, NonCanonical, Severity};
+
+use std::path::Path;
+
+/// Format a lockfile the way that `nix flake lock` writes it: with its keys sorted, two-space
+/// indentation, and a trailing newline.
+pub(crate) fn canonicalize(source: &str) -> Result<String, FlakeCheckerError> {
+    // serde_json's maps are sorted by key
+    let value: serde_json::Value = serde_json::from_str(source)?;
+    Ok(serde_json::to_string_pretty(&value)? + "\n")
+}
+
+/// Check that a lockfile is canonically formatted, returning a `non-canonical` issue that points
+/// at the first line that differs if not.
+pub(crate) fn check(source: &str) -> Result<Option<Issue>, FlakeCheckerError> {
+    let canonical = canonicalize(source)?;
+    if canonical == source {
+        return Ok(None);
+    }
+
+    let mut lines = source.split_inclusive('\n');
+    let mut canonical_lines = canonical.split_inclusive('\n');
+    let mut line = 1;
+    while lines.next() == canonical_lines.next() {
+        line += 1;
+    }
+
+    Ok(Some(Issue {
+        input: String::new(),
+        kind: IssueKind::NonCanonical(NonCanonical { line }),
+        location: None,
+        provenance: None,
+        severity: Severity::Warning,
+        message: None,
+    }))
+}
+
+/// Rewrite a local lockfile with canonical formatting, if it isn't already, returning whether it
+/// did.
+pub(crate) fn fix(path: &Path) -> Result<bool, FlakeCheckerError> {
+    let source = std::fs::read_to_string(path)?;
+    let canonical = canonicalize(&source)?;
+    if canonical == source {
+        return Ok(false);
+    }
+    std::fs::write(path, canonical)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::canonical::{canonicalize, check};
+    use crate::issue::{IssueKind, NonCanonical};
+
+    #[test]
+    fn test_canonical_format() {
+        let clean = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        assert_eq!(check(&clean).unwrap(), None);
+
+        // Hand edits that `nix flake lock` would undo: a missing trailing newline, reordered
+        // keys, and other indentation
+        let unterminated = clean.trim_end();
+        let reordered = clean.replacen("\"version\"", "\"aaa\"", 1);
+        let reordered = canonicalize(&reordered)
+            .unwrap()
+            .replacen("\"aaa\"", "\"version\"", 1);
+        let compact =
+            serde_json::to_string(&serde_json::from_str::<serde_json::Value>(&clean).unwrap())
+                .unwrap();
+
+        let line = |source: &str| match check(source).unwrap().map(|issue| issue.kind) {
+            Some(IssueKind::NonCanonical(NonCanonical { line })) => line,
+            other => panic!("expected a non-canonical issue, got {other:?}"),
+        };
+        assert_eq!(line(unterminated), clean.lines().count());
+        assert_eq!(line(&reordered), 2);
+        assert_eq!(line(&compact), 1);
+
+        for source in [unterminated, &reordered, &compact] {
+            assert_eq!(canonicalize(source).unwrap(), clean);
+        }
+    }
+}
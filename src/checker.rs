@@ -0,0 +1,339 @@
+//! A programmatic way to compose check runs, for library consumers that don't use the
+//! configuration files or command-line flags.
+
+use crate::checks::{Check, CheckRegistry};
+use crate::error::FlakeCheckerError;
+use crate::flake::{check_deps, check_flake_lock, selected_deps, FlakeCheckConfig};
+use crate::issue::{Issue, Severity};
+use crate::policy::Policy;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use parse_flake_lock::{FlakeLock, Node};
+use serde::Serialize;
+
+/// Runs checks against lockfiles with a fixed configuration.
+///
+/// ```no_run
+/// use flake_checker::{FlakeChecker, FlakeLock, MaxAge, Policy};
+///
+/// let policy: Policy = r#"{"allowed_owners": ["NixOS", "my-org"]}"#.parse()?;
+/// let report = FlakeChecker::builder()
+///     .with_policy(policy)
+///     .with_check(MaxAge::new(30))
+///     .run(&FlakeLock::new(std::path::Path::new("flake.lock"))?)?;
+/// for issue in report.errors() {
+///     eprintln!("{}: {}", issue.input, issue.kind.name());
+/// }
+/// # Ok::<(), flake_checker::FlakeCheckerError>(())
+/// ```
+#[derive(Clone)]
+pub struct FlakeChecker {
+    config: FlakeCheckConfig,
+}
+
+impl FlakeChecker {
+    /// A builder that starts from the default configuration.
+    pub fn builder() -> FlakeCheckerBuilder {
+        FlakeCheckerBuilder::default()
+    }
+
+    /// A checker with the given configuration.
+    pub fn new(config: FlakeCheckConfig) -> Self {
+        Self { config }
+    }
+
+    /// The configuration that checks are run with.
+    pub fn config(&self) -> &FlakeCheckConfig {
+        &self.config
+    }
+
+    /// Run the enabled checks against a lockfile. Fails if a Nixpkgs input named in the
+    /// configuration doesn't exist.
+    pub fn run(&self, flake_lock: &FlakeLock) -> Result<CheckReport, FlakeCheckerError> {
+        check_flake_lock(flake_lock, &self.config)
+    }
+}
+
+/// Builds a [FlakeChecker]. Adding a check with [with_check](Self::with_check) runs exactly the
+/// added checks instead of the built-in ones, along with the rules and requirements of any
+/// policies.
+#[derive(Default)]
+pub struct FlakeCheckerBuilder {
+    config: FlakeCheckConfig,
+    checks: Option<CheckRegistry>,
+    policies: Vec<Policy>,
+    error: Option<FlakeCheckerError>,
+}
+
+impl FlakeCheckerBuilder {
+    /// Start from the given configuration rather than the default one.
+    pub fn with_config(mut self, config: FlakeCheckConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Apply a policy's overrides to the configuration, after any policies added before it.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policies.push(policy);
+        self
+    }
+
+    /// Add a check to run, after any checks added before it.
+    pub fn with_check(mut self, check: impl Check + 'static) -> Self {
+        let checks = self.checks.get_or_insert_with(CheckRegistry::empty);
+        if let Err(err) = checks.register(check) {
+            self.error.get_or_insert(err);
+        }
+        self
+    }
+
+    /// Build the checker. Fails if two checks have the same name, or if a policy's rules or
+    /// requirements clash with the checks.
+    pub fn build(self) -> Result<FlakeChecker, FlakeCheckerError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let mut config = self.config;
+        if let Some(checks) = self.checks {
+            config.checks = checks;
+        }
+        for policy in self.policies.iter() {
+            policy
+                .validate_against(&config.checks)
+                .map_err(|err| FlakeCheckerError::Invalid(format!("invalid policy: {err}")))?;
+            config = policy.apply(&config);
+        }
+        Ok(FlakeChecker::new(config))
+    }
+
+    /// Build the checker and run it against a lockfile.
+    pub fn run(self, flake_lock: &FlakeLock) -> Result<CheckReport, FlakeCheckerError> {
+        self.build()?.run(flake_lock)
+    }
+}
+
+/// The outcome of a check run against a lockfile.
+#[derive(Clone, Debug)]
+pub struct CheckReport {
+    /// The issues found, sorted by input.
+    pub issues: Vec<Issue>,
+    /// Each way in which the errors exceed the configuration's budget.
+    pub violations: Vec<String>,
+    /// The run's totals.
+    pub stats: CheckStats,
+}
+
+impl CheckReport {
+    pub(crate) fn new(
+        flake_lock: &FlakeLock,
+        config: &FlakeCheckConfig,
+        issues: Vec<Issue>,
+    ) -> Self {
+        let stats = CheckStats::new(flake_lock, config, &issues);
+        let errors: Vec<Issue> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .cloned()
+            .collect();
+        let violations = config.budget.violations(&errors);
+        Self {
+            issues,
+            violations,
+            stats,
+        }
+    }
+
+    /// The issues that are errors.
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.with_severity(Severity::Error)
+    }
+
+    /// The issues that are warnings.
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.with_severity(Severity::Warning)
+    }
+
+    /// Whether the lockfile passes: its errors (if any) are within the budget.
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity == severity)
+    }
+}
+
+/// The totals of a check run: what was checked, what was found, and what didn't run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CheckStats {
+    /// The inputs that at least one check ran against, in order.
+    pub inputs_checked: Vec<String>,
+    /// The number of issues of each kind.
+    pub issues_by_kind: BTreeMap<String, usize>,
+    /// The number of issues that are errors.
+    pub errors: usize,
+    /// The number of issues that are warnings.
+    pub warnings: usize,
+    /// The checks that didn't run against any input, in the order that they'd run.
+    pub skipped: Vec<SkippedCheck>,
+}
+
+/// A check that didn't run against any input.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SkippedCheck {
+    /// The check's name.
+    pub check: String,
+    /// Why it didn't run.
+    pub reason: SkipReason,
+}
+
+/// Why a check didn't run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The configuration disables it.
+    Disabled,
+    /// None of the lockfile's inputs are selected for it.
+    NoInputs,
+}
+
+impl SkipReason {
+    /// The reason's name, like `no-inputs`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Disabled => "disabled",
+            Self::NoInputs => "no-inputs",
+        }
+    }
+}
+
+impl CheckStats {
+    /// The totals of a run of the configuration's checks against a lockfile that found `issues`.
+    pub fn new(flake_lock: &FlakeLock, config: &FlakeCheckConfig, issues: &[Issue]) -> Self {
+        // Any missing Nixpkgs inputs fail the run before there are issues to count
+        let deps = selected_deps(flake_lock, config).unwrap_or_default();
+        let mut inputs_checked = BTreeSet::new();
+        let mut skipped = Vec::new();
+        for check in config.checks.iter() {
+            let reason = if check.enabled(config) {
+                let inputs: Vec<String> = check_deps(flake_lock, config, check.name(), &deps)
+                    .iter()
+                    .filter(|(_, node)| matches!(node, Node::Repo(_)))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                if !inputs.is_empty() {
+                    inputs_checked.extend(inputs);
+                    continue;
+                }
+                SkipReason::NoInputs
+            } else {
+                SkipReason::Disabled
+            };
+            skipped.push(SkippedCheck {
+                check: check.name().to_string(),
+                reason,
+            });
+        }
+
+        let mut stats = Self {
+            inputs_checked: inputs_checked.into_iter().collect(),
+            skipped,
+            ..Self::default()
+        };
+        for issue in issues {
+            *stats
+                .issues_by_kind
+                .entry(issue.kind.name().to_string())
+                .or_default() += 1;
+            match issue.severity {
+                Severity::Error => stats.errors += 1,
+                Severity::Warning => stats.warnings += 1,
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checker::{FlakeChecker, SkipReason};
+    use crate::checks::{AllowedOwners, MaxAge};
+    use crate::policy::Policy;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_builder() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let report = FlakeChecker::builder().run(&flake_lock).unwrap();
+        let kinds: Vec<&str> = report.warnings().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["disallowed", "non-upstream", "outdated"]);
+        assert!(report.passed());
+        assert_eq!(report.stats.inputs_checked, ["nixpkgs"]);
+        assert_eq!(report.stats.issues_by_kind["outdated"], 1);
+        assert_eq!((report.stats.errors, report.stats.warnings), (0, 3));
+        assert!(report.stats.skipped.is_empty());
+
+        // Only the added checks run, with the policy's fail mode and requirements
+        let policy: Policy =
+            r#"{"fail_mode": true, "requirements": {"named": "input == 'nixpkgs'"}}"#
+                .parse()
+                .unwrap();
+        let report = FlakeChecker::builder()
+            .with_policy(policy.clone())
+            .with_check(MaxAge::new(i64::MAX))
+            .with_check(AllowedOwners::new(["bitcoin-miner-org"]))
+            .run(&flake_lock)
+            .unwrap();
+        assert!(report.issues.is_empty());
+        let report = FlakeChecker::builder()
+            .with_policy(policy)
+            .with_check(AllowedOwners::new(["NixOS"]))
+            .run(&flake_lock)
+            .unwrap();
+        let kinds: Vec<&str> = report.errors().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["non-upstream"]);
+        assert!(!report.passed());
+
+        let policy: Policy =
+            r#"{"check_outdated": false, "check_scopes": {"owner": "transitive"}}"#
+                .parse()
+                .unwrap();
+        let report = FlakeChecker::builder()
+            .with_policy(policy)
+            .run(&flake_lock)
+            .unwrap();
+        let skipped: Vec<(&str, SkipReason)> = report
+            .stats
+            .skipped
+            .iter()
+            .map(|skipped| (skipped.check.as_str(), skipped.reason))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                ("max-age", SkipReason::Disabled),
+                ("owner", SkipReason::NoInputs)
+            ]
+        );
+
+        assert!(FlakeChecker::builder()
+            .with_check(MaxAge::new(30))
+            .with_check(MaxAge::new(60))
+            .build()
+            .is_err());
+        let clashing: Policy = serde_json::from_str(r#"{"rules": {"max-age": "()"}}"#).unwrap();
+        assert!(FlakeChecker::builder()
+            .with_policy(clashing)
+            .build()
+            .is_err());
+    }
+}
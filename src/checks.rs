@@ -0,0 +1,717 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{
+    iso8601, num_days_old, FlakeCheckConfig, DEVENV_NIXPKGS_OWNER, DEVENV_NIXPKGS_REF,
+    DEVENV_NIXPKGS_REPO,
+};
+use crate::issue::{CheckError, Disallowed, IssueKind, NonUpstream, Outdated, Severity};
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parse_flake_lock::RepoNode;
+
+/// A check that's run against each Nixpkgs input. Implement it to add your own checks to a
+/// [CheckRegistry].
+pub trait Check: Send + Sync {
+    /// The name of the check, as used by `--only` and `--skip`.
+    fn name(&self) -> &str;
+
+    /// The code of the issues that the check reports, like `outdated` (see [IssueKind::name]).
+    fn code(&self) -> &str;
+
+    /// A description of what the check requires of inputs, with the configuration's thresholds.
+    fn description(&self, config: &FlakeCheckConfig) -> String;
+
+    /// The severity of the check's issues outside of fail mode.
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Whether the check needs network access (which `--offline` disallows).
+    fn requires_network(&self) -> bool {
+        false
+    }
+
+    /// Whether the configuration enables the check.
+    fn enabled(&self, config: &FlakeCheckConfig) -> bool {
+        !config.disabled_checks.contains(self.name())
+    }
+
+    /// Enable or disable the check in the configuration.
+    fn set_enabled(&self, config: &mut FlakeCheckConfig, enabled: bool) {
+        if enabled {
+            config.disabled_checks.remove(self.name());
+        } else {
+            config.disabled_checks.insert(self.name().to_string());
+        }
+    }
+
+    /// Check an input (with its name in the lockfile's root), returning the kind of issue found
+    /// (if any). Only networked checks may fail.
+    fn run(
+        &self,
+        input: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError>;
+}
+
+impl dyn Check + '_ {
+    /// Check an input. A networked check that fails (after any retries) reports a `check-error`
+    /// issue rather than failing the run.
+    pub fn check(
+        &self,
+        input: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        match self.run(input, repo, config) {
+            Err(err) if self.requires_network() => Ok(Some(IssueKind::CheckError(CheckError {
+                check: self.name().to_string(),
+                error: err.to_string(),
+            }))),
+            result => result,
+        }
+    }
+}
+
+/// The checks that are run, by name, in the order in which they run. The default registry has
+/// the built-in checks.
+#[derive(Clone)]
+pub struct CheckRegistry {
+    checks: Vec<Arc<dyn Check>>,
+}
+
+impl Default for CheckRegistry {
+    fn default() -> Self {
+        Self {
+            checks: BUILTINS
+                .iter()
+                .map(|builtin| Arc::new(builtin.clone()) as Arc<dyn Check>)
+                .collect(),
+        }
+    }
+}
+
+impl CheckRegistry {
+    /// A registry without any checks, not even the built-in ones.
+    pub fn empty() -> Self {
+        Self { checks: Vec::new() }
+    }
+
+    /// Register a check to run after the checks registered before it. Fails if there's already a
+    /// check with the same name.
+    pub fn register(&mut self, check: impl Check + 'static) -> Result<(), FlakeCheckerError> {
+        if self.get(check.name()).is_some() {
+            return Err(FlakeCheckerError::Invalid(format!(
+                "there's already a check named `{}`",
+                check.name()
+            )));
+        }
+        self.checks.push(Arc::new(check));
+        Ok(())
+    }
+
+    /// Register the checks of another registry that aren't registered yet, after the others.
+    pub(crate) fn merge(&mut self, other: &CheckRegistry) {
+        for check in other.checks.iter() {
+            if self.get(check.name()).is_none() {
+                self.checks.push(check.clone());
+            }
+        }
+    }
+
+    /// The check with the given name, if it's registered.
+    pub fn get(&self, name: &str) -> Option<&dyn Check> {
+        self.iter().find(|check| check.name() == name)
+    }
+
+    /// The registered checks, in the order in which they run.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Check> {
+        self.checks.iter().map(|check| check.as_ref())
+    }
+
+    /// The severity of issues with the given code outside of fail mode, according to the check
+    /// that reports them (warnings for issues that no check reports, like formatting issues).
+    pub(crate) fn default_severity(&self, code: &str) -> Severity {
+        self.iter()
+            .find(|check| check.code() == code)
+            .map(|check| check.default_severity())
+            .unwrap_or(Severity::Warning)
+    }
+}
+
+// A built-in check, which is enabled by a flag of the configuration.
+#[derive(Clone)]
+struct Builtin {
+    name: &'static str,
+    code: &'static str,
+    description: fn(&FlakeCheckConfig) -> String,
+    enabled: fn(&FlakeCheckConfig) -> bool,
+    set_enabled: fn(&mut FlakeCheckConfig, bool),
+    requires_network: bool,
+    run: fn(&RepoNode, &FlakeCheckConfig) -> Result<Option<IssueKind>, FlakeCheckerError>,
+}
+
+impl Check for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn code(&self) -> &str {
+        self.code
+    }
+
+    fn description(&self, config: &FlakeCheckConfig) -> String {
+        (self.description)(config)
+    }
+
+    fn requires_network(&self) -> bool {
+        self.requires_network
+    }
+
+    fn enabled(&self, config: &FlakeCheckConfig) -> bool {
+        (self.enabled)(config)
+    }
+
+    fn set_enabled(&self, config: &mut FlakeCheckConfig, enabled: bool) {
+        (self.set_enabled)(config, enabled)
+    }
+
+    fn run(
+        &self,
+        _: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        (self.run)(repo, config)
+    }
+}
+
+// The built-in checks, in the order in which they run.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "refs",
+        code: "disallowed",
+        description: |config| {
+            format!(
+                "the input's Git ref must be one of {}",
+                config.allowed_refs.join(", ")
+            )
+        },
+        enabled: |config| config.check_supported,
+        set_enabled: |config, enabled| config.check_supported = enabled,
+        requires_network: false,
+        run: |repo, config| Ok(disallowed(repo, &config.allowed_refs, config)),
+    },
+    Builtin {
+        name: "max-age",
+        code: "outdated",
+        description: |config| {
+            format!(
+                "the input must have been modified at most {} days ago",
+                config.max_days
+            )
+        },
+        enabled: |config| config.check_outdated,
+        set_enabled: |config, enabled| config.check_outdated = enabled,
+        requires_network: false,
+        run: |repo, config| Ok(outdated(repo, config.max_days, config.now())),
+    },
+    Builtin {
+        name: "owner",
+        code: "non-upstream",
+        description: |config| {
+            format!(
+                "the input's GitHub owner must be one of {}",
+                config.allowed_owners.join(", ")
+            )
+        },
+        enabled: |config| config.check_owner,
+        set_enabled: |config, enabled| config.check_owner = enabled,
+        requires_network: false,
+        run: |repo, config| Ok(non_upstream(repo, &config.allowed_owners, config)),
+    },
+];
+
+fn disallowed(
+    repo: &RepoNode,
+    allowed_refs: &[String],
+    config: &FlakeCheckConfig,
+) -> Option<IssueKind> {
+    repo.original.git_ref.as_ref().and_then(|git_ref| {
+        let allowed = allowed_refs.contains(git_ref)
+            || (is_devenv_nixpkgs(repo, config) && git_ref == DEVENV_NIXPKGS_REF);
+        (!allowed).then(|| {
+            IssueKind::Disallowed(Disallowed {
+                reference: git_ref.to_string(),
+            })
+        })
+    })
+}
+
+fn outdated(repo: &RepoNode, max_days: i64, now: DateTime<Utc>) -> Option<IssueKind> {
+    let num_days_old = num_days_old(repo.locked.last_modified, now);
+    (num_days_old > max_days).then(|| {
+        IssueKind::Outdated(Outdated {
+            num_days_old,
+            max_days,
+            last_modified: repo.locked.last_modified,
+            locked_at: iso8601(repo.locked.last_modified),
+        })
+    })
+}
+
+fn non_upstream(
+    repo: &RepoNode,
+    allowed_owners: &[String],
+    config: &FlakeCheckConfig,
+) -> Option<IssueKind> {
+    if is_devenv_nixpkgs(repo, config) {
+        return None;
+    }
+    let owner = &repo.original.owner;
+    let host = repo.host();
+    let owner_allowed = allowed_owners
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(owner));
+    // An allowed owner on a self-hosted forge isn't upstream, unless the host is allowed too
+    let host_allowed = repo
+        .forge()
+        .is_some_and(|forge| forge.host().eq_ignore_ascii_case(host))
+        || config
+            .allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host));
+    (!owner_allowed || !host_allowed).then(|| {
+        IssueKind::NonUpstream(NonUpstream {
+            owner: owner.to_string(),
+            host: owner_allowed.then(|| host.to_string()),
+        })
+    })
+}
+
+// Whether an input is devenv's default Nixpkgs, in a devenv lockfile.
+fn is_devenv_nixpkgs(repo: &RepoNode, config: &FlakeCheckConfig) -> bool {
+    config.devenv
+        && repo
+            .original
+            .owner
+            .eq_ignore_ascii_case(DEVENV_NIXPKGS_OWNER)
+        && repo.original.repo == DEVENV_NIXPKGS_REPO
+}
+
+/// The `refs` check with its own allowed Git refs, rather than the configuration's, for composing
+/// checks with [FlakeChecker](crate::FlakeChecker).
+#[derive(Clone, Debug)]
+pub struct AllowedRefs {
+    refs: Vec<String>,
+}
+
+impl AllowedRefs {
+    /// A check that inputs use one of the given Git refs.
+    pub fn new<S: Into<String>>(refs: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            refs: refs.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Check for AllowedRefs {
+    fn name(&self) -> &str {
+        "refs"
+    }
+
+    fn code(&self) -> &str {
+        "disallowed"
+    }
+
+    fn description(&self, _: &FlakeCheckConfig) -> String {
+        format!(
+            "the input's Git ref must be one of {}",
+            self.refs.join(", ")
+        )
+    }
+
+    fn run(
+        &self,
+        _: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        Ok(disallowed(repo, &self.refs, config))
+    }
+}
+
+/// The `max-age` check with its own maximum age, rather than the configuration's.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxAge {
+    max_days: i64,
+}
+
+impl MaxAge {
+    /// A check that inputs were modified at most the given number of days ago.
+    pub fn new(max_days: i64) -> Self {
+        Self { max_days }
+    }
+}
+
+impl Check for MaxAge {
+    fn name(&self) -> &str {
+        "max-age"
+    }
+
+    fn code(&self) -> &str {
+        "outdated"
+    }
+
+    fn description(&self, _: &FlakeCheckConfig) -> String {
+        format!(
+            "the input must have been modified at most {} days ago",
+            self.max_days
+        )
+    }
+
+    fn run(
+        &self,
+        _: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        Ok(outdated(repo, self.max_days, config.now()))
+    }
+}
+
+/// The `owner` check with its own allowed GitHub owners, rather than the configuration's.
+#[derive(Clone, Debug)]
+pub struct AllowedOwners {
+    owners: Vec<String>,
+}
+
+impl AllowedOwners {
+    /// A check that inputs come from one of the given GitHub owners (compared
+    /// case-insensitively).
+    pub fn new<S: Into<String>>(owners: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            owners: owners.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Check for AllowedOwners {
+    fn name(&self) -> &str {
+        "owner"
+    }
+
+    fn code(&self) -> &str {
+        "non-upstream"
+    }
+
+    fn description(&self, _: &FlakeCheckConfig) -> String {
+        format!(
+            "the input's GitHub owner must be one of {}",
+            self.owners.join(", ")
+        )
+    }
+
+    fn run(
+        &self,
+        _: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        Ok(non_upstream(repo, &self.owners, config))
+    }
+}
+
+/// Disable the checks that aren't selected: those not in `only` (unless it's empty) and those in
+/// `skip`.
+pub(crate) fn select(config: &mut FlakeCheckConfig, only: &[String], skip: &[String]) {
+    for check in config.checks.clone().iter() {
+        let name = check.name().to_string();
+        if (!only.is_empty() && !only.contains(&name)) || skip.contains(&name) {
+            check.set_enabled(config, false);
+        }
+    }
+}
+
+/// Fail if `only` or `skip` name a check that isn't registered.
+pub(crate) fn require_known(
+    checks: &CheckRegistry,
+    only: &[String],
+    skip: &[String],
+) -> Result<(), FlakeCheckerError> {
+    match only
+        .iter()
+        .chain(skip)
+        .find(|name| checks.get(name).is_none())
+    {
+        Some(name) => Err(FlakeCheckerError::Invalid(format!(
+            "there's no check named `{name}` (see --list-checks)"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fail if the configuration enables any check that needs network access.
+pub(crate) fn require_offline(config: &FlakeCheckConfig) -> Result<(), FlakeCheckerError> {
+    let networked: Vec<String> = config
+        .checks
+        .iter()
+        .filter(|check| check.requires_network() && check.enabled(config))
+        .map(|check| format!("the {} check", check.name()))
+        .collect();
+    if networked.is_empty() {
+        Ok(())
+    } else {
+        Err(FlakeCheckerError::Offline(networked.join(", ")))
+    }
+}
+
+/// List the available checks and what they check, one per line.
+pub(crate) fn list(config: &FlakeCheckConfig) -> String {
+    let checks = &config.checks;
+    let width = checks
+        .iter()
+        .map(|check| check.name().len())
+        .max()
+        .unwrap_or_default();
+    checks
+        .iter()
+        .map(|check| format!("{:<width$}  {}\n", check.name(), check.description(config)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checks::{list, require_known, select, Check, CheckRegistry};
+    use crate::error::FlakeCheckerError;
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::issue::{Custom, IssueKind, NonUpstream, Severity};
+
+    use parse_flake_lock::{FlakeLock, Node, RepoNode};
+
+    fn enabled(config: &FlakeCheckConfig) -> Vec<&str> {
+        config
+            .checks
+            .iter()
+            .filter(|check| check.enabled(config))
+            .map(|check| check.name())
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let mut config = FlakeCheckConfig::default();
+        select(&mut config, &[String::from("max-age")], &[]);
+        assert_eq!(enabled(&config), ["max-age"]);
+
+        let mut config = FlakeCheckConfig::default();
+        select(&mut config, &[], &[String::from("refs")]);
+        assert_eq!(enabled(&config), ["max-age", "owner"]);
+    }
+
+    struct Unreachable;
+
+    impl Check for Unreachable {
+        fn name(&self) -> &str {
+            "unreachable"
+        }
+
+        fn code(&self) -> &str {
+            "unreachable"
+        }
+
+        fn description(&self, _: &FlakeCheckConfig) -> String {
+            String::new()
+        }
+
+        fn requires_network(&self) -> bool {
+            true
+        }
+
+        fn run(
+            &self,
+            _: &str,
+            _: &RepoNode,
+            _: &FlakeCheckConfig,
+        ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+            Err(FlakeCheckerError::Invalid(String::from("timed out")))
+        }
+    }
+
+    #[test]
+    fn test_registered_checks_are_selectable() {
+        let only = [String::from("unreachable")];
+        let mut config = FlakeCheckConfig::default();
+        assert!(require_known(&config.checks, &only, &[]).is_err());
+
+        let mut registered = CheckRegistry::empty();
+        registered.register(Unreachable).unwrap();
+        config.checks.merge(&registered);
+        config.checks.merge(&CheckRegistry::default());
+        assert!(require_known(&config.checks, &only, &[]).is_ok());
+        select(&mut config, &only, &[]);
+        assert_eq!(enabled(&config), ["unreachable"]);
+    }
+
+    #[test]
+    fn test_networked_check_errors_are_issues() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let Some(Node::Repo(repo)) = flake_lock.root.get("nixpkgs") else {
+            panic!("no nixpkgs input");
+        };
+        let config = FlakeCheckConfig::default();
+
+        let check: &dyn Check = &Unreachable;
+        let Ok(Some(IssueKind::CheckError(error))) = check.check("nixpkgs", repo, &config) else {
+            panic!("expected a check-error issue");
+        };
+        assert_eq!(error.check, "unreachable");
+        assert!(check.run("nixpkgs", repo, &config).is_err());
+    }
+
+    // A custom check that requires the NixOS owner to be spelled the way that GitHub does.
+    struct Spelling;
+
+    impl Check for Spelling {
+        fn name(&self) -> &str {
+            "spelling"
+        }
+
+        fn code(&self) -> &str {
+            "misspelled-owner"
+        }
+
+        fn description(&self, _: &FlakeCheckConfig) -> String {
+            String::from("the input's owner must be spelled NixOS")
+        }
+
+        fn default_severity(&self) -> Severity {
+            Severity::Error
+        }
+
+        fn run(
+            &self,
+            _: &str,
+            repo: &RepoNode,
+            _: &FlakeCheckConfig,
+        ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+            let owner = &repo.original.owner;
+            Ok(
+                (owner.eq_ignore_ascii_case("NixOS") && owner != "NixOS").then(|| {
+                    IssueKind::Custom(Custom {
+                        code: String::from("misspelled-owner"),
+                        message: format!("the owner `{owner}` isn't spelled NixOS"),
+                    })
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn test_custom_checks() {
+        let mut checks = CheckRegistry::default();
+        checks.register(Spelling).unwrap();
+        assert!(checks.register(Spelling).is_err());
+
+        let mut config = FlakeCheckConfig {
+            checks,
+            check_outdated: false,
+            ..FlakeCheckConfig::default()
+        };
+        assert_eq!(enabled(&config), ["refs", "owner", "spelling"]);
+
+        // flake.clean.4.lock's nixpkgs input has the owner `nixos`
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.4.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let kinds: Vec<&str> = issues.iter().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["misspelled-owner"]);
+        assert_eq!(issues[0].severity, Severity::Error);
+
+        select(&mut config, &[], &[String::from("spelling")]);
+        assert_eq!(enabled(&config), ["refs", "owner"]);
+        assert!(find_issues(&flake_lock, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_devenv_nixpkgs() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/devenv.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let Some(Node::Repo(repo)) = flake_lock.root.get("nixpkgs") else {
+            panic!("no nixpkgs input");
+        };
+
+        let issues = |config: &FlakeCheckConfig| -> Vec<String> {
+            config
+                .checks
+                .iter()
+                .filter(|check| check.name() != "max-age")
+                .filter_map(|check| check.check("nixpkgs", repo, config).unwrap())
+                .map(|kind| kind.name().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            issues(&FlakeCheckConfig::default()),
+            ["disallowed", "non-upstream"]
+        );
+        let config = FlakeCheckConfig {
+            devenv: true,
+            ..FlakeCheckConfig::default()
+        };
+        assert!(issues(&config).is_empty());
+    }
+
+    #[test]
+    fn test_self_hosted_forges() {
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.clean.0.lock").unwrap())
+                .unwrap();
+        lock["nodes"]["nixpkgs"]["original"]["host"] = "git.example.com".into();
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        let Some(Node::Repo(repo)) = flake_lock.root.get("nixpkgs") else {
+            panic!("no nixpkgs input");
+        };
+        assert_eq!(repo.url(), "https://git.example.com/NixOS/nixpkgs");
+
+        let config = FlakeCheckConfig::default();
+        let owner = config.checks.get("owner").unwrap();
+        assert_eq!(
+            owner.check("nixpkgs", repo, &config).unwrap(),
+            Some(IssueKind::NonUpstream(NonUpstream {
+                owner: String::from("NixOS"),
+                host: Some(String::from("git.example.com")),
+            }))
+        );
+        let config = FlakeCheckConfig {
+            allowed_hosts: vec![String::from("git.example.com")],
+            ..FlakeCheckConfig::default()
+        };
+        assert_eq!(owner.check("nixpkgs", repo, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_list_thresholds() {
+        let config = FlakeCheckConfig {
+            allowed_refs: vec![String::from("nixos-25.05")],
+            max_days: 90,
+            allowed_owners: vec![String::from("NixOS"), String::from("acme")],
+            ..FlakeCheckConfig::default()
+        };
+        assert_eq!(
+            list(&config),
+            "refs     the input's Git ref must be one of nixos-25.05\n\
+             max-age  the input must have been modified at most 90 days ago\n\
+             owner    the input's GitHub owner must be one of NixOS, acme\n"
+        );
+    }
+}
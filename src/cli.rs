@@ -0,0 +1,901 @@
+use crate::budget::{parse_kind_budget, Budget};
+use crate::checks;
+use crate::conflict::Resolution;
+use crate::daemon::parse_interval;
+use crate::error::FlakeCheckerError;
+use crate::fix::Engine;
+use crate::flake::{parse_time, FlakeCheckConfig};
+use crate::forecast::ForecastFormat;
+use crate::http::NetworkConfig;
+use crate::i18n::Lang;
+use crate::issue::Severity;
+use crate::list::ListFormat;
+use crate::lockfile::{InputFormat, MissingMode};
+use crate::selector::{parse_check_scope, Scope, Selector};
+use crate::stats::StatsFormat;
+use crate::summary::OutputFormat;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+
+/// A flake.lock checker for Nix projects.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+/// The policy that lockfiles are checked against.
+#[derive(Args, Clone)]
+pub(crate) struct PolicyArgs {
+    /// Check for outdated Nixpkgs inputs.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_CHECK_OUTDATED", default_value_t = true)]
+    pub check_outdated: bool,
+
+    /// Check that Nixpkgs inputs have "NixOS" as the GitHub owner.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_CHECK_OWNER", default_value_t = true)]
+    pub check_owner: bool,
+
+    /// Check that Git refs for Nixpkgs inputs are supported.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_CHECK_SUPPORTED",
+        default_value_t = true
+    )]
+    pub check_supported: bool,
+
+    /// Check that lockfiles are formatted the way that `nix flake lock` writes them.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_CHECK_FORMAT", default_value_t = true)]
+    pub check_format: bool,
+
+    /// Report fields of lockfile nodes that aren't recognized, like attributes added by a newer
+    /// version of Nix, as issues.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_STRICT_FIELDS", default_value_t = false)]
+    pub strict_fields: bool,
+
+    /// Fail with an exit code of 1 if any issues are encountered.
+    #[arg(
+        long,
+        short,
+        env = "NIX_FLAKE_CHECKER_FAIL_MODE",
+        default_value_t = false
+    )]
+    pub fail_mode: bool,
+
+    /// Report outdated inputs as warnings until they're more than this many days old, and as
+    /// errors (which fail the run) after that, regardless of --fail-mode.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_ERROR_AFTER_DAYS", value_name = "DAYS")]
+    pub error_after_days: Option<i64>,
+
+    /// Report inputs on disallowed refs as warnings until this date (like 2025-06-01), and as
+    /// errors (which fail the run) after it, regardless of --fail-mode.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_DISALLOWED_ERROR_AFTER",
+        value_name = "DATE"
+    )]
+    pub disallowed_error_after: Option<NaiveDate>,
+
+    /// Check as if it were this time, like 2024-01-01 or 2024-01-01T12:00:00Z, instead of now:
+    /// inputs' ages, and the dates that issues escalate at, are measured from it.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_AT", value_name = "TIME", value_parser = parse_time)]
+    pub at: Option<DateTime<Utc>>,
+
+    /// Nixpkgs input keys as a comma-separated list [default: the root inputs locked to a GitHub
+    /// repository named nixpkgs, whatever they're named].
+    #[arg(
+        long,
+        short,
+        env = "NIX_FLAKE_CHECKER_NIXPKGS_KEYS",
+        value_delimiter = ',',
+        name = "KEY_LIST"
+    )]
+    pub nixpkgs_keys: Vec<String>,
+
+    /// Check the inputs that match a selector, like `owner=NixOS,repo=nixpkgs` or `name=^nixpkgs`,
+    /// instead of those named by --nixpkgs-keys. Repeat to check inputs that match any of them.
+    #[arg(long = "select", value_name = "SELECTOR", value_parser = str::parse::<Selector>)]
+    pub selectors: Vec<Selector>,
+
+    /// Run a check against only `direct` inputs (the root's own), only `transitive` ones (inputs
+    /// of other inputs), or `all` of them, as check=scope (can be repeated).
+    #[arg(
+        long = "scope",
+        env = "NIX_FLAKE_CHECKER_SCOPES",
+        value_delimiter = ',',
+        value_name = "CHECK=SCOPE",
+        value_parser = parse_check_scope
+    )]
+    pub check_scopes: Vec<(String, Scope)>,
+
+    /// Only run the specified checks, as a comma-separated list (see --list-checks). These can
+    /// include the rules and requirements of policy files.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_ONLY", value_delimiter = ',')]
+    pub only: Vec<String>,
+
+    /// Skip the specified checks, as a comma-separated list (see --list-checks). These can include
+    /// the rules and requirements of policy files.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_SKIP", value_delimiter = ',')]
+    pub skip: Vec<String>,
+
+    /// External checks to run against each lockfile, as a comma-separated list of executables
+    /// that read the lockfile as JSON on stdin and write their issues as JSON on stdout.
+    #[arg(
+        long = "plugin",
+        env = "NIX_FLAKE_CHECKER_PLUGINS",
+        value_delimiter = ',',
+        value_name = "PATH"
+    )]
+    pub plugins: Vec<PathBuf>,
+
+    /// The timeout for each network request, in seconds.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_NETWORK_TIMEOUT", default_value_t = 30)]
+    pub network_timeout: u64,
+
+    /// How many times to retry network requests that fail transiently.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_RETRIES", default_value_t = 2)]
+    pub retries: u32,
+
+    /// The delay before the first retry of a network request, in milliseconds (doubling for each
+    /// subsequent retry).
+    #[arg(long, env = "NIX_FLAKE_CHECKER_RETRY_BACKOFF", default_value_t = 1000)]
+    pub retry_backoff: u64,
+
+    /// A proxy URL for all network requests. Without it, the HTTP_PROXY, HTTPS_PROXY, and NO_PROXY
+    /// environment variables are honored.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_PROXY")]
+    pub proxy: Option<String>,
+
+    /// A GitHub token for authenticating GitHub API requests (for remote lockfiles and
+    /// organization scans), which raises rate limits and allows access to private repositories.
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+    pub github_token: Option<String>,
+}
+
+impl PolicyArgs {
+    /// The configuration, with only the checks that --only and --skip select, which must be
+    /// built-in ones.
+    pub(crate) fn config(&self) -> Result<FlakeCheckConfig, FlakeCheckerError> {
+        let mut config = self.base_config();
+        checks::require_known(&config.checks, &self.only, &self.skip)?;
+        self.select(&mut config);
+        Ok(config)
+    }
+
+    /// The configuration before --only and --skip select its checks, for runs whose policy files
+    /// register checks of their own.
+    pub(crate) fn base_config(&self) -> FlakeCheckConfig {
+        FlakeCheckConfig {
+            check_supported: self.check_supported,
+            check_outdated: self.check_outdated,
+            check_owner: self.check_owner,
+            check_format: self.check_format,
+            strict_fields: self.strict_fields,
+            fail_mode: self.fail_mode,
+            nixpkgs_keys: self.nixpkgs_keys.clone(),
+            selectors: self.selectors.clone(),
+            check_scopes: self.check_scopes.iter().cloned().collect(),
+            error_after_days: self.error_after_days,
+            disallowed_error_after: self.disallowed_error_after,
+            now: self.at,
+            plugins: self.plugins.clone(),
+            budget: Budget::default(),
+            devenv: false,
+            network: NetworkConfig {
+                timeout: Duration::from_secs(self.network_timeout),
+                retries: self.retries,
+                backoff: Duration::from_millis(self.retry_backoff),
+                proxy: self.proxy.clone(),
+                github_token: self.github_token.clone(),
+            },
+            ..FlakeCheckConfig::default()
+        }
+    }
+
+    /// Disable the checks that --only and --skip don't select.
+    pub(crate) fn select(&self, config: &mut FlakeCheckConfig) {
+        checks::select(config, &self.only, &self.skip);
+    }
+}
+
+/// Arguments for checking lockfiles (the `check` subcommand and the default invocation).
+#[derive(Args)]
+pub(crate) struct CheckArgs {
+    #[command(flatten)]
+    pub policy: PolicyArgs,
+
+    /// Don't send aggregate sums of each issue type.
+    ///
+    /// See <https://github.com/determinateSystems/flake-checker>.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_NO_TELEMETRY", default_value_t = false)]
+    pub no_telemetry: bool,
+
+    /// Ignore a missing flake.lock file.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_IGNORE_MISSING_FLAKE_LOCK",
+        default_value_t = true
+    )]
+    pub ignore_missing_flake_lock: bool,
+
+    /// What to do about lockfiles that don't exist: fail the run, report a `missing` issue that
+    /// doesn't fail it, or skip them silently. Overrides --ignore-missing-flake-lock.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_IGNORE_MISSING",
+        value_enum,
+        value_name = "MODE"
+    )]
+    pub ignore_missing: Option<MissingMode>,
+
+    /// The paths to the flake.lock files to check, with `-` for stdin [default: flake.lock].
+    #[arg(env = "NIX_FLAKE_CHECKER_FLAKE_LOCK_PATH", value_delimiter = ',')]
+    pub flake_lock_paths: Vec<PathBuf>,
+
+    /// The format of the lockfiles. Each format has its own default path, and for formats other
+    /// than `flake`, the commit time of each GitHub pin is looked up via the GitHub API.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_FORMAT_IN",
+        value_enum,
+        default_value_t = InputFormat::Flake
+    )]
+    pub format_in: InputFormat,
+
+    /// Check every flake.lock file beneath this directory (respecting .gitignore files).
+    #[arg(long, env = "NIX_FLAKE_CHECKER_RECURSIVE", value_name = "DIR")]
+    pub recursive: Option<PathBuf>,
+
+    /// Check the flake.lock files matching this glob pattern (can be repeated). Patterns prefixed
+    /// with `!` exclude matching files.
+    #[arg(
+        long = "glob",
+        env = "NIX_FLAKE_CHECKER_GLOB",
+        value_delimiter = ',',
+        value_name = "PATTERN",
+        conflicts_with = "recursive"
+    )]
+    pub glob_patterns: Vec<String>,
+
+    /// Check the flake.lock of a remote flake, as a `github:owner/repo[/ref]` or
+    /// `flakehub:org/flake[/version]` flake reference or an HTTPS URL (can be repeated).
+    #[arg(
+        long = "remote",
+        env = "NIX_FLAKE_CHECKER_REMOTE",
+        value_delimiter = ',',
+        value_name = "REFERENCE"
+    )]
+    pub remotes: Vec<String>,
+
+    /// Resolve merge conflicts in local lockfiles before checking them, by keeping our or their
+    /// side of each conflict, or by keeping ours and then running `nix flake lock`.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_RESOLVE",
+        value_enum,
+        value_name = "STRATEGY"
+    )]
+    pub resolve: Option<Resolution>,
+
+    /// Fix the issues in local lockfiles before checking them, by running `nix flake update` for
+    /// outdated inputs and `nix flake lock --override-input` to move inputs on disallowed refs to
+    /// the closest supported ref.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_FIX", default_value_t = false)]
+    pub fix: bool,
+
+    /// Rewrite the URLs in flake.nix that point at disallowed refs to the closest supported refs,
+    /// so that the next `nix flake lock` doesn't undo the fix. Only the refs are changed.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_FIX_FLAKE_NIX", default_value_t = false)]
+    pub fix_flake_nix: bool,
+
+    /// Open a GitHub pull request with the lockfiles that --fix updated (or update the one that's
+    /// already open), with a description of the fixed issues. Needs a GitHub token that can push
+    /// to the repository.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_CREATE_PR",
+        default_value_t = false,
+        requires = "fix"
+    )]
+    pub create_pr: bool,
+
+    /// The branch that --create-pr pushes to.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_PR_BRANCH",
+        default_value = "flake-checker/fix",
+        value_name = "BRANCH"
+    )]
+    pub pr_branch: String,
+
+    /// How --fix updates lockfiles: with the Nix CLI, or in place, by looking up the latest
+    /// revisions on GitHub, for environments without Nix.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_FIX_ENGINE",
+        value_enum,
+        default_value_t = Engine::Nix
+    )]
+    pub fix_engine: Engine,
+
+    /// Print the `nix flake lock` or `nix flake update` command that would resolve each issue,
+    /// and include it in the `jsonl` output.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_SUGGEST", default_value_t = false)]
+    pub suggest: bool,
+
+    /// Rewrite local lockfiles that aren't canonically formatted the way that `nix flake lock`
+    /// writes them before checking them.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_FIX_FORMAT", default_value_t = false)]
+    pub fix_format: bool,
+
+    /// Read local flake.lock files as of this git revision instead of from the working tree.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_GIT_REF", value_name = "REV")]
+    pub git_ref: Option<String>,
+
+    /// Exempt inputs that were added to local lockfiles within this many days (according to
+    /// their git history) from the max-age check, so that newly adopted inputs aren't held to
+    /// the same standard as mature ones right away.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_NEW_INPUT_GRACE_DAYS",
+        value_name = "DAYS"
+    )]
+    pub new_input_grace_days: Option<i64>,
+
+    /// For --new-input-grace-days, the lockfile as it was at the start of the grace period
+    /// (inputs that it doesn't have are new), instead of looking it up in the git history.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_PREVIOUS_LOCK",
+        value_name = "FILE",
+        requires = "new_input_grace_days"
+    )]
+    pub previous_lock: Option<PathBuf>,
+
+    /// Resolve indirect inputs (like `inputs.nixpkgs.url = "nixpkgs";`) through the flake
+    /// registries and check the sources they point to, which are otherwise skipped. The user and
+    /// system registries are consulted, then the global registry unless --offline is given.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_RESOLVE_INDIRECT",
+        default_value_t = false
+    )]
+    pub resolve_indirect: bool,
+
+    /// The registry.json files to resolve indirect inputs through, instead of the user and system
+    /// registries.
+    #[arg(long = "registry", value_name = "FILE", requires = "resolve_indirect")]
+    pub registry_paths: Vec<PathBuf>,
+
+    /// Guarantee that the run doesn't access the network, failing if any of the selected checks,
+    /// lockfiles, or integrations would. Implies --no-telemetry.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_OFFLINE", default_value_t = false)]
+    pub offline: bool,
+
+    /// Re-run the checks whenever a local lockfile changes.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_WATCH", default_value_t = false)]
+    pub watch: bool,
+
+    /// Browse the inputs and issues of each lockfile interactively in the terminal.
+    #[arg(long, default_value_t = false)]
+    pub tui: bool,
+
+    /// Print which checks would run, with their thresholds and the inputs they'd consider,
+    /// without running them.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_EXPLAIN", default_value_t = false)]
+    pub explain: bool,
+
+    /// Print details about each lockfile to stderr as it's checked, like the fields of its nodes
+    /// that aren't recognized.
+    #[arg(
+        long,
+        short,
+        env = "NIX_FLAKE_CHECKER_VERBOSE",
+        default_value_t = false
+    )]
+    pub verbose: bool,
+
+    /// List the available checks (for --only and --skip), including those that the policy files
+    /// of --recursive register, and exit.
+    #[arg(long, default_value_t = false)]
+    pub list_checks: bool,
+
+    /// A baseline file of known issues, which are reported separately and don't cause failures.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_BASELINE", value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// Write all current issues to the baseline file instead of checking against it.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_WRITE_BASELINE",
+        default_value_t = false,
+        requires = "baseline"
+    )]
+    pub write_baseline: bool,
+
+    /// Display Markdown summary (in GitHub Actions).
+    #[arg(
+        long,
+        short,
+        env = "NIX_FLAKE_CHECKER_MARKDOWN_SUMMARY",
+        default_value_t = true
+    )]
+    pub markdown_summary: bool,
+
+    /// Print the results in the specified format instead of the default summary.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_FORMAT", value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// The language to render issue messages and summaries in.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+    pub lang: Lang,
+
+    /// Publish a Bitbucket Code Insights report for the current commit (in Bitbucket Pipelines).
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_BITBUCKET_REPORT",
+        default_value_t = false
+    )]
+    pub bitbucket_report: bool,
+
+    /// A Bitbucket access token for publishing reports outside of Bitbucket Pipelines.
+    #[arg(long, env = "BITBUCKET_ACCESS_TOKEN", hide_env_values = true)]
+    pub bitbucket_token: Option<String>,
+
+    /// Post a commit status for the current commit via the Gitea/Forgejo API.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_SET_COMMIT_STATUS",
+        default_value_t = false,
+        requires = "gitea_token"
+    )]
+    pub set_commit_status: bool,
+
+    /// A Gitea/Forgejo access token for posting commit statuses.
+    #[arg(long, env = "GITEA_TOKEN", hide_env_values = true)]
+    pub gitea_token: Option<String>,
+
+    /// The URL that the commit status links to (defaults to the current Actions run).
+    #[arg(long, env = "NIX_FLAKE_CHECKER_COMMIT_STATUS_TARGET_URL")]
+    pub commit_status_target_url: Option<String>,
+
+    /// A Microsoft Teams incoming webhook URL to post a summary of issues to.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_TEAMS_WEBHOOK_URL",
+        hide_env_values = true
+    )]
+    pub teams_webhook_url: Option<String>,
+
+    /// The minimum severity of issues that triggers a Teams notification.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_TEAMS_MIN_SEVERITY",
+        value_enum,
+        default_value_t = Severity::Warning
+    )]
+    pub teams_min_severity: Severity,
+
+    /// Only report issues of the specified kinds (as a comma-separated list).
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_ONLY_KIND",
+        value_delimiter = ',',
+        value_parser = [
+            "disallowed",
+            "outdated",
+            "non-upstream",
+            "check-error",
+            "non-canonical",
+            "missing",
+            "unknown-fields"
+        ]
+    )]
+    pub only_kind: Vec<String>,
+
+    /// Only report issues at or above the specified severity.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_MIN_SEVERITY", value_enum)]
+    pub min_severity: Option<Severity>,
+
+    /// Only report issues for inputs matching the specified glob pattern (can be repeated).
+    #[arg(long = "input", env = "NIX_FLAKE_CHECKER_INPUT", value_delimiter = ',')]
+    pub input_patterns: Vec<String>,
+
+    /// The maximum number of issues to render in the summary.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_MAX_ISSUES")]
+    pub max_issues: Option<usize>,
+
+    /// List the N oldest inputs and a histogram of input ages in the summary, whether or not they
+    /// exceed the maximum age.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_TOP", value_name = "N")]
+    pub top: Option<usize>,
+
+    /// Show one entry per input with all of its findings in the summary, instead of a section per
+    /// kind of issue.
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_GROUP_BY_INPUT",
+        default_value_t = false
+    )]
+    pub group_by_input: bool,
+
+    /// A directory of Handlebars partials (`*.hbs`) and Rhai helpers (`*.rhai`) to use in the
+    /// summary and message templates.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_TEMPLATE_DIR", value_name = "DIR")]
+    pub template_dir: Option<PathBuf>,
+
+    /// In fail mode, only fail if more than this many issues are found.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_MAX_ALLOWED_ISSUES")]
+    pub max_allowed_issues: Option<usize>,
+
+    /// In fail mode, only fail if more than N issues of a kind are found, as kind=N (can be
+    /// repeated).
+    #[arg(
+        long,
+        env = "NIX_FLAKE_CHECKER_MAX_ALLOWED_ISSUES_PER_KIND",
+        value_delimiter = ',',
+        value_parser = parse_kind_budget
+    )]
+    pub max_allowed_issues_per_kind: Vec<(String, usize)>,
+
+    /// Append a record of the run (the hashes of each lockfile and its policy, and the issues
+    /// found) to this audit log, which chains each record to the one before it.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_AUDIT_LOG", value_name = "FILE")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Compare the issues with a previous run's results (as written by --format jsonl) and
+    /// highlight the regressions and fixes in the summary.
+    #[arg(long, env = "NIX_FLAKE_CHECKER_COMPARE_TO", value_name = "FILE")]
+    pub compare_to: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Check lockfiles against the policy (the default when no subcommand is given).
+    Check(Box<CheckArgs>),
+    /// Check lockfiles and print the results in a machine-readable format, like `report html`
+    /// (the same as `check --format`). Takes the same options as `check`.
+    Report {
+        /// The format to print the results in.
+        #[arg(value_enum, value_name = "FORMAT")]
+        report_format: OutputFormat,
+
+        #[command(flatten)]
+        check: Box<CheckArgs>,
+    },
+    /// Fix the issues in local lockfiles and then check them (the same as `check --fix`). Takes
+    /// the same options as `check`.
+    Fix {
+        #[command(flatten)]
+        check: Box<CheckArgs>,
+    },
+    /// Compare two lockfiles and print the changed inputs and the issues introduced or resolved,
+    /// as Markdown.
+    Diff {
+        /// The lockfile before the change (`-` for stdin).
+        old: PathBuf,
+        /// The lockfile after the change (`-` for stdin).
+        new: PathBuf,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages and summaries in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+    },
+    /// Forecast how many days remain until each checked input exceeds the maximum age and until
+    /// its release branch reaches end of life.
+    Forecast {
+        /// The path to the flake.lock file to forecast (`-` for stdin).
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The format to print the forecast in.
+        #[arg(long, value_enum, default_value_t = ForecastFormat::Table)]
+        format: ForecastFormat,
+    },
+    /// Print a breakdown of a lockfile's nodes: their types, owners, direct and transitive
+    /// inputs, Nixpkgs revisions, and ages.
+    Stats {
+        /// The path to the flake.lock file to describe (`-` for stdin).
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+
+        /// The format to print the statistics in.
+        #[arg(long, value_enum, default_value_t = StatsFormat::Table)]
+        format: StatsFormat,
+    },
+    /// Print the JSON Schema of the issues in the `jsonl` output.
+    Schema,
+    /// Explain why a node is in a lockfile, by printing the chains of inputs from the root that
+    /// lead to it.
+    Why {
+        /// The node, by its key in the lockfile's `nodes` (like `nixpkgs_2`) or the name of a
+        /// root input.
+        node: String,
+
+        /// The path to the flake.lock file (`-` for stdin).
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+    },
+    /// List a lockfile's nodes that match the given filters, with their key fields.
+    List {
+        /// The path to the flake.lock file to query (`-` for stdin).
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+
+        /// Only list nodes of this type (like `github`, `path`, or `tarball`).
+        #[arg(long = "type")]
+        node_type: Option<String>,
+
+        /// Only list nodes with this owner (ignoring case).
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Only list nodes last modified more than this long ago, in days (like `30d`) or weeks
+        /// (like `4w`).
+        #[arg(long, value_parser = crate::list::parse_age, value_name = "AGE")]
+        older_than: Option<i64>,
+
+        /// The format to print the nodes in.
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Check that the entries of flake registries point to allowed sources, since a poisoned
+    /// registry affects every flake on the machine that uses it.
+    Registry {
+        /// The registry.json files to check [default: the user and system registries].
+        paths: Vec<PathBuf>,
+
+        /// Only allow entries for repositories with these owners (comma-separated).
+        #[arg(long = "allowed-owner", value_delimiter = ',', value_name = "OWNER")]
+        allowed_owners: Vec<String>,
+
+        /// Only allow entries with these flake reference types, like `github` (comma-separated).
+        #[arg(long = "allowed-type", value_delimiter = ',', value_name = "TYPE")]
+        allowed_types: Vec<String>,
+
+        /// Only allow entries fetched from these hosts, like `github.com` (comma-separated).
+        #[arg(long = "allowed-host", value_delimiter = ',', value_name = "HOST")]
+        allowed_hosts: Vec<String>,
+
+        /// Fail if any registry entry isn't allowed.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_FAIL_MODE", default_value_t = false)]
+        fail_mode: bool,
+    },
+    /// Serve an HTTP API for checking lockfiles (`POST /check`).
+    Serve {
+        /// The address to listen on.
+        #[arg(
+            long,
+            env = "NIX_FLAKE_CHECKER_LISTEN",
+            default_value = "127.0.0.1:8080"
+        )]
+        listen: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages and summaries in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+    },
+    /// Run a GitHub App that checks the lockfiles changed by pushes and pull requests and reports
+    /// the results as check runs.
+    GithubApp {
+        /// The address to listen for webhook events on.
+        #[arg(
+            long,
+            env = "NIX_FLAKE_CHECKER_LISTEN",
+            default_value = "127.0.0.1:8080"
+        )]
+        listen: String,
+
+        /// The GitHub App's ID.
+        #[arg(long, env = "GITHUB_APP_ID")]
+        app_id: String,
+
+        /// The path to the GitHub App's private key (in PEM format).
+        #[arg(long, env = "GITHUB_APP_PRIVATE_KEY_PATH")]
+        private_key: PathBuf,
+
+        /// The GitHub App's webhook secret.
+        #[arg(long, env = "GITHUB_APP_WEBHOOK_SECRET", hide_env_values = true)]
+        webhook_secret: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages and summaries in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+    },
+    /// Run a language server on stdio that reports issues in open flake.lock files as
+    /// diagnostics.
+    Lsp {
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+    },
+    /// Check the flake.lock of every repository in a GitHub organization and rank them by their
+    /// number of issues (pass --github-token to include private repositories).
+    Org {
+        /// The name of the GitHub organization.
+        org: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages and summaries in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+
+        /// Print the results in the specified format instead of the default summary.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_FORMAT", value_enum)]
+        format: Option<OutputFormat>,
+
+        /// The maximum number of issues to render in the summary.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_MAX_ISSUES")]
+        max_issues: Option<usize>,
+    },
+    /// Diagnose the environment that lockfiles are checked in: the lockfile, the policy file, the
+    /// GitHub step summary, the GitHub API's rate limit, and the endpoints that a run would reach.
+    Doctor {
+        /// The path to the flake.lock file that would be checked.
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// Don't diagnose the telemetry endpoint.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_NO_TELEMETRY", default_value_t = false)]
+        no_telemetry: bool,
+
+        /// Skip the diagnoses that need network access.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_OFFLINE", default_value_t = false)]
+        offline: bool,
+    },
+    /// Re-check lockfiles periodically and notify a webhook (and update a metrics file) when their
+    /// issues change, until interrupted.
+    Daemon {
+        /// The paths to the flake.lock files to check [default: flake.lock].
+        flake_lock_paths: Vec<PathBuf>,
+
+        /// Also check the flake.lock of a remote flake, as for the check command (can be
+        /// repeated).
+        #[arg(long = "remote", value_delimiter = ',', value_name = "REFERENCE")]
+        remotes: Vec<String>,
+
+        /// How often to re-check the lockfiles, like 6h or 30m.
+        #[arg(
+            long,
+            env = "NIX_FLAKE_CHECKER_INTERVAL",
+            default_value = "6h",
+            value_parser = parse_interval
+        )]
+        interval: Duration,
+
+        /// A URL to POST a JSON notification to whenever a lockfile's issues change.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_WEBHOOK_URL")]
+        webhook_url: Option<String>,
+
+        /// A file to write Prometheus metrics to after each round of checks (for the textfile
+        /// collector).
+        #[arg(long, env = "NIX_FLAKE_CHECKER_METRICS_FILE", value_name = "FILE")]
+        metrics_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// The language to render issue messages in.
+        #[arg(long, env = "NIX_FLAKE_CHECKER_LANG", value_enum, default_value_t = Lang::En)]
+        lang: Lang,
+    },
+    /// Manage the git pre-commit hook.
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+    /// Work with audit logs written by --audit-log.
+    AuditLog {
+        #[command(subcommand)]
+        action: AuditLogAction,
+    },
+    /// Work with baselines of known issues.
+    Baseline {
+        #[command(subcommand)]
+        action: BaselineAction,
+    },
+    /// Work with policy files.
+    Policy {
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum HookAction {
+    /// Install a pre-commit hook that checks staged flake.lock files.
+    Install {
+        /// Overwrite an existing pre-commit hook.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum BaselineAction {
+    /// Check lockfiles and record all of the issues found in a baseline file, which later runs
+    /// with --baseline report as known rather than failing on them. Takes the same options as
+    /// `check`.
+    Create {
+        /// The baseline file to write (overwriting it if it exists).
+        #[arg(
+            long,
+            default_value = "flake-checker-baseline.json",
+            value_name = "FILE"
+        )]
+        output: PathBuf,
+
+        #[command(flatten)]
+        check: Box<CheckArgs>,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum PolicyAction {
+    /// Print the policy that a lockfile is checked against, as JSON: the policy options, overridden
+    /// by the nearest flake-checker.json in the lockfile's directory or its parents (up to the
+    /// current directory).
+    Show {
+        /// The path to the flake.lock file.
+        #[arg(default_value = "flake.lock")]
+        flake_lock_path: PathBuf,
+
+        /// Show the policy that `--recursive DIR` would check the lockfile against, from the
+        /// nearest policy file up to this directory.
+        #[arg(long, value_name = "DIR")]
+        recursive: Option<PathBuf>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+    /// Generate a starting policy from existing lockfiles, which allows the refs and owners of
+    /// their Nixpkgs inputs and their current age (rounded up), to tighten over time.
+    FromLock {
+        /// The paths to the flake.lock files to inspect.
+        #[arg(default_value = "flake.lock")]
+        flake_lock_paths: Vec<PathBuf>,
+
+        /// Write the policy to this file (like flake-checker.json) instead of printing it.
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum AuditLogAction {
+    /// Verify that no records have been edited or removed from an audit log.
+    Verify {
+        /// The path to the audit log.
+        path: PathBuf,
+    },
+}
@@ -0,0 +1,145 @@
+use crate::error::FlakeCheckerError;
+use crate::issue::Issue;
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// An issue from a previous run's results, as written by `--format jsonl`.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct PreviousIssue {
+    pub path: String,
+    pub input: String,
+    pub kind: String,
+    pub message: String,
+}
+
+/// The issues that a previous run found, for highlighting what's changed since.
+#[derive(Debug, Default)]
+pub(crate) struct PreviousResults {
+    pub issues: Vec<PreviousIssue>,
+}
+
+/// How a lockfile's issues differ from the previous run's.
+#[derive(Debug, Default)]
+pub(crate) struct Comparison {
+    /// Issues that the previous run didn't find.
+    pub regressions: Vec<Issue>,
+    /// Issues that the previous run found but this run didn't.
+    pub fixes: Vec<PreviousIssue>,
+}
+
+impl PreviousResults {
+    /// Load a previous run's results, either as JSON Lines (as written by `--format jsonl`) or as
+    /// a JSON array of the same objects.
+    pub(crate) fn load(path: &Path) -> Result<Self, FlakeCheckerError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse(results: &str) -> Result<Self, FlakeCheckerError> {
+        let lines: Vec<serde_json::Value> = if results.trim_start().starts_with('[') {
+            serde_json::from_str(results)?
+        } else {
+            results
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()?
+        };
+        // Skip the totals that follow each lockfile's issues
+        let issues = lines
+            .into_iter()
+            .filter(|line| line["record"] != "totals")
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { issues })
+    }
+
+    /// Compare a lockfile's issues with the previous run's issues for the same lockfile.
+    pub(crate) fn compare(&self, flake_lock_path: &Path, issues: &[Issue]) -> Comparison {
+        let previous: Vec<&PreviousIssue> = self
+            .issues
+            .iter()
+            .filter(|previous| Path::new(&previous.path) == flake_lock_path)
+            .collect();
+        let same = |previous: &PreviousIssue, issue: &Issue| {
+            previous.input == issue.input && previous.kind == issue.kind.name()
+        };
+
+        Comparison {
+            regressions: issues
+                .iter()
+                .filter(|issue| !previous.iter().any(|previous| same(previous, issue)))
+                .cloned()
+                .collect(),
+            fixes: previous
+                .into_iter()
+                .filter(|previous| !issues.iter().any(|issue| same(previous, issue)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compare::PreviousResults;
+    use crate::issue::{Disallowed, Issue, IssueKind, NonUpstream, Severity};
+
+    use std::path::Path;
+
+    #[test]
+    fn test_compare() {
+        let results = r#"
+{"path":"flake.lock","input":"nixpkgs","kind":"disallowed","details":{},"message":"old","known":false}
+{"path":"flake.lock","input":"nixpkgs","kind":"outdated","details":{},"message":"fixed","known":false}
+{"path":"other/flake.lock","input":"nixpkgs","kind":"non-upstream","details":{},"message":"elsewhere","known":false}
+{"path":"flake.lock","record":"totals","inputs_checked":["nixpkgs"],"errors":0,"warnings":2}
+"#;
+        let previous = PreviousResults::parse(results).unwrap();
+        let issues = [
+            Issue {
+                input: String::from("nixpkgs"),
+                kind: IssueKind::Disallowed(Disallowed {
+                    reference: String::from("release-21.05"),
+                }),
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
+            },
+            Issue {
+                input: String::from("nixpkgs"),
+                kind: IssueKind::NonUpstream(NonUpstream {
+                    owner: String::from("someone"),
+                    host: None,
+                }),
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
+            },
+        ];
+
+        let comparison = previous.compare(Path::new("flake.lock"), &issues);
+        let regressions: Vec<&str> = comparison
+            .regressions
+            .iter()
+            .map(|issue| issue.kind.name())
+            .collect();
+        assert_eq!(regressions, ["non-upstream"]);
+        let fixes: Vec<&str> = comparison
+            .fixes
+            .iter()
+            .map(|fix| fix.message.as_str())
+            .collect();
+        assert_eq!(fixes, ["fixed"]);
+
+        let array = PreviousResults::parse(&format!(
+            "[{}]",
+            results.trim().lines().collect::<Vec<_>>().join(",")
+        ))
+        .unwrap();
+        assert_eq!(array.issues.len(), 3);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::FlakeCheckerError;
+
+use std::path::Path;
+use std::process::Command;
+
+use parse_flake_lock::{conflict_marker_lines, FlakeLock};
+
+/// How to resolve merge conflicts in a lockfile.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub(crate) enum Resolution {
+    /// Keep our side of each conflict.
+    Ours,
+    /// Keep their side of each conflict.
+    Theirs,
+    /// Keep our side of each conflict, then let `nix flake lock` lock any inputs that flake.nix
+    /// needs but the result lacks.
+    Regenerate,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Both,
+    Ours,
+    Base,
+    Theirs,
+}
+
+// Keep one side of each conflict, dropping the markers (and any diff3-style base sections).
+fn keep_side(source: &str, keep_ours: bool) -> String {
+    let mut side = Side::Both;
+    let mut resolved = String::new();
+
+    for line in source.split_inclusive('\n') {
+        let next = if line.starts_with("<<<<<<<") {
+            Some(Side::Ours)
+        } else if line.starts_with("|||||||") {
+            Some(Side::Base)
+        } else if line.starts_with("=======") {
+            Some(Side::Theirs)
+        } else if line.starts_with(">>>>>>>") {
+            Some(Side::Both)
+        } else {
+            None
+        };
+
+        match next {
+            Some(next) => side = next,
+            None if side == Side::Both
+                || (side == Side::Ours && keep_ours)
+                || (side == Side::Theirs && !keep_ours) =>
+            {
+                resolved.push_str(line)
+            }
+            None => {}
+        }
+    }
+
+    resolved
+}
+
+/// Resolve the merge conflicts in a local lockfile, if it has any, returning whether it did.
+/// Fails (leaving the lockfile as is) if the resolved lockfile isn't valid.
+pub(crate) fn resolve(path: &Path, resolution: Resolution) -> Result<bool, FlakeCheckerError> {
+    let source = std::fs::read_to_string(path)?;
+    if conflict_marker_lines(&source).is_empty() {
+        return Ok(false);
+    }
+
+    let resolved = keep_side(&source, resolution != Resolution::Theirs);
+    resolved.parse::<FlakeLock>()?;
+    std::fs::write(path, resolved)?;
+
+    if resolution == Resolution::Regenerate {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let status = Command::new("nix")
+            .args(["flake", "lock"])
+            .current_dir(dir)
+            .status()?;
+        if !status.success() {
+            return Err(FlakeCheckerError::Invalid(format!(
+                "`nix flake lock` failed in {}",
+                dir.display()
+            )));
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::conflict::keep_side;
+
+    use parse_flake_lock::{FlakeLock, FlakeLockParseError};
+
+    #[test]
+    fn test_conflicts() {
+        let clean = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let rev = "\"rev\": \"";
+        let start = clean.find(rev).unwrap();
+        let line_start = clean[..start].rfind('\n').unwrap() + 1;
+        let line_end = start + clean[start..].find('\n').unwrap() + 1;
+        let ours = &clean[line_start..line_end];
+        let theirs = ours.replace(rev, &format!("{rev}0"));
+
+        let conflicted = format!(
+            "{}<<<<<<< HEAD\n{ours}=======\n{theirs}>>>>>>> update\n{}",
+            &clean[..line_start],
+            &clean[line_end..]
+        );
+
+        let line = clean[..line_start].lines().count() + 1;
+        match conflicted.parse::<FlakeLock>() {
+            Err(FlakeLockParseError::Conflict(lines)) => {
+                assert_eq!(lines, [line, line + 2, line + 4])
+            }
+            other => panic!("expected a conflict error, got {other:?}"),
+        }
+
+        assert_eq!(keep_side(&conflicted, true), clean);
+        assert_eq!(
+            keep_side(&conflicted, false),
+            clean.replacen(rev, &format!("{rev}0"), 1)
+        );
+    }
+}
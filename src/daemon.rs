@@ -0,0 +1,228 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::http;
+use crate::i18n::Lang;
+use crate::issue::Issue;
+use crate::lockfile::Lockfile;
+use crate::summary::{Report, Summary};
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use parse_flake_lock::{FlakeLock, FlakeLockParseError};
+use serde_json::json;
+
+/// Where the daemon reports to.
+pub(crate) struct Sinks {
+    /// A URL to POST a JSON notification to whenever a lockfile's issues change.
+    pub webhook_url: Option<String>,
+    /// A file to write metrics to after each round, for Prometheus' textfile collector.
+    pub metrics_file: Option<PathBuf>,
+}
+
+// The state of a lockfile as of a round of checks: its issues, by input and kind, or why it
+// couldn't be checked.
+type State = Result<BTreeSet<IssueKey>, String>;
+
+// An issue's input and kind.
+type IssueKey = (String, String);
+
+/// Parse an interval like `6h`, `30m`, `45s`, or `1d`.
+pub(crate) fn parse_interval(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid interval `{value}` (expected a duration like 6h or 30m)");
+    let seconds = match value.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let number = &value[..value.len() - 1];
+    match number.parse::<u64>() {
+        Ok(number) if number > 0 => Ok(Duration::from_secs(number * seconds)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Re-check the lockfiles every `interval` until interrupted, notifying the webhook about each
+/// lockfile whose issues change (after the first round, which establishes their initial state).
+pub(crate) fn run(
+    lockfiles: Vec<Lockfile>,
+    config: FlakeCheckConfig,
+    lang: Lang,
+    interval: Duration,
+    sinks: Sinks,
+) -> Result<(), FlakeCheckerError> {
+    let mut states: HashMap<PathBuf, State> = HashMap::new();
+
+    loop {
+        let mut summaries = Vec::new();
+        for lockfile in lockfiles.iter() {
+            let path = lockfile.display_path();
+            let (state, issues) = match check(lockfile, &config) {
+                Ok((flake_lock, source, issues)) => {
+                    let state = Ok(issues
+                        .iter()
+                        .map(|issue| (issue.input.clone(), issue.kind.name().to_string()))
+                        .collect());
+                    summaries.push(Summary::new(
+                        &issues,
+                        &flake_lock,
+                        &source,
+                        path.clone(),
+                        config.clone(),
+                        lang,
+                        None,
+                    ));
+                    (state, issues)
+                }
+                Err(err) => (Err(err.to_string()), Vec::new()),
+            };
+
+            if let Some(previous) = states.get(&path).filter(|previous| **previous != state) {
+                println!("{}: {}", path.display(), describe(previous, &state));
+                if let Some(url) = &sinks.webhook_url {
+                    let notification = notification(&path, previous, &state, &issues, lang);
+                    if let Err(err) = notify(url, &notification, &config) {
+                        eprintln!("couldn't notify {url}: {err}");
+                    }
+                }
+            }
+            states.insert(path, state);
+        }
+
+        if let Some(metrics_file) = &sinks.metrics_file {
+            // Write atomically, so that the collector never reads a partial file
+            let partial = metrics_file.with_extension("prom.tmp");
+            std::fs::write(&partial, Report::new(summaries).prometheus())?;
+            std::fs::rename(&partial, metrics_file)?;
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+fn check(
+    lockfile: &Lockfile,
+    config: &FlakeCheckConfig,
+) -> Result<(FlakeLock, String, Vec<Issue>), FlakeCheckerError> {
+    let source = lockfile.read(&config.network)?.ok_or_else(|| {
+        FlakeCheckerError::Invalid(format!(
+            "no flake lockfile found at {:?}",
+            lockfile.display_path()
+        ))
+    })?;
+    let flake_lock: FlakeLock = source
+        .parse()
+        .map_err(|err: FlakeLockParseError| err.with_path(&lockfile.display_path()))?;
+    let issues = find_issues(&flake_lock, config)?;
+    Ok((flake_lock, source, issues))
+}
+
+// The issues in `current` that aren't in `previous`, and the other way around (or neither, if the
+// lockfile couldn't be checked this time).
+fn changes(previous: &State, current: &State) -> (Vec<IssueKey>, Vec<IssueKey>) {
+    let empty = BTreeSet::new();
+    let previous = previous.as_ref().unwrap_or(&empty);
+    let Ok(current) = current else {
+        return (Vec::new(), Vec::new());
+    };
+    (
+        current.difference(previous).cloned().collect(),
+        previous.difference(current).cloned().collect(),
+    )
+}
+
+fn describe(previous: &State, current: &State) -> String {
+    match current {
+        Err(err) => format!("couldn't be checked: {err}"),
+        Ok(_) => {
+            let (new, fixed) = changes(previous, current);
+            format!("{} new issue(s), {} fixed", new.len(), fixed.len())
+        }
+    }
+}
+
+fn notification(
+    path: &std::path::Path,
+    previous: &State,
+    current: &State,
+    issues: &[Issue],
+    lang: Lang,
+) -> serde_json::Value {
+    let (new, fixed) = changes(previous, current);
+    let pairs = |pairs: Vec<IssueKey>| -> Vec<serde_json::Value> {
+        pairs
+            .into_iter()
+            .map(|(input, kind)| json!({ "input": input, "kind": kind }))
+            .collect()
+    };
+    json!({
+        "path": path.to_string_lossy(),
+        "error": current.as_ref().err(),
+        "issues": issues
+            .iter()
+            .map(|issue| json!({
+                "input": issue.input,
+                "kind": issue.kind.name(),
+                "message": issue.message(lang),
+            }))
+            .collect::<Vec<_>>(),
+        "new": pairs(new),
+        "fixed": pairs(fixed),
+    })
+}
+
+fn notify(
+    url: &str,
+    notification: &serde_json::Value,
+    config: &FlakeCheckConfig,
+) -> Result<(), FlakeCheckerError> {
+    let body = serde_json::to_string(notification)?;
+    http::send(&config.network, |client| {
+        client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::daemon::{changes, parse_interval, State};
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_daemon() {
+        assert_eq!(parse_interval("6h"), Ok(Duration::from_secs(6 * 60 * 60)));
+        assert_eq!(parse_interval("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("6").is_err());
+        assert!(parse_interval("").is_err());
+
+        let state = |issues: &[(&str, &str)]| -> State {
+            Ok(issues
+                .iter()
+                .map(|(input, kind)| (input.to_string(), kind.to_string()))
+                .collect())
+        };
+        let previous = state(&[("nixpkgs", "outdated"), ("nixpkgs", "disallowed")]);
+        let current = state(&[("nixpkgs", "outdated"), ("nixpkgs-old", "non-upstream")]);
+        let (new, fixed) = changes(&previous, &current);
+        assert_eq!(
+            new,
+            [(String::from("nixpkgs-old"), String::from("non-upstream"))]
+        );
+        assert_eq!(
+            fixed,
+            [(String::from("nixpkgs"), String::from("disallowed"))]
+        );
+
+        // Failing to check a lockfile doesn't count as fixing its issues
+        let (new, fixed) = changes(&previous, &Err(String::from("unreachable")));
+        assert!(new.is_empty() && fixed.is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, input_details, FlakeCheckConfig, InputDetails};
+use crate::i18n::Lang;
+use crate::issue::Issue;
+
+use handlebars::Handlebars;
+use parse_flake_lock::FlakeLock;
+use serde_json::json;
+
+/// The changes between two versions of a `flake.lock`, in terms of both its root-level inputs and
+/// the issues found in it.
+pub(crate) struct LockDiff {
+    pub added: Vec<InputDetails>,
+    pub removed: Vec<InputDetails>,
+    /// Inputs locked to a different revision, as `(old, new)` pairs.
+    pub updated: Vec<(InputDetails, InputDetails)>,
+    pub introduced: Vec<Issue>,
+    pub resolved: Vec<Issue>,
+}
+
+impl LockDiff {
+    pub(crate) fn new(
+        old: &FlakeLock,
+        new: &FlakeLock,
+        config: &FlakeCheckConfig,
+    ) -> Result<Self, FlakeCheckerError> {
+        let old_inputs = input_details(old, config.now());
+        let new_inputs = input_details(new, config.now());
+
+        let find = |inputs: &[InputDetails], name: &str| {
+            inputs.iter().find(|input| input.name == name).cloned()
+        };
+
+        let added = new_inputs
+            .iter()
+            .filter(|input| find(&old_inputs, &input.name).is_none())
+            .cloned()
+            .collect();
+        let removed = old_inputs
+            .iter()
+            .filter(|input| find(&new_inputs, &input.name).is_none())
+            .cloned()
+            .collect();
+        let updated = new_inputs
+            .iter()
+            .filter_map(|input| {
+                let old_input = find(&old_inputs, &input.name)?;
+                (old_input.rev != input.rev || old_input.last_modified != input.last_modified)
+                    .then(|| (old_input, input.clone()))
+            })
+            .collect();
+
+        let old_issues = find_issues(old, config)?;
+        let new_issues = find_issues(new, config)?;
+
+        // Issues are identified by input and kind, so an outdated input that's still outdated
+        // after an update is neither introduced nor resolved
+        let same = |a: &Issue, b: &Issue| a.input == b.input && a.kind.name() == b.kind.name();
+        let introduced = new_issues
+            .iter()
+            .filter(|issue| !old_issues.iter().any(|old| same(old, issue)))
+            .cloned()
+            .collect();
+        let resolved = old_issues
+            .iter()
+            .filter(|issue| !new_issues.iter().any(|new| same(new, issue)))
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            added,
+            removed,
+            updated,
+            introduced,
+            resolved,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.updated.is_empty()
+            && self.introduced.is_empty()
+            && self.resolved.is_empty()
+    }
+
+    /// Render the diff as Markdown for pull request review.
+    pub(crate) fn markdown(
+        &self,
+        old_path: &str,
+        new_path: &str,
+        lang: Lang,
+    ) -> Result<String, FlakeCheckerError> {
+        let updated: Vec<_> = self
+            .updated
+            .iter()
+            .map(|(old, new)| {
+                let age_delta = old
+                    .num_days_old
+                    .zip(new.num_days_old)
+                    .map(|(old_age, new_age)| new_age - old_age);
+                json!({
+                    "name": new.name,
+                    "old": old,
+                    "new": new,
+                    "old_rev": old.rev.as_deref().map(short_rev),
+                    "new_rev": new.rev.as_deref().map(short_rev),
+                    "age_delta": age_delta.map(|delta| format!("{delta:+}")),
+                })
+            })
+            .collect();
+        let messages = |issues: &[Issue]| -> Vec<String> {
+            issues.iter().map(|issue| issue.message(lang)).collect()
+        };
+
+        let data = json!({
+            "old_path": old_path,
+            "new_path": new_path,
+            "unchanged": self.is_empty(),
+            "added": self.added,
+            "removed": self.removed,
+            "updated": updated,
+            "introduced": messages(&self.introduced),
+            "resolved": messages(&self.resolved),
+        });
+
+        let mut handlebars = Handlebars::new();
+        handlebars
+            .register_template_string("diff.md", include_str!("templates/diff_md.hbs"))
+            .map_err(Box::new)?;
+
+        Ok(handlebars.render("diff.md", &data)?)
+    }
+}
+
+fn short_rev(rev: &str) -> &str {
+    &rev[..rev.len().min(7)]
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use parse_flake_lock::FlakeLock;
+
+    use crate::diff::LockDiff;
+    use crate::flake::FlakeCheckConfig;
+
+    #[test]
+    fn test_diff_clean_to_dirty() {
+        let old = FlakeLock::new(Path::new("tests/flake.clean.0.lock")).unwrap();
+        let new = FlakeLock::new(Path::new("tests/flake.dirty.0.lock")).unwrap();
+        let config = FlakeCheckConfig::default();
+
+        let diff = LockDiff::new(&old, &new, &config).unwrap();
+
+        let removed: Vec<&str> = diff.removed.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(removed, ["crane", "flake-compat", "flake-utils"]);
+        assert!(diff.added.is_empty());
+        assert!(diff.updated.iter().any(|(_, new)| new.name == "nixpkgs"));
+
+        let introduced: Vec<&str> = diff.introduced.iter().map(|i| i.kind.name()).collect();
+        assert_eq!(introduced, ["disallowed", "non-upstream"]);
+        assert!(diff.resolved.is_empty());
+
+        let unchanged = LockDiff::new(&old, &old, &config).unwrap();
+        assert!(unchanged.is_empty());
+    }
+}
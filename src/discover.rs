@@ -0,0 +1,247 @@
+use crate::error::FlakeCheckerError;
+use crate::filter::glob_match;
+use crate::lockfile::DEVENV_LOCK;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FLAKE_LOCK: &str = "flake.lock";
+
+/// Find every `flake.lock` (and devenv `devenv.lock`) file beneath `root`, skipping anything
+/// excluded by a `.gitignore` along the way. The results are sorted.
+pub(crate) fn find_flake_locks(root: &Path) -> Result<Vec<PathBuf>, FlakeCheckerError> {
+    let mut found = Vec::new();
+    walk(root, "", &mut Vec::new(), &mut found)?;
+    Ok(found)
+}
+
+/// Find the `flake.lock` files matching any of the provided glob patterns (relative to the current
+/// directory), other than those matching an exclusion pattern (prefixed with `!`). The results are
+/// sorted.
+pub(crate) fn find_flake_locks_by_glob(
+    patterns: &[String],
+) -> Result<Vec<PathBuf>, FlakeCheckerError> {
+    let (excludes, includes): (Vec<&str>, Vec<&str>) = patterns
+        .iter()
+        .map(|pattern| pattern.trim_start_matches("./"))
+        .partition(|pattern| pattern.starts_with('!'));
+    let excludes: Vec<&str> = excludes
+        .iter()
+        .map(|pattern| pattern[1..].trim_start_matches("./"))
+        .collect();
+
+    let mut found = BTreeSet::new();
+    for include in includes {
+        // Only walk the part of the tree that the pattern can match
+        let base: Vec<&str> = include
+            .split('/')
+            .take_while(|segment| !segment.contains(['*', '?']))
+            .collect();
+        let base = match base.join("/") {
+            base if base.is_empty() || base == include => PathBuf::from("."),
+            base => PathBuf::from(base),
+        };
+        if !base.is_dir() {
+            continue;
+        }
+
+        for path in find_flake_locks(&base)? {
+            let path = path.strip_prefix(".").unwrap_or(&path).to_path_buf();
+            let text = path.to_string_lossy();
+            if path_glob_match(include, &text)
+                && !excludes
+                    .iter()
+                    .any(|exclude| path_glob_match(exclude, &text))
+            {
+                found.insert(path);
+            }
+        }
+    }
+
+    Ok(found.into_iter().collect())
+}
+
+// Walks the directory at `root/rel`, accumulating the ignore rules of each `.gitignore` found.
+fn walk(
+    root: &Path,
+    rel: &str,
+    rules: &mut Vec<IgnoreRule>,
+    found: &mut Vec<PathBuf>,
+) -> Result<(), FlakeCheckerError> {
+    let dir = root.join(rel);
+
+    let num_inherited = rules.len();
+    if let Ok(gitignore) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(
+            gitignore
+                .lines()
+                .filter_map(|line| IgnoreRule::parse(line, rel)),
+        );
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        let path = if rel.is_empty() {
+            name.clone()
+        } else {
+            format!("{rel}/{name}")
+        };
+
+        if name == ".git" || is_ignored(rules, &path, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            walk(root, &path, rules, found)?;
+        } else if name == FLAKE_LOCK || name == DEVENV_LOCK {
+            found.push(root.join(&path));
+        }
+    }
+
+    rules.truncate(num_inherited);
+
+    Ok(())
+}
+
+fn is_ignored(rules: &[IgnoreRule], path: &str, is_dir: bool) -> bool {
+    // The last matching rule wins
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// A single pattern from a `.gitignore` file.
+#[derive(Debug)]
+struct IgnoreRule {
+    /// The directory containing the `.gitignore` (relative to the walk root).
+    base: String,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str, base: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        // Patterns without a slash match at any depth; patterns with one are relative to the
+        // `.gitignore` itself
+        let pattern = match line.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{line}"),
+        };
+
+        Some(Self {
+            base: base.to_string(),
+            pattern,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let path = if self.base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(&self.base)
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(rest) => rest,
+                None => return false,
+            }
+        };
+
+        path_glob_match(&self.pattern, path)
+    }
+}
+
+/// Match a `/`-separated `path` against a glob `pattern` in which `*` and `?` don't match across
+/// path segments and a `**` segment matches any number of segments.
+pub(crate) fn path_glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, path_rest)) => {
+                glob_match(segment, first) && segments_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{path_glob_match, IgnoreRule};
+
+    #[test]
+    fn test_path_glob_match() {
+        let cases = [
+            ("services/*/flake.lock", "services/api/flake.lock", true),
+            (
+                "services/*/flake.lock",
+                "services/api/nested/flake.lock",
+                false,
+            ),
+            ("**/flake.lock", "flake.lock", true),
+            ("**/flake.lock", "a/b/c/flake.lock", true),
+            ("a/**", "a/b/c", true),
+            ("*.lock", "nested/flake.lock", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                path_glob_match(pattern, path),
+                expected,
+                "{pattern} vs. {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let rule = IgnoreRule::parse("target/", "").unwrap();
+        assert!(rule.matches("target", true));
+        assert!(rule.matches("crates/foo/target", true));
+        assert!(!rule.matches("target", false));
+
+        let rule = IgnoreRule::parse("/vendor", "sub").unwrap();
+        assert!(rule.matches("sub/vendor", true));
+        assert!(!rule.matches("sub/deeper/vendor", true));
+        assert!(!rule.matches("vendor", true));
+
+        assert!(IgnoreRule::parse("!keep", "").unwrap().negated);
+        assert!(IgnoreRule::parse("# comment", "").is_none());
+    }
+}
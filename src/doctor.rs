@@ -0,0 +1,393 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::policy::{self, Policy};
+use crate::{canonical, otel, telemetry};
+
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use parse_flake_lock::{FlakeLock, FlakeLockParseError};
+
+// GitHub's rate limit endpoint, which doesn't count against the rate limit
+const RATE_LIMIT_URL: &str = "https://api.github.com/rate_limit";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Status {
+    Ok,
+    Warning,
+    Failure,
+}
+
+/// The outcome of diagnosing one aspect of the environment, with a suggestion for fixing it if
+/// it's not OK.
+#[derive(Debug)]
+pub(crate) struct Diagnosis {
+    pub topic: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnosis {
+    fn ok(topic: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            topic,
+            status: Status::Ok,
+            detail: detail.into(),
+            suggestion: None,
+        }
+    }
+
+    fn problem(
+        topic: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        suggestion: impl Into<String>,
+    ) -> Self {
+        Self {
+            topic,
+            status,
+            detail: detail.into(),
+            suggestion: Some(suggestion.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Failure => "error",
+        };
+        write!(f, "[{status}] {}: {}", self.topic, self.detail)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n    -> {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the environment is diagnosed for: the lockfile that would be checked, and whether the
+/// network may be used.
+pub(crate) struct Environment<'a> {
+    pub flake_lock_path: &'a Path,
+    pub network: &'a NetworkConfig,
+    pub telemetry: bool,
+    pub offline: bool,
+}
+
+/// Diagnose everything that a check run depends on.
+pub(crate) fn diagnose(env: &Environment) -> Vec<Diagnosis> {
+    let mut diagnoses = vec![
+        lockfile(env.flake_lock_path),
+        policy_file(env.flake_lock_path),
+        step_summary(
+            std::env::var_os("GITHUB_ACTIONS").is_some(),
+            std::env::var_os("GITHUB_STEP_SUMMARY")
+                .as_deref()
+                .map(Path::new),
+        ),
+    ];
+    if env.offline {
+        return diagnoses;
+    }
+
+    diagnoses.push(github_rate_limit(env.network));
+    if env.telemetry {
+        diagnoses.push(reachable(
+            "telemetry",
+            telemetry::TELEMETRY_ENDPOINT,
+            env.network,
+            "pass --no-telemetry (or set NIX_FLAKE_CHECKER_NO_TELEMETRY=true) to skip telemetry",
+        ));
+    }
+    if let Some(exporter) = otel::OtelExporter::from_env() {
+        diagnoses.push(reachable(
+            "OpenTelemetry",
+            exporter.endpoint(),
+            env.network,
+            "check OTEL_EXPORTER_OTLP_ENDPOINT, or unset it to disable the export",
+        ));
+    }
+    diagnoses
+}
+
+fn lockfile(path: &Path) -> Diagnosis {
+    const TOPIC: &str = "lockfile";
+    let shown = path.display();
+
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Diagnosis::problem(
+                TOPIC,
+                Status::Failure,
+                format!("{shown} doesn't exist"),
+                "run `nix flake lock` to create it, or pass the path to an existing lockfile",
+            )
+        }
+        Err(err) => {
+            return Diagnosis::problem(
+                TOPIC,
+                Status::Failure,
+                format!("couldn't read {shown}: {err}"),
+                "check the lockfile's permissions",
+            )
+        }
+    };
+
+    match source.parse::<FlakeLock>() {
+        Err(FlakeLockParseError::Conflict(lines)) => Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            format!(
+                "{shown} has merge conflict markers on {} line(s)",
+                lines.len()
+            ),
+            "resolve the conflicts, or run flake-checker with --resolve ours|theirs|regenerate",
+        ),
+        Err(FlakeLockParseError::UnsupportedVersion(version)) => Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            format!("{shown} has version {version}, which flake-checker can't read"),
+            "regenerate it with `nix flake lock` using a current Nix, or upgrade flake-checker",
+        ),
+        Err(FlakeLockParseError::Syntax(err)) => Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            format!(
+                "{shown} isn't a valid lockfile at line {}, column {}: {}",
+                err.line, err.column, err.message
+            ),
+            "fix it by hand, or regenerate it with `nix flake lock`",
+        ),
+        Err(err) => Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            format!("{shown} isn't a valid lockfile: {err}"),
+            "regenerate it with `nix flake lock`",
+        ),
+        Ok(flake_lock) => match canonical::check(&source) {
+            Ok(None) => Diagnosis::ok(
+                TOPIC,
+                format!(
+                    "{shown} is valid, with {} root input(s)",
+                    flake_lock.root.len()
+                ),
+            ),
+            _ => Diagnosis::problem(
+                TOPIC,
+                Status::Warning,
+                format!("{shown} is valid, but isn't canonically formatted"),
+                "run `nix flake lock` or flake-checker --fix-format",
+            ),
+        },
+    }
+}
+
+fn policy_file(flake_lock_path: &Path) -> Diagnosis {
+    const TOPIC: &str = "policy";
+
+    // Policy files are looked up as far as the directory being scanned, so this finds any policy
+    // file that a scan could apply
+    let nearest = std::path::absolute(flake_lock_path)
+        .ok()
+        .and_then(|path| policy::find_nearest(&path, Path::new("/")));
+    let Some(path) = nearest else {
+        return Diagnosis::ok(
+            TOPIC,
+            format!(
+                "no {} applies, so the command-line flags apply as given",
+                policy::POLICY_FILE
+            ),
+        );
+    };
+
+    match Policy::load(&path) {
+        Ok(_) => Diagnosis::ok(
+            TOPIC,
+            format!("{} applies when scanning with --recursive", path.display()),
+        ),
+        Err(err) => Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            err.to_string(),
+            "fix the policy file; its fields are described under \"Monorepos\" in the README",
+        ),
+    }
+}
+
+fn step_summary(github_actions: bool, path: Option<&Path>) -> Diagnosis {
+    const TOPIC: &str = "GitHub step summary";
+
+    match (github_actions, path) {
+        (false, _) => Diagnosis::ok(
+            TOPIC,
+            "not running in GitHub Actions, so summaries are printed instead",
+        ),
+        (true, None) => Diagnosis::problem(
+            TOPIC,
+            Status::Warning,
+            "GITHUB_STEP_SUMMARY isn't set, so the Markdown summary can't be written",
+            "run on a GitHub-hosted or up-to-date self-hosted runner, or set NIX_FLAKE_CHECKER_MARKDOWN_SUMMARY=false",
+        ),
+        (true, Some(path)) => match OpenOptions::new().append(true).create(true).open(path) {
+            Ok(_) => Diagnosis::ok(TOPIC, format!("{} is writable", path.display())),
+            Err(err) => Diagnosis::problem(
+                TOPIC,
+                Status::Failure,
+                format!("{} isn't writable: {err}", path.display()),
+                "check the permissions of the runner's temporary directory",
+            ),
+        },
+    }
+}
+
+fn github_rate_limit(network: &NetworkConfig) -> Diagnosis {
+    const TOPIC: &str = "GitHub API";
+
+    let response = http::client(network).and_then(|client| {
+        Ok(network
+            .authorize_github(client.get(RATE_LIMIT_URL))
+            .header("User-Agent", "flake-checker")
+            .send()?)
+    });
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            return Diagnosis::problem(
+                TOPIC,
+                Status::Warning,
+                format!("api.github.com is unreachable: {err}"),
+                network_suggestion(network),
+            )
+        }
+    };
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            "GitHub rejected the token",
+            "check that --github-token (or GITHUB_TOKEN) is valid and hasn't expired",
+        );
+    }
+    match response.text() {
+        Ok(body) if status.is_success() => rate_limit(&body, network.github_token.is_some())
+            .unwrap_or_else(|err| {
+                Diagnosis::problem(
+                    TOPIC,
+                    Status::Warning,
+                    format!("couldn't read the rate limit: {err}"),
+                    "retry later",
+                )
+            }),
+        _ => Diagnosis::problem(
+            TOPIC,
+            Status::Warning,
+            format!("GitHub responded with {status}"),
+            "retry later",
+        ),
+    }
+}
+
+// Diagnose the core rate limit from the response of GitHub's rate limit endpoint.
+fn rate_limit(body: &str, authenticated: bool) -> Result<Diagnosis, FlakeCheckerError> {
+    const TOPIC: &str = "GitHub API";
+
+    let body: serde_json::Value = serde_json::from_str(body)?;
+    let core = &body["resources"]["core"];
+    let (Some(remaining), Some(limit)) = (core["remaining"].as_u64(), core["limit"].as_u64())
+    else {
+        return Err(FlakeCheckerError::Invalid(String::from(
+            "the response has no core rate limit",
+        )));
+    };
+    let detail = format!("{remaining} of {limit} requests remaining this hour");
+
+    Ok(if remaining == 0 {
+        let reset = core["reset"]
+            .as_i64()
+            .map(crate::flake::iso8601)
+            .unwrap_or_else(|| String::from("within the hour"));
+        Diagnosis::problem(
+            TOPIC,
+            Status::Failure,
+            format!("{detail} (resets {reset})"),
+            if authenticated {
+                "wait for the rate limit to reset"
+            } else {
+                "pass --github-token (or set GITHUB_TOKEN) for a higher rate limit"
+            },
+        )
+    } else if !authenticated {
+        Diagnosis::problem(
+            TOPIC,
+            Status::Warning,
+            format!("unauthenticated, with {detail}"),
+            "pass --github-token (or set GITHUB_TOKEN) for a higher rate limit and access to private repositories",
+        )
+    } else {
+        Diagnosis::ok(TOPIC, format!("authenticated, with {detail}"))
+    })
+}
+
+// Whether an endpoint responds at all; any HTTP response means that it's reachable.
+fn reachable(
+    topic: &'static str,
+    url: &str,
+    network: &NetworkConfig,
+    suggestion: &str,
+) -> Diagnosis {
+    match http::client(network).and_then(|client| Ok(client.head(url).send()?)) {
+        Ok(_) => Diagnosis::ok(topic, format!("{url} is reachable")),
+        Err(err) => Diagnosis::problem(
+            topic,
+            Status::Warning,
+            format!("{url} is unreachable: {err}"),
+            format!("{suggestion}, or {}", network_suggestion(network)),
+        ),
+    }
+}
+
+fn network_suggestion(network: &NetworkConfig) -> &'static str {
+    match network.proxy {
+        Some(_) => "check that the --proxy is reachable",
+        None => "check your connection, pass --proxy if you're behind one, or pass --offline",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::doctor::{lockfile, rate_limit, step_summary, Status};
+
+    use std::path::Path;
+
+    #[test]
+    fn test_doctor() {
+        let status = |path: &str| lockfile(Path::new(path)).status;
+        assert_eq!(status("tests/flake.clean.0.lock"), Status::Ok);
+        assert_eq!(status("tests/flake.dirty.0.lock"), Status::Warning);
+        assert_eq!(status("tests/missing.lock"), Status::Failure);
+
+        assert_eq!(step_summary(false, None).status, Status::Ok);
+        assert_eq!(step_summary(true, None).status, Status::Warning);
+
+        let body = |remaining: u64| {
+            format!(
+                r#"{{"resources": {{"core": {{"limit": 60, "remaining": {remaining}, "reset": 1700000000}}}}}}"#
+            )
+        };
+        assert_eq!(rate_limit(&body(60), true).unwrap().status, Status::Ok);
+        assert_eq!(
+            rate_limit(&body(60), false).unwrap().status,
+            Status::Warning
+        );
+        let exhausted = rate_limit(&body(0), false).unwrap();
+        assert_eq!(exhausted.status, Status::Failure);
+        assert!(exhausted.detail.contains("2023-11-14"));
+        assert!(rate_limit("{}", true).is_err());
+    }
+}
@@ -1,17 +1,69 @@
+/// The ways in which checking lockfiles can fail.
 #[derive(Debug, thiserror::Error)]
 pub enum FlakeCheckerError {
+    /// A required environment variable is missing or invalid.
     #[error("env var error: {0}")]
     EnvVar(#[from] std::env::VarError),
+    /// A lockfile couldn't be parsed.
     #[error("couldn't parse flake.lock: {0}")]
     FlakeLock(#[from] parse_flake_lock::FlakeLockParseError),
+    /// A file couldn't be read or written, or a command couldn't be run.
     #[error("couldn't access flake.lock: {0}")]
     Io(#[from] std::io::Error),
+    /// A network request failed.
+    #[cfg(feature = "cli")]
+    #[error("http request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// A JSON document couldn't be parsed or serialized.
     #[error("couldn't parse flake.lock: {0}")]
     Json(#[from] serde_json::Error),
+    /// A summary or message template couldn't be rendered.
     #[error("handlebars render error: {0}")]
     Render(#[from] handlebars::RenderError),
+    /// A summary template is invalid.
     #[error("handlebars template error: {0}")]
     Template(#[from] Box<handlebars::TemplateError>),
+    /// A lockfile, policy, or other input is invalid.
     #[error("invalid flake.lock: {0}")]
     Invalid(String),
+    /// A remote flake reference couldn't be parsed.
+    #[error("invalid remote flake reference (expected github:owner/repo[/ref], flakehub:org/flake[/version], or a URL): {0}")]
+    InvalidRemote(String),
+    /// The GitHub App couldn't authenticate or handle a webhook.
+    #[error("GitHub App error: {0}")]
+    GitHubApp(String),
+    /// Network access is needed, but `--offline` disallows it.
+    #[error("network access is disabled by --offline, but is required by {0}")]
+    Offline(String),
+    /// A git command failed.
+    #[error("git error: {0}")]
+    Git(String),
+    /// A pre-commit hook is already installed, and `--force` wasn't given to overwrite it.
+    #[error("a pre-commit hook already exists at {}; use --force to overwrite it", .0.display())]
+    HookExists(std::path::PathBuf),
+}
+
+impl FlakeCheckerError {
+    /// The process exit code for the error, which tells errors apart from lockfiles that fail their
+    /// checks (1): 2 for invalid lockfiles, policies, and other input (like a hook that would be
+    /// overwritten), 3 for failures of the environment, like files that can't be read, missing
+    /// environment variables, and network or git errors, and 4 for summaries and messages that
+    /// can't be rendered.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::FlakeLock(_)
+            | Self::Json(_)
+            | Self::Invalid(_)
+            | Self::InvalidRemote(_)
+            | Self::HookExists(_) => 2,
+            Self::EnvVar(_)
+            | Self::Io(_)
+            | Self::GitHubApp(_)
+            | Self::Offline(_)
+            | Self::Git(_) => 3,
+            #[cfg(feature = "cli")]
+            Self::Http(_) => 3,
+            Self::Render(_) | Self::Template(_) => 4,
+        }
+    }
 }
@@ -0,0 +1,271 @@
+use crate::error::FlakeCheckerError;
+use crate::issue::{Issue, IssueKind};
+
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+// The prefix of exemption comments in flake.nix.
+const MARKER: &str = "flake-checker:";
+
+/// What an exemption allows.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Rule {
+    /// `allow-ref <ref>`: the input may use this (otherwise disallowed) ref.
+    Ref(String),
+    /// `allow-owner <owner>`: the input may come from this non-upstream owner.
+    Owner(String),
+    /// `allow-outdated`: the input may be older than the maximum age.
+    Outdated,
+}
+
+/// An exemption from a check for one input, declared by a comment in flake.nix like
+/// `# flake-checker: allow-ref nixos-unstable reason="needed for X" until=2025-06-01`. The
+/// exemption covers the input that's defined on the same line as the comment or on the next line
+/// (or the input given by `input=<name>`), and stops applying after its `until` date.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Exemption {
+    pub input: String,
+    pub rule: Rule,
+    pub reason: Option<String>,
+    pub until: Option<NaiveDate>,
+    /// The line of flake.nix that the comment is on.
+    pub line: usize,
+}
+
+impl Exemption {
+    /// Whether the exemption covers an issue, regardless of whether it has expired.
+    pub(crate) fn covers(&self, issue: &Issue) -> bool {
+        if issue.input != self.input {
+            return false;
+        }
+        match (&self.rule, &issue.kind) {
+            (Rule::Ref(git_ref), IssueKind::Disallowed(disallowed)) => {
+                *git_ref == disallowed.reference
+            }
+            (Rule::Owner(owner), IssueKind::NonUpstream(non_upstream)) => {
+                owner.eq_ignore_ascii_case(&non_upstream.owner)
+            }
+            (Rule::Outdated, IssueKind::Outdated(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn is_expired(&self, today: NaiveDate) -> bool {
+        self.until.is_some_and(|until| until < today)
+    }
+}
+
+/// The exemptions declared in a flake.nix.
+#[derive(Debug, Default)]
+pub(crate) struct Exemptions {
+    pub exemptions: Vec<Exemption>,
+}
+
+impl Exemptions {
+    /// Load the exemptions from the flake.nix next to a local lockfile, if there is one.
+    pub(crate) fn load(flake_lock_path: &Path) -> Result<Self, FlakeCheckerError> {
+        let flake_nix_path = match flake_lock_path.parent() {
+            Some(dir) => dir.join("flake.nix"),
+            None => Path::new("flake.nix").to_path_buf(),
+        };
+        match std::fs::read_to_string(&flake_nix_path) {
+            Ok(flake_nix) => Self::parse(&flake_nix).map_err(|err| {
+                FlakeCheckerError::Invalid(format!("{}:{err}", flake_nix_path.display()))
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Parse the exemption comments in flake.nix, failing on the first malformed one with its line
+    /// number.
+    pub(crate) fn parse(flake_nix: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = flake_nix.lines().collect();
+        let mut exemptions = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            let number = index + 1;
+            let Some((code, comment)) = line.split_once('#') else {
+                continue;
+            };
+            let Some(directive) = comment.trim().strip_prefix(MARKER) else {
+                continue;
+            };
+
+            let mut words = words(directive).map_err(|err| format!("{number}: {err}"))?;
+            if words.is_empty() {
+                return Err(format!("{number}: the exemption is empty"));
+            }
+            let rule = match words.remove(0).as_str() {
+                "allow-ref" if !words.is_empty() => Rule::Ref(words.remove(0)),
+                "allow-owner" if !words.is_empty() => Rule::Owner(words.remove(0)),
+                "allow-outdated" => Rule::Outdated,
+                other => {
+                    return Err(format!(
+                        "{number}: `{other}` isn't an exemption (expected allow-ref <ref>, allow-owner <owner>, or allow-outdated)"
+                    ))
+                }
+            };
+
+            let mut input = None;
+            let mut reason = None;
+            let mut until = None;
+            for word in words {
+                match word.split_once('=') {
+                    Some(("input", value)) => input = Some(value.to_string()),
+                    Some(("reason", value)) => reason = Some(value.to_string()),
+                    Some(("until", value)) => {
+                        until =
+                            Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+                                format!("{number}: `{value}` isn't a date like 2025-06-01")
+                            })?)
+                    }
+                    _ => return Err(format!("{number}: unexpected `{word}` in the exemption")),
+                }
+            }
+
+            // The input defined on the same line, or else on the next line that isn't a comment
+            let input = input
+                .or_else(|| defined_input(code))
+                .or_else(|| {
+                    lines[index + 1..]
+                        .iter()
+                        .find(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+                        .and_then(|line| defined_input(line))
+                })
+                .ok_or_else(|| {
+                    format!("{number}: the exemption isn't next to an input (add input=<name>)")
+                })?;
+
+            exemptions.push(Exemption {
+                input,
+                rule,
+                reason,
+                until,
+                line: number,
+            });
+        }
+
+        Ok(Self { exemptions })
+    }
+
+    /// The exemption that covers an issue and hasn't expired, if there is one.
+    pub(crate) fn exempting(&self, issue: &Issue, today: NaiveDate) -> Option<&Exemption> {
+        self.exemptions
+            .iter()
+            .find(|exemption| exemption.covers(issue) && !exemption.is_expired(today))
+    }
+}
+
+// The input that a line of flake.nix defines, from `name.url = ...`, `inputs.name.url = ...`, or
+// `name = {`.
+fn defined_input(line: &str) -> Option<String> {
+    let (attr_path, _) = line.split_once('=')?;
+    let attr_path = attr_path.trim();
+    let attr_path = attr_path.strip_prefix("inputs.").unwrap_or(attr_path);
+    let name = attr_path.split('.').next()?.trim_matches('"');
+    let is_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '\''));
+    (is_name && name != "inputs" && name != "outputs").then(|| name.to_string())
+}
+
+// Split a directive into words, keeping double-quoted values (like `reason="a b"`) together
+// without their quotes.
+fn words(directive: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in directive.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("unterminated quote in the exemption"));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exemption::{Exemptions, Rule};
+    use crate::issue::{Disallowed, Issue, IssueKind, NonUpstream, Severity};
+
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_exemptions() {
+        let flake_nix = r#"{
+  inputs = {
+    # flake-checker: allow-ref nixos-22.05 reason="needed for pkg X" until=2025-06-01
+    nixpkgs.url = "github:NixOS/nixpkgs/nixos-22.05";
+    fork.url = "github:someone/nixpkgs"; # flake-checker: allow-owner someone
+  };
+  # flake-checker: allow-outdated input=old
+}
+"#;
+        let exemptions = Exemptions::parse(flake_nix).unwrap();
+        let inputs: Vec<(&str, &Rule, usize)> = exemptions
+            .exemptions
+            .iter()
+            .map(|exemption| (exemption.input.as_str(), &exemption.rule, exemption.line))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                ("nixpkgs", &Rule::Ref(String::from("nixos-22.05")), 3),
+                ("fork", &Rule::Owner(String::from("someone")), 5),
+                ("old", &Rule::Outdated, 7),
+            ]
+        );
+        assert_eq!(
+            exemptions.exemptions[0].reason.as_deref(),
+            Some("needed for pkg X")
+        );
+
+        let issue = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("nixos-22.05"),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        let date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        assert!(exemptions.exempting(&issue, date("2025-06-01")).is_some());
+        assert!(exemptions.exempting(&issue, date("2025-06-02")).is_none());
+
+        let other_owner = Issue {
+            input: String::from("fork"),
+            kind: IssueKind::NonUpstream(NonUpstream {
+                owner: String::from("someone-else"),
+                host: None,
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        assert!(exemptions
+            .exempting(&other_owner, date("2025-01-01"))
+            .is_none());
+
+        assert!(Exemptions::parse("# flake-checker: allow-everything\nx.url = \"\";").is_err());
+        assert!(Exemptions::parse("# flake-checker: allow-outdated\n").is_err());
+        assert!(Exemptions::parse("# flake-checker: allow-outdated until=soon\nx = {").is_err());
+    }
+}
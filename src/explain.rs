@@ -0,0 +1,150 @@
+use crate::flake::{check_deps, selected_deps, FlakeCheckConfig};
+use crate::selector::{Scope, Selector};
+
+use std::fmt::Write;
+use std::path::Path;
+
+use parse_flake_lock::{FlakeLock, Node};
+
+fn selectors(selectors: &[Selector]) -> String {
+    selectors
+        .iter()
+        .map(|selector| format!("`{selector}`"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Describe the checks that would run against a lockfile, their thresholds, and the inputs that
+/// each would consider, without running them.
+pub(crate) fn explain(flake_lock: &FlakeLock, path: &Path, config: &FlakeCheckConfig) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let mut line = |text: String| {
+        let _ = writeln!(out, "{text}");
+    };
+
+    line(format!("Lockfile: {}", path.display()));
+    line(String::new());
+    if config.selectors.is_empty() && config.nixpkgs_keys.is_empty() {
+        line(String::from(
+            "Input selector: root inputs locked to a GitHub repository named nixpkgs (GitHub inputs are checked; indirect inputs are selected but not checked)",
+        ));
+    } else if config.selectors.is_empty() {
+        line(format!(
+            "Input selector: root inputs named {} (GitHub inputs are checked; indirect inputs are selected but not checked)",
+            config.nixpkgs_keys.join(", ")
+        ));
+    } else {
+        line(format!(
+            "Input selector: inputs matching {}",
+            selectors(&config.selectors)
+        ));
+    }
+
+    let (selected, checked): (Vec<String>, Vec<String>) = match selected_deps(flake_lock, config) {
+        Ok(deps) => {
+            let selected = deps.keys().cloned().collect();
+            let checked = deps
+                .iter()
+                .filter(|(_, node)| matches!(node, Node::Repo(_)))
+                .map(|(name, _)| name.clone())
+                .collect();
+            (selected, checked)
+        }
+        Err(err) => {
+            line(format!("  ! {err}"));
+            (vec![], vec![])
+        }
+    };
+
+    for name in selected.iter() {
+        let note = if checked.contains(name) {
+            ""
+        } else {
+            " (not checked)"
+        };
+        line(format!("  + {name}{note}"));
+    }
+    let mut unselected: Vec<&String> = flake_lock
+        .root
+        .keys()
+        .filter(|name| !selected.contains(name))
+        .collect();
+    unselected.sort();
+    for name in unselected {
+        line(format!("  - {name}"));
+    }
+
+    let considered = if checked.is_empty() {
+        String::from("(no inputs)")
+    } else {
+        checked.join(", ")
+    };
+    let status = |enabled: bool| if enabled { "enabled" } else { "disabled" };
+
+    line(String::new());
+    line(String::from("Checks:"));
+    for check in config.checks.iter() {
+        line(format!(
+            "  {} ({}): {}",
+            check.name(),
+            status(check.enabled(config)),
+            check.description(config)
+        ));
+    }
+    line(format!("  Inputs considered: {considered}"));
+    let deps = selected_deps(flake_lock, config).unwrap_or_default();
+    for check in config.checks.iter() {
+        let name = check.name();
+        let check_selectors = config.check_selectors.get(name);
+        let scope = config.check_scopes.get(name).copied().unwrap_or_default();
+        if check_selectors.is_none() && scope == Scope::All {
+            continue;
+        }
+        let mut how = Vec::new();
+        how.extend(
+            check_selectors
+                .map(|check_selectors| format!("matching {}", selectors(check_selectors))),
+        );
+        if scope != Scope::All {
+            how.push(format!("{} inputs only", scope.as_str()));
+        }
+        let inputs: Vec<String> = check_deps(flake_lock, config, name, &deps)
+            .iter()
+            .filter(|(_, node)| matches!(node, Node::Repo(_)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        line(format!(
+            "  Inputs considered by {name} ({}): {}",
+            how.join(", "),
+            if inputs.is_empty() {
+                String::from("(no inputs)")
+            } else {
+                inputs.join(", ")
+            }
+        ));
+    }
+
+    line(String::new());
+    line(format!(
+        "Issues would be reported as {}s{}.",
+        config.severity().as_str(),
+        if config.fail_mode {
+            " and fail the run"
+        } else {
+            ""
+        }
+    ));
+    if let Some(days) = config.error_after_days {
+        line(format!(
+            "Outdated inputs would be errors once they're more than {days} days old."
+        ));
+    }
+    if let Some(date) = config.disallowed_error_after {
+        line(format!(
+            "Inputs on disallowed refs would be errors after {date}."
+        ));
+    }
+
+    out
+}
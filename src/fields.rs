@@ -0,0 +1,74 @@
+//! Fields of lockfile nodes that flake-checker doesn't recognize, like attributes that a newer
+//! version of Nix added. They're kept when parsing rather than dropped, noted with `--verbose`, and
+//! reported as `unknown-fields` issues with `--strict-fields`.
+
+use crate::issue::{Issue, IssueKind, Severity, UnknownFields};
+
+use std::collections::BTreeMap;
+
+use parse_flake_lock::FlakeLock;
+
+/// The unrecognized fields of each node that has any, by the name that issues use for the node:
+/// the name of a root input that resolves to it, or else its key in `nodes`.
+pub(crate) fn unknown_fields(flake_lock: &FlakeLock) -> BTreeMap<String, Vec<String>> {
+    let mut root_inputs: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut root_node_names: Vec<_> = flake_lock.root_node_names.iter().collect();
+    root_node_names.sort();
+    for (input, key) in root_node_names {
+        root_inputs.entry(key.as_str()).or_insert(input.as_str());
+    }
+
+    flake_lock
+        .nodes
+        .iter()
+        .filter_map(|(key, node)| {
+            let fields = node.unknown_fields();
+            let name = root_inputs.get(key.as_str()).copied().unwrap_or(key);
+            (!fields.is_empty()).then(|| (name.to_string(), fields))
+        })
+        .collect()
+}
+
+/// An `unknown-fields` issue for each node with fields that aren't recognized.
+pub(crate) fn check(flake_lock: &FlakeLock) -> Vec<Issue> {
+    unknown_fields(flake_lock)
+        .into_iter()
+        .map(|(input, fields)| Issue {
+            input,
+            kind: IssueKind::UnknownFields(UnknownFields { fields }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fields::{check, unknown_fields};
+    use crate::issue::{IssueKind, UnknownFields};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_unknown_fields() {
+        let source = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        assert!(unknown_fields(&flake_lock).is_empty());
+
+        let mut lock: serde_json::Value = serde_json::from_str(&source).unwrap();
+        lock["nodes"]["nixpkgs"]["locked"]["treeHash"] = "0a1c2e3f".into();
+        lock["nodes"]["nixpkgs"]["attrs"] = serde_json::json!({});
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        let issues = check(&flake_lock);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].input, "nixpkgs");
+        assert_eq!(
+            issues[0].kind,
+            IssueKind::UnknownFields(UnknownFields {
+                fields: vec![String::from("attrs"), String::from("locked.treeHash")]
+            })
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::issue::{Issue, Severity};
+
+/// Criteria for which issues are reported. Filtering doesn't affect which checks run.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IssueFilter {
+    /// Only report issues of these kinds (all kinds if empty).
+    pub kinds: Vec<String>,
+    /// Only report issues at or above this severity.
+    pub min_severity: Option<Severity>,
+    /// Only report issues for inputs matching one of these glob patterns (all inputs if empty).
+    pub input_patterns: Vec<String>,
+}
+
+impl IssueFilter {
+    pub(crate) fn matches(&self, issue: &Issue) -> bool {
+        (self.kinds.is_empty() || self.kinds.iter().any(|k| k == issue.kind.name()))
+            && self.min_severity.is_none_or(|min| issue.severity >= min)
+            && (self.input_patterns.is_empty()
+                || self
+                    .input_patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, &issue.input)))
+    }
+}
+
+/// Match `text` against a glob `pattern` in which `*` matches any sequence of characters and `?`
+/// matches any single character.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    p = star + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        let cases = [
+            ("nixpkgs", "nixpkgs", true),
+            ("nixpkgs*", "nixpkgs-unstable", true),
+            ("nixpkgs*", "nixpkgs", true),
+            ("*pkgs", "nixpkgs", true),
+            ("nix?kgs", "nixpkgs", true),
+            ("nixpkgs", "nixpkgs-unstable", false),
+            ("*-unstable", "nixpkgs", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYbZ", false),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "{pattern} vs. {text}");
+        }
+    }
+}
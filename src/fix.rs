@@ -0,0 +1,262 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig, ALLOWED_REFS};
+use crate::i18n::Lang;
+use crate::issue::{Issue, IssueKind};
+use crate::update;
+
+use std::path::Path;
+use std::process::Command;
+
+use parse_flake_lock::{FlakeLock, Node};
+
+/// A Nix command that resolves an issue with a root input.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Fix {
+    pub input: String,
+    pub args: Vec<String>,
+    /// The ref that the command moves the input to, if it changes.
+    pub git_ref: Option<String>,
+}
+
+/// How fixes are applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub(crate) enum Engine {
+    /// Run the Nix CLI.
+    #[default]
+    Nix,
+    /// Update the lockfile directly, fetching the latest revisions from GitHub, for when the Nix
+    /// CLI isn't available (only GitHub inputs can be updated this way).
+    InPlace,
+}
+
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "nix {}", self.args.join(" "))
+    }
+}
+
+/// The command that would resolve an issue, if there is one: outdated inputs are updated, inputs
+/// on disallowed refs are locked to the closest supported ref instead, and non-upstream inputs
+/// are locked to upstream Nixpkgs on the same (or closest supported) ref.
+pub(crate) fn suggestion(flake_lock: &FlakeLock, issue: &Issue) -> Option<Fix> {
+    let Some(Node::Repo(repo_node)) = flake_lock.root.get(&issue.input) else {
+        return None;
+    };
+    let original = &repo_node.original;
+    let override_input = |owner: &str, repo: &str, git_ref: &str| {
+        let mut url = format!("{}:{owner}/{repo}/{git_ref}", original.node_type);
+        if let Some(dir) = repo_node.dir() {
+            url.push_str(&format!("?dir={dir}"));
+        }
+        ["flake", "lock", "--override-input", &issue.input, &url]
+            .map(String::from)
+            .to_vec()
+    };
+
+    let (args, git_ref) = match &issue.kind {
+        IssueKind::Disallowed(disallowed) => {
+            let git_ref = replacement_ref(&disallowed.reference);
+            let args = override_input(&original.owner, &original.repo, git_ref);
+            (args, Some(git_ref))
+        }
+        IssueKind::Outdated(_) => {
+            let args = ["flake", "update", &issue.input].map(String::from).to_vec();
+            (args, None)
+        }
+        IssueKind::NonUpstream(_) => {
+            let git_ref = match original.git_ref.as_deref() {
+                Some(git_ref) if ALLOWED_REFS.contains(&git_ref) => git_ref,
+                Some(git_ref) => replacement_ref(git_ref),
+                None => "nixos-unstable",
+            };
+            let args = override_input("NixOS", "nixpkgs", git_ref);
+            (args, Some(git_ref))
+        }
+        _ => return None,
+    };
+    Some(Fix {
+        input: issue.input.clone(),
+        args,
+        git_ref: git_ref.map(String::from),
+    })
+}
+
+/// Plan a fix for each input with outdated or disallowed issues. An input with both issues only
+/// needs to be locked to the new ref.
+pub(crate) fn plan(flake_lock: &FlakeLock, issues: &[Issue]) -> Vec<Fix> {
+    let mut fixes: Vec<Fix> = Vec::new();
+
+    for issue in issues.iter() {
+        if !issue.kind.is_disallowed() && !issue.kind.is_outdated() {
+            continue;
+        }
+        let Some(fix) = suggestion(flake_lock, issue) else {
+            continue;
+        };
+
+        match fixes
+            .iter_mut()
+            .find(|planned| planned.input == issue.input)
+        {
+            Some(planned) if issue.kind.is_disallowed() => *planned = fix,
+            Some(_) => {}
+            None => fixes.push(fix),
+        }
+    }
+
+    fixes
+}
+
+/// The supported ref closest to a disallowed one: the newest supported release of the same kind
+/// (like the newest `nixos-*-small` branch for an old `nixos-*-small` branch), or the unstable
+/// branch of the same kind if there's no release of that kind.
+pub(crate) fn replacement_ref(reference: &str) -> &'static str {
+    let prefix = if reference.starts_with("nixpkgs") || reference.contains("darwin") {
+        "nixpkgs-"
+    } else {
+        "nixos-"
+    };
+    let suffix = |git_ref: &str| {
+        ["-small", "-darwin"]
+            .into_iter()
+            .find(|suffix| git_ref.ends_with(suffix))
+    };
+    let same_kind =
+        |allowed: &&&str| allowed.starts_with(prefix) && suffix(allowed) == suffix(reference);
+
+    let is_release = |allowed: &str| allowed.contains('.');
+    let newest_release = ALLOWED_REFS
+        .iter()
+        .filter(same_kind)
+        .filter(|allowed| is_release(allowed))
+        .max();
+    let unstable = ALLOWED_REFS
+        .iter()
+        .filter(same_kind)
+        .find(|allowed| allowed.contains("unstable"));
+
+    let wants_release = is_release(reference) && !reference.contains("unstable");
+    let preferred = if wants_release {
+        newest_release.or(unstable)
+    } else {
+        unstable.or(newest_release)
+    };
+    preferred.copied().unwrap_or("nixos-unstable")
+}
+
+/// Fix a local lockfile's outdated inputs and inputs on disallowed refs, printing what was fixed,
+/// and return its new contents along with the fixed issues.
+pub(crate) fn fix(
+    flake_lock_path: &Path,
+    source: String,
+    config: &FlakeCheckConfig,
+    engine: Engine,
+    lang: Lang,
+) -> Result<(String, Vec<Issue>), FlakeCheckerError> {
+    let flake_lock: FlakeLock = source.parse()?;
+    let before = find_issues(&flake_lock, config)?;
+    let fixes = plan(&flake_lock, &before);
+    if fixes.is_empty() {
+        return Ok((source, Vec::new()));
+    }
+
+    match engine {
+        Engine::Nix => apply(flake_lock_path, &fixes)?,
+        Engine::InPlace => update::apply(
+            flake_lock_path,
+            &source,
+            &flake_lock,
+            &fixes,
+            &config.network,
+        )?,
+    }
+    let source = std::fs::read_to_string(flake_lock_path)?;
+    let after = find_issues(&source.parse()?, config)?;
+
+    // The details of outdated issues change with each update, so match issues by input and kind
+    let fixed: Vec<Issue> = before
+        .into_iter()
+        .filter(|issue| {
+            !after
+                .iter()
+                .any(|other| other.input == issue.input && other.kind.name() == issue.kind.name())
+        })
+        .collect();
+    for issue in fixed.iter() {
+        println!("fixed: {}", issue.message(lang));
+    }
+    Ok((source, fixed))
+}
+
+/// Run the fixes in the lockfile's directory, failing on the first one that fails.
+pub(crate) fn apply(flake_lock_path: &Path, fixes: &[Fix]) -> Result<(), FlakeCheckerError> {
+    let dir = match flake_lock_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    for fix in fixes.iter() {
+        println!("running `{fix}`");
+        let status = Command::new("nix")
+            .args(&fix.args)
+            .current_dir(dir)
+            .status()?;
+        if !status.success() {
+            return Err(FlakeCheckerError::Invalid(format!(
+                "`{fix}` failed in {}",
+                dir.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fix::{plan, replacement_ref, suggestion};
+    use crate::flake::{find_issues, FlakeCheckConfig};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_fix() {
+        assert_eq!(replacement_ref("nixos-22.05"), "nixos-23.05");
+        assert_eq!(replacement_ref("nixos-21.11-small"), "nixos-23.05-small");
+        assert_eq!(
+            replacement_ref("nixpkgs-21.11-darwin"),
+            "nixpkgs-23.05-darwin"
+        );
+        assert_eq!(replacement_ref("release-21.05"), "nixos-23.05");
+        assert_eq!(replacement_ref("master"), "nixos-unstable");
+        assert_eq!(replacement_ref("nixpkgs-master"), "nixpkgs-unstable");
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &FlakeCheckConfig::default()).unwrap();
+        let fixes: Vec<String> = plan(&flake_lock, &issues)
+            .iter()
+            .map(|fix| fix.to_string())
+            .collect();
+        assert_eq!(
+            fixes,
+            ["nix flake lock --override-input nixpkgs github:bitcoin-miner-org/nixpkgs/nixos-unstable"]
+        );
+
+        let suggestions: Vec<String> = issues
+            .iter()
+            .filter_map(|issue| suggestion(&flake_lock, issue))
+            .map(|fix| fix.to_string())
+            .collect();
+        assert_eq!(
+            suggestions,
+            [
+                "nix flake lock --override-input nixpkgs github:bitcoin-miner-org/nixpkgs/nixos-unstable",
+                "nix flake lock --override-input nixpkgs github:NixOS/nixpkgs/nixos-unstable",
+                "nix flake update nixpkgs",
+            ]
+        );
+    }
+}
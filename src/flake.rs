@@ -1,13 +1,26 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
 
-use crate::issue::{Disallowed, Issue, IssueKind, NonUpstream, Outdated};
+use crate::budget::Budget;
+use crate::checker::CheckReport;
+use crate::checks::{Check, CheckRegistry};
+use crate::graph::InputGraph;
+use crate::http::NetworkConfig;
+use crate::i18n::Lang;
+use crate::issue::{Issue, IssueKind, Severity};
+use crate::messages::MessageTemplate;
+use crate::selector::{select, Scope, Selector};
+use crate::template_dir::TemplateDir;
 use crate::FlakeCheckerError;
 
-use chrono::{Duration, Utc};
-use parse_flake_lock::{FlakeLock, Node};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use parse_flake_lock::{FlakeLock, GitNode, MercurialNode, Node};
+use serde::Serialize;
 
+/// The supported Nixpkgs branches, which inputs may use by default.
 // Update this when necessary by running the get-allowed-refs.sh script to fetch
 // the current values from monitoring.nixos.org
 pub const ALLOWED_REFS: &[&str] = &[
@@ -21,14 +34,81 @@ pub const ALLOWED_REFS: &[&str] = &[
     "nixpkgs-23.05-darwin",
     "nixpkgs-unstable",
 ];
+/// The maximum age of inputs by default, in days.
 pub const MAX_DAYS: i64 = 30;
 
-pub(crate) struct FlakeCheckConfig {
+// devenv's default Nixpkgs input: a fork of nixpkgs-unstable that devenv tests and caches
+pub(crate) const DEVENV_NIXPKGS_OWNER: &str = "cachix";
+pub(crate) const DEVENV_NIXPKGS_REPO: &str = "devenv-nixpkgs";
+pub(crate) const DEVENV_NIXPKGS_REF: &str = "rolling";
+
+// NixOS releases are supported for a month after the next release, seven months after their own
+const RELEASE_SUPPORT_MONTHS: u32 = 7;
+
+/// Which checks to run against lockfiles, and with which thresholds.
+#[derive(Clone)]
+pub struct FlakeCheckConfig {
+    /// Check that inputs use allowed Git refs.
     pub check_supported: bool,
+    /// Check that inputs are at most the maximum age.
     pub check_outdated: bool,
+    /// Check that inputs come from allowed owners.
     pub check_owner: bool,
+    /// Check that the lockfile is formatted the way that `nix flake lock` writes it.
+    pub check_format: bool,
+    /// Report the fields of lockfile nodes that aren't recognized.
+    pub strict_fields: bool,
+    /// Whether issues are errors rather than warnings.
     pub fail_mode: bool,
+    /// The root inputs that are checked, unless there are selectors. If there are none, the root
+    /// inputs that are Nixpkgs are found whatever they're named.
     pub nixpkgs_keys: Vec<String>,
+    /// The inputs that are checked, instead of the root inputs named by `nixpkgs_keys`, if any.
+    pub selectors: Vec<Selector>,
+    /// The inputs that each check runs against, by check name, for checks that don't run against
+    /// the inputs that are otherwise checked.
+    pub check_selectors: BTreeMap<String, Vec<Selector>>,
+    /// Whether each check runs against direct inputs, transitive inputs, or all of them, by check
+    /// name (all of them by default).
+    pub check_scopes: BTreeMap<String, Scope>,
+    /// Templates for the messages of the issues that each check reports, by check name, for
+    /// checks whose built-in messages should be replaced.
+    pub messages: BTreeMap<String, MessageTemplate>,
+    /// Partials and helpers to register alongside the summary and message templates.
+    pub template_dir: TemplateDir,
+    /// The time that inputs' ages (and other time-based checks) are measured at, instead of the
+    /// current time, for reproducible runs and historical audits.
+    pub now: Option<DateTime<Utc>>,
+    /// The Git refs that inputs may use.
+    pub allowed_refs: Vec<String>,
+    /// The maximum age of inputs, in days.
+    pub max_days: i64,
+    /// The GitHub owners that inputs may come from (compared case-insensitively).
+    pub allowed_owners: Vec<String>,
+    /// The hosts that inputs may come from besides their forges' default hosts, like a GitHub
+    /// Enterprise server (compared case-insensitively).
+    pub allowed_hosts: Vec<String>,
+    /// Outdated inputs older than this many days are errors (and younger ones are warnings),
+    /// regardless of fail mode.
+    pub error_after_days: Option<i64>,
+    /// Inputs on disallowed refs are errors after this date (and warnings until then),
+    /// regardless of fail mode.
+    pub disallowed_error_after: Option<NaiveDate>,
+    /// The checks that can run.
+    pub checks: CheckRegistry,
+    /// The checks (other than the built-in ones, which have their own flags) that are disabled,
+    /// by name.
+    pub disabled_checks: BTreeSet<String>,
+    /// External checks to run against each lockfile: executables that read it as JSON on stdin and
+    /// write their issues as JSON on stdout, or WebAssembly modules that do the same.
+    pub plugins: Vec<PathBuf>,
+    /// The number of issues tolerated in fail mode.
+    pub budget: Budget,
+    /// How checks that need network access make requests.
+    pub network: NetworkConfig,
+    /// Whether the lockfile is devenv's, whose default Nixpkgs input passes the refs and owner
+    /// checks.
+    pub devenv: bool,
 }
 
 impl Default for FlakeCheckConfig {
@@ -37,17 +117,323 @@ impl Default for FlakeCheckConfig {
             check_supported: true,
             check_outdated: true,
             check_owner: true,
+            check_format: true,
+            strict_fields: false,
             fail_mode: false,
-            nixpkgs_keys: vec![String::from("nixpkgs")],
+            nixpkgs_keys: Vec::new(),
+            selectors: Vec::new(),
+            check_selectors: BTreeMap::new(),
+            check_scopes: BTreeMap::new(),
+            messages: BTreeMap::new(),
+            template_dir: TemplateDir::default(),
+            now: None,
+            allowed_refs: ALLOWED_REFS.iter().map(|r| r.to_string()).collect(),
+            max_days: MAX_DAYS,
+            allowed_owners: vec![String::from("NixOS")],
+            allowed_hosts: Vec::new(),
+            error_after_days: None,
+            disallowed_error_after: None,
+            checks: CheckRegistry::default(),
+            disabled_checks: BTreeSet::new(),
+            plugins: Vec::new(),
+            budget: Budget::default(),
+            network: NetworkConfig::default(),
+            devenv: false,
+        }
+    }
+}
+
+impl FlakeCheckConfig {
+    /// The time that checks are run at: the configured time, or else the current time.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+
+    /// The severity of any issues found: errors in fail mode and warnings otherwise.
+    pub fn severity(&self) -> Severity {
+        if self.fail_mode {
+            Severity::Error
+        } else {
+            Severity::Warning
+        }
+    }
+
+    /// The severity of an issue: outdated inputs and disallowed refs escalate from warnings to
+    /// errors at their thresholds, if they have them, and other issues are errors in fail mode or
+    /// have the default severity of the check that reported them.
+    pub fn issue_severity(&self, issue: &Issue) -> Severity {
+        let escalated = match &issue.kind {
+            IssueKind::Outdated(outdated) => self
+                .error_after_days
+                .map(|days| outdated.num_days_old > days),
+            IssueKind::Disallowed(_) => self
+                .disallowed_error_after
+                .map(|date| self.now().date_naive() > date),
+            _ => None,
+        };
+        match escalated {
+            Some(true) => Severity::Error,
+            Some(false) => Severity::Warning,
+            None => self
+                .severity()
+                .max(self.checks.default_severity(issue.kind.name())),
+        }
+    }
+
+    /// When a warning escalates to an error, if it does: at an age in days for outdated inputs,
+    /// or after a date for disallowed refs.
+    pub(crate) fn escalation(&self, issue: &Issue) -> Option<Escalation> {
+        if self.issue_severity(issue) == Severity::Error {
+            return None;
+        }
+        match &issue.kind {
+            IssueKind::Outdated(_) => self.error_after_days.map(Escalation::AtAge),
+            IssueKind::Disallowed(_) => self.disallowed_error_after.map(Escalation::AfterDate),
+            _ => None,
+        }
+    }
+}
+
+/// When an issue that's a warning becomes an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Escalation {
+    /// Once the input is older than this many days.
+    AtAge(i64),
+    /// After this date.
+    AfterDate(NaiveDate),
+}
+
+impl Escalation {
+    /// A description of the grace period in the given language.
+    pub(crate) fn message(&self, lang: Lang) -> String {
+        match self {
+            Self::AtAge(days) => lang.tr("escalates-at-age", &[("days", days)]),
+            Self::AfterDate(date) => lang.tr("escalates-after-date", &[("date", date)]),
+        }
+    }
+}
+
+/// Reporting details about a root-level input of the flake.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct InputDetails {
+    pub name: String,
+    pub node_type: String,
+    pub owner: Option<String>,
+    pub repo: Option<String>,
+    /// The subdirectory of the repository that the input's flake is in, if it isn't at the root.
+    pub dir: Option<String>,
+    /// The host of the forge that the repository is on.
+    pub host: Option<String>,
+    /// The web URL of the repository.
+    pub url: Option<String>,
+    /// Whether the input is a flake, rather than an input with `flake = false`.
+    pub flake: bool,
+    pub git_ref: Option<String>,
+    pub rev: Option<String>,
+    pub last_modified: Option<i64>,
+    pub locked_at: Option<String>,
+    pub num_days_old: Option<i64>,
+}
+
+impl InputDetails {
+    pub(crate) fn new(name: &str, node: &Node, now: DateTime<Utc>) -> Option<Self> {
+        let mut details = Self {
+            name: name.to_string(),
+            node_type: String::new(),
+            owner: None,
+            repo: None,
+            dir: None,
+            host: None,
+            url: None,
+            flake: node.is_flake(),
+            git_ref: None,
+            rev: None,
+            last_modified: None,
+            locked_at: None,
+            num_days_old: None,
+        };
+
+        match node {
+            Node::Root(_) => return None,
+            Node::Repo(repo) => {
+                details.node_type = repo.locked.node_type.clone();
+                details.owner = Some(repo.original.owner.clone());
+                details.repo = Some(repo.original.repo.clone());
+                details.dir = repo.dir().map(String::from);
+                details.host = Some(repo.host().to_string());
+                details.url = Some(repo.url());
+                details.git_ref = repo.original.git_ref.clone();
+                details.rev = Some(repo.locked.rev.clone());
+                details.last_modified = Some(repo.locked.last_modified);
+            }
+            Node::Indirect(indirect) => {
+                details.node_type = indirect.locked.node_type.clone();
+                details.owner = Some(indirect.locked.owner.clone());
+                details.repo = Some(indirect.locked.repo.clone());
+                details.rev = Some(indirect.locked.rev.clone());
+                details.last_modified = Some(indirect.locked.last_modified);
+            }
+            Node::Path(path) => {
+                details.node_type = path.locked.node_type.clone();
+                details.git_ref = path.original.git_ref.clone();
+                details.last_modified = Some(path.locked.last_modified);
+            }
+            Node::Tarball(tarball) => {
+                details.node_type = tarball.locked.node_type.clone();
+            }
+            Node::Git(GitNode {
+                locked, original, ..
+            })
+            | Node::Mercurial(MercurialNode {
+                locked, original, ..
+            }) => {
+                details.node_type = locked.node_type.clone();
+                details.dir = original.dir.clone().or_else(|| locked.dir.clone());
+                details.git_ref = original.git_ref.clone();
+                details.rev = locked.rev.clone();
+                details.last_modified = Some(locked.last_modified);
+            }
+            Node::Fallthrough(value) => {
+                let field = |section: &str, key: &str| {
+                    value
+                        .get(section)
+                        .and_then(|s| s.get(key))
+                        .and_then(|v| v.as_str())
+                        .map(String::from)
+                };
+                details.node_type = field("locked", "type").unwrap_or_default();
+                details.owner = field("original", "owner");
+                details.repo = field("original", "repo");
+                details.dir = field("original", "dir");
+                details.git_ref = field("original", "ref");
+                details.rev = field("locked", "rev");
+                details.last_modified = value
+                    .get("locked")
+                    .and_then(|l| l.get("lastModified"))
+                    .and_then(|v| v.as_i64());
+            }
+        }
+
+        details.locked_at = details.last_modified.map(iso8601);
+        details.num_days_old = details
+            .last_modified
+            .map(|last_modified| num_days_old(last_modified, now));
+
+        Some(details)
+    }
+}
+
+/// Collect [InputDetails] for all of the flake's root-level inputs, sorted by name, with their
+/// ages at `now`.
+pub(crate) fn input_details(flake_lock: &FlakeLock, now: DateTime<Utc>) -> Vec<InputDetails> {
+    let mut inputs: Vec<InputDetails> = flake_lock
+        .root
+        .iter()
+        .filter_map(|(name, node)| InputDetails::new(name, node, now))
+        .collect();
+    inputs.sort_by(|a, b| a.name.cmp(&b.name));
+    inputs
+}
+
+/// The age at `now` of an input with the given `lastModified` timestamp, in days.
+pub(crate) fn num_days_old(last_modified: i64, now: DateTime<Utc>) -> i64 {
+    let diff = now.timestamp() - last_modified;
+    Duration::seconds(diff).num_days()
+}
+
+/// Parse a time from the command line: an RFC 3339 timestamp (like `2024-01-01T12:00:00Z`), a
+/// date (like `2024-01-01`, at midnight UTC), or a Unix timestamp.
+pub(crate) fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let naive = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .or_else(|| {
+            value
+                .parse()
+                .ok()
+                .and_then(|timestamp| NaiveDateTime::from_timestamp_opt(timestamp, 0))
+        })
+        .ok_or_else(|| {
+            format!("expected an RFC 3339 timestamp, a date like 2024-01-01, or a Unix timestamp, got `{value}`")
+        })?;
+    Ok(DateTime::<Utc>::from_utc(naive, Utc))
+}
+
+/// Format a Unix timestamp as a UTC ISO-8601 date, like `2023-04-13T03:55:09Z`.
+pub(crate) fn iso8601(timestamp: i64) -> String {
+    NaiveDateTime::from_timestamp_opt(timestamp, 0)
+        .map(|naive| {
+            DateTime::<Utc>::from_utc(naive, Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+        })
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// The date on which a release branch (like `nixos-23.05` or `nixpkgs-23.05-darwin`) reaches
+/// end of life, or `None` for branches without one (like `nixos-unstable`).
+pub(crate) fn ref_eol(git_ref: &str) -> Option<NaiveDate> {
+    let (year, month) = git_ref.split('-').find_map(|part| part.split_once('.'))?;
+    let (year, month): (i32, u32) = (year.parse().ok()?, month.parse().ok()?);
+
+    // The day before the first day of the month after support ends
+    let months = month + RELEASE_SUPPORT_MONTHS;
+    NaiveDate::from_ymd_opt(2000 + year + (months / 12) as i32, months % 12 + 1, 1)?.pred_opt()
+}
+
+/// The inputs that are checked: those that the configuration's selectors match, or else the root
+/// inputs named by its Nixpkgs keys.
+pub(crate) fn selected_deps(
+    flake_lock: &FlakeLock,
+    config: &FlakeCheckConfig,
+) -> Result<BTreeMap<String, Node>, FlakeCheckerError> {
+    if !config.selectors.is_empty() {
+        Ok(select(flake_lock, &config.selectors))
+    } else if config.nixpkgs_keys.is_empty() {
+        find_nixpkgs(flake_lock)
+    } else {
+        nixpkgs_deps(flake_lock, config.nixpkgs_keys.clone())
+    }
+}
+
+// Root inputs locked to a GitHub repository named `nixpkgs`, whatever their owner
+fn nixpkgs_selector() -> Selector {
+    Selector {
+        node_type: Some(String::from("github")),
+        repo: Some(String::from("nixpkgs")),
+        direct: true,
+        ..Selector::default()
+    }
+}
+
+/// The root inputs that are Nixpkgs, whatever they're named (like `pkgs`): those locked to a
+/// GitHub repository named `nixpkgs`, with follows resolved, and indirect inputs locked to one.
+/// Inputs that merely have `nixpkgs` in their names, like `nixpkgs-fmt`, aren't included.
+pub(crate) fn find_nixpkgs(
+    flake_lock: &FlakeLock,
+) -> Result<BTreeMap<String, Node>, FlakeCheckerError> {
+    let mut deps = select(flake_lock, &[nixpkgs_selector()]);
+    for (name, node) in flake_lock.root.iter() {
+        if let Node::Indirect(indirect) = node {
+            if indirect.locked.node_type == "github" && indirect.locked.repo == "nixpkgs" {
+                deps.insert(name.clone(), node.clone());
+            }
         }
     }
+    if deps.is_empty() {
+        return Err(FlakeCheckerError::Invalid(String::from(
+            "no Nixpkgs inputs found among the root inputs (name them with --nixpkgs-keys)",
+        )));
+    }
+    Ok(deps)
 }
 
-fn nixpkgs_deps(
+pub(crate) fn nixpkgs_deps(
     flake_lock: &FlakeLock,
     keys: Vec<String>,
-) -> Result<HashMap<String, Node>, FlakeCheckerError> {
-    let mut deps: HashMap<String, Node> = HashMap::new();
+) -> Result<BTreeMap<String, Node>, FlakeCheckerError> {
+    let mut deps: BTreeMap<String, Node> = BTreeMap::new();
 
     for (ref key, node) in flake_lock.root.clone() {
         if let Node::Repo(_) = &node {
@@ -71,96 +457,223 @@ fn nixpkgs_deps(
         .collect();
 
     if !missing.is_empty() {
-        let error_msg = format!(
+        let mut error_msg = format!(
             "no nixpkgs dependency found for specified {}: {}",
             if missing.len() > 1 { "keys" } else { "key" },
             missing.join(", ")
         );
+        // Point at Nixpkgs inputs with other names, which are usually what was meant
+        let renamed: Vec<String> = select(flake_lock, &[nixpkgs_selector()])
+            .into_keys()
+            .filter(|name| !keys.contains(name))
+            .collect();
+        if !renamed.is_empty() {
+            error_msg.push_str(&format!(
+                " (the lockfile has Nixpkgs inputs named {})",
+                renamed.join(", ")
+            ));
+        }
         return Err(FlakeCheckerError::Invalid(error_msg));
     }
 
     Ok(deps)
 }
 
-pub(crate) fn check_flake_lock(
+/// Run the enabled checks against the Nixpkgs inputs of a lockfile, returning a report of the
+/// issues found (sorted by input) with their totals. Fails if a Nixpkgs input named in the
+/// configuration doesn't exist.
+pub fn check_flake_lock(
+    flake_lock: &FlakeLock,
+    config: &FlakeCheckConfig,
+) -> Result<CheckReport, FlakeCheckerError> {
+    let issues = find_issues(flake_lock, config)?;
+    Ok(CheckReport::new(flake_lock, config, issues))
+}
+
+/// Like [check_flake_lock], but only returns the issues.
+pub(crate) fn find_issues(
     flake_lock: &FlakeLock,
     config: &FlakeCheckConfig,
 ) -> Result<Vec<Issue>, FlakeCheckerError> {
-    let mut issues = vec![];
+    check_flake_lock_with(flake_lock, config, |_| {})
+}
 
-    let deps = nixpkgs_deps(flake_lock, config.nixpkgs_keys.clone())?;
-
-    for (name, dep) in deps {
-        if let Node::Repo(repo) = dep {
-            // Check if not explicitly supported
-            if config.check_supported {
-                if let Some(ref git_ref) = repo.original.git_ref {
-                    if !ALLOWED_REFS.contains(&git_ref.as_str()) {
-                        issues.push(Issue {
-                            input: name.clone(),
-                            kind: IssueKind::Disallowed(Disallowed {
-                                reference: git_ref.to_string(),
-                            }),
-                        });
-                    }
-                }
-            }
+/// The inputs that a check runs against: those its own selectors pick, or `deps` otherwise,
+/// narrowed to its scope.
+pub(crate) fn check_deps<'a>(
+    flake_lock: &FlakeLock,
+    config: &FlakeCheckConfig,
+    check: &str,
+    deps: &'a BTreeMap<String, Node>,
+) -> Cow<'a, BTreeMap<String, Node>> {
+    let deps = match config.check_selectors.get(check) {
+        Some(selectors) => Cow::Owned(select(flake_lock, selectors)),
+        None => Cow::Borrowed(deps),
+    };
+    match config.check_scopes.get(check) {
+        Some(scope) if *scope != Scope::All => Cow::Owned(
+            deps.iter()
+                .filter(|(name, _)| scope.includes(flake_lock.root.contains_key(*name)))
+                .map(|(name, node)| (name.clone(), node.clone()))
+                .collect(),
+        ),
+        _ => deps,
+    }
+}
 
-            // Check if outdated
-            if config.check_outdated {
-                let now_timestamp = Utc::now().timestamp();
-                let diff = now_timestamp - repo.locked.last_modified;
-                let num_days_old = Duration::seconds(diff).num_days();
+// Each enabled check with the inputs it runs against
+type SelectedChecks<'a> = Vec<(&'a dyn Check, Cow<'a, BTreeMap<String, Node>>)>;
 
-                if num_days_old > MAX_DAYS {
-                    issues.push(Issue {
-                        input: name.clone(),
-                        kind: IssueKind::Outdated(Outdated { num_days_old }),
-                    });
-                }
-            }
+/// Like [find_issues], but calls `on_issue` with each issue as soon as it's found.
+pub(crate) fn check_flake_lock_with(
+    flake_lock: &FlakeLock,
+    config: &FlakeCheckConfig,
+    mut on_issue: impl FnMut(&Issue),
+) -> Result<Vec<Issue>, FlakeCheckerError> {
+    let mut issues = vec![];
+    let mut report = |mut issue: Issue| {
+        issue.severity = config.issue_severity(&issue);
+        on_issue(&issue);
+        issues.push(issue);
+    };
+
+    let deps = selected_deps(flake_lock, config)?;
+    let checks: SelectedChecks = config
+        .checks
+        .iter()
+        .filter(|check| check.enabled(config))
+        .map(|check| (check, check_deps(flake_lock, config, check.name(), &deps)))
+        .collect();
+    let names: BTreeSet<&String> = checks.iter().flat_map(|(_, deps)| deps.keys()).collect();
 
-            // Check that the GitHub owner is NixOS
-            if config.check_owner {
-                let owner = repo.original.owner;
-                if owner.to_lowercase() != "nixos" {
-                    issues.push(Issue {
+    // Only inputs of other inputs have a provenance, so the graph is only needed for them
+    let graph = names
+        .iter()
+        .any(|name| !flake_lock.root.contains_key(*name))
+        .then(|| InputGraph::new(flake_lock).ok())
+        .flatten();
+    for name in names {
+        let provenance = graph
+            .as_ref()
+            .filter(|_| !flake_lock.root.contains_key(name))
+            .and_then(|graph| graph.path_to(name));
+        for (check, deps) in checks.iter() {
+            if let Some(Node::Repo(repo)) = deps.get(name) {
+                if let Some(kind) = check.check(name, repo, config)? {
+                    let mut issue = Issue {
                         input: name.clone(),
-                        kind: IssueKind::NonUpstream(NonUpstream { owner }),
-                    });
+                        kind,
+                        location: None,
+                        provenance: provenance.clone(),
+                        severity: Severity::Warning,
+                        message: None,
+                    };
+                    if let Some(template) = config.messages.get(check.name()) {
+                        issue.message = Some(template.render(&issue, repo, config)?);
+                    }
+                    report(issue);
                 }
             }
         }
     }
+
+    // Inputs are checked in name order, but sort anyway so that the order doesn't depend on the
+    // order in which the checks run
+    sort_issues(&mut issues);
+
     Ok(issues)
 }
 
+/// Sort issues by input and then by kind, the order in which every output format lists them.
+pub(crate) fn sort_issues(issues: &mut [Issue]) {
+    issues.sort_by(|a, b| {
+        a.input
+            .cmp(&b.input)
+            .then_with(|| a.kind.name().cmp(b.kind.name()))
+    });
+}
+
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
+    use std::collections::BTreeSet;
+    use std::path::{Path, PathBuf};
+
+    use chrono::{NaiveDate, Utc};
 
     use crate::{
-        check_flake_lock,
-        issue::{Disallowed, Issue, IssueKind, NonUpstream},
+        flake::{find_issues, input_details, parse_time, ref_eol, selected_deps, Escalation},
+        issue::{Disallowed, Issue, IssueKind, NonUpstream, Outdated, Severity},
         FlakeCheckConfig, FlakeLock,
     };
+    use parse_flake_lock::{FlakeLockParseError, Forge, Node};
+
+    #[test]
+    fn test_ref_eol() {
+        assert_eq!(ref_eol("nixos-22.11"), NaiveDate::from_ymd_opt(2023, 6, 30));
+        assert_eq!(
+            ref_eol("nixpkgs-23.05-darwin"),
+            NaiveDate::from_ymd_opt(2023, 12, 31)
+        );
+        assert_eq!(ref_eol("nixos-unstable"), None);
+    }
 
     #[test]
     fn test_clean_flake_locks() {
         for n in 0..=7 {
             let path = PathBuf::from(format!("tests/flake.clean.{n}.lock"));
             let flake_lock = FlakeLock::new(&path).expect("couldn't create flake.lock");
+            // Some of these have other Nixpkgs inputs, which are found by default
             let config = FlakeCheckConfig {
                 check_outdated: false,
+                nixpkgs_keys: vec![String::from("nixpkgs")],
                 ..Default::default()
             };
-            let issues = check_flake_lock(&flake_lock, &config).expect(&format!(
-                "couldn't run check_flake_lock function in {path:?}"
-            ));
-            assert!(issues.is_empty());
+            let issues = find_issues(&flake_lock, &config)
+                .unwrap_or_else(|_| panic!("couldn't run find_issues function in {path:?}"));
+            assert!(issues.is_empty(), "{path:?}: {issues:?}");
         }
     }
 
+    #[test]
+    fn test_renamed_nixpkgs_inputs() {
+        let config = FlakeCheckConfig {
+            check_outdated: false,
+            ..Default::default()
+        };
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap())
+                .unwrap();
+        let inputs = lock["nodes"]["root"]["inputs"].as_object_mut().unwrap();
+        let nixpkgs = inputs.remove("nixpkgs").unwrap();
+        inputs.insert(String::from("pkgs"), nixpkgs);
+        let rust_overlay = inputs.remove("rust-overlay").unwrap();
+        inputs.insert(String::from("nixpkgs-fmt"), rust_overlay);
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+
+        // Nixpkgs is found by what it is rather than by its name, and other inputs with Nixpkgs
+        // in their names aren't
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let inputs: BTreeSet<&str> = issues.iter().map(|issue| issue.input.as_str()).collect();
+        assert_eq!(inputs, BTreeSet::from(["pkgs"]));
+        assert_eq!(issues.len(), 2);
+
+        // Inputs that follow Nixpkgs are found too
+        let flake_lock = FlakeLock::new(Path::new("tests/flake.clean.3.lock")).unwrap();
+        let names: Vec<String> = selected_deps(&flake_lock, &config)
+            .unwrap()
+            .into_keys()
+            .collect();
+        assert!(names.contains(&String::from("nixpkgs")));
+        assert!(names.contains(&String::from("nixpkgs-nodejs")));
+
+        // Explicit keys override the detection
+        let config = FlakeCheckConfig {
+            nixpkgs_keys: vec![String::from("nixpkgs")],
+            ..config
+        };
+        assert!(find_issues(&flake_lock, &config).unwrap().is_empty());
+    }
+
     #[test]
     fn test_dirty_flake_locks() {
         let cases: Vec<(&str, Vec<Issue>)> = vec![
@@ -172,12 +685,21 @@ mod test {
                         kind: IssueKind::Disallowed(Disallowed {
                             reference: String::from("this-should-fail"),
                         }),
+                        location: None,
+                        provenance: None,
+                        severity: Severity::Warning,
+                        message: None,
                     },
                     Issue {
                         input: String::from("nixpkgs"),
                         kind: IssueKind::NonUpstream(NonUpstream {
                             owner: String::from("bitcoin-miner-org"),
+                            host: None,
                         }),
+                        location: None,
+                        provenance: None,
+                        severity: Severity::Warning,
+                        message: None,
                     },
                 ],
             ),
@@ -189,12 +711,21 @@ mod test {
                         kind: IssueKind::Disallowed(Disallowed {
                             reference: String::from("probably-nefarious"),
                         }),
+                        location: None,
+                        provenance: None,
+                        severity: Severity::Warning,
+                        message: None,
                     },
                     Issue {
                         input: String::from("nixpkgs"),
                         kind: IssueKind::NonUpstream(NonUpstream {
                             owner: String::from("pretty-shady"),
+                            host: None,
                         }),
+                        location: None,
+                        provenance: None,
+                        severity: Severity::Warning,
+                        message: None,
                     },
                 ],
             ),
@@ -207,13 +738,111 @@ mod test {
                 check_outdated: false,
                 ..Default::default()
             };
-            let issues = check_flake_lock(&flake_lock, &config)
-                .expect("couldn't run check_flake_lock function");
+            let issues =
+                find_issues(&flake_lock, &config).expect("couldn't run find_issues function");
             dbg!(&path);
             assert_eq!(issues, expected_issues);
         }
     }
 
+    #[test]
+    fn test_node_types() {
+        let flake_lock = FlakeLock::new(&PathBuf::from("tests/flake.clean.5.lock")).unwrap();
+        let Some(Node::Git(wezterm)) = flake_lock.nodes.get("wezterm") else {
+            panic!("expected a Git node");
+        };
+        assert_eq!(wezterm.locked.rev_count, Some(7255));
+        assert_eq!(wezterm.original.url, "https://github.com/wez/wezterm.git");
+        let Some(Node::Repo(nixpkgs)) = flake_lock.root.get("nixpkgs") else {
+            panic!("expected a repository node");
+        };
+        assert_eq!(nixpkgs.forge(), Some(Forge::GitHub));
+
+        // A working tree with uncommitted changes has no revision
+        let details = input_details(&flake_lock, Utc::now());
+        let a = details.iter().find(|input| input.name == "a").unwrap();
+        assert_eq!((a.node_type.as_str(), a.rev.as_deref()), ("git", None));
+        assert!(a.num_days_old.is_some());
+    }
+
+    #[test]
+    fn test_lock_versions() {
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap())
+                .unwrap();
+        let with_version = |lock: &serde_json::Value, version: u64| {
+            let mut lock = lock.clone();
+            lock["version"] = version.into();
+            lock.to_string().parse::<FlakeLock>()
+        };
+
+        // Version 5 lockfiles can keep locked attributes in `info`
+        let locked = lock["nodes"]["nixpkgs"]["locked"].as_object_mut().unwrap();
+        let last_modified = locked.remove("lastModified").unwrap();
+        lock["nodes"]["nixpkgs"]["info"] = serde_json::json!({ "lastModified": last_modified });
+        let flake_lock = with_version(&lock, 5).unwrap();
+        let Some(Node::Repo(nixpkgs)) = flake_lock.root.get("nixpkgs") else {
+            panic!("expected a repository node");
+        };
+        assert_eq!(nixpkgs.locked.last_modified, 1681358109);
+
+        for version in [4, 8] {
+            assert!(matches!(
+                with_version(&lock, version),
+                Err(FlakeLockParseError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn test_non_flake_inputs() {
+        let source = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let config = FlakeCheckConfig::default();
+        let issues = find_issues(&source.parse().unwrap(), &config).unwrap();
+
+        // Inputs with `flake = false` are still held to the age and source policies
+        let mut lock: serde_json::Value = serde_json::from_str(&source).unwrap();
+        lock["nodes"]["nixpkgs"]["flake"] = false.into();
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        assert_eq!(find_issues(&flake_lock, &config).unwrap(), issues);
+
+        let details = input_details(&flake_lock, Utc::now());
+        let flake = |name: &str| details.iter().find(|d| d.name == name).unwrap().flake;
+        assert!(!flake("nixpkgs"));
+        assert!(flake("rust-overlay"));
+    }
+
+    #[test]
+    fn test_empty_follows() {
+        // Nix writes `follows = ""` as an empty path, which refers to the flake itself
+        let mut lock: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("tests/flake.clean.0.lock").unwrap())
+                .unwrap();
+        lock["nodes"]["root"]["inputs"]["me"] = serde_json::json!([]);
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        assert_eq!(flake_lock.root_node_names["me"], "root");
+        assert!(find_issues(&flake_lock, &FlakeCheckConfig::default()).is_ok());
+
+        lock["nodes"]["root"]["inputs"]["me"] = serde_json::json!(["nixpkgs", "missing"]);
+        assert!(lock.to_string().parse::<FlakeLock>().is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let source = "{\n  \"nodes\": {},\n  \"root\": \"root\"\n  \"version\": 7\n}\n";
+        let Err(FlakeLockParseError::Syntax(err)) = source.parse::<FlakeLock>() else {
+            panic!("expected a syntax error");
+        };
+        assert_eq!((err.line, err.column), (4, 3));
+        assert_eq!(err.message, "expected `,` or `}`");
+        assert_eq!(
+            FlakeLockParseError::Syntax(err)
+                .with_path(Path::new("sub/flake.lock"))
+                .to_string(),
+            "sub/flake.lock:4:3 expected `,` or `}`\n  2 |   \"nodes\": {},\n  3 |   \"root\": \"root\"\n> 4 |   \"version\": 7\n    |   ^\n  5 | }"
+        );
+    }
+
     #[test]
     fn test_explicit_nixpkgs_keys() {
         let cases: Vec<(&str, Vec<String>, Vec<Issue>)> = vec![(
@@ -223,7 +852,12 @@ mod test {
                 input: String::from("nixpkgs-alt"),
                 kind: IssueKind::NonUpstream(NonUpstream {
                     owner: String::from("seems-pretty-shady"),
+                    host: None,
                 }),
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
             }],
         )];
 
@@ -235,12 +869,36 @@ mod test {
                 nixpkgs_keys,
                 ..Default::default()
             };
-            let issues = check_flake_lock(&flake_lock, &config)
-                .expect("couldn't run check_flake_lock function");
+            let issues =
+                find_issues(&flake_lock, &config).expect("couldn't run find_issues function");
             assert_eq!(issues, expected_issues);
         }
     }
 
+    #[test]
+    fn test_issues_are_sorted() {
+        let path = PathBuf::from("tests/flake.explicit-keys.0.lock");
+        let flake_lock = FlakeLock::new(&path).expect("couldn't create flake.lock");
+        let config = FlakeCheckConfig {
+            nixpkgs_keys: vec![String::from("nixpkgs-alt"), String::from("nixpkgs")],
+            ..Default::default()
+        };
+        let issues = find_issues(&flake_lock, &config).expect("couldn't run find_issues function");
+
+        let order: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|i| (i.input.as_str(), i.kind.name()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("nixpkgs", "outdated"),
+                ("nixpkgs-alt", "non-upstream"),
+                ("nixpkgs-alt", "outdated"),
+            ]
+        );
+    }
+
     #[test]
     fn test_missing_nixpkgs_keys() {
         let cases: Vec<(&str, Vec<String>, String)> = vec![(
@@ -252,6 +910,11 @@ mod test {
             "flake.clean.1.lock",
             vec![String::from("nixpkgs"), String::from("nixpkgs-other")],
             String::from("invalid flake.lock: no nixpkgs dependency found for specified key: nixpkgs-other"),
+        ),
+        (
+            "flake.explicit-keys.0.lock",
+            vec![String::from("pkgs"), String::from("nixpkgs")],
+            String::from("invalid flake.lock: no nixpkgs dependency found for specified key: pkgs (the lockfile has Nixpkgs inputs named nixpkgs-alt)"),
         )];
         for (file, nixpkgs_keys, expected_err) in cases {
             let path = PathBuf::from(format!("tests/{file}"));
@@ -262,10 +925,102 @@ mod test {
                 ..Default::default()
             };
 
-            let result = check_flake_lock(&flake_lock, &config);
+            let result = find_issues(&flake_lock, &config);
 
             assert!(result.is_err());
             assert_eq!(result.unwrap_err().to_string(), expected_err);
         }
     }
+
+    #[test]
+    fn test_severity_escalation() {
+        let outdated = |num_days_old| Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Outdated(Outdated {
+                num_days_old,
+                max_days: 30,
+                last_modified: 0,
+                locked_at: String::new(),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        let disallowed = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("nixos-22.05"),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        let non_upstream = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::NonUpstream(NonUpstream {
+                owner: String::from("someone"),
+                host: None,
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+
+        let config = FlakeCheckConfig {
+            error_after_days: Some(60),
+            disallowed_error_after: NaiveDate::from_ymd_opt(2000, 1, 1),
+            ..Default::default()
+        };
+        assert_eq!(config.issue_severity(&outdated(45)), Severity::Warning);
+        assert_eq!(
+            config.escalation(&outdated(45)),
+            Some(Escalation::AtAge(60))
+        );
+        assert_eq!(config.issue_severity(&outdated(61)), Severity::Error);
+        assert_eq!(config.escalation(&outdated(61)), None);
+        assert_eq!(config.issue_severity(&disallowed), Severity::Error);
+        assert_eq!(config.issue_severity(&non_upstream), Severity::Warning);
+
+        // Thresholds decide the severity of their issues even in fail mode
+        let config = FlakeCheckConfig {
+            fail_mode: true,
+            disallowed_error_after: NaiveDate::from_ymd_opt(9999, 1, 1),
+            ..config
+        };
+        assert_eq!(config.issue_severity(&outdated(45)), Severity::Warning);
+        assert_eq!(config.issue_severity(&disallowed), Severity::Warning);
+        assert_eq!(config.issue_severity(&non_upstream), Severity::Error);
+    }
+
+    #[test]
+    fn test_checking_at_a_time() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let outdated = |at: &str| -> Vec<i64> {
+            let config = FlakeCheckConfig {
+                now: Some(parse_time(at).unwrap()),
+                ..Default::default()
+            };
+            find_issues(&flake_lock, &config)
+                .unwrap()
+                .into_iter()
+                .filter_map(|issue| match issue.kind {
+                    IssueKind::Outdated(outdated) => Some(outdated.num_days_old),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // nixpkgs was last modified on 2023-04-13
+        assert!(outdated("2023-05-01").is_empty());
+        assert_eq!(outdated("2023-06-01T12:00:00+02:00"), [49]);
+        assert_eq!(outdated("1704067200"), [262]);
+        assert_eq!(parse_time("1704067200"), parse_time("2024-01-01"));
+        assert!(parse_time("next tuesday").is_err());
+    }
 }
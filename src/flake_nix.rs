@@ -0,0 +1,296 @@
+use crate::error::FlakeCheckerError;
+use crate::fix::replacement_ref;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::issue::{Issue, IssueKind};
+
+use std::path::Path;
+
+use parse_flake_lock::{FlakeLock, Node};
+
+/// An input URL in `flake.nix` that was moved from a disallowed ref to a supported one.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Rewrite {
+    pub input: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Rewrite the URLs in the `flake.nix` next to a local lockfile that point at disallowed refs, so
+/// that re-locking doesn't bring the disallowed refs back, and print what was rewritten.
+pub(crate) fn fix(
+    flake_lock_path: &Path,
+    source: &str,
+    config: &FlakeCheckConfig,
+) -> Result<(), FlakeCheckerError> {
+    let flake_nix_path = match flake_lock_path.parent() {
+        Some(dir) => dir.join("flake.nix"),
+        None => Path::new("flake.nix").to_path_buf(),
+    };
+    if !flake_nix_path.exists() {
+        println!(
+            "no flake.nix found at {:?}; not rewriting any refs",
+            flake_nix_path
+        );
+        return Ok(());
+    }
+
+    let flake_lock: FlakeLock = source.parse()?;
+    let issues = find_issues(&flake_lock, config)?;
+    let flake_nix = std::fs::read_to_string(&flake_nix_path)?;
+    let (rewritten, rewrites) = rewrite(&flake_nix, &flake_lock, &issues);
+    if rewrites.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::write(&flake_nix_path, rewritten)?;
+    for Rewrite { input, from, to } in rewrites.iter() {
+        println!(
+            "rewrote the `{input}` input in {:?} from `{from}` to `{to}`",
+            flake_nix_path
+        );
+    }
+    println!("run `nix flake lock` (or pass --fix) to lock the new refs");
+    Ok(())
+}
+
+/// Rewrite the URLs of the inputs with disallowed refs to the closest supported refs, leaving the
+/// rest of `flake.nix` exactly as written. URLs are matched by their owner, repo, and ref, both as
+/// `github:owner/repo/ref` and as `github:owner/repo?ref=ref`.
+pub(crate) fn rewrite(
+    flake_nix: &str,
+    flake_lock: &FlakeLock,
+    issues: &[Issue],
+) -> (String, Vec<Rewrite>) {
+    let mut rewritten = flake_nix.to_string();
+    let mut rewrites = Vec::new();
+
+    for issue in issues.iter() {
+        let IssueKind::Disallowed(disallowed) = &issue.kind else {
+            continue;
+        };
+        let Some(Node::Repo(repo)) = flake_lock.root.get(&issue.input) else {
+            continue;
+        };
+        let original = &repo.original;
+        let prefix = format!(
+            "{}:{}/{}",
+            original.node_type, original.owner, original.repo
+        );
+        let to = replacement_ref(&disallowed.reference);
+
+        let mut found = false;
+        rewritten = map_strings(&rewritten, |url| {
+            let new = rewrite_url(url, &prefix, repo.dir(), &disallowed.reference, to);
+            found |= new.is_some();
+            new
+        });
+        if found {
+            rewrites.push(Rewrite {
+                input: issue.input.clone(),
+                from: disallowed.reference.clone(),
+                to: to.to_string(),
+            });
+        }
+    }
+
+    (rewritten, rewrites)
+}
+
+// Move a flake URL with the given `type:owner/repo` prefix and subdirectory from one ref to
+// another, if it's on that ref. Owners and repos are compared case-insensitively, like GitHub does.
+fn rewrite_url(url: &str, prefix: &str, dir: Option<&str>, from: &str, to: &str) -> Option<String> {
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    };
+    // Flakes in other subdirectories of the repository are other inputs
+    let url_dir = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|param| param.strip_prefix("dir="));
+    if url_dir != dir {
+        return None;
+    }
+
+    if path.eq_ignore_ascii_case(&format!("{prefix}/{from}")) {
+        let path = format!("{}/{to}", &path[..prefix.len()]);
+        return Some(match query {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        });
+    }
+
+    if path.eq_ignore_ascii_case(prefix) {
+        let query = query?;
+        let params: Vec<&str> = query.split('&').collect();
+        let old = format!("ref={from}");
+        if params.contains(&old.as_str()) {
+            let params: Vec<String> = params
+                .into_iter()
+                .map(|param| {
+                    if param == old {
+                        format!("ref={to}")
+                    } else {
+                        param.to_string()
+                    }
+                })
+                .collect();
+            return Some(format!("{path}?{}", params.join("&")));
+        }
+    }
+
+    None
+}
+
+// Apply a function to the contents of each double-quoted string in Nix source, skipping comments
+// and keeping everything else as is. Strings with interpolations are left alone.
+fn map_strings(source: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find(['"', '#', '/']) {
+        let (before, from) = rest.split_at(start);
+        out.push_str(before);
+
+        if from.starts_with('#') {
+            let end = from.find('\n').unwrap_or(from.len());
+            out.push_str(&from[..end]);
+            rest = &from[end..];
+        } else if from.starts_with("/*") {
+            let end = from.find("*/").map(|end| end + 2).unwrap_or(from.len());
+            out.push_str(&from[..end]);
+            rest = &from[end..];
+        } else if let Some(after) = from.strip_prefix('/') {
+            out.push('/');
+            rest = after;
+        } else {
+            // Find the closing quote, skipping escaped characters
+            let mut end = None;
+            let mut escaped = false;
+            for (i, c) in from.char_indices().skip(1) {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => {
+                        end = Some(i);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let Some(end) = end else {
+                out.push_str(from);
+                return out;
+            };
+
+            let contents = &from[1..end];
+            let mapped = if contents.contains("${") || contents.contains('\\') {
+                None
+            } else {
+                f(contents)
+            };
+            out.push('"');
+            out.push_str(mapped.as_deref().unwrap_or(contents));
+            out.push('"');
+            rest = &from[end + 1..];
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::flake_nix::{rewrite, rewrite_url, Rewrite};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_rewrite() {
+        let prefix = "github:NixOS/nixpkgs";
+        assert_eq!(
+            rewrite_url(
+                "github:nixos/nixpkgs/nixos-22.05",
+                prefix,
+                None,
+                "nixos-22.05",
+                "nixos-23.05"
+            ),
+            Some(String::from("github:nixos/nixpkgs/nixos-23.05"))
+        );
+        assert_eq!(
+            rewrite_url(
+                "github:NixOS/nixpkgs?ref=nixos-22.05&dir=lib",
+                prefix,
+                Some("lib"),
+                "nixos-22.05",
+                "nixos-23.05"
+            ),
+            Some(String::from("github:NixOS/nixpkgs?ref=nixos-23.05&dir=lib"))
+        );
+        assert_eq!(
+            rewrite_url(
+                "github:NixOS/nixpkgs/nixos-22.05?dir=lib",
+                prefix,
+                None,
+                "nixos-22.05",
+                "nixos-23.05"
+            ),
+            None
+        );
+        assert_eq!(
+            rewrite_url(
+                "github:NixOS/nixpkgs/nixos-22.055",
+                prefix,
+                None,
+                "nixos-22.05",
+                "nixos-23.05"
+            ),
+            None
+        );
+        assert_eq!(
+            rewrite_url(
+                "github:NixOS/nixpkgs-foo/nixos-22.05",
+                prefix,
+                None,
+                "nixos-22.05",
+                "nixos-23.05"
+            ),
+            None
+        );
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &FlakeCheckConfig::default()).unwrap();
+        let flake_nix = r#"{
+  inputs = {
+    # Was "github:bitcoin-miner-org/nixpkgs/this-should-fail" before
+    nixpkgs.url = "github:bitcoin-miner-org/nixpkgs/this-should-fail";
+    flake-utils.url = "github:numtide/flake-utils";
+  };
+  outputs = { self, nixpkgs, ... }: { greeting = "${nixpkgs}/this-should-fail"; };
+}
+"#;
+        let (rewritten, rewrites) = rewrite(flake_nix, &flake_lock, &issues);
+        assert_eq!(
+            rewritten,
+            flake_nix.replacen(
+                r#"nixpkgs.url = "github:bitcoin-miner-org/nixpkgs/this-should-fail""#,
+                r#"nixpkgs.url = "github:bitcoin-miner-org/nixpkgs/nixos-unstable""#,
+                1
+            )
+        );
+        assert_eq!(
+            rewrites,
+            [Rewrite {
+                input: String::from("nixpkgs"),
+                from: String::from("this-should-fail"),
+                to: String::from("nixos-unstable"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{input_details, ref_eol, selected_deps, FlakeCheckConfig};
+use crate::table;
+
+use parse_flake_lock::{FlakeLock, Node};
+use serde::Serialize;
+
+/// Output formats for `flake-checker forecast`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub(crate) enum ForecastFormat {
+    /// An aligned, human-readable table.
+    Table,
+    /// A JSON array with one object per input.
+    Json,
+}
+
+/// When a checked input will start failing the checks if it isn't updated.
+#[derive(Debug, Serialize)]
+pub(crate) struct InputForecast {
+    pub input: String,
+    pub git_ref: Option<String>,
+    pub num_days_old: Option<i64>,
+    /// The days left until the input exceeds the maximum age (negative once it has).
+    pub days_until_outdated: Option<i64>,
+    /// The end-of-life date of the input's release branch, if it has one.
+    pub ref_eol: Option<String>,
+    /// The days left until the input's release branch reaches end of life (negative once it has).
+    pub days_until_eol: Option<i64>,
+}
+
+/// Forecast the inputs that the checks consider, soonest to fail first.
+pub(crate) fn forecast(
+    flake_lock: &FlakeLock,
+    config: &FlakeCheckConfig,
+) -> Result<Vec<InputForecast>, FlakeCheckerError> {
+    let deps = selected_deps(flake_lock, config)?;
+    let now = config.now();
+    let today = now.date_naive();
+
+    let mut forecasts: Vec<InputForecast> = input_details(flake_lock, now)
+        .into_iter()
+        // Only GitHub inputs are checked
+        .filter(|details| matches!(deps.get(&details.name), Some(Node::Repo(_))))
+        .map(|details| {
+            let eol = details.git_ref.as_deref().and_then(ref_eol);
+            InputForecast {
+                days_until_outdated: details.num_days_old.map(|age| config.max_days - age),
+                ref_eol: eol.map(|eol| eol.to_string()),
+                days_until_eol: eol.map(|eol| (eol - today).num_days()),
+                input: details.name,
+                git_ref: details.git_ref,
+                num_days_old: details.num_days_old,
+            }
+        })
+        .collect();
+    forecasts.sort_by_key(|forecast| forecast.days_left().unwrap_or(i64::MAX));
+
+    Ok(forecasts)
+}
+
+impl InputForecast {
+    // The days until the first of the input's deadlines, if it has any.
+    fn days_left(&self) -> Option<i64> {
+        match (self.days_until_outdated, self.days_until_eol) {
+            (Some(outdated), Some(eol)) => Some(outdated.min(eol)),
+            (outdated, eol) => outdated.or(eol),
+        }
+    }
+}
+
+pub(crate) fn generate(
+    forecasts: &[InputForecast],
+    format: ForecastFormat,
+) -> Result<String, FlakeCheckerError> {
+    Ok(match format {
+        ForecastFormat::Table => table(forecasts),
+        ForecastFormat::Json => serde_json::to_string_pretty(forecasts)? + "\n",
+    })
+}
+
+fn table(forecasts: &[InputForecast]) -> String {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+    let rows: Vec<[String; 6]> = forecasts
+        .iter()
+        .map(|forecast| {
+            [
+                forecast.input.clone(),
+                or_dash(forecast.git_ref.clone()),
+                or_dash(forecast.num_days_old.map(|age| age.to_string())),
+                or_dash(forecast.days_until_outdated.map(describe_days)),
+                or_dash(forecast.ref_eol.clone()),
+                or_dash(forecast.days_until_eol.map(describe_days)),
+            ]
+        })
+        .collect();
+
+    table::render(["input", "ref", "age", "outdated", "ref eol", "eol"], &rows)
+}
+
+fn describe_days(days: i64) -> String {
+    match days {
+        -1 => String::from("1 day ago"),
+        days if days < 0 => format!("{} days ago", -days),
+        0 => String::from("today"),
+        1 => String::from("in 1 day"),
+        days => format!("in {days} days"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::FlakeCheckConfig;
+    use crate::forecast::forecast;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_forecast() {
+        let source = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+
+        let forecasts = forecast(&flake_lock, &FlakeCheckConfig::default()).unwrap();
+        assert_eq!(forecasts.len(), 1);
+
+        let nixpkgs = &forecasts[0];
+        assert_eq!(nixpkgs.input, "nixpkgs");
+        assert_eq!(
+            nixpkgs.days_until_outdated,
+            nixpkgs.num_days_old.map(|age| 30 - age)
+        );
+    }
+}
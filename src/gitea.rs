@@ -0,0 +1,97 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::issue::Severity;
+use crate::summary::Report;
+
+use std::env;
+
+use serde_json::json;
+
+const STATUS_CONTEXT: &str = "flake-checker";
+
+/// Posts the checker's result as a commit status via the [Gitea] / [Forgejo] API.
+///
+/// [Forgejo]: https://forgejo.org
+/// [Gitea]: https://about.gitea.com
+pub(crate) struct GiteaStatusReporter {
+    server_url: String,
+    repo: String,
+    commit: String,
+    token: String,
+    target_url: Option<String>,
+    network: NetworkConfig,
+}
+
+impl GiteaStatusReporter {
+    /// Create a reporter from the environment that Gitea and Forgejo Actions provide
+    /// (`GITHUB_SERVER_URL`, `GITHUB_REPOSITORY`, `GITHUB_SHA`, and `GITHUB_RUN_ID`).
+    /// `GITEA_SERVER_URL` takes precedence over `GITHUB_SERVER_URL` if set. Unless a target URL is
+    /// supplied, the status links to the current Actions run.
+    pub(crate) fn from_env(
+        token: String,
+        target_url: Option<String>,
+        network: NetworkConfig,
+    ) -> Result<Self, FlakeCheckerError> {
+        let server_url = env::var("GITEA_SERVER_URL").or_else(|_| env::var("GITHUB_SERVER_URL"))?;
+        let server_url = server_url.trim_end_matches('/').to_string();
+        let repo = env::var("GITHUB_REPOSITORY")?;
+        let commit = env::var("GITHUB_SHA")?;
+
+        let target_url = target_url.or_else(|| {
+            env::var("GITHUB_RUN_ID")
+                .ok()
+                .map(|run_id| format!("{server_url}/{repo}/actions/runs/{run_id}"))
+        });
+
+        Ok(Self {
+            server_url,
+            repo,
+            commit,
+            token,
+            target_url,
+            network,
+        })
+    }
+
+    pub(crate) fn report(&self, report: &Report) -> Result<(), FlakeCheckerError> {
+        let num_issues = report.num_issues();
+
+        let state = if num_issues == 0 {
+            "success"
+        } else if report.severity() == Severity::Error {
+            "failure"
+        } else {
+            "warning"
+        };
+
+        let target = match report.summaries.len() {
+            1 => String::from("flake.lock"),
+            n => format!("{n} lockfiles"),
+        };
+        let description = match num_issues {
+            0 => format!("No issues found in {target}"),
+            1 => format!("1 issue found in {target}"),
+            n => format!("{n} issues found in {target}"),
+        };
+
+        let status = json!({
+            "state": state,
+            "context": STATUS_CONTEXT,
+            "description": description,
+            "target_url": self.target_url,
+        });
+
+        http::client(&self.network)?
+            .post(format!(
+                "{}/api/v1/repos/{}/statuses/{}",
+                self.server_url, self.repo, self.commit
+            ))
+            .header("Authorization", format!("token {}", self.token))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&status)?)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,566 @@
+//! A [GitHub App] that receives `push` and `pull_request` webhook events, checks the lockfiles
+//! they change, and reports the results as check runs with annotations.
+//!
+//! [GitHub App]: https://docs.github.com/en/apps
+
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::http;
+use crate::i18n::Lang;
+use crate::issue::Severity;
+use crate::serve::{read_request, write_response, Request, Response};
+use crate::summary::{Report, Summary};
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::Engine;
+use parse_flake_lock::source::SourceMap;
+use parse_flake_lock::{FlakeLock, FlakeLockParseError};
+use ring::{hmac, rand, signature};
+use serde_json::json;
+
+const API_URL: &str = "https://api.github.com";
+const CHECK_RUN_NAME: &str = "Flake checker";
+// The Checks API accepts at most 50 annotations per request
+const MAX_ANNOTATIONS: usize = 50;
+const PER_PAGE: usize = 100;
+
+pub(crate) struct GitHubApp {
+    app_id: String,
+    key_pair: signature::RsaKeyPair,
+    webhook_secret: hmac::Key,
+    client: reqwest::blocking::Client,
+    config: FlakeCheckConfig,
+    lang: Lang,
+}
+
+/// The lockfiles to check at a commit, from a webhook event.
+#[derive(Debug, PartialEq)]
+struct Job {
+    installation_id: u64,
+    owner: String,
+    repo: String,
+    head_sha: String,
+    changes: Changes,
+}
+
+#[derive(Debug, PartialEq)]
+enum Changes {
+    /// The lockfiles changed by a push.
+    Paths(Vec<String>),
+    /// A pull request, whose changed lockfiles are listed via the API.
+    PullRequest(u64),
+}
+
+impl GitHubApp {
+    /// Create an app from its ID, the path to its (PEM-encoded) private key, and its webhook
+    /// secret.
+    pub(crate) fn new(
+        app_id: String,
+        private_key: &Path,
+        webhook_secret: &str,
+        config: FlakeCheckConfig,
+        lang: Lang,
+    ) -> Result<Self, FlakeCheckerError> {
+        let pem = std::fs::read(private_key)?;
+        let key_pair = match rustls_pemfile::read_one(&mut pem.as_slice())? {
+            Some(rustls_pemfile::Item::RSAKey(der)) => signature::RsaKeyPair::from_der(&der),
+            Some(rustls_pemfile::Item::PKCS8Key(der)) => signature::RsaKeyPair::from_pkcs8(&der),
+            _ => {
+                return Err(FlakeCheckerError::GitHubApp(format!(
+                    "no RSA private key in {}",
+                    private_key.display()
+                )))
+            }
+        }
+        .map_err(|err| FlakeCheckerError::GitHubApp(format!("invalid private key: {err}")))?;
+
+        Ok(Self {
+            app_id,
+            key_pair,
+            webhook_secret: hmac::Key::new(hmac::HMAC_SHA256, webhook_secret.as_bytes()),
+            client: http::client(&config.network)?,
+            config,
+            lang,
+        })
+    }
+
+    /// Receive webhook events on the given address until the process is terminated. Each event
+    /// is acknowledged before its lockfiles are checked, as GitHub expects a prompt response.
+    pub(crate) fn serve(self, address: &str) -> Result<(), FlakeCheckerError> {
+        let listener = TcpListener::bind(address)?;
+        println!(
+            "listening for webhook events on http://{}",
+            listener.local_addr()?
+        );
+
+        let app = Arc::new(self);
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let app = Arc::clone(&app);
+            std::thread::spawn(move || {
+                let (response, job) = match read_request(&stream) {
+                    Ok(request) => match app.receive(&request) {
+                        Ok(job) => (
+                            Response {
+                                status: 202,
+                                body: json!({ "status": "accepted" }),
+                            },
+                            job,
+                        ),
+                        Err(response) => (response, None),
+                    },
+                    Err(response) => (response, None),
+                };
+                // There's nobody to report a failure to write the response to
+                let _ = write_response(&mut stream, &response);
+
+                if let Some(job) = job {
+                    if let Err(err) = app.run(&job) {
+                        eprintln!(
+                            "couldn't check {}/{}@{}: {err}",
+                            job.owner, job.repo, job.head_sha
+                        );
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    // Authenticates a webhook delivery and extracts the job (if any) from its event.
+    fn receive(&self, request: &Request) -> Result<Option<Job>, Response> {
+        if request.method != "POST" {
+            return Err(Response::error(405, "method not allowed"));
+        }
+        if !self.verify_signature(request.header("X-Hub-Signature-256"), &request.body) {
+            return Err(Response::error(401, "invalid signature"));
+        }
+
+        let event = request.header("X-GitHub-Event").unwrap_or_default();
+        let payload: serde_json::Value = serde_json::from_slice(&request.body)
+            .map_err(|err| Response::error(400, format!("invalid payload: {err}")))?;
+
+        Ok(job(event, &payload))
+    }
+
+    fn verify_signature(&self, signature: Option<&str>, body: &[u8]) -> bool {
+        signature
+            .and_then(|signature| signature.strip_prefix("sha256="))
+            .and_then(decode_hex)
+            .is_some_and(|tag| hmac::verify(&self.webhook_secret, body, &tag).is_ok())
+    }
+
+    fn run(&self, job: &Job) -> Result<(), FlakeCheckerError> {
+        let token = self.installation_token(job.installation_id)?;
+        let repo_url = format!("{API_URL}/repos/{}/{}", job.owner, job.repo);
+
+        let paths = match &job.changes {
+            Changes::Paths(paths) => paths.clone(),
+            Changes::PullRequest(number) => {
+                self.pull_request_lockfiles(&repo_url, *number, &token)?
+            }
+        };
+        if paths.is_empty() {
+            return Ok(());
+        }
+        println!(
+            "checking {} in {}/{}@{}",
+            paths.join(", "),
+            job.owner,
+            job.repo,
+            job.head_sha
+        );
+
+        let mut summaries = Vec::new();
+        let mut annotations = Vec::new();
+        for path in paths.iter() {
+            let source = api_accepting(
+                &self.client,
+                reqwest::Method::GET,
+                &format!("{repo_url}/contents/{path}"),
+                &token,
+                "application/vnd.github.raw",
+            )
+            .query(&[("ref", &job.head_sha)])
+            .send()?
+            .error_for_status()?
+            .text()?;
+            let flake_lock: FlakeLock = source
+                .parse()
+                .map_err(|err: FlakeLockParseError| err.with_path(Path::new(path)))?;
+            let mut issues = find_issues(&flake_lock, &self.config)?;
+
+            if let Ok(source_map) = SourceMap::new(&source) {
+                for issue in issues.iter_mut() {
+                    issue.locate(Path::new(path), &flake_lock, &source_map);
+                }
+            }
+            for issue in issues.iter() {
+                // Annotations without a line point at the top of the file
+                let line = issue
+                    .location
+                    .as_ref()
+                    .map(|location| location.line)
+                    .unwrap_or(1);
+                annotations.push(json!({
+                    "path": path,
+                    "start_line": line,
+                    "end_line": line,
+                    "annotation_level": match issue.severity {
+                        Severity::Error => "failure",
+                        Severity::Warning => "warning",
+                    },
+                    "title": issue.kind.name(),
+                    "message": issue.message(self.lang),
+                }));
+            }
+
+            summaries.push(Summary::new(
+                &issues,
+                &flake_lock,
+                &source,
+                PathBuf::from(path),
+                self.config.clone(),
+                self.lang,
+                None,
+            ));
+        }
+
+        let report = Report::new(summaries);
+        let num_issues = report.num_issues();
+        let conclusion = if num_issues == 0 {
+            "success"
+        } else if report.severity() == Severity::Error {
+            "failure"
+        } else {
+            "neutral"
+        };
+        let target = match report.summaries.len() {
+            1 => String::from("flake.lock"),
+            n => format!("{n} lockfiles"),
+        };
+        let title = match num_issues {
+            0 => format!("No issues found in {target}"),
+            1 => format!("1 issue found in {target}"),
+            n => format!("{n} issues found in {target}"),
+        };
+        let mut summary = String::new();
+        for summary_md in report.summaries.iter().map(Summary::markdown) {
+            summary.push_str(&summary_md?);
+        }
+        let mut outputs = check_run_outputs(&title, &summary, &annotations).into_iter();
+
+        let check_run = json!({
+            "name": CHECK_RUN_NAME,
+            "head_sha": job.head_sha,
+            "status": "completed",
+            "conclusion": conclusion,
+            "output": outputs.next(),
+        });
+        let created = api(
+            &self.client,
+            reqwest::Method::POST,
+            &format!("{repo_url}/check-runs"),
+            &token,
+        )
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&check_run)?)
+        .send()?
+        .error_for_status()?
+        .text()?;
+        let created: serde_json::Value = serde_json::from_str(&created)?;
+
+        // Each update appends its annotations to the ones already on the check run
+        let remaining: Vec<_> = outputs.collect();
+        if !remaining.is_empty() {
+            let Some(id) = created.get("id").and_then(serde_json::Value::as_u64) else {
+                return Err(FlakeCheckerError::GitHubApp(String::from(
+                    "the created check run has no id",
+                )));
+            };
+            for output in remaining {
+                api(
+                    &self.client,
+                    reqwest::Method::PATCH,
+                    &format!("{repo_url}/check-runs/{id}"),
+                    &token,
+                )
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_string(&json!({ "output": output }))?)
+                .send()?
+                .error_for_status()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Lists the lockfiles that a pull request adds or modifies.
+    fn pull_request_lockfiles(
+        &self,
+        repo_url: &str,
+        number: u64,
+        token: &str,
+    ) -> Result<Vec<String>, FlakeCheckerError> {
+        let mut paths = Vec::new();
+
+        for page in 1.. {
+            let response = api(
+                &self.client,
+                reqwest::Method::GET,
+                &format!("{repo_url}/pulls/{number}/files"),
+                token,
+            )
+            .query(&[
+                ("per_page", PER_PAGE.to_string()),
+                ("page", page.to_string()),
+            ])
+            .send()?
+            .error_for_status()?
+            .text()?;
+            let files: Vec<serde_json::Value> = serde_json::from_str(&response)?;
+
+            paths.extend(
+                files
+                    .iter()
+                    .filter(|file| file["status"] != "removed")
+                    .filter_map(|file| file["filename"].as_str())
+                    .filter(|path| is_flake_lock(path))
+                    .map(String::from),
+            );
+
+            if files.len() < PER_PAGE {
+                break;
+            }
+        }
+
+        Ok(paths)
+    }
+
+    // Exchanges a JWT signed with the app's private key for an installation access token.
+    fn installation_token(&self, installation_id: u64) -> Result<String, FlakeCheckerError> {
+        let response = api(
+            &self.client,
+            reqwest::Method::POST,
+            &format!("{API_URL}/app/installations/{installation_id}/access_tokens"),
+            &self.jwt()?,
+        )
+        .send()?
+        .error_for_status()?
+        .text()?;
+        let response: serde_json::Value = serde_json::from_str(&response)?;
+
+        response["token"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| FlakeCheckerError::GitHubApp(String::from("no installation token")))
+    }
+
+    fn jwt(&self) -> Result<String, FlakeCheckerError> {
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+        let now = chrono::Utc::now().timestamp();
+
+        // Backdated to allow for clock drift; GitHub allows at most 10 minutes of validity
+        let header = json!({ "alg": "RS256", "typ": "JWT" });
+        let claims = json!({ "iat": now - 60, "exp": now + 540, "iss": self.app_id });
+        let message = format!(
+            "{}.{}",
+            encode(serde_json::to_string(&header)?.as_bytes()),
+            encode(serde_json::to_string(&claims)?.as_bytes())
+        );
+
+        let mut signature = vec![0; self.key_pair.public_modulus_len()];
+        self.key_pair
+            .sign(
+                &signature::RSA_PKCS1_SHA256,
+                &rand::SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| FlakeCheckerError::GitHubApp(String::from("couldn't sign JWT")))?;
+
+        Ok(format!("{message}.{}", encode(&signature)))
+    }
+}
+
+// Extracts the job from a webhook event, if it's one that calls for a check.
+fn job(event: &str, payload: &serde_json::Value) -> Option<Job> {
+    let installation_id = payload["installation"]["id"].as_u64()?;
+    let owner = payload["repository"]["owner"]["login"]
+        .as_str()?
+        .to_string();
+    let repo = payload["repository"]["name"].as_str()?.to_string();
+
+    let (head_sha, changes) = match event {
+        // Branch deletions have nothing to check
+        "push" if payload["deleted"] != true => {
+            let mut paths: Vec<String> = payload["commits"]
+                .as_array()?
+                .iter()
+                .flat_map(|commit| {
+                    ["added", "modified"]
+                        .into_iter()
+                        .filter_map(|key| commit[key].as_array())
+                        .flatten()
+                })
+                .filter_map(|path| path.as_str())
+                .filter(|path| is_flake_lock(path))
+                .map(String::from)
+                .collect();
+            paths.sort();
+            paths.dedup();
+            if paths.is_empty() {
+                return None;
+            }
+            (payload["after"].as_str()?, Changes::Paths(paths))
+        }
+        "pull_request"
+            if matches!(
+                payload["action"].as_str(),
+                Some("opened" | "reopened" | "synchronize")
+            ) =>
+        {
+            (
+                payload["pull_request"]["head"]["sha"].as_str()?,
+                Changes::PullRequest(payload["number"].as_u64()?),
+            )
+        }
+        _ => return None,
+    };
+
+    Some(Job {
+        installation_id,
+        owner,
+        repo,
+        head_sha: head_sha.to_string(),
+        changes,
+    })
+}
+
+fn is_flake_lock(path: &str) -> bool {
+    path == "flake.lock" || path.ends_with("/flake.lock")
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Splits a check run's annotations into the outputs to send, the first with the check run and
+// the rest as updates, since each request carries at most MAX_ANNOTATIONS of them.
+fn check_run_outputs(
+    title: &str,
+    summary: &str,
+    annotations: &[serde_json::Value],
+) -> Vec<serde_json::Value> {
+    let output = |annotations: &[serde_json::Value]| {
+        json!({
+            "title": title,
+            "summary": summary,
+            "annotations": annotations,
+        })
+    };
+    if annotations.is_empty() {
+        return vec![output(&[])];
+    }
+    annotations.chunks(MAX_ANNOTATIONS).map(output).collect()
+}
+
+// Starts a request to the GitHub API, which responds with JSON unless another media type is
+// accepted.
+fn api(
+    client: &reqwest::blocking::Client,
+    method: reqwest::Method,
+    url: &str,
+    token: &str,
+) -> reqwest::blocking::RequestBuilder {
+    api_accepting(client, method, url, token, "application/vnd.github+json")
+}
+
+fn api_accepting(
+    client: &reqwest::blocking::Client,
+    method: reqwest::Method,
+    url: &str,
+    token: &str,
+    accept: &str,
+) -> reqwest::blocking::RequestBuilder {
+    client
+        .request(method, url)
+        .header("Accept", accept)
+        .header("User-Agent", "flake-checker")
+        .bearer_auth(token)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_run_outputs, decode_hex, job, Changes, Job};
+
+    use serde_json::json;
+
+    #[test]
+    fn test_push_job() {
+        let payload = json!({
+            "after": "abc123",
+            "installation": { "id": 42 },
+            "repository": { "name": "infra", "owner": { "login": "acme" } },
+            "commits": [
+                { "added": ["hosts/flake.lock"], "modified": ["README.md"] },
+                { "added": [], "modified": ["flake.lock", "hosts/flake.lock"] },
+            ],
+        });
+
+        assert_eq!(
+            job("push", &payload),
+            Some(Job {
+                installation_id: 42,
+                owner: String::from("acme"),
+                repo: String::from("infra"),
+                head_sha: String::from("abc123"),
+                changes: Changes::Paths(vec![
+                    String::from("flake.lock"),
+                    String::from("hosts/flake.lock")
+                ]),
+            })
+        );
+
+        let mut unrelated = payload.clone();
+        unrelated["commits"] = json!([{ "modified": ["not-a-flake.lock.txt"] }]);
+        assert_eq!(job("push", &unrelated), None);
+        assert_eq!(job("issues", &payload), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff10"), Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    #[test]
+    fn test_check_run_outputs() {
+        let annotations: Vec<_> = (0..120).map(|line| json!({ "start_line": line })).collect();
+        let outputs = check_run_outputs("title", "summary", &annotations);
+        let sizes: Vec<_> = outputs
+            .iter()
+            .map(|output| output["annotations"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![50, 50, 20]);
+        assert_eq!(outputs[2]["annotations"][0], json!({ "start_line": 100 }));
+        assert!(outputs.iter().all(|output| output["title"] == "title"));
+
+        let outputs = check_run_outputs("title", "summary", &[]);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0]["annotations"], json!([]));
+    }
+}
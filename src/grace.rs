@@ -0,0 +1,116 @@
+use crate::error::FlakeCheckerError;
+use crate::http::NetworkConfig;
+use crate::issue::Issue;
+use crate::lockfile::Lockfile;
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Command;
+
+use parse_flake_lock::FlakeLock;
+
+/// The root inputs that were added to a lockfile within a grace period, which are exempt from the
+/// age check until they've been around for the whole period.
+#[derive(Debug, Default)]
+pub(crate) struct NewInputs {
+    pub names: BTreeSet<String>,
+    pub days: i64,
+}
+
+impl NewInputs {
+    /// The inputs of a lockfile that aren't in the lockfile as it was at the start of the grace
+    /// period (all of them if it didn't exist then).
+    pub(crate) fn new(flake_lock: &FlakeLock, previous: Option<&FlakeLock>, days: i64) -> Self {
+        let names = flake_lock
+            .root
+            .keys()
+            .filter(|name| previous.is_none_or(|previous| !previous.root.contains_key(*name)))
+            .cloned()
+            .collect();
+        Self { names, days }
+    }
+
+    /// Whether an issue is about an input's age, for an input that's in its grace period.
+    pub(crate) fn covers(&self, issue: &Issue) -> bool {
+        issue.kind.is_outdated() && self.names.contains(&issue.input)
+    }
+}
+
+/// A local lockfile as it was `days` days ago, from the last commit before then in its git
+/// history (as of `rev`), or `None` if it didn't exist then.
+pub(crate) fn lockfile_days_ago(
+    path: &Path,
+    rev: &str,
+    days: i64,
+) -> Result<Option<String>, FlakeCheckerError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-list", "-1", &format!("--before={days} days ago"), rev])
+        .output()?;
+    if !output.status.success() {
+        return Err(FlakeCheckerError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    // Without a commit from before then, the history is younger than the grace period
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if commit.is_empty() {
+        return Ok(None);
+    }
+    Lockfile::Path(path.to_path_buf())
+        .at_git_rev(&commit)
+        .read(&NetworkConfig::default())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grace::NewInputs;
+    use crate::issue::{Issue, IssueKind, NonUpstream, Outdated, Severity};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_new_inputs() {
+        let read =
+            |path: &str| -> FlakeLock { std::fs::read_to_string(path).unwrap().parse().unwrap() };
+        let previous = read("tests/flake.clean.0.lock");
+        let current = read("tests/flake.explicit-keys.0.lock");
+
+        let new_inputs = NewInputs::new(&current, Some(&previous), 14);
+        assert_eq!(new_inputs.names.iter().collect::<Vec<_>>(), ["nixpkgs-alt"]);
+        assert_eq!(NewInputs::new(&current, None, 14).names.len(), 6);
+
+        let outdated = |input: &str| Issue {
+            input: input.to_string(),
+            kind: IssueKind::Outdated(Outdated {
+                num_days_old: 90,
+                max_days: 30,
+                last_modified: 0,
+                locked_at: String::new(),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        assert!(new_inputs.covers(&outdated("nixpkgs-alt")));
+        assert!(!new_inputs.covers(&outdated("nixpkgs")));
+        assert!(!new_inputs.covers(&Issue {
+            input: String::from("nixpkgs-alt"),
+            kind: IssueKind::NonUpstream(NonUpstream {
+                owner: String::from("someone"),
+                host: None,
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        }));
+    }
+}
@@ -0,0 +1,306 @@
+//! The graph of a lockfile's inputs: which node each `inputs` edge leads to once `follows` are
+//! resolved, which nodes depend on which, and which nodes are reachable from the root. Checks that
+//! look beyond a single input, like finding orphaned or duplicated nodes, build on this.
+
+use crate::error::FlakeCheckerError;
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use parse_flake_lock::{FlakeLock, Input, Node};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef};
+use petgraph::Direction;
+
+// Guards against cycles of `follows` in malformed lockfiles
+const MAX_DEPTH: usize = 32;
+
+/// An `inputs` edge from one node to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    /// The name of the input.
+    pub input: String,
+    /// The path of input names that the input follows, starting from the root, if the input
+    /// follows another input rather than being locked itself.
+    pub follows: Option<Vec<String>>,
+}
+
+/// The graph of a lockfile's nodes, with an edge for each input whose node exists.
+///
+/// ```no_run
+/// use flake_checker::{FlakeLock, InputGraph};
+///
+/// let flake_lock = FlakeLock::new(std::path::Path::new("flake.lock"))?;
+/// let graph = InputGraph::new(&flake_lock)?;
+/// for orphan in graph.unreachable() {
+///     println!("{orphan} isn't used by any input");
+/// }
+/// # Ok::<(), flake_checker::FlakeCheckerError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct InputGraph {
+    graph: DiGraph<String, Edge>,
+    indices: HashMap<String, NodeIndex>,
+    root: NodeIndex,
+}
+
+impl InputGraph {
+    /// Build the graph of a lockfile. Inputs that lead to nodes that aren't in the lockfile, or
+    /// that follow paths that can't be resolved, are left out.
+    pub fn new(flake_lock: &FlakeLock) -> Result<Self, FlakeCheckerError> {
+        let root_key = flake_lock
+            .nodes
+            .iter()
+            .find_map(|(key, node)| matches!(node, Node::Root(_)).then_some(key))
+            .ok_or_else(|| FlakeCheckerError::Invalid(String::from("the lock has no root node")))?;
+
+        let mut keys: Vec<&String> = flake_lock.nodes.keys().collect();
+        keys.sort();
+        let mut graph = DiGraph::new();
+        let indices: HashMap<String, NodeIndex> = keys
+            .iter()
+            .map(|key| ((*key).clone(), graph.add_node((*key).clone())))
+            .collect();
+
+        for key in keys {
+            let mut inputs: Vec<(String, Input)> =
+                node_inputs(&flake_lock.nodes[key]).into_iter().collect();
+            inputs.sort_by(|a, b| a.0.cmp(&b.0));
+            for (input, target) in inputs {
+                let (target, follows) = match target {
+                    Input::String(target) => (Some(target), None),
+                    Input::List(path) => (
+                        resolve(flake_lock, root_key, &path, 0).map(String::from),
+                        Some(path),
+                    ),
+                };
+                if let Some(target) = target.and_then(|target| indices.get(&target)) {
+                    graph.add_edge(indices[key], *target, Edge { input, follows });
+                }
+            }
+        }
+
+        let root = indices[root_key];
+        Ok(Self {
+            graph,
+            indices,
+            root,
+        })
+    }
+
+    /// The key of the root node.
+    pub fn root(&self) -> &str {
+        &self.graph[self.root]
+    }
+
+    /// The keys of every node, in order.
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.graph
+            .node_indices()
+            .map(|index| self.graph[index].as_str())
+    }
+
+    /// The inputs of a node, as pairs of the edge and the key of the node it leads to, in order of
+    /// input name.
+    pub fn inputs(&self, node: &str) -> Vec<(&Edge, &str)> {
+        let Some(index) = self.indices.get(node) else {
+            return Vec::new();
+        };
+        let mut inputs: Vec<(&Edge, &str)> = self
+            .graph
+            .edges_directed(*index, Direction::Outgoing)
+            .map(|edge| (edge.weight(), self.graph[edge.target()].as_str()))
+            .collect();
+        inputs.sort_by(|a, b| a.0.input.cmp(&b.0.input));
+        inputs
+    }
+
+    /// The nodes that depend on a node, as pairs of the key of the dependent node and the edge
+    /// that leads from it, in order.
+    pub fn dependents(&self, node: &str) -> Vec<(&str, &Edge)> {
+        let Some(index) = self.indices.get(node) else {
+            return Vec::new();
+        };
+        let mut dependents: Vec<(&str, &Edge)> = self
+            .graph
+            .edges_directed(*index, Direction::Incoming)
+            .map(|edge| (self.graph[edge.source()].as_str(), edge.weight()))
+            .collect();
+        dependents.sort_by(|a, b| (a.0, &a.1.input).cmp(&(b.0, &b.1.input)));
+        dependents
+    }
+
+    /// Resolve a `follows` path of input names, starting from the root, to the key of the node it
+    /// leads to. An empty path leads to the root itself.
+    pub fn resolve<S: AsRef<str>>(&self, path: &[S]) -> Option<&str> {
+        let mut index = self.root;
+        for input in path {
+            index = self
+                .graph
+                .edges_directed(index, Direction::Outgoing)
+                .find(|edge| edge.weight().input == input.as_ref())?
+                .target();
+        }
+        Some(&self.graph[index])
+    }
+
+    /// The shortest chain of input names that leads from the root to a node, without going through
+    /// inputs that follow other inputs, or `None` if the node isn't reachable. The chain is empty
+    /// for the root itself.
+    pub fn path_to(&self, node: &str) -> Option<Vec<String>> {
+        let target = *self.indices.get(node)?;
+        let mut previous: HashMap<NodeIndex, (NodeIndex, &str)> = HashMap::new();
+        let mut queue = VecDeque::from([self.root]);
+        while let Some(index) = queue.pop_front() {
+            if index == target {
+                let mut path = Vec::new();
+                let mut index = index;
+                while let Some((parent, input)) = previous.get(&index) {
+                    path.push(input.to_string());
+                    index = *parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let mut edges: Vec<_> = self
+                .graph
+                .edges_directed(index, Direction::Outgoing)
+                .filter(|edge| edge.weight().follows.is_none())
+                .collect();
+            edges.sort_by(|a, b| a.weight().input.cmp(&b.weight().input));
+            for edge in edges {
+                if edge.target() != self.root && !previous.contains_key(&edge.target()) {
+                    previous.insert(edge.target(), (index, &edge.weight().input));
+                    queue.push_back(edge.target());
+                }
+            }
+        }
+        None
+    }
+
+    /// The keys of the nodes that are reachable from the root, including the root.
+    pub fn reachable(&self) -> BTreeSet<&str> {
+        let mut reachable = BTreeSet::new();
+        let mut bfs = Bfs::new(&self.graph, self.root);
+        while let Some(index) = bfs.next(&self.graph) {
+            reachable.insert(self.graph[index].as_str());
+        }
+        reachable
+    }
+
+    /// The keys of the nodes that aren't reachable from the root, which are left over in the
+    /// lockfile, in order.
+    pub fn unreachable(&self) -> Vec<&str> {
+        let reachable = self.reachable();
+        self.nodes()
+            .filter(|node| !reachable.contains(node))
+            .collect()
+    }
+
+    /// The underlying graph, whose nodes are weighted by their keys, for traversals that this type
+    /// doesn't provide.
+    pub fn graph(&self) -> &DiGraph<String, Edge> {
+        &self.graph
+    }
+}
+
+fn node_inputs(node: &Node) -> HashMap<String, Input> {
+    match node {
+        Node::Root(root) => root.inputs.clone(),
+        Node::Repo(repo) => repo.inputs.clone().unwrap_or_default(),
+        Node::Indirect(indirect) => indirect.inputs.clone().unwrap_or_default(),
+        Node::Path(path) => path.inputs.clone().unwrap_or_default(),
+        Node::Tarball(tarball) => tarball.inputs.clone().unwrap_or_default(),
+        Node::Git(git) => git.inputs.clone().unwrap_or_default(),
+        Node::Mercurial(mercurial) => mercurial.inputs.clone().unwrap_or_default(),
+        Node::Fallthrough(value) => value
+            .get("inputs")
+            .and_then(|inputs| serde_json::from_value(inputs.clone()).ok())
+            .unwrap_or_default(),
+    }
+}
+
+// Follow a path of input names from the root, resolving any inputs along the way that follow
+// other inputs themselves.
+fn resolve<'a>(
+    flake_lock: &'a FlakeLock,
+    root_key: &'a str,
+    path: &[String],
+    depth: usize,
+) -> Option<&'a str> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let mut key = root_key;
+    for input in path {
+        key = match node_inputs(flake_lock.nodes.get(key)?).remove(input)? {
+            Input::String(target) => flake_lock.nodes.get_key_value(&target)?.0,
+            Input::List(path) => resolve(flake_lock, root_key, &path, depth + 1)?,
+        };
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graph::InputGraph;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_graph() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let graph = InputGraph::new(&flake_lock).unwrap();
+
+        assert_eq!(graph.root(), "root");
+        assert_eq!(
+            graph.resolve(&["rust-overlay", "flake-utils", "systems"]),
+            Some("systems")
+        );
+        assert_eq!(graph.resolve(&["nonexistent"]), None);
+
+        let dependents: Vec<&str> = graph
+            .dependents("systems")
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(dependents, ["flake-utils"]);
+
+        assert_eq!(
+            graph.path_to("systems").unwrap(),
+            ["rust-overlay", "flake-utils", "systems"]
+        );
+        assert_eq!(graph.path_to("nixpkgs_3"), None);
+        assert!(graph.reachable().contains("systems"));
+        assert_eq!(graph.unreachable(), ["nixpkgs_3"]);
+
+        // rust-overlay's nixpkgs follows crane's, which follows the root's
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let graph = InputGraph::new(&flake_lock).unwrap();
+        assert_eq!(
+            graph.resolve(&["crane", "rust-overlay", "nixpkgs"]),
+            Some("nixpkgs")
+        );
+        let (edge, node) = graph.inputs("rust-overlay")[1];
+        assert_eq!(node, "nixpkgs");
+        assert_eq!(
+            edge.follows.as_deref(),
+            Some(&["crane", "nixpkgs"].map(String::from)[..])
+        );
+        let dependents: Vec<&str> = graph
+            .dependents("nixpkgs")
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(
+            dependents,
+            ["crane", "root", "rust-overlay", "rust-overlay_2"]
+        );
+        assert!(graph.unreachable().is_empty());
+    }
+}
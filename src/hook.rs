@@ -0,0 +1,99 @@
+use crate::error::FlakeCheckerError;
+
+use std::path::PathBuf;
+use std::process::Command;
+
+// Checks the flake.lock files staged for commit, failing the commit if they have any issues. The
+// paths are NUL-separated, so that paths with spaces or newlines are passed intact, and
+// flake-checker isn't run at all when none are staged.
+const PRE_COMMIT_HOOK: &str = r#"#!/bin/sh
+# Installed by `flake-checker hook install`
+git diff --cached --name-only -z --diff-filter=ACMR -- 'flake.lock' '**/flake.lock' |
+  xargs -0 -r flake-checker --fail-mode --no-telemetry
+"#;
+
+/// Write the pre-commit hook to the current repository's hooks directory, returning its path.
+pub(crate) fn install(force: bool) -> Result<PathBuf, FlakeCheckerError> {
+    // This respects `core.hooksPath` and works in linked worktrees
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()?;
+    if !output.status.success() {
+        return Err(FlakeCheckerError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let hooks_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let hook_path = hooks_dir.join("pre-commit");
+
+    if hook_path.exists() && !force {
+        return Err(FlakeCheckerError::HookExists(hook_path));
+    }
+
+    std::fs::create_dir_all(&hooks_dir)?;
+    std::fs::write(&hook_path, PRE_COMMIT_HOOK)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(hook_path)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::hook::PRE_COMMIT_HOOK;
+
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    #[test]
+    fn test_pre_commit_hook_paths() {
+        let dir = std::env::temp_dir().join(format!("flake-checker-hook-{}", std::process::id()));
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        std::fs::create_dir_all(dir.join("with space/sub")).unwrap();
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        git(&["init", "-q"]);
+        for path in ["flake.lock", "with space/sub/flake.lock", "notflake.lock"] {
+            std::fs::write(dir.join(path), "{}").unwrap();
+        }
+        git(&["add", "flake.lock", "with space", "notflake.lock"]);
+
+        // A stand-in for flake-checker that prints its arguments, one per line
+        let fake = dir.join("bin/flake-checker");
+        std::fs::write(
+            &fake,
+            "#!/bin/sh\nfor arg in \"$@\"; do echo \"$arg\"; done\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            dir.join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let output = Command::new("sh")
+            .args(["-c", PRE_COMMIT_HOOK])
+            .env("PATH", path)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "--fail-mode\n--no-telemetry\nflake.lock\nwith space/sub/flake.lock\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(feature = "cli")]
+use crate::error::FlakeCheckerError;
+
+use std::time::Duration;
+
+#[cfg(feature = "cli")]
+use reqwest::blocking::{Client, RequestBuilder, Response};
+
+/// Timeouts, retries, and the proxy for network requests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConfig {
+    /// The timeout for each attempt at a request.
+    pub timeout: Duration,
+    /// How many times to retry a request that fails transiently.
+    pub retries: u32,
+    /// The delay before the first retry, which doubles for each subsequent retry.
+    pub backoff: Duration,
+    /// A proxy for all requests, overriding the `HTTP_PROXY` and `HTTPS_PROXY` environment
+    /// variables (hosts in `NO_PROXY` are still reached directly).
+    pub proxy: Option<String>,
+    /// A token for authenticating GitHub API requests, for higher rate limits and access to private
+    /// repositories.
+    pub github_token: Option<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            retries: 2,
+            backoff: Duration::from_secs(1),
+            proxy: None,
+            github_token: None,
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl NetworkConfig {
+    /// Authenticate a GitHub API request with the token, if there is one.
+    pub(crate) fn authorize_github(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.github_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Build a client with the configured timeout and proxy. Without an explicit proxy, the proxy
+/// environment variables apply.
+#[cfg(feature = "cli")]
+pub(crate) fn client(config: &NetworkConfig) -> Result<Client, FlakeCheckerError> {
+    let mut builder = Client::builder().timeout(config.timeout);
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?.no_proxy(reqwest::NoProxy::from_env()));
+    }
+    Ok(builder.build()?)
+}
+
+/// Send a request, retrying connection failures, timeouts, rate limiting, and server errors with
+/// exponential backoff. Other error statuses are returned as errors immediately.
+#[cfg(feature = "cli")]
+pub(crate) fn send(
+    config: &NetworkConfig,
+    request: impl Fn(&Client) -> RequestBuilder,
+) -> Result<Response, FlakeCheckerError> {
+    let client = client(config)?;
+
+    let mut attempt = 0;
+    loop {
+        let result = request(&client).send();
+        let retryable = match &result {
+            Ok(response) => {
+                let status = response.status();
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(err) => err.is_connect() || err.is_timeout(),
+        };
+
+        if !retryable || attempt >= config.retries {
+            return Ok(result?.error_for_status()?);
+        }
+
+        std::thread::sleep(config.backoff * 2u32.saturating_pow(attempt));
+        attempt += 1;
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use crate::http::{client, send, NetworkConfig};
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn test_send_retries_server_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        // Fail the first request, then succeed
+        let server = std::thread::spawn(move || {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    &stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+                )
+                .unwrap();
+            }
+        });
+
+        let config = NetworkConfig {
+            timeout: Duration::from_secs(5),
+            retries: 1,
+            backoff: Duration::from_millis(10),
+            ..NetworkConfig::default()
+        };
+        let response = send(&config, |client| client.get(&url)).unwrap();
+        assert_eq!(response.text().unwrap(), "ok");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_uses_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            request_line
+        });
+
+        let config = NetworkConfig {
+            proxy: Some(proxy),
+            ..NetworkConfig::default()
+        };
+        client(&config)
+            .unwrap()
+            .get("http://flake-checker.invalid/flake.lock")
+            .send()
+            .unwrap();
+
+        assert_eq!(
+            server.join().unwrap(),
+            "GET http://flake-checker.invalid/flake.lock HTTP/1.1\r\n"
+        );
+    }
+}
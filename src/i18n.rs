@@ -0,0 +1,226 @@
+use std::fmt::Display;
+
+/// Languages that issue messages and summaries can be rendered in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Lang {
+    /// English.
+    #[default]
+    En,
+    /// German.
+    De,
+}
+
+// Message catalogs, keyed by message ID. Placeholders of the form `{name}` are substituted by
+// `Lang::tr`. Every catalog must define every ID that appears in the English catalog.
+const EN: &[(&str, &str)] = &[
+    (
+        "disallowed",
+        "the `{input}` input uses the non-supported Git branch `{reference}` for Nixpkgs",
+    ),
+    (
+        "outdated",
+        "the `{input}` input is {num_days_old} days old ({age}, locked {locked_at}; the max allowed is {max_days})",
+    ),
+    (
+        "non-upstream",
+        "the `{input}` input has the non-upstream owner `{owner}` rather than `NixOS` (upstream)",
+    ),
+    (
+        "non-upstream-host",
+        "the `{input}` input comes from the non-upstream host `{host}` rather than `github.com` (upstream)",
+    ),
+    (
+        "check-error",
+        "the `{input}` input couldn't be checked by the {check} check: {error}",
+    ),
+    (
+        "check-error-lockfile",
+        "the lockfile couldn't be checked by the {check} check: {error}",
+    ),
+    (
+        "non-canonical",
+        "the lockfile isn't formatted the way `nix flake lock` writes it (from line {line}), so the next `nix flake lock` will reformat it",
+    ),
+    ("missing", "the lockfile doesn't exist"),
+    (
+        "unknown-fields",
+        "the `{input}` input has fields that flake-checker doesn't recognize: {fields}",
+    ),
+    (
+        "no-issues",
+        "The Determinate Nix Flake Checker scanned {file} and found no issues",
+    ),
+    ("issue", "issue"),
+    ("issues", "issues"),
+    ("pr-title", "Fix flake.lock issues found by the Flake Checker"),
+    (
+        "pr-fixed",
+        "`flake-checker --fix` fixed these issues in `{file}`:",
+    ),
+    (
+        "escalates-at-age",
+        "a warning until it's more than {days} days old",
+    ),
+    ("escalates-after-date", "a warning until {date}"),
+    ("age-less-than-a-day", "less than a day"),
+    ("age-day", "≈1 day"),
+    ("age-days", "≈{n} days"),
+    ("age-weeks", "≈{n} weeks"),
+    ("age-months", "≈{n} months"),
+    ("age-years", "≈{n} years"),
+];
+
+const DE: &[(&str, &str)] = &[
+    (
+        "disallowed",
+        "der Input `{input}` verwendet den nicht unterstützten Git-Branch `{reference}` für Nixpkgs",
+    ),
+    (
+        "outdated",
+        "der Input `{input}` ist {num_days_old} Tage alt ({age}, gesperrt am {locked_at}; das erlaubte Maximum ist {max_days})",
+    ),
+    (
+        "non-upstream",
+        "der Input `{input}` hat den Nicht-Upstream-Eigentümer `{owner}` statt `NixOS` (Upstream)",
+    ),
+    (
+        "non-upstream-host",
+        "der Input `{input}` stammt vom Nicht-Upstream-Host `{host}` statt `github.com` (Upstream)",
+    ),
+    (
+        "check-error",
+        "der Input `{input}` konnte von der Prüfung {check} nicht geprüft werden: {error}",
+    ),
+    (
+        "check-error-lockfile",
+        "die Lockdatei konnte von der Prüfung {check} nicht geprüft werden: {error}",
+    ),
+    (
+        "non-canonical",
+        "die Lockdatei ist nicht so formatiert, wie `nix flake lock` sie schreibt (ab Zeile {line}), daher wird das nächste `nix flake lock` sie neu formatieren",
+    ),
+    ("missing", "die Lockdatei existiert nicht"),
+    (
+        "unknown-fields",
+        "der Input `{input}` hat Felder, die flake-checker nicht kennt: {fields}",
+    ),
+    (
+        "no-issues",
+        "Der Determinate Nix Flake Checker hat {file} geprüft und keine Probleme gefunden",
+    ),
+    ("issue", "Problem"),
+    ("issues", "Probleme"),
+    (
+        "pr-title",
+        "Vom Flake Checker gefundene flake.lock-Probleme beheben",
+    ),
+    (
+        "pr-fixed",
+        "`flake-checker --fix` hat diese Probleme in `{file}` behoben:",
+    ),
+    (
+        "escalates-at-age",
+        "eine Warnung, bis es älter als {days} Tage ist",
+    ),
+    ("escalates-after-date", "eine Warnung bis zum {date}"),
+    ("age-less-than-a-day", "weniger als ein Tag"),
+    ("age-day", "≈1 Tag"),
+    ("age-days", "≈{n} Tage"),
+    ("age-weeks", "≈{n} Wochen"),
+    ("age-months", "≈{n} Monate"),
+    ("age-years", "≈{n} Jahre"),
+];
+
+impl Lang {
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => EN,
+            Self::De => DE,
+        }
+    }
+
+    /// Look up the message with the given ID and substitute its `{name}` placeholders. Falls
+    /// back to English (and then to the ID itself) if the message is missing.
+    pub(crate) fn tr(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let lookup = |catalog: &[(&str, &'static str)]| {
+            catalog
+                .iter()
+                .find(|(key, _)| *key == id)
+                .map(|(_, message)| *message)
+        };
+
+        let mut message = lookup(self.catalog())
+            .or_else(|| lookup(EN))
+            .unwrap_or(id)
+            .to_string();
+
+        for (name, value) in args {
+            message = message.replace(&format!("{{{name}}}"), &value.to_string());
+        }
+
+        message
+    }
+
+    /// A rough, human-friendly rendering of a number of days, like "≈3 months".
+    pub(crate) fn humanize_days(&self, days: i64) -> String {
+        let (id, n) = match days {
+            i64::MIN..=0 => ("age-less-than-a-day", 0),
+            1 => ("age-day", 1),
+            2..=13 => ("age-days", days),
+            14..=59 => ("age-weeks", days / 7),
+            60..=729 => ("age-months", days / 30),
+            _ => ("age-years", days / 365),
+        };
+        self.tr(id, &[("n", &n)])
+    }
+
+    /// The Handlebars template for the plain-text summary.
+    pub(crate) fn summary_txt_template(&self) -> &'static str {
+        match self {
+            Self::En => include_str!("templates/summary_txt.hbs"),
+            Self::De => include_str!("templates/de/summary_txt.hbs"),
+        }
+    }
+
+    /// The Handlebars template for the Markdown summary.
+    pub(crate) fn summary_md_template(&self) -> &'static str {
+        match self {
+            Self::En => include_str!("templates/summary_md.hbs"),
+            Self::De => include_str!("templates/de/summary_md.hbs"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Lang, DE, EN};
+
+    #[test]
+    fn test_catalogs_are_complete() {
+        for (id, _) in EN {
+            assert!(
+                DE.iter().any(|(key, _)| key == id),
+                "German catalog is missing message {id}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_placeholder_substitution() {
+        let message = Lang::De.tr(
+            "outdated",
+            &[
+                ("input", &"nixpkgs"),
+                ("num_days_old", &45),
+                ("age", &Lang::De.humanize_days(45)),
+                ("locked_at", &"2023-04-13T03:55:09Z"),
+                ("max_days", &30),
+            ],
+        );
+        assert_eq!(
+            message,
+            "der Input `nixpkgs` ist 45 Tage alt (≈6 Wochen, gesperrt am 2023-04-13T03:55:09Z; das erlaubte Maximum ist 30)"
+        );
+    }
+}
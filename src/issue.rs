@@ -1,35 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use parse_flake_lock::source::{escape_pointer_token, SourceMap};
+use parse_flake_lock::FlakeLock;
 use serde::Serialize;
 
+use crate::i18n::Lang;
+
+/// How serious an issue is. Issues are warnings unless fail mode is enabled or they escalate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Reported, but doesn't fail the run.
+    Warning,
+    /// Fails the run.
+    Error,
+}
+
+impl Severity {
+    /// The lowercase name of the severity.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// An issue found with a root input of a lockfile (or with the lockfile as a whole).
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub(crate) struct Issue {
+pub struct Issue {
+    /// The name of the root input, the key of the node for inputs of other inputs that selectors
+    /// picked, or an empty string for issues with the whole lockfile.
     pub input: String,
+    /// What's wrong with the input, with the details.
     pub kind: IssueKind,
+    /// How serious the issue is under the configuration that it was found with.
+    pub severity: Severity,
+    /// Where in the lockfile the issue is, once it's been [located](Issue::locate).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    /// For inputs of other inputs, the names of the inputs along the way from the root, like
+    /// `["devenv", "nixpkgs"]`, which leads to the root input to update or add a `follows` to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Vec<String>>,
+    /// The message that a policy's [template](crate::MessageTemplate) gave the issue, which
+    /// replaces its built-in message in every language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
+/// Where an issue is in a lockfile: the field that it's about, or else the input's node.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Location {
+    /// The lockfile's path.
+    pub path: PathBuf,
+    /// The 1-based line.
+    pub line: usize,
+    /// The 1-based column, in bytes.
+    pub column: usize,
+    /// A JSON Pointer to the field or node, or an empty string for the whole lockfile.
+    pub json_pointer: String,
+}
+
+/// The kinds of issue that the checks report.
 #[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(untagged)]
-pub(crate) enum IssueKind {
+pub enum IssueKind {
+    /// The input uses a Git ref that isn't allowed.
     Disallowed(Disallowed),
+    /// The input is older than the maximum age.
     Outdated(Outdated),
+    /// The input doesn't come from an allowed owner.
     NonUpstream(NonUpstream),
+    /// The input couldn't be checked.
+    CheckError(CheckError),
+    /// The lockfile isn't formatted canonically.
+    NonCanonical(NonCanonical),
+    /// The lockfile doesn't exist.
+    Missing(Missing),
+    /// A node of the lockfile has fields that aren't recognized.
+    UnknownFields(UnknownFields),
+    /// An issue reported by a check outside of this crate.
+    Custom(Custom),
 }
 
+/// An input on a Git ref that isn't allowed.
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub(crate) struct Disallowed {
-    pub(crate) reference: String,
+pub struct Disallowed {
+    /// The input's Git ref.
+    pub reference: String,
 }
 
+/// An input that's older than the maximum age.
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub(crate) struct Outdated {
-    pub(crate) num_days_old: i64,
+pub struct Outdated {
+    /// The input's age, in days.
+    pub num_days_old: i64,
+    /// The maximum age that the policy allows, in days.
+    pub max_days: i64,
+    /// The `lastModified` timestamp of the locked input.
+    pub last_modified: i64,
+    /// The `lastModified` timestamp as a UTC ISO-8601 date.
+    pub locked_at: String,
 }
 
+/// An input from an owner that isn't allowed.
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub(crate) struct NonUpstream {
-    pub(crate) owner: String,
+pub struct NonUpstream {
+    /// The input's owner.
+    pub owner: String,
+    /// The input's host, if the owner is allowed but the host isn't (like a mirror of Nixpkgs on
+    /// a self-hosted forge).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
 }
 
+/// A check that couldn't be completed, like a networked check whose endpoint was unreachable.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CheckError {
+    /// The name of the check.
+    pub check: String,
+    /// Why the check couldn't be completed.
+    pub error: String,
+}
+
+/// A lockfile that isn't formatted the way that `nix flake lock` writes it, so that the next
+/// `nix flake lock` would reformat it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NonCanonical {
+    /// The 1-based number of the first line that differs from the canonical formatting.
+    pub line: usize,
+}
+
+/// A lockfile that doesn't exist, reported as an issue with `--ignore-missing warning`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Missing {}
+
+/// A lockfile node with fields that aren't recognized, like attributes that a newer version of Nix
+/// added, reported with `--strict-fields`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnknownFields {
+    /// The paths of the fields within the node, like `locked.treeHash`.
+    pub fields: Vec<String>,
+}
+
+/// An issue reported by a [check](crate::Check) outside of this crate.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Custom {
+    /// The code of the issue, which is its [name](IssueKind::name).
+    pub code: String,
+    /// A human-readable description of the issue.
+    pub message: String,
+}
+
+/// The [names](IssueKind::name) of all of the built-in kinds of issue.
+pub(crate) const KIND_NAMES: &[&str] = &[
+    "disallowed",
+    "outdated",
+    "non-upstream",
+    "check-error",
+    "non-canonical",
+    "missing",
+    "unknown-fields",
+];
+
 impl IssueKind {
+    /// A short, stable name for the kind of issue.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Disallowed(_) => "disallowed",
+            Self::Outdated(_) => "outdated",
+            Self::NonUpstream(_) => "non-upstream",
+            Self::CheckError(_) => "check-error",
+            Self::NonCanonical(_) => "non-canonical",
+            Self::Missing(_) => "missing",
+            Self::UnknownFields(_) => "unknown-fields",
+            Self::Custom(custom) => &custom.code,
+        }
+    }
+
+    /// The `flake.lock` node section and field that the issue pertains to.
+    pub(crate) fn field(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Disallowed(_) => ("original", "ref"),
+            Self::Outdated(_) => ("locked", "lastModified"),
+            Self::NonUpstream(_) => ("original", "owner"),
+            Self::CheckError(_) => ("locked", "rev"),
+            // Non-canonical formatting is a property of the whole lockfile, not of an input
+            Self::NonCanonical(_) | Self::Missing(_) => ("", ""),
+            // Custom issues, and nodes' unknown fields, point at the input's node
+            Self::Custom(_) | Self::UnknownFields(_) => ("", ""),
+        }
+    }
+
     pub(crate) fn is_disallowed(&self) -> bool {
         matches!(self, Self::Disallowed(_))
     }
@@ -41,4 +205,177 @@ impl IssueKind {
     pub(crate) fn is_non_upstream(&self) -> bool {
         matches!(self, Self::NonUpstream(_))
     }
+
+    pub(crate) fn is_check_error(&self) -> bool {
+        matches!(self, Self::CheckError(_))
+    }
+
+    pub(crate) fn is_non_canonical(&self) -> bool {
+        matches!(self, Self::NonCanonical(_))
+    }
+
+    pub(crate) fn is_missing(&self) -> bool {
+        matches!(self, Self::Missing(_))
+    }
+
+    pub(crate) fn is_unknown_fields(&self) -> bool {
+        matches!(self, Self::UnknownFields(_))
+    }
+
+    pub(crate) fn is_custom(&self) -> bool {
+        matches!(self, Self::Custom(_))
+    }
+}
+
+impl Issue {
+    /// Find where the issue is in the lockfile's source: the field that it's about, falling back
+    /// to the input's node, or the first line that isn't formatted canonically. Issues that can't
+    /// be found (like missing lockfiles) are left without a location.
+    pub fn locate(&mut self, path: &Path, flake_lock: &FlakeLock, source_map: &SourceMap) {
+        let location = |json_pointer: String, (line, column)| Location {
+            path: path.to_path_buf(),
+            line,
+            column,
+            json_pointer,
+        };
+        self.location = match &self.kind {
+            IssueKind::NonCanonical(NonCanonical { line }) => {
+                Some(location(String::new(), (*line, 1)))
+            }
+            IssueKind::Missing(_) => None,
+            _ => {
+                let node = flake_lock
+                    .root_node_names
+                    .get(&self.input)
+                    .or_else(|| {
+                        flake_lock
+                            .nodes
+                            .get_key_value(&self.input)
+                            .map(|(key, _)| key)
+                    })
+                    .map(|name| format!("/nodes/{}", escape_pointer_token(name)));
+                let (section, field) = self.kind.field();
+                node.and_then(|node| {
+                    [format!("{node}/{section}/{field}"), node]
+                        .into_iter()
+                        .find_map(|pointer| {
+                            let span = source_map.span(&pointer)?;
+                            Some(location(pointer, source_map.line_col(span.start)))
+                        })
+                })
+            }
+        };
+    }
+
+    /// The chain of inputs that pulls the input in, like `root → devenv → nixpkgs`, for inputs of
+    /// other inputs.
+    pub fn provenance_path(&self) -> Option<String> {
+        self.provenance
+            .as_ref()
+            .map(|path| format!("root → {}", path.join(" → ")))
+    }
+
+    /// A human-readable description of the issue in the given language.
+    pub fn message(&self, lang: Lang) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        let input = &self.input;
+        match &self.kind {
+            IssueKind::Disallowed(Disallowed { reference }) => {
+                lang.tr("disallowed", &[("input", input), ("reference", reference)])
+            }
+            IssueKind::Outdated(Outdated {
+                num_days_old,
+                max_days,
+                locked_at,
+                ..
+            }) => lang.tr(
+                "outdated",
+                &[
+                    ("input", input),
+                    ("num_days_old", num_days_old),
+                    ("age", &lang.humanize_days(*num_days_old)),
+                    ("locked_at", locked_at),
+                    ("max_days", max_days),
+                ],
+            ),
+            IssueKind::NonUpstream(NonUpstream {
+                host: Some(host), ..
+            }) => lang.tr("non-upstream-host", &[("input", input), ("host", host)]),
+            IssueKind::NonUpstream(NonUpstream { owner, .. }) => {
+                lang.tr("non-upstream", &[("input", input), ("owner", owner)])
+            }
+            // Plugins can fail for the whole lockfile rather than an input
+            IssueKind::CheckError(CheckError { check, error }) if input.is_empty() => lang.tr(
+                "check-error-lockfile",
+                &[("check", check), ("error", error)],
+            ),
+            IssueKind::CheckError(CheckError { check, error }) => lang.tr(
+                "check-error",
+                &[("input", input), ("check", check), ("error", error)],
+            ),
+            IssueKind::NonCanonical(NonCanonical { line }) => {
+                lang.tr("non-canonical", &[("line", line)])
+            }
+            IssueKind::Missing(Missing {}) => lang.tr("missing", &[]),
+            IssueKind::UnknownFields(UnknownFields { fields }) => lang.tr(
+                "unknown-fields",
+                &[("input", input), ("fields", &fields.join(", "))],
+            ),
+            IssueKind::Custom(Custom { message, .. }) => message.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::issue::{Custom, Disallowed, Issue, IssueKind, Location, Missing, Severity};
+
+    use std::path::{Path, PathBuf};
+
+    use parse_flake_lock::source::SourceMap;
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_locate() {
+        let path = Path::new("tests/flake.dirty.0.lock");
+        let source = std::fs::read_to_string(path).unwrap();
+        let source_map = SourceMap::new(&source).unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        let located = |kind: IssueKind| {
+            let mut issue = Issue {
+                input: String::from("nixpkgs"),
+                kind,
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
+            };
+            issue.locate(path, &flake_lock, &source_map);
+            issue.location
+        };
+
+        let location = |line, column, json_pointer: &str| Location {
+            path: PathBuf::from("tests/flake.dirty.0.lock"),
+            line,
+            column,
+            json_pointer: json_pointer.to_string(),
+        };
+        assert_eq!(
+            located(IssueKind::Disallowed(Disallowed {
+                reference: String::from("this-should-fail"),
+            })),
+            Some(location(32, 16, "/nodes/nixpkgs/original/ref"))
+        );
+        // Issues that aren't about a field point at the input's node
+        assert_eq!(
+            located(IssueKind::Custom(Custom {
+                code: String::from("custom"),
+                message: String::new(),
+            })),
+            Some(location(21, 16, "/nodes/nixpkgs"))
+        );
+        assert_eq!(located(IssueKind::Missing(Missing {})), None);
+    }
 }
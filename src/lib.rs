@@ -0,0 +1,151 @@
+//! Checks the Nixpkgs inputs of Nix flake lockfiles for outdated revisions, unsupported
+//! branches, and non-upstream owners.
+//!
+//! The `flake-checker` binary is a thin wrapper around this library, which can also be embedded
+//! in other tools:
+//!
+//! ```no_run
+//! use flake_checker::{check_flake_lock, FlakeCheckConfig, FlakeLock, Lang};
+//!
+//! let flake_lock = FlakeLock::new(std::path::Path::new("flake.lock"))?;
+//! for issue in check_flake_lock(&flake_lock, &FlakeCheckConfig::default())?.issues {
+//!     println!("{}", issue.message(Lang::En));
+//! }
+//! # Ok::<(), flake_checker::FlakeCheckerError>(())
+//! ```
+
+#![warn(missing_docs)]
+// The core modules have helpers that only the CLI uses
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+#[cfg(feature = "cli")]
+mod app;
+#[cfg(feature = "cli")]
+mod audit;
+#[cfg(feature = "cli")]
+mod baseline;
+#[cfg(feature = "cli")]
+mod bitbucket;
+mod budget;
+#[cfg(feature = "cli")]
+mod canonical;
+mod checker;
+mod checks;
+#[cfg(feature = "cli")]
+mod cli;
+#[cfg(feature = "cli")]
+mod compare;
+#[cfg(feature = "cli")]
+mod conflict;
+#[cfg(feature = "cli")]
+mod daemon;
+#[cfg(feature = "cli")]
+mod diff;
+#[cfg(feature = "cli")]
+mod discover;
+#[cfg(feature = "cli")]
+mod doctor;
+mod error;
+#[cfg(feature = "cli")]
+mod exemption;
+#[cfg(feature = "cli")]
+mod explain;
+#[cfg(feature = "cli")]
+mod fields;
+#[cfg(feature = "cli")]
+mod filter;
+#[cfg(feature = "cli")]
+mod fix;
+mod flake;
+#[cfg(feature = "cli")]
+mod flake_nix;
+#[cfg(feature = "cli")]
+mod forecast;
+#[cfg(feature = "cli")]
+mod gitea;
+#[cfg(feature = "cli")]
+mod github_app;
+#[cfg(feature = "cli")]
+mod grace;
+mod graph;
+#[cfg(feature = "cli")]
+mod hook;
+mod http;
+mod i18n;
+mod issue;
+#[cfg(feature = "cli")]
+mod list;
+#[cfg(feature = "cli")]
+mod lockfile;
+#[cfg(feature = "cli")]
+mod lsp;
+mod messages;
+#[cfg(feature = "cli")]
+mod niv;
+#[cfg(feature = "cli")]
+mod npins;
+#[cfg(feature = "cli")]
+mod org;
+#[cfg(feature = "cli")]
+mod otel;
+#[cfg(feature = "cli")]
+mod pins;
+#[cfg(feature = "cli")]
+mod plugin;
+mod policy;
+#[cfg(feature = "cli")]
+mod pull_request;
+#[cfg(feature = "cli")]
+mod registry;
+mod requirements;
+mod rules;
+mod selector;
+#[cfg(feature = "cli")]
+mod serve;
+#[cfg(feature = "cli")]
+mod snippet;
+#[cfg(feature = "cli")]
+mod stats;
+#[cfg(feature = "cli")]
+mod summary;
+#[cfg(feature = "cli")]
+mod table;
+#[cfg(feature = "cli")]
+mod teams;
+#[cfg(feature = "cli")]
+mod telemetry;
+mod template_dir;
+#[cfg(feature = "cli")]
+mod tui;
+#[cfg(feature = "cli")]
+mod update;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "cli")]
+mod watch;
+#[cfg(feature = "cli")]
+mod why;
+
+pub use budget::Budget;
+pub use checker::{
+    CheckReport, CheckStats, FlakeChecker, FlakeCheckerBuilder, SkipReason, SkippedCheck,
+};
+pub use checks::{AllowedOwners, AllowedRefs, Check, CheckRegistry, MaxAge};
+pub use error::FlakeCheckerError;
+pub use flake::{check_flake_lock, FlakeCheckConfig, ALLOWED_REFS, MAX_DAYS};
+pub use graph::{Edge, InputGraph};
+pub use http::NetworkConfig;
+pub use i18n::Lang;
+pub use issue::{
+    CheckError, Custom, Disallowed, Issue, IssueKind, Location, Missing, NonCanonical, NonUpstream,
+    Outdated, Severity, UnknownFields,
+};
+pub use messages::MessageTemplate;
+pub use parse_flake_lock::{FlakeLock, FlakeLockParseError, Node};
+pub use policy::Policy;
+pub use selector::{Scope, Selector};
+pub use template_dir::TemplateDir;
+
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub use app::main as run_cli;
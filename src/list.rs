@@ -0,0 +1,149 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::InputDetails;
+use crate::table;
+
+use chrono::Utc;
+use parse_flake_lock::FlakeLock;
+
+/// Output formats for `flake-checker list`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub(crate) enum ListFormat {
+    /// An aligned, human-readable table.
+    Table,
+    /// A JSON array with one object per input.
+    Json,
+}
+
+/// Which of a lockfile's nodes to list. Unset criteria match every node.
+#[derive(Debug, Default)]
+pub(crate) struct Query {
+    pub node_type: Option<String>,
+    /// Matched case-insensitively, as GitHub owners are.
+    pub owner: Option<String>,
+    pub older_than_days: Option<i64>,
+}
+
+impl Query {
+    fn matches(&self, details: &InputDetails) -> bool {
+        let node_type = self
+            .node_type
+            .as_ref()
+            .is_none_or(|node_type| &details.node_type == node_type);
+        let owner = self.owner.as_ref().is_none_or(|owner| {
+            details
+                .owner
+                .as_ref()
+                .is_some_and(|o| o.eq_ignore_ascii_case(owner))
+        });
+        let older_than = self
+            .older_than_days
+            .is_none_or(|days| details.num_days_old.is_some_and(|age| age > days));
+        node_type && owner && older_than
+    }
+}
+
+/// List the lockfile's nodes (not just the root inputs) that match the query, in name order.
+pub(crate) fn list(flake_lock: &FlakeLock, query: &Query) -> Vec<InputDetails> {
+    let mut nodes: Vec<InputDetails> = flake_lock
+        .nodes
+        .iter()
+        .filter_map(|(key, node)| InputDetails::new(key, node, Utc::now()))
+        .filter(|details| query.matches(details))
+        .collect();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    nodes
+}
+
+/// Parse an age like `30d` or `4w` (or a plain number of days) into days.
+pub(crate) fn parse_age(value: &str) -> Result<i64, String> {
+    let (number, multiplier) = match value.as_bytes().last() {
+        Some(b'd') => (&value[..value.len() - 1], 1),
+        Some(b'w') => (&value[..value.len() - 1], 7),
+        _ => (value, 1),
+    };
+    number
+        .parse::<i64>()
+        .map(|number| number * multiplier)
+        .map_err(|_| {
+            format!(
+                "invalid age `{value}` (expected a number of days, like 30d, or weeks, like 4w)"
+            )
+        })
+}
+
+pub(crate) fn generate(
+    nodes: &[InputDetails],
+    format: ListFormat,
+) -> Result<String, FlakeCheckerError> {
+    Ok(match format {
+        ListFormat::Table => table(nodes),
+        ListFormat::Json => serde_json::to_string_pretty(nodes)? + "\n",
+    })
+}
+
+fn table(nodes: &[InputDetails]) -> String {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+    let rows: Vec<[String; 8]> = nodes
+        .iter()
+        .map(|details| {
+            [
+                details.name.clone(),
+                details.node_type.clone(),
+                or_dash(details.owner.clone()),
+                or_dash(details.repo.clone()),
+                or_dash(details.dir.clone()),
+                String::from(if details.flake { "yes" } else { "no" }),
+                or_dash(details.git_ref.clone()),
+                or_dash(details.num_days_old.map(|age| age.to_string())),
+            ]
+        })
+        .collect();
+
+    table::render(
+        [
+            "node", "type", "owner", "repo", "dir", "flake", "ref", "age",
+        ],
+        &rows,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::list::{list, parse_age, Query};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_list() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let names = |query: &Query| -> Vec<String> {
+            list(&flake_lock, query)
+                .into_iter()
+                .map(|details| details.name)
+                .collect()
+        };
+
+        assert_eq!(names(&Query::default()).len(), 6);
+        assert_eq!(
+            names(&Query {
+                owner: Some(String::from("nixos")),
+                ..Query::default()
+            }),
+            ["nixpkgs_2", "nixpkgs_3"]
+        );
+        assert!(names(&Query {
+            node_type: Some(String::from("path")),
+            ..Query::default()
+        })
+        .is_empty());
+
+        assert_eq!(parse_age("30d"), Ok(30));
+        assert_eq!(parse_age("4w"), Ok(28));
+        assert_eq!(parse_age("7"), Ok(7));
+        assert!(parse_age("soon").is_err());
+    }
+}
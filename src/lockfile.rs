@@ -0,0 +1,377 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::{niv, npins};
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The name of devenv's lockfile
+pub(crate) const DEVENV_LOCK: &str = "devenv.lock";
+
+// A lockfile without any inputs, which stands in for missing lockfiles in results
+pub(crate) const EMPTY_FLAKE_LOCK: &str =
+    r#"{ "nodes": { "root": { "inputs": {} } }, "root": "root", "version": 7 }"#;
+
+// The lockfile path that reads the lockfile from stdin
+const STDIN_PATH: &str = "-";
+
+/// The formats that lockfiles can be read in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub(crate) enum InputFormat {
+    /// A Nix flake's `flake.lock`.
+    #[default]
+    Flake,
+    /// niv's `nix/sources.json`, whose GitHub sources are checked like flake inputs.
+    Niv,
+    /// npins' `npins/sources.json`, whose GitHub pins are checked like flake inputs.
+    Npins,
+    /// devenv's `devenv.lock`, which has the same structure as a `flake.lock`. devenv's default
+    /// Nixpkgs input (cachix/devenv-nixpkgs on the rolling branch) passes the refs and owner
+    /// checks.
+    Devenv,
+}
+
+impl InputFormat {
+    /// The path that lockfiles in this format are read from by default.
+    pub(crate) fn default_path(self) -> &'static str {
+        match self {
+            Self::Flake => "flake.lock",
+            Self::Niv => niv::SOURCES_PATH,
+            Self::Npins => npins::SOURCES_PATH,
+            Self::Devenv => DEVENV_LOCK,
+        }
+    }
+
+    /// The format of a particular lockfile: lockfiles named `devenv.lock` are devenv's, unless
+    /// another format was requested.
+    pub(crate) fn detect(self, lockfile: &Lockfile) -> Self {
+        let path = match lockfile {
+            Lockfile::Path(path) | Lockfile::GitRevision { path, .. } => path,
+            _ => return self,
+        };
+        match path.file_name() {
+            Some(name) if self == Self::Flake && name == DEVENV_LOCK => Self::Devenv,
+            _ => self,
+        }
+    }
+
+    /// Whether lockfiles in this format are `flake.lock` files (as written by `nix flake lock`).
+    pub(crate) fn is_flake_lock(self) -> bool {
+        matches!(self, Self::Flake | Self::Devenv)
+    }
+
+    /// Convert a lockfile in this format into an equivalent `flake.lock`. Other formats don't
+    /// record when revisions were committed, so converting them requires network access.
+    pub(crate) fn to_flake_lock(
+        self,
+        source: String,
+        network: &NetworkConfig,
+    ) -> Result<String, FlakeCheckerError> {
+        match self {
+            Self::Flake | Self::Devenv => Ok(source),
+            Self::Niv => niv::to_flake_lock(&source, network),
+            Self::Npins => npins::to_flake_lock(&source, network),
+        }
+    }
+}
+
+/// What to do about a local lockfile that doesn't exist.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub(crate) enum MissingMode {
+    /// Fail the run.
+    Error,
+    /// Report a `missing` issue, which doesn't fail the run even in fail mode.
+    Warning,
+    /// Skip the lockfile silently.
+    Skip,
+}
+
+/// Where to read a `flake.lock` from.
+#[derive(Clone, Debug)]
+pub(crate) enum Lockfile {
+    Path(PathBuf),
+    Stdin,
+    Remote(Remote),
+    /// A lockfile as of the given revision in the local git object database.
+    GitRevision {
+        rev: String,
+        path: PathBuf,
+    },
+}
+
+impl Lockfile {
+    pub(crate) fn from_path(path: PathBuf) -> Self {
+        if path.as_os_str() == STDIN_PATH {
+            Self::Stdin
+        } else {
+            Self::Path(path)
+        }
+    }
+
+    /// The path used to identify the lockfile in output.
+    pub(crate) fn display_path(&self) -> PathBuf {
+        match self {
+            Self::Path(path) => path.clone(),
+            Self::Stdin => PathBuf::from(STDIN_PATH),
+            Self::Remote(remote) => PathBuf::from(remote.to_string()),
+            Self::GitRevision { rev, path } => PathBuf::from(format!("{rev}:{}", path.display())),
+        }
+    }
+
+    /// Read the lockfile at the given git revision instead of from the working tree (if it's a local
+    /// file).
+    pub(crate) fn at_git_rev(self, rev: &str) -> Self {
+        match self {
+            Self::Path(path) => Self::GitRevision {
+                rev: rev.to_string(),
+                path,
+            },
+            other => other,
+        }
+    }
+
+    /// Read the lockfile's contents, or `None` if it's a local file that doesn't exist.
+    pub(crate) fn read(
+        &self,
+        network: &NetworkConfig,
+    ) -> Result<Option<String>, FlakeCheckerError> {
+        match self {
+            Self::Path(path) if !path.exists() => Ok(None),
+            Self::Path(path) => Ok(Some(std::fs::read_to_string(path)?)),
+            Self::Stdin => Ok(Some(std::io::read_to_string(std::io::stdin())?)),
+            Self::Remote(remote) => remote.fetch(network).map(Some),
+            Self::GitRevision { rev, path } => git_show(rev, path),
+        }
+    }
+}
+
+// Reads a file's contents at a revision with `git show`, from within the file's own directory so
+// that the file's repository is used.
+fn git_show(rev: &str, path: &Path) -> Result<Option<String>, FlakeCheckerError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| FlakeCheckerError::Git(format!("not a file: {}", path.display())))?;
+    let object = format!("{rev}:./{}", file_name.to_string_lossy());
+
+    let git = |args: &[&str]| Command::new("git").arg("-C").arg(dir).args(args).output();
+
+    if !git(&["cat-file", "-e", &object])?.status.success() {
+        // Either the revision or the file at that revision doesn't exist; make sure it's the latter
+        let rev_exists = git(&[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ])?
+        .status
+        .success();
+        if !rev_exists {
+            return Err(FlakeCheckerError::Git(format!("unknown revision: {rev}")));
+        }
+        return Ok(None);
+    }
+
+    let output = git(&["show", &object])?;
+    if !output.status.success() {
+        return Err(FlakeCheckerError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+/// A `flake.lock` that isn't checked out locally.
+#[derive(Clone, Debug)]
+pub(crate) enum Remote {
+    /// A `github:owner/repo[/ref]` flake reference, fetched via the GitHub API.
+    GitHub {
+        owner: String,
+        repo: String,
+        git_ref: Option<String>,
+    },
+    /// A `flakehub:org/flake/version` reference to a flake release published on [FlakeHub], which
+    /// is resolved to its source GitHub repository and revision.
+    ///
+    /// [FlakeHub]: https://flakehub.com
+    FlakeHub {
+        org: String,
+        flake: String,
+        version: String,
+    },
+    /// A raw HTTP(S) URL.
+    Url(String),
+}
+
+impl Remote {
+    pub(crate) fn parse(reference: &str) -> Result<Self, FlakeCheckerError> {
+        if reference.starts_with("https://") || reference.starts_with("http://") {
+            return Ok(Self::Url(reference.to_string()));
+        }
+
+        let invalid = || FlakeCheckerError::InvalidRemote(reference.to_string());
+
+        if let Some(path) = reference.strip_prefix("flakehub:") {
+            let parts: Vec<&str> = path.splitn(3, '/').collect();
+            return match parts[..] {
+                [org, flake, version]
+                    if !org.is_empty() && !flake.is_empty() && !version.is_empty() =>
+                {
+                    Ok(Self::FlakeHub {
+                        org: org.to_string(),
+                        flake: flake.to_string(),
+                        version: version.to_string(),
+                    })
+                }
+                [org, flake] if !org.is_empty() && !flake.is_empty() => Ok(Self::FlakeHub {
+                    org: org.to_string(),
+                    flake: flake.to_string(),
+                    version: String::from("*"),
+                }),
+                _ => Err(invalid()),
+            };
+        }
+
+        let path = reference.strip_prefix("github:").ok_or_else(invalid)?;
+        let mut parts = path.splitn(3, '/');
+        let owner = parts.next().filter(|s| !s.is_empty()).ok_or_else(invalid)?;
+        let repo = parts.next().filter(|s| !s.is_empty()).ok_or_else(invalid)?;
+        let git_ref = parts.next().filter(|s| !s.is_empty());
+
+        Ok(Self::GitHub {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            git_ref: git_ref.map(String::from),
+        })
+    }
+
+    pub(crate) fn fetch(&self, network: &NetworkConfig) -> Result<String, FlakeCheckerError> {
+        let response = match self {
+            Self::GitHub {
+                owner,
+                repo,
+                git_ref,
+            } => http::send(network, |client| {
+                let mut request = client
+                    .get(format!(
+                        "https://api.github.com/repos/{owner}/{repo}/contents/flake.lock"
+                    ))
+                    .header("Accept", "application/vnd.github.raw")
+                    .header("User-Agent", "flake-checker");
+                if let Some(git_ref) = git_ref {
+                    request = request.query(&[("ref", git_ref)]);
+                }
+                network.authorize_github(request)
+            })?,
+            Self::FlakeHub { .. } => return self.resolve_flakehub(network)?.fetch(network),
+            Self::Url(url) => http::send(network, |client| client.get(url))?,
+        };
+
+        Ok(response.text()?)
+    }
+
+    // Looks up the GitHub repository and revision that a FlakeHub release was published from.
+    fn resolve_flakehub(&self, network: &NetworkConfig) -> Result<Self, FlakeCheckerError> {
+        let Self::FlakeHub {
+            org,
+            flake,
+            version,
+        } = self
+        else {
+            return Ok(self.clone());
+        };
+
+        let response = http::send(network, |client| {
+            client
+                .get(format!(
+                    "https://api.flakehub.com/f/{org}/{flake}/{version}"
+                ))
+                .header("Accept", "application/json")
+                .header("User-Agent", "flake-checker")
+        })?
+        .text()?;
+        let release: serde_json::Value = serde_json::from_str(&response)?;
+
+        let unresolved = || {
+            FlakeCheckerError::InvalidRemote(format!(
+                "{self} (FlakeHub didn't report the release's source repository and revision)"
+            ))
+        };
+        let (owner, repo) = release
+            .get("source_github_owner_repo")
+            .and_then(|v| v.as_str())
+            .and_then(|owner_repo| owner_repo.split_once('/'))
+            .ok_or_else(unresolved)?;
+        let revision = release
+            .get("revision")
+            .and_then(|v| v.as_str())
+            .ok_or_else(unresolved)?;
+
+        Ok(Self::GitHub {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            git_ref: Some(revision.to_string()),
+        })
+    }
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GitHub {
+                owner,
+                repo,
+                git_ref: Some(git_ref),
+            } => write!(f, "github:{owner}/{repo}/{git_ref}"),
+            Self::GitHub { owner, repo, .. } => write!(f, "github:{owner}/{repo}"),
+            Self::FlakeHub {
+                org,
+                flake,
+                version,
+            } => write!(f, "flakehub:{org}/{flake}/{version}"),
+            Self::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Remote;
+
+    #[test]
+    fn test_parse_remote() {
+        let cases = [
+            ("github:NixOS/nixpkgs", Some("github:NixOS/nixpkgs")),
+            (
+                "github:DeterminateSystems/flake-checker/main",
+                Some("github:DeterminateSystems/flake-checker/main"),
+            ),
+            (
+                "https://example.com/flake.lock",
+                Some("https://example.com/flake.lock"),
+            ),
+            (
+                "flakehub:DeterminateSystems/flake-checker/0.1",
+                Some("flakehub:DeterminateSystems/flake-checker/0.1"),
+            ),
+            ("github:NixOS", None),
+            ("flakehub:NixOS", None),
+            ("gitlab:foo/bar", None),
+        ];
+
+        for (reference, expected) in cases {
+            assert_eq!(
+                Remote::parse(reference)
+                    .ok()
+                    .map(|r| r.to_string())
+                    .as_deref(),
+                expected,
+                "{reference}"
+            );
+        }
+    }
+}
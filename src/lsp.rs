@@ -0,0 +1,250 @@
+//! A minimal [Language Server Protocol](https://microsoft.github.io/language-server-protocol/)
+//! server over stdio that publishes the issues in open `flake.lock` files as diagnostics.
+
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::i18n::Lang;
+use crate::issue::Severity;
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use parse_flake_lock::source::SourceMap;
+use parse_flake_lock::FlakeLock;
+use serde_json::json;
+
+// LSP's DiagnosticSeverity values
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+
+/// Serve the language server on stdin/stdout until the client sends `exit`.
+pub(crate) fn serve(config: FlakeCheckConfig, lang: Lang) -> Result<(), FlakeCheckerError> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut output = std::io::stdout();
+    // The text of the open documents, by URI
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "initialize" => {
+                let result = json!({
+                    "capabilities": {
+                        // Full document sync, with the text included on save
+                        "textDocumentSync": { "openClose": true, "change": 1, "save": { "includeText": true } },
+                    },
+                    "serverInfo": { "name": "flake-checker", "version": env!("CARGO_PKG_VERSION") },
+                });
+                write_message(
+                    &mut output,
+                    &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+                )?;
+            }
+            "shutdown" => {
+                write_message(
+                    &mut output,
+                    &json!({ "jsonrpc": "2.0", "id": message["id"], "result": null }),
+                )?;
+            }
+            "exit" => break,
+            "textDocument/didOpen" | "textDocument/didChange" | "textDocument/didSave" => {
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    "textDocument/didChange" => params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str()),
+                    _ => params["text"].as_str(),
+                };
+                if let Some(text) = text {
+                    documents.insert(uri.to_string(), text.to_string());
+                }
+                if let Some(text) = documents.get(uri) {
+                    if uri.ends_with("flake.lock") {
+                        publish(&mut output, uri, diagnostics(text, &config, lang))?;
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                publish(&mut output, uri, vec![])?;
+            }
+            _ => {
+                // Requests (which have an ID) must get a response, even if unsupported
+                if !message["id"].is_null() {
+                    write_message(
+                        &mut output,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": message["id"],
+                            "error": { "code": -32601, "message": format!("unsupported method {method}") },
+                        }),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The diagnostics for the issues in a `flake.lock`, or for why it couldn't be parsed.
+fn diagnostics(source: &str, config: &FlakeCheckConfig, lang: Lang) -> Vec<serde_json::Value> {
+    let diagnostic = |range: serde_json::Value, severity: u8, code: &str, message: String| {
+        json!({
+            "range": range,
+            "severity": severity,
+            "source": "flake-checker",
+            "code": code,
+            "message": message,
+        })
+    };
+    let start_of_file = json!({
+        "start": { "line": 0, "character": 0 },
+        "end": { "line": 0, "character": 0 },
+    });
+
+    let (flake_lock, source_map) = match (source.parse::<FlakeLock>(), SourceMap::new(source)) {
+        (Ok(flake_lock), Ok(source_map)) => (flake_lock, source_map),
+        (Err(err), _) | (_, Err(err)) => {
+            return vec![diagnostic(
+                start_of_file,
+                SEVERITY_ERROR,
+                "parse",
+                err.to_string(),
+            )]
+        }
+    };
+    let issues = match find_issues(&flake_lock, config) {
+        Ok(issues) => issues,
+        Err(err) => {
+            return vec![diagnostic(
+                start_of_file,
+                SEVERITY_ERROR,
+                "check",
+                err.to_string(),
+            )]
+        }
+    };
+    issues
+        .into_iter()
+        .map(|mut issue| {
+            // Point at the offending field, falling back to the input's node (or the start of the
+            // file) if it can't be found. Diagnostics are for the open document, so the path
+            // doesn't matter.
+            issue.locate(Path::new("flake.lock"), &flake_lock, &source_map);
+            let span = issue
+                .location
+                .as_ref()
+                .and_then(|location| source_map.span(&location.json_pointer));
+            let range = match span {
+                Some(span) => json!({
+                    "start": position(&source_map, span.start),
+                    "end": position(&source_map, span.end),
+                }),
+                None => start_of_file.clone(),
+            };
+            let severity = match issue.severity {
+                Severity::Error => SEVERITY_ERROR,
+                Severity::Warning => SEVERITY_WARNING,
+            };
+            diagnostic(range, severity, issue.kind.name(), issue.message(lang))
+        })
+        .collect()
+}
+
+// LSP positions are 0-based and count UTF-16 code units, which matches the byte offsets of the
+// (ASCII) keys and values that issues point at.
+fn position(source_map: &SourceMap, offset: usize) -> serde_json::Value {
+    let (line, column) = source_map.line_col(offset);
+    json!({ "line": line - 1, "character": column - 1 })
+}
+
+fn publish(
+    output: &mut impl Write,
+    uri: &str,
+    diagnostics: Vec<serde_json::Value>,
+) -> Result<(), FlakeCheckerError> {
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }),
+    )
+}
+
+// Reads a `Content-Length`-framed JSON-RPC message, or `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<serde_json::Value>, FlakeCheckerError> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(FlakeCheckerError::Invalid(String::from(
+            "LSP message without a Content-Length header",
+        )));
+    };
+    let mut body = vec![0; content_length];
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(
+    output: &mut impl Write,
+    message: &serde_json::Value,
+) -> Result<(), FlakeCheckerError> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::FlakeCheckConfig;
+    use crate::i18n::Lang;
+    use crate::lsp::diagnostics;
+
+    #[test]
+    fn test_diagnostics() {
+        let source = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let config = FlakeCheckConfig {
+            check_outdated: false,
+            ..Default::default()
+        };
+
+        let diagnostics = diagnostics(&source, &config, Lang::En);
+        let codes: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes, ["disallowed", "non-upstream"]);
+
+        // The disallowed ref is on line 32 of the lockfile
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 31);
+
+        let invalid = super::diagnostics("{", &config, Lang::En);
+        assert_eq!(invalid[0]["code"], "parse");
+    }
+}
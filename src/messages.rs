@@ -0,0 +1,139 @@
+//! Message templates in policy files: Handlebars snippets that replace the messages of the issues
+//! that a check reports, so that findings can use an organization's own phrasing and link to its
+//! runbooks.
+//!
+//! A template sees the input's name as `input`, the input as `node` (with `type`, `owner`, `repo`,
+//! `dir`, `host`, `url`, `flake`, `ref`, `rev`, `last_modified`, and `num_days_old`), and the thresholds as
+//! `policy` (with `allowed_refs`, `allowed_owners`, `allowed_hosts`, and `max_days`), like a rule
+//! does. It also sees the issue's `kind` and `severity`, its details as `issue` (like
+//! `issue.max_days` for outdated inputs), and the built-in `message`.
+
+use crate::error::FlakeCheckerError;
+use crate::flake::{num_days_old, FlakeCheckConfig};
+use crate::i18n::Lang;
+use crate::issue::Issue;
+
+use std::str::FromStr;
+
+use handlebars::{no_escape, Handlebars, Template};
+use parse_flake_lock::RepoNode;
+use serde::Deserialize;
+use serde_json::json;
+
+/// A template for the messages of the issues that a check reports.
+///
+/// ```
+/// use flake_checker::MessageTemplate;
+///
+/// let template: MessageTemplate =
+///     "{{input}} is {{issue.num_days_old}} days old, see https://wiki.example.com/nixpkgs".parse()?;
+/// # Ok::<(), flake_checker::FlakeCheckerError>(())
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct MessageTemplate(String);
+
+impl TryFrom<String> for MessageTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        Template::compile(&template).map_err(|err| format!("invalid message template: {err}"))?;
+        Ok(Self(template))
+    }
+}
+
+impl FromStr for MessageTemplate {
+    type Err = FlakeCheckerError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Self::try_from(template.to_string()).map_err(FlakeCheckerError::Invalid)
+    }
+}
+
+impl MessageTemplate {
+    /// Render the message of an issue that a check reported for an input.
+    pub(crate) fn render(
+        &self,
+        issue: &Issue,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<String, FlakeCheckerError> {
+        let data = json!({
+            "input": issue.input,
+            "node": {
+                "type": repo.original.node_type,
+                "owner": repo.original.owner,
+                "repo": repo.original.repo,
+                "dir": repo.dir(),
+                "host": repo.host(),
+                "url": repo.url(),
+                "flake": repo.is_flake(),
+                "ref": repo.original.git_ref,
+                "rev": repo.locked.rev,
+                "last_modified": repo.locked.last_modified,
+                "num_days_old": num_days_old(repo.locked.last_modified, config.now()),
+            },
+            "policy": {
+                "allowed_refs": config.allowed_refs,
+                "allowed_owners": config.allowed_owners,
+                "allowed_hosts": config.allowed_hosts,
+                "max_days": config.max_days,
+            },
+            "kind": issue.kind.name(),
+            "severity": config.issue_severity(issue).as_str(),
+            "issue": issue.kind,
+            "message": issue.message(Lang::En),
+        });
+
+        // Messages are plain text, which the summaries escape for themselves
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(no_escape);
+        config.template_dir.register(&mut handlebars)?;
+        Ok(handlebars.render_template(&self.0, &data)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::i18n::Lang;
+    use crate::policy::Policy;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_message_templates() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+  "messages": {
+    "owner": "{{input}} comes from {{node.owner}} rather than {{#each policy.allowed_owners}}{{this}}{{/each}}, see <https://wiki.example.com/forks>",
+    "refs": "{{severity}}: {{message}}"
+  }
+}"#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let config = policy.apply(&FlakeCheckConfig::default());
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let messages: Vec<String> = issues.iter().map(|issue| issue.message(Lang::De)).collect();
+        assert_eq!(
+            messages[0],
+            "warning: the `nixpkgs` input uses the non-supported Git branch `this-should-fail` for Nixpkgs"
+        );
+        assert_eq!(
+            messages[1],
+            "nixpkgs comes from bitcoin-miner-org rather than NixOS, see <https://wiki.example.com/forks>"
+        );
+        // Checks without templates keep their built-in messages
+        assert_eq!(issues[2].message, None);
+
+        assert!(serde_json::from_str::<Policy>(r#"{"messages": {"refs": "{{#if}}"}}"#).is_err());
+        let unknown: Policy = serde_json::from_str(r#"{"messages": {"stale": "old"}}"#).unwrap();
+        assert!(unknown.validate().is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::error::FlakeCheckerError;
+use crate::http::NetworkConfig;
+use crate::pins::{self, Pin};
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+// The path that niv keeps its sources in, relative to the project root
+pub(crate) const SOURCES_PATH: &str = "nix/sources.json";
+
+/// An entry in niv's `sources.json`. GitHub sources have an owner, repo, and revision.
+#[derive(Debug, Deserialize)]
+struct Source {
+    branch: Option<String>,
+    owner: Option<String>,
+    repo: Option<String>,
+    rev: Option<String>,
+    sha256: Option<String>,
+    #[serde(rename = "type")]
+    source_type: Option<String>,
+    url: Option<String>,
+}
+
+/// Convert niv's `sources.json` into an equivalent `flake.lock`. niv doesn't record when revisions
+/// were committed, so the commit times of GitHub sources are looked up via the GitHub API.
+pub(crate) fn to_flake_lock(
+    sources: &str,
+    network: &NetworkConfig,
+) -> Result<String, FlakeCheckerError> {
+    pins::to_flake_lock(parse(sources)?, |owner, repo, rev| {
+        pins::commit_time(owner, repo, rev, network)
+    })
+}
+
+fn parse(sources: &str) -> Result<Vec<Pin>, FlakeCheckerError> {
+    let sources: BTreeMap<String, Source> = serde_json::from_str(sources)?;
+    Ok(sources
+        .into_iter()
+        .map(|(name, source)| Pin {
+            name,
+            owner: source.owner,
+            repo: source.repo,
+            rev: source.rev,
+            git_ref: source.branch,
+            hash: source.sha256,
+            pin_type: source.source_type.unwrap_or_else(|| String::from("file")),
+            url: source.url,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::niv::parse;
+    use crate::pins;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_niv_sources() {
+        let sources = r#"{
+            "niv": {
+                "branch": "master",
+                "owner": "nmattia",
+                "repo": "niv",
+                "rev": "e0ca65c81a2d7a4d82a189f1e23a48d59ad42070",
+                "sha256": "1pjgjvsd8qkmzaxjrgqx6wnapcg9dqd8z46v3sbm4ahqdkxhg8zn",
+                "type": "tarball",
+                "url": "https://github.com/nmattia/niv/archive/e0ca65c81a2d7a4d82a189f1e23a48d59ad42070.tar.gz"
+            },
+            "nixpkgs": {
+                "branch": "release-21.05",
+                "owner": "NixOS",
+                "repo": "nixpkgs",
+                "rev": "5f244caea76105b63d826911b2a1563d33ff1cdc",
+                "sha256": "1xlgynfw9svy7nvh9nkxsxdzncv9hg99gbvbwv3gmrhmzc3sar75",
+                "type": "tarball",
+                "url": "https://github.com/NixOS/nixpkgs/archive/5f244caea76105b63d826911b2a1563d33ff1cdc.tar.gz"
+            },
+            "tools": {
+                "sha256": "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73",
+                "type": "file",
+                "url": "https://example.com/tools.tar.gz"
+            }
+        }"#;
+
+        let source =
+            pins::to_flake_lock(parse(sources).unwrap(), |_, _, _| Ok(1_622_505_600)).unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        assert_eq!(flake_lock.root.len(), 3);
+
+        let issues = find_issues(&flake_lock, &FlakeCheckConfig::default()).unwrap();
+        let kinds: Vec<&str> = issues.iter().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["disallowed", "outdated"]);
+    }
+}
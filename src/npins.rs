@@ -0,0 +1,115 @@
+use crate::error::FlakeCheckerError;
+use crate::http::NetworkConfig;
+use crate::pins::{self, Pin};
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+// The path that npins keeps its pins in, relative to the project root
+pub(crate) const SOURCES_PATH: &str = "npins/sources.json";
+
+#[derive(Debug, Deserialize)]
+struct Sources {
+    pins: BTreeMap<String, NpinsPin>,
+}
+
+/// A pin in npins' `sources.json`. Git and release pins name the repository they track.
+#[derive(Debug, Deserialize)]
+struct NpinsPin {
+    #[serde(rename = "type")]
+    pin_type: String,
+    repository: Option<Repository>,
+    branch: Option<String>,
+    version: Option<String>,
+    revision: Option<String>,
+    url: Option<String>,
+    hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    #[serde(rename = "type")]
+    repository_type: String,
+    owner: Option<String>,
+    repo: Option<String>,
+}
+
+/// Convert npins' `sources.json` into an equivalent `flake.lock`. npins doesn't record when
+/// revisions were committed, so the commit times of GitHub pins are looked up via the GitHub API.
+pub(crate) fn to_flake_lock(
+    sources: &str,
+    network: &NetworkConfig,
+) -> Result<String, FlakeCheckerError> {
+    pins::to_flake_lock(parse(sources)?, |owner, repo, rev| {
+        pins::commit_time(owner, repo, rev, network)
+    })
+}
+
+fn parse(sources: &str) -> Result<Vec<Pin>, FlakeCheckerError> {
+    let sources: Sources = serde_json::from_str(sources)?;
+    Ok(sources
+        .pins
+        .into_iter()
+        .map(|(name, pin)| {
+            let github = pin
+                .repository
+                .filter(|repository| repository.repository_type == "GitHub");
+            Pin {
+                name,
+                owner: github.as_ref().and_then(|github| github.owner.clone()),
+                repo: github.as_ref().and_then(|github| github.repo.clone()),
+                rev: github.as_ref().and(pin.revision),
+                // Release pins track tags rather than branches
+                git_ref: pin.branch.or(pin.version),
+                hash: pin.hash,
+                pin_type: pin.pin_type,
+                url: pin.url,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::npins::parse;
+    use crate::pins;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_npins_sources() {
+        let sources = r#"{
+            "pins": {
+                "nixpkgs": {
+                    "type": "Git",
+                    "repository": { "type": "GitHub", "owner": "NixOS", "repo": "nixpkgs" },
+                    "branch": "nixos-unstable",
+                    "revision": "5f244caea76105b63d826911b2a1563d33ff1cdc",
+                    "url": "https://github.com/NixOS/nixpkgs/archive/5f244caea76105b63d826911b2a1563d33ff1cdc.tar.gz",
+                    "hash": "1xlgynfw9svy7nvh9nkxsxdzncv9hg99gbvbwv3gmrhmzc3sar75"
+                },
+                "nixos": {
+                    "type": "Channel",
+                    "name": "nixos-23.05",
+                    "url": "https://releases.nixos.org/nixos/23.05/nixos-23.05.1234.abcdef/nixexprs.tar.xz",
+                    "hash": "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+                }
+            },
+            "version": 3
+        }"#;
+
+        let pins = parse(sources).unwrap();
+        assert_eq!(pins[1].owner.as_deref(), Some("NixOS"));
+        assert_eq!(pins[0].owner, None);
+
+        let source = pins::to_flake_lock(pins, |_, _, _| Ok(1_622_505_600)).unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        assert_eq!(flake_lock.root.len(), 2);
+
+        let issues = find_issues(&flake_lock, &FlakeCheckConfig::default()).unwrap();
+        let kinds: Vec<&str> = issues.iter().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["outdated"]);
+    }
+}
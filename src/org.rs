@@ -0,0 +1,130 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::http::{self, NetworkConfig};
+use crate::i18n::Lang;
+use crate::lockfile::Remote;
+use crate::summary::{Report, Summary};
+
+use std::path::PathBuf;
+
+use parse_flake_lock::FlakeLock;
+
+const PER_PAGE: usize = 100;
+
+/// Check the `flake.lock` on the default branch of every (non-archived) repository in a GitHub
+/// organization, returning a report whose summaries are ranked from most to fewest issues.
+pub(crate) fn scan(
+    org: &str,
+    config: &FlakeCheckConfig,
+    lang: Lang,
+    max_issues: Option<usize>,
+) -> Result<Report, FlakeCheckerError> {
+    let mut summaries = Vec::new();
+
+    for repo in list_repos(org, &config.network)? {
+        let remote = Remote::GitHub {
+            owner: org.to_string(),
+            repo,
+            git_ref: None,
+        };
+
+        let source = match remote.fetch(&config.network) {
+            Ok(source) => source,
+            // The repo doesn't have a flake.lock
+            Err(FlakeCheckerError::Http(err))
+                if err.status() == Some(reqwest::StatusCode::NOT_FOUND) =>
+            {
+                continue
+            }
+            Err(err) => return Err(err),
+        };
+
+        let flake_lock: FlakeLock = match source.parse() {
+            Ok(flake_lock) => flake_lock,
+            Err(err) => {
+                eprintln!("skipping {remote}: {err}");
+                continue;
+            }
+        };
+        let issues = find_issues(&flake_lock, config)?;
+
+        summaries.push(Summary::new(
+            &issues,
+            &flake_lock,
+            &source,
+            PathBuf::from(remote.to_string()),
+            config.clone(),
+            lang,
+            max_issues,
+        ));
+    }
+
+    // Worst offenders first; the sort is stable, so ties stay in name order
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.issues.len()));
+
+    Ok(Report::new(summaries))
+}
+
+/// Print a table of the scanned repositories, ranked by their number of issues.
+pub(crate) fn print_ranking(report: &Report) {
+    let width = report
+        .summaries
+        .iter()
+        .map(|summary| summary.flake_lock_path.to_string_lossy().len())
+        .max()
+        .unwrap_or_default()
+        .max("repository".len());
+
+    println!(
+        "{:>4}  {:<width$}  {:>6}  kinds",
+        "rank", "repository", "issues"
+    );
+    for (rank, summary) in report.summaries.iter().enumerate() {
+        let mut kinds: Vec<&str> = summary.issues.iter().map(|i| i.kind.name()).collect();
+        kinds.sort();
+        kinds.dedup();
+        println!(
+            "{:>4}  {:<width$}  {:>6}  {}",
+            rank + 1,
+            summary.flake_lock_path.to_string_lossy(),
+            summary.issues.len(),
+            kinds.join(", ")
+        );
+    }
+}
+
+// Lists the names of the organization's repositories, in name order.
+fn list_repos(org: &str, network: &NetworkConfig) -> Result<Vec<String>, FlakeCheckerError> {
+    let mut repos = Vec::new();
+
+    for page in 1.. {
+        let response = http::send(network, |client| {
+            network.authorize_github(
+                client
+                    .get(format!("https://api.github.com/orgs/{org}/repos"))
+                    .query(&[
+                        ("per_page", PER_PAGE.to_string()),
+                        ("page", page.to_string()),
+                    ])
+                    .header("Accept", "application/vnd.github+json")
+                    .header("User-Agent", "flake-checker"),
+            )
+        })?
+        .text()?;
+        let page: Vec<serde_json::Value> = serde_json::from_str(&response)?;
+        let num_repos = page.len();
+
+        repos.extend(
+            page.iter()
+                .filter(|repo| repo.get("archived") != Some(&serde_json::Value::Bool(true)))
+                .filter_map(|repo| repo.get("name")?.as_str().map(String::from)),
+        );
+
+        if num_repos < PER_PAGE {
+            break;
+        }
+    }
+
+    repos.sort();
+    Ok(repos)
+}
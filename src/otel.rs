@@ -0,0 +1,206 @@
+use crate::flake::InputDetails;
+use crate::http::{self, NetworkConfig};
+use crate::issue::Issue;
+
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const CHECKS: &[(&str, &str)] = &[
+    ("supported-refs", "disallowed"),
+    ("max-age", "outdated"),
+    ("upstream-owner", "non-upstream"),
+];
+
+/// An exporter that sends a trace of each check run to an OTLP/HTTP collector, configured using
+/// the standard `OTEL_EXPORTER_OTLP_*` environment variables.
+pub(crate) struct OtelExporter {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    service_name: String,
+}
+
+/// Wall-clock bounds of a check run, in nanoseconds since the Unix epoch.
+pub(crate) struct RunTiming {
+    pub start: u128,
+    pub end: u128,
+}
+
+impl OtelExporter {
+    /// Returns `None` unless `OTEL_EXPORTER_OTLP_ENDPOINT` (or the traces-specific
+    /// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) is set.
+    pub(crate) fn from_env() -> Option<Self> {
+        let endpoint = match env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+            Ok(endpoint) if !endpoint.is_empty() => endpoint,
+            _ => {
+                let base = env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+                if base.is_empty() {
+                    return None;
+                }
+                format!("{}/v1/traces", base.trim_end_matches('/'))
+            }
+        };
+
+        let headers = env::var("OTEL_EXPORTER_OTLP_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+
+        let service_name =
+            env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| String::from("flake-checker"));
+
+        Some(Self {
+            endpoint,
+            headers,
+            service_name,
+        })
+    }
+
+    /// The URL that traces are sent to.
+    pub(crate) fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Build the OTLP/JSON trace payload for a run: a root span for the run plus one child span
+    /// per check. The checks are evaluated together per input, so the child spans share the run's
+    /// timing window.
+    pub(crate) fn payload(
+        &self,
+        timing: &RunTiming,
+        issues: &[Issue],
+        inputs: &[InputDetails],
+    ) -> Value {
+        let trace_id = random_hex(16);
+        let run_span_id = random_hex(8);
+
+        let mut run_attributes = vec![
+            attribute("flake_checker.issues", json!({ "intValue": issues.len() })),
+            attribute("flake_checker.inputs", json!({ "intValue": inputs.len() })),
+        ];
+        for input in inputs.iter() {
+            if let Some(num_days_old) = input.num_days_old {
+                run_attributes.push(attribute(
+                    &format!("flake_checker.input.{}.age_days", input.name),
+                    json!({ "intValue": num_days_old }),
+                ));
+            }
+        }
+
+        let mut spans = vec![span(
+            &trace_id,
+            &run_span_id,
+            None,
+            "flake-checker run",
+            timing,
+            run_attributes,
+        )];
+
+        for (check, kind) in CHECKS {
+            let count = issues.iter().filter(|i| i.kind.name() == *kind).count();
+            spans.push(span(
+                &trace_id,
+                &random_hex(8),
+                Some(&run_span_id),
+                &format!("check {check}"),
+                timing,
+                vec![
+                    attribute("flake_checker.check", json!({ "stringValue": check })),
+                    attribute("flake_checker.issues", json!({ "intValue": count })),
+                ],
+            ));
+        }
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", json!({ "stringValue": self.service_name })),
+                        attribute(
+                            "service.version",
+                            json!({ "stringValue": env!("CARGO_PKG_VERSION") }),
+                        ),
+                    ],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "flake-checker", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    pub(crate) fn export(
+        &self,
+        network: &NetworkConfig,
+        timing: &RunTiming,
+        issues: &[Issue],
+        inputs: &[InputDetails],
+    ) {
+        let payload = serde_json::to_string(&self.payload(timing, issues, inputs));
+        if let (Ok(serialized), Ok(client)) = (payload, http::client(network)) {
+            let mut request = client
+                .post(&self.endpoint)
+                .body(serialized)
+                .header("Content-Type", "application/json")
+                .timeout(std::time::Duration::from_millis(3000));
+            for (name, value) in self.headers.iter() {
+                request = request.header(name, value);
+            }
+            let _ = request.send();
+        }
+    }
+}
+
+pub(crate) fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn span(
+    trace_id: &str,
+    span_id: &str,
+    parent_span_id: Option<&str>,
+    name: &str,
+    timing: &RunTiming,
+    attributes: Vec<Value>,
+) -> Value {
+    json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "parentSpanId": parent_span_id.unwrap_or_default(),
+        "name": name,
+        // SPAN_KIND_INTERNAL
+        "kind": 1,
+        "startTimeUnixNano": timing.start.to_string(),
+        "endTimeUnixNano": timing.end.to_string(),
+        "attributes": attributes,
+    })
+}
+
+// Trace and span IDs only need to be unique, not unpredictable, so hash the current time and
+// process ID rather than pulling in a random number generator.
+fn random_hex(num_bytes: usize) -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    let mut hasher = Sha256::new();
+    hasher.update(now_nanos().to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(
+        COUNTER
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            .to_le_bytes(),
+    );
+
+    let result = hasher.finalize();
+    format!("{:x}", result)[..num_bytes * 2].to_string()
+}
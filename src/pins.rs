@@ -0,0 +1,114 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+
+use chrono::DateTime;
+use serde_json::json;
+
+/// A dependency pinned by a tool other than Nix flakes (like niv or npins). Pins of GitHub
+/// repositories have an owner, repo, and revision.
+#[derive(Debug)]
+pub(crate) struct Pin {
+    pub name: String,
+    pub owner: Option<String>,
+    pub repo: Option<String>,
+    pub rev: Option<String>,
+    /// The branch or tag that the pin tracks.
+    pub git_ref: Option<String>,
+    pub hash: Option<String>,
+    /// The tool's own name for the kind of pin, for pins that aren't of GitHub repositories.
+    pub pin_type: String,
+    pub url: Option<String>,
+}
+
+/// Convert pins into an equivalent `flake.lock`, with each pin as a root input of the same name,
+/// so that the usual checks apply. GitHub pins are locked as of their commit times, which
+/// `commit_time` looks up.
+pub(crate) fn to_flake_lock(
+    pins: Vec<Pin>,
+    commit_time: impl Fn(&str, &str, &str) -> Result<i64, FlakeCheckerError>,
+) -> Result<String, FlakeCheckerError> {
+    let mut nodes = serde_json::Map::new();
+    let mut root_inputs = serde_json::Map::new();
+    for pin in pins {
+        let node = match &pin {
+            Pin {
+                owner: Some(owner),
+                repo: Some(repo),
+                rev: Some(rev),
+                ..
+            } => json!({
+                "locked": {
+                    "lastModified": commit_time(owner, repo, rev)?,
+                    "narHash": pin.hash.clone().unwrap_or_default(),
+                    "owner": owner,
+                    "repo": repo,
+                    "rev": rev,
+                    "type": "github",
+                },
+                "original": {
+                    "owner": owner,
+                    "ref": pin.git_ref,
+                    "repo": repo,
+                    "type": "github",
+                },
+            }),
+            // Other pins aren't checked, but are still listed as inputs
+            _ => json!({
+                "locked": { "type": pin.pin_type, "url": pin.url },
+                "original": { "type": pin.pin_type, "url": pin.url },
+            }),
+        };
+        root_inputs.insert(pin.name.clone(), json!(pin.name));
+        nodes.insert(pin.name, node);
+    }
+    nodes.insert(String::from("root"), json!({ "inputs": root_inputs }));
+
+    Ok(serde_json::to_string_pretty(&json!({
+        "nodes": nodes,
+        "root": "root",
+        "version": 7,
+    }))?)
+}
+
+/// The Unix timestamp of a commit on GitHub, which is what Nix locks as `lastModified`.
+pub(crate) fn commit_time(
+    owner: &str,
+    repo: &str,
+    rev: &str,
+    network: &NetworkConfig,
+) -> Result<i64, FlakeCheckerError> {
+    Ok(commit(owner, repo, rev, network)?.1)
+}
+
+/// The full hash and Unix timestamp of the commit that a revision (or a branch or tag) of a GitHub
+/// repository resolves to.
+pub(crate) fn commit(
+    owner: &str,
+    repo: &str,
+    rev: &str,
+    network: &NetworkConfig,
+) -> Result<(String, i64), FlakeCheckerError> {
+    let response = http::send(network, |client| {
+        network.authorize_github(
+            client
+                .get(format!(
+                    "https://api.github.com/repos/{owner}/{repo}/commits/{rev}"
+                ))
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", "flake-checker"),
+        )
+    })?
+    .text()?;
+    let commit: serde_json::Value = serde_json::from_str(&response)?;
+
+    let sha = commit["sha"].as_str().map(String::from);
+    let time = commit["commit"]["committer"]["date"]
+        .as_str()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.timestamp());
+    sha.zip(time).ok_or_else(|| {
+        FlakeCheckerError::Invalid(format!(
+            "no commit hash and date for {owner}/{repo}@{rev} in the GitHub API's response"
+        ))
+    })
+}
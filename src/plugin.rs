@@ -0,0 +1,227 @@
+//! External checks, written in any language: executables that read a lockfile as JSON on stdin
+//! and write the issues that they find as JSON on stdout, or WebAssembly modules (`.wasm` files)
+//! that do the same in a sandbox (see the `wasm` module).
+//!
+//! The input is an object with the protocol `version` (currently 1), the lockfile's `path`, the
+//! parsed `lock`, the `nixpkgs_keys` of the inputs being checked (whether named by
+//! `--nixpkgs-keys`, matched by selectors, or detected), and the `policy` that the built-in checks
+//! use (`allowed_refs`, `allowed_owners`, `allowed_hosts`, and `max_days`). The output must be an
+//! object with a list of `issues`, each with a `code` (the kind of issue, like `unpinned`), a
+//! `message`, and the name of the root `input` that it's about (omitted for issues with the whole
+//! lockfile).
+
+use crate::flake::{selected_deps, FlakeCheckConfig};
+use crate::issue::{CheckError, Custom, Issue, IssueKind, Severity};
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use parse_flake_lock::FlakeLock;
+use serde::Deserialize;
+use serde_json::json;
+
+/// The version of the protocol, which plugins receive as `version`.
+const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Deserialize)]
+struct Output {
+    issues: Vec<PluginIssue>,
+}
+
+#[derive(Deserialize)]
+struct PluginIssue {
+    #[serde(default)]
+    input: String,
+    code: String,
+    message: String,
+}
+
+/// Run a plugin against a lockfile. A plugin that fails, or that doesn't write the expected JSON,
+/// reports a `check-error` issue rather than failing the run.
+pub(crate) fn run(
+    plugin: &Path,
+    flake_lock_path: &Path,
+    flake_lock: &FlakeLock,
+    source: &str,
+    config: &FlakeCheckConfig,
+) -> Vec<Issue> {
+    let issues =
+        invoke(plugin, flake_lock_path, flake_lock, source, config).unwrap_or_else(|error| {
+            vec![Issue {
+                input: String::new(),
+                kind: IssueKind::CheckError(CheckError {
+                    check: plugin.display().to_string(),
+                    error,
+                }),
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
+            }]
+        });
+    issues
+        .into_iter()
+        .map(|mut issue| {
+            issue.severity = config.issue_severity(&issue);
+            issue
+        })
+        .collect()
+}
+
+fn invoke(
+    plugin: &Path,
+    flake_lock_path: &Path,
+    flake_lock: &FlakeLock,
+    source: &str,
+    config: &FlakeCheckConfig,
+) -> Result<Vec<Issue>, String> {
+    let lock: serde_json::Value =
+        serde_json::from_str(source).map_err(|err| format!("invalid lockfile: {err}"))?;
+    let nixpkgs_keys: Vec<String> = selected_deps(flake_lock, config)
+        .map_err(|err| err.to_string())?
+        .into_keys()
+        .collect();
+    let input = json!({
+        "version": PROTOCOL_VERSION,
+        "path": flake_lock_path.to_string_lossy(),
+        "lock": lock,
+        "nixpkgs_keys": nixpkgs_keys,
+        "policy": {
+            "allowed_refs": config.allowed_refs,
+            "allowed_owners": config.allowed_owners,
+            "allowed_hosts": config.allowed_hosts,
+            "max_days": config.max_days,
+        },
+    })
+    .to_string();
+
+    let output = if plugin
+        .extension()
+        .is_some_and(|extension| extension == "wasm")
+    {
+        run_wasm(plugin, &input)?
+    } else {
+        run_executable(plugin, input)?
+    };
+    let output: Output = serde_json::from_slice(&output)
+        .map_err(|err| format!("its output isn't a list of issues: {err}"))?;
+
+    output
+        .issues
+        .into_iter()
+        .map(|issue| {
+            if issue.code.is_empty() {
+                return Err(String::from("it reported an issue without a code"));
+            }
+            Ok(Issue {
+                input: issue.input,
+                kind: IssueKind::Custom(Custom {
+                    code: issue.code,
+                    message: issue.message,
+                }),
+                location: None,
+                provenance: None,
+                severity: Severity::Warning,
+                message: None,
+            })
+        })
+        .collect()
+}
+
+fn run_executable(plugin: &Path, input: String) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(plugin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("couldn't run it: {err}"))?;
+
+    // Write the input while reading the output, so that a plugin that writes before it has read
+    // everything doesn't block. Plugins don't have to read all of their input.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| String::from("couldn't write to its stdin"))?;
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("couldn't run it: {err}"))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(format!("it failed ({})", output.status));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(feature = "wasm")]
+fn run_wasm(plugin: &Path, input: &str) -> Result<Vec<u8>, String> {
+    crate::wasm::run(plugin, input)
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run_wasm(_: &Path, _: &str) -> Result<Vec<u8>, String> {
+    Err(String::from(
+        "this build of flake-checker doesn't support WebAssembly plugins (it needs the `wasm` feature)",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::flake::FlakeCheckConfig;
+    use crate::issue::IssueKind;
+    use crate::plugin::run;
+
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_plugins() {
+        let dir = std::env::temp_dir().join(format!("flake-checker-plugin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plugin = |name: &str, script: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let source = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let flake_lock = source.parse().unwrap();
+        let config = FlakeCheckConfig::default();
+        let path = Path::new("flake.lock");
+
+        // The plugin sees the protocol version, the lock, and the detected Nixpkgs inputs
+        let echo = plugin(
+            "echo",
+            r#"input=$(cat)
+case "$input" in *'"version":1'*) ;; *) exit 1 ;; esac
+case "$input" in *'"root":"root"'*) ;; *) exit 1 ;; esac
+case "$input" in *'"nixpkgs_keys":["nixpkgs"]'*) ;; *) exit 1 ;; esac
+echo '{"issues": [{"input": "nixpkgs", "code": "unpinned", "message": "not pinned"}]}'"#,
+        );
+        let issues = run(&echo, path, &flake_lock, &source, &config);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].input, "nixpkgs");
+        assert_eq!(issues[0].kind.name(), "unpinned");
+
+        let failing = plugin("failing", "exit 3");
+        let issues = run(&failing, path, &flake_lock, &source, &config);
+        let IssueKind::CheckError(error) = &issues[0].kind else {
+            panic!("expected a check-error issue");
+        };
+        assert!(error.error.contains("failed"), "{}", error.error);
+
+        let garbled = plugin("garbled", "echo 'no issues here'");
+        assert!(run(&garbled, path, &flake_lock, &source, &config)[0]
+            .kind
+            .is_check_error());
+
+        assert!(
+            run(&dir.join("missing"), path, &flake_lock, &source, &config)[0]
+                .kind
+                .is_check_error()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
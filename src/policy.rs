@@ -0,0 +1,407 @@
+use crate::budget::{check_kind, Budget};
+use crate::checks::CheckRegistry;
+use crate::error::FlakeCheckerError;
+use crate::flake::{num_days_old, selected_deps, FlakeCheckConfig};
+use crate::http::NetworkConfig;
+use crate::messages::MessageTemplate;
+use crate::requirements::{Expression, Requirement};
+use crate::rules::{Rule, Script};
+use crate::selector::{Scope, Selector};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use parse_flake_lock::{FlakeLock, Node};
+use serde::Deserialize;
+use serde_json::json;
+
+/// The name of the policy files that govern the lockfiles in their directory and beneath it (when
+/// scanning with `--recursive`).
+pub(crate) const POLICY_FILE: &str = "flake-checker.json";
+
+/// Overrides of the check configuration, from a policy file or an API request. Absent fields keep
+/// the configured defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub(crate) check_supported: Option<bool>,
+    pub(crate) check_outdated: Option<bool>,
+    pub(crate) check_owner: Option<bool>,
+    pub(crate) check_format: Option<bool>,
+    pub(crate) strict_fields: Option<bool>,
+    pub(crate) fail_mode: Option<bool>,
+    pub(crate) nixpkgs_keys: Option<Vec<String>>,
+    /// The inputs to check, instead of the root inputs named by `nixpkgs_keys`.
+    pub(crate) selectors: Option<Vec<Selector>>,
+    /// The inputs that each check runs against, by check name, overriding `selectors`.
+    pub(crate) check_selectors: Option<BTreeMap<String, Vec<Selector>>>,
+    /// Whether each check runs against `direct`, `transitive`, or `all` inputs, by check name.
+    pub(crate) check_scopes: Option<BTreeMap<String, Scope>>,
+    /// Handlebars templates for the messages of the issues that each check reports, by check name.
+    pub(crate) messages: Option<BTreeMap<String, MessageTemplate>>,
+    /// The Git refs that inputs may use, instead of the supported Nixpkgs branches.
+    pub(crate) allowed_refs: Option<Vec<String>>,
+    /// The maximum age of inputs, in days.
+    pub(crate) max_days: Option<i64>,
+    /// The GitHub owners that inputs may come from, instead of just NixOS.
+    pub(crate) allowed_owners: Option<Vec<String>>,
+    /// The hosts that inputs may come from besides their forges' default hosts.
+    pub(crate) allowed_hosts: Option<Vec<String>>,
+    /// Outdated inputs older than this many days are errors, and younger ones warnings.
+    pub(crate) error_after_days: Option<i64>,
+    /// Inputs on disallowed refs are errors after this date (like `2025-06-01`), and warnings
+    /// until then.
+    pub(crate) disallowed_error_after: Option<NaiveDate>,
+    /// External checks to run, relative to the policy file's directory.
+    pub(crate) plugins: Option<Vec<PathBuf>>,
+    /// Inline rules, as Rhai scripts by name, which are run against each input after the other
+    /// checks.
+    pub(crate) rules: Option<BTreeMap<String, Script>>,
+    /// Requirements, as CEL expressions by name that each input must satisfy, which are checked
+    /// after the other checks.
+    pub(crate) requirements: Option<BTreeMap<String, Expression>>,
+    /// Only fail if more than this many issues are found.
+    pub(crate) max_allowed_issues: Option<usize>,
+    /// Only fail if more than this many issues of a kind are found, by kind.
+    pub(crate) budgets: Option<BTreeMap<String, usize>>,
+    /// The timeout for each network request, in seconds.
+    pub(crate) network_timeout: Option<u64>,
+    /// How many times to retry network requests that fail transiently.
+    pub(crate) retries: Option<u32>,
+    /// The delay before the first retry of a network request, in milliseconds.
+    pub(crate) retry_backoff: Option<u64>,
+}
+
+impl FromStr for Policy {
+    type Err = FlakeCheckerError;
+
+    /// Parse and validate a policy in the JSON format of policy files.
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        let invalid = |err: String| FlakeCheckerError::Invalid(format!("invalid policy: {err}"));
+        let policy: Self = serde_json::from_str(policy).map_err(|err| invalid(err.to_string()))?;
+        policy.validate().map_err(invalid)?;
+        Ok(policy)
+    }
+}
+
+impl Policy {
+    /// Load and validate a policy file. Its plugins are relative to its directory.
+    pub fn load(path: &Path) -> Result<Self, FlakeCheckerError> {
+        let invalid = |err: String| {
+            FlakeCheckerError::Invalid(format!("invalid policy file {}: {err}", path.display()))
+        };
+        let mut policy: Self = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|err| invalid(err.to_string()))?;
+        policy.validate().map_err(invalid)?;
+        if let Some(plugins) = policy.plugins.as_mut() {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            for plugin in plugins.iter_mut() {
+                *plugin = dir.join(&*plugin);
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Check that the budgets are for known kinds of issue, and that the rules and requirements
+    /// have names of their own.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.validate_against(&CheckRegistry::default())
+    }
+
+    /// Like [Policy::validate], but with the rules and requirements alongside the given checks
+    /// rather than the built-in ones.
+    pub(crate) fn validate_against(&self, checks: &CheckRegistry) -> Result<(), String> {
+        self.budgets
+            .iter()
+            .flat_map(BTreeMap::keys)
+            .try_for_each(|kind| check_kind(kind))?;
+
+        let mut names: BTreeSet<&str> = checks.iter().map(|check| check.name()).collect();
+        let rules = self.rules.iter().flat_map(BTreeMap::keys);
+        let requirements = self.requirements.iter().flat_map(BTreeMap::keys);
+        if let Some(name) = rules.chain(requirements).find(|name| !names.insert(name)) {
+            return Err(format!("there's already a check named `{name}`"));
+        }
+        let selected = self.check_selectors.iter().flat_map(BTreeMap::keys);
+        let scoped = self.check_scopes.iter().flat_map(BTreeMap::keys);
+        if let Some(name) = selected
+            .chain(scoped)
+            .find(|name| !names.contains(name.as_str()))
+        {
+            return Err(format!(
+                "there's no check named `{name}` to select inputs for"
+            ));
+        }
+        match self
+            .messages
+            .iter()
+            .flat_map(BTreeMap::keys)
+            .find(|name| !names.contains(name.as_str()))
+        {
+            Some(name) => Err(format!(
+                "there's no check named `{name}` to give messages to"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply the policy's overrides to a configuration, with its rules and requirements
+    /// registered after the configuration's checks.
+    pub fn apply(&self, defaults: &FlakeCheckConfig) -> FlakeCheckConfig {
+        let mut checks = defaults.checks.clone();
+        for (name, script) in self.rules.iter().flatten() {
+            // Rules with the name of another check are rejected by `validate`
+            let _ = checks.register(Rule {
+                name: name.clone(),
+                script: script.clone(),
+            });
+        }
+        for (name, expression) in self.requirements.iter().flatten() {
+            let _ = checks.register(Requirement {
+                name: name.clone(),
+                expression: expression.clone(),
+            });
+        }
+
+        FlakeCheckConfig {
+            check_supported: self.check_supported.unwrap_or(defaults.check_supported),
+            check_outdated: self.check_outdated.unwrap_or(defaults.check_outdated),
+            check_owner: self.check_owner.unwrap_or(defaults.check_owner),
+            check_format: self.check_format.unwrap_or(defaults.check_format),
+            strict_fields: self.strict_fields.unwrap_or(defaults.strict_fields),
+            fail_mode: self.fail_mode.unwrap_or(defaults.fail_mode),
+            nixpkgs_keys: self
+                .nixpkgs_keys
+                .clone()
+                .unwrap_or_else(|| defaults.nixpkgs_keys.clone()),
+            selectors: self
+                .selectors
+                .clone()
+                .unwrap_or_else(|| defaults.selectors.clone()),
+            check_selectors: defaults
+                .check_selectors
+                .clone()
+                .into_iter()
+                .chain(self.check_selectors.clone().unwrap_or_default())
+                .collect(),
+            check_scopes: defaults
+                .check_scopes
+                .clone()
+                .into_iter()
+                .chain(self.check_scopes.clone().unwrap_or_default())
+                .collect(),
+            messages: defaults
+                .messages
+                .clone()
+                .into_iter()
+                .chain(self.messages.clone().unwrap_or_default())
+                .collect(),
+            allowed_refs: self
+                .allowed_refs
+                .clone()
+                .unwrap_or_else(|| defaults.allowed_refs.clone()),
+            max_days: self.max_days.unwrap_or(defaults.max_days),
+            allowed_owners: self
+                .allowed_owners
+                .clone()
+                .unwrap_or_else(|| defaults.allowed_owners.clone()),
+            allowed_hosts: self
+                .allowed_hosts
+                .clone()
+                .unwrap_or_else(|| defaults.allowed_hosts.clone()),
+            error_after_days: self.error_after_days.or(defaults.error_after_days),
+            disallowed_error_after: self
+                .disallowed_error_after
+                .or(defaults.disallowed_error_after),
+            template_dir: defaults.template_dir.clone(),
+            now: defaults.now,
+            checks,
+            disabled_checks: defaults.disabled_checks.clone(),
+            plugins: self
+                .plugins
+                .clone()
+                .unwrap_or_else(|| defaults.plugins.clone()),
+            budget: Budget {
+                max_issues: self.max_allowed_issues.or(defaults.budget.max_issues),
+                per_kind: defaults
+                    .budget
+                    .per_kind
+                    .clone()
+                    .into_iter()
+                    .chain(self.budgets.clone().unwrap_or_default())
+                    .collect(),
+            },
+            network: NetworkConfig {
+                timeout: self
+                    .network_timeout
+                    .map(Duration::from_secs)
+                    .unwrap_or(defaults.network.timeout),
+                retries: self.retries.unwrap_or(defaults.network.retries),
+                backoff: self
+                    .retry_backoff
+                    .map(Duration::from_millis)
+                    .unwrap_or(defaults.network.backoff),
+                proxy: defaults.network.proxy.clone(),
+                github_token: defaults.network.github_token.clone(),
+            },
+            devenv: defaults.devenv,
+        }
+    }
+}
+
+/// Generate a starting policy that the given lockfiles pass: their Nixpkgs inputs' refs are
+/// allowed alongside the configured ones, as are their owners, and the maximum age is the oldest
+/// input's age rounded up to a multiple of 30 days (but no less than the configured maximum).
+pub(crate) fn from_locks(
+    flake_locks: &[FlakeLock],
+    config: &FlakeCheckConfig,
+) -> Result<serde_json::Value, FlakeCheckerError> {
+    let mut allowed_refs: BTreeSet<String> = config.allowed_refs.iter().cloned().collect();
+    let mut allowed_owners: BTreeSet<String> = config.allowed_owners.iter().cloned().collect();
+    let mut max_days = config.max_days;
+
+    for flake_lock in flake_locks.iter() {
+        for node in selected_deps(flake_lock, config)?.values() {
+            let Node::Repo(repo) = node else {
+                continue;
+            };
+            allowed_refs.extend(repo.original.git_ref.clone());
+            if !allowed_owners
+                .iter()
+                .any(|owner| owner.eq_ignore_ascii_case(&repo.original.owner))
+            {
+                allowed_owners.insert(repo.original.owner.clone());
+            }
+            let age = num_days_old(repo.locked.last_modified, config.now());
+            max_days = max_days.max((age + 29) / 30 * 30);
+        }
+    }
+
+    Ok(json!({
+        "nixpkgs_keys": config.nixpkgs_keys,
+        "allowed_refs": allowed_refs,
+        "allowed_owners": allowed_owners,
+        "max_days": max_days,
+    }))
+}
+
+/// The thresholds of a configuration, as the summaries and `policy show` report them.
+pub(crate) fn describe(config: &FlakeCheckConfig) -> serde_json::Value {
+    json!({
+        "check_supported": config.check_supported,
+        "check_outdated": config.check_outdated,
+        "check_owner": config.check_owner,
+        "check_format": config.check_format,
+        "strict_fields": config.strict_fields,
+        "fail_mode": config.fail_mode,
+        "nixpkgs_keys": config.nixpkgs_keys,
+        "max_days": config.max_days,
+        "allowed_refs": config.allowed_refs,
+        "allowed_owners": config.allowed_owners,
+        "allowed_hosts": config.allowed_hosts,
+        "error_after_days": config.error_after_days,
+        "disallowed_error_after": config.disallowed_error_after,
+    })
+}
+
+/// Find the policy file nearest to a lockfile, looking in the lockfile's directory and then each
+/// parent directory up to (and including) `root`.
+pub(crate) fn find_nearest(flake_lock_path: &Path, root: &Path) -> Option<PathBuf> {
+    flake_lock_path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(POLICY_FILE))
+        .find(|path| path.is_file())
+}
+
+/// The policy file that governs a lockfile: when lockfiles are discovered beneath a `--recursive`
+/// root, the nearest one up to that root, and otherwise none.
+pub(crate) fn governing(flake_lock_path: &Path, recursive: Option<&Path>) -> Option<PathBuf> {
+    recursive.and_then(|root| find_nearest(flake_lock_path, root))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::policy::{from_locks, Policy};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_policy_overrides_defaults() {
+        let policy: Policy =
+            serde_json::from_str(r#"{ "check_outdated": false, "nixpkgs_keys": ["nixpkgs-alt"] }"#)
+                .unwrap();
+        let config = policy.apply(&FlakeCheckConfig::default());
+
+        assert!(!config.check_outdated);
+        assert!(config.check_supported);
+        assert_eq!(config.nixpkgs_keys, ["nixpkgs-alt"]);
+
+        assert!(serde_json::from_str::<Policy>(r#"{ "check_everything": true }"#).is_err());
+
+        let policy: Policy =
+            serde_json::from_str(r#"{ "budgets": { "outdated": 2, "stale": 0 } }"#).unwrap();
+        assert!(policy.validate().is_err());
+
+        let policy: Policy = serde_json::from_str(
+            r#"{ "selectors": [{ "repo": "nixpkgs" }], "check_selectors": { "owner": [{ "direct": true }] } }"#,
+        )
+        .unwrap();
+        assert!(policy.validate().is_ok());
+        let config = policy.apply(&FlakeCheckConfig::default());
+        assert_eq!(config.selectors[0].to_string(), "repo=nixpkgs");
+        assert!(config.check_selectors.contains_key("owner"));
+        let policy: Policy =
+            serde_json::from_str(r#"{ "check_selectors": { "stale": [{}] } }"#).unwrap();
+        assert!(policy.validate().is_err());
+        let policy: Policy =
+            serde_json::from_str(r#"{ "check_scopes": { "max-age": "transitive" } }"#).unwrap();
+        assert!(policy.validate().is_ok());
+        assert!(
+            serde_json::from_str::<Policy>(r#"{ "check_scopes": { "max-age": "some" } }"#).is_err()
+        );
+
+        let mut defaults = FlakeCheckConfig::default();
+        defaults.budget.per_kind = [
+            (String::from("outdated"), 5),
+            (String::from("disallowed"), 1),
+        ]
+        .into();
+        let policy: Policy = serde_json::from_str(r#"{ "budgets": { "outdated": 2 } }"#).unwrap();
+        assert_eq!(
+            policy.apply(&defaults).budget.per_kind,
+            [
+                (String::from("disallowed"), 1),
+                (String::from("outdated"), 2)
+            ]
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_policy_from_locks() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let defaults = FlakeCheckConfig::default();
+        let generated = from_locks(std::slice::from_ref(&flake_lock), &defaults).unwrap();
+
+        assert_eq!(
+            generated["allowed_owners"],
+            serde_json::json!(["NixOS", "bitcoin-miner-org"])
+        );
+        let max_days = generated["max_days"].as_i64().unwrap();
+        assert!(max_days % 30 == 0 && max_days > defaults.max_days);
+
+        // The lockfile passes the generated policy
+        let policy: Policy = serde_json::from_value(generated).unwrap();
+        let issues = find_issues(&flake_lock, &policy.apply(&defaults)).unwrap();
+        assert!(issues.is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Opening pull requests with the lockfiles that `--fix` updated, via the GitHub API, so that
+//! the fixes can be reviewed and merged like any other change.
+
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::i18n::Lang;
+use crate::issue::Issue;
+use crate::summary::Report;
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use reqwest::Method;
+use serde_json::json;
+
+const API_URL: &str = "https://api.github.com";
+
+/// A lockfile that `--fix` updated, with the issues that it fixed.
+pub(crate) struct FixedLockfile {
+    pub path: PathBuf,
+    pub source: String,
+    pub fixed: Vec<Issue>,
+}
+
+/// Commits fixed lockfiles to a branch and opens a pull request for it.
+pub(crate) struct PullRequestCreator {
+    repo: String,
+    base: Option<String>,
+    branch: String,
+    workspace: PathBuf,
+    token: String,
+    network: NetworkConfig,
+}
+
+impl PullRequestCreator {
+    /// Create a creator from the environment that GitHub Actions provides (`GITHUB_REPOSITORY`
+    /// and `GITHUB_WORKSPACE`). The pull request targets the branch that the run is for, or the
+    /// repository's default branch if the run isn't for a branch.
+    pub(crate) fn from_env(
+        branch: String,
+        network: NetworkConfig,
+    ) -> Result<Self, FlakeCheckerError> {
+        let token = network.github_token.clone().ok_or_else(|| {
+            FlakeCheckerError::Invalid(String::from(
+                "--create-pr needs a GitHub token (pass --github-token or set GITHUB_TOKEN)",
+            ))
+        })?;
+        let repo = env::var("GITHUB_REPOSITORY")?;
+        let base = match env::var("GITHUB_REF_TYPE").as_deref() {
+            Ok("branch") => env::var("GITHUB_REF_NAME").ok(),
+            _ => None,
+        };
+        let workspace = match env::var_os("GITHUB_WORKSPACE") {
+            Some(workspace) => PathBuf::from(workspace),
+            None => env::current_dir()?,
+        };
+
+        Ok(Self {
+            repo,
+            base,
+            branch,
+            workspace,
+            token,
+            network,
+        })
+    }
+
+    /// Commit the lockfiles to the branch, replacing whatever the branch had, and open a pull
+    /// request for it (or update the one that's already open), returning its URL.
+    pub(crate) fn create(
+        &self,
+        lockfiles: &[FixedLockfile],
+        report: &Report,
+        lang: Lang,
+    ) -> Result<String, FlakeCheckerError> {
+        let repo = &self.repo;
+        let base = match &self.base {
+            Some(base) => base.clone(),
+            None => string(
+                &self.api(Method::GET, &format!("repos/{repo}"), None)?,
+                "default_branch",
+            )?,
+        };
+        let title = lang.tr("pr-title", &[]);
+
+        // Commit all of the lockfiles at once, on top of the base branch
+        let head = self.api(
+            Method::GET,
+            &format!("repos/{repo}/git/ref/heads/{base}"),
+            None,
+        )?;
+        let base_sha = string(&head["object"], "sha")?;
+        let base_commit = self.api(
+            Method::GET,
+            &format!("repos/{repo}/git/commits/{base_sha}"),
+            None,
+        )?;
+        let base_tree = base_commit["tree"]["sha"].clone();
+
+        let mut entries = Vec::new();
+        for lockfile in lockfiles.iter() {
+            entries.push(json!({
+                "path": repo_path(&lockfile.path, &self.workspace)?,
+                "mode": "100644",
+                "type": "blob",
+                "content": lockfile.source,
+            }));
+        }
+        let tree = self.api(
+            Method::POST,
+            &format!("repos/{repo}/git/trees"),
+            Some(json!({ "base_tree": base_tree, "tree": entries })),
+        )?;
+        let commit = self.api(
+            Method::POST,
+            &format!("repos/{repo}/git/commits"),
+            Some(json!({
+                "message": title,
+                "tree": string(&tree, "sha")?,
+                "parents": [base_sha],
+            })),
+        )?;
+        let commit_sha = string(&commit, "sha")?;
+
+        let branch = &self.branch;
+        let existing = self.api(
+            Method::GET,
+            &format!("repos/{repo}/git/matching-refs/heads/{branch}"),
+            None,
+        )?;
+        let exists = existing.as_array().is_some_and(|refs| {
+            refs.iter()
+                .any(|r| r["ref"] == format!("refs/heads/{branch}"))
+        });
+        if exists {
+            self.api(
+                Method::PATCH,
+                &format!("repos/{repo}/git/refs/heads/{branch}"),
+                Some(json!({ "sha": commit_sha, "force": true })),
+            )?;
+        } else {
+            self.api(
+                Method::POST,
+                &format!("repos/{repo}/git/refs"),
+                Some(json!({ "ref": format!("refs/heads/{branch}"), "sha": commit_sha })),
+            )?;
+        }
+
+        let body = body(lockfiles, report, lang)?;
+        let owner = repo.split('/').next().unwrap_or_default();
+        let open = self.api(
+            Method::GET,
+            &format!("repos/{repo}/pulls?state=open&head={owner}:{branch}"),
+            None,
+        )?;
+        let pull_request = match open.as_array().and_then(|pulls| pulls.first()) {
+            Some(pull_request) => self.api(
+                Method::PATCH,
+                &format!("repos/{repo}/pulls/{}", pull_request["number"]),
+                Some(json!({ "title": title, "body": body })),
+            )?,
+            None => self.api(
+                Method::POST,
+                &format!("repos/{repo}/pulls"),
+                Some(json!({ "title": title, "body": body, "head": branch, "base": base })),
+            )?,
+        };
+        string(&pull_request, "html_url")
+    }
+
+    fn api(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, FlakeCheckerError> {
+        let body = body.map(|body| body.to_string());
+        let response = http::send(&self.network, |client| {
+            let request = client
+                .request(method.clone(), format!("{API_URL}/{path}"))
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", "flake-checker")
+                .bearer_auth(&self.token);
+            match &body {
+                Some(body) => request
+                    .header("Content-Type", "application/json")
+                    .body(body.clone()),
+                None => request,
+            }
+        })?;
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+}
+
+// A string field of an API response.
+fn string(value: &serde_json::Value, field: &str) -> Result<String, FlakeCheckerError> {
+    value[field].as_str().map(String::from).ok_or_else(|| {
+        FlakeCheckerError::Invalid(format!("no `{field}` in the GitHub API's response"))
+    })
+}
+
+// A lockfile's path within the repository, which is checked out at `workspace`.
+fn repo_path(path: &Path, workspace: &Path) -> Result<String, FlakeCheckerError> {
+    let absolute = std::path::absolute(path)?;
+    let relative = absolute.strip_prefix(workspace).map_err(|_| {
+        FlakeCheckerError::Invalid(format!(
+            "{} isn't in the repository at {}",
+            path.display(),
+            workspace.display()
+        ))
+    })?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Ok(components.join("/"))
+}
+
+// The pull request's body: the issues that were fixed in each lockfile, followed by the usual
+// summaries of the fixed lockfiles.
+fn body(
+    lockfiles: &[FixedLockfile],
+    report: &Report,
+    lang: Lang,
+) -> Result<String, FlakeCheckerError> {
+    let mut body = String::new();
+    for lockfile in lockfiles.iter() {
+        let file = lockfile.path.display();
+        body.push_str(&lang.tr("pr-fixed", &[("file", &file)]));
+        body.push_str("\n\n");
+        for issue in lockfile.fixed.iter() {
+            body.push_str(&format!("* {}\n", issue.message(lang)));
+        }
+        body.push('\n');
+    }
+    for summary in report.summaries.iter() {
+        if lockfiles
+            .iter()
+            .any(|lockfile| lockfile.path == summary.flake_lock_path)
+        {
+            body.push_str(&summary.markdown()?);
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pull_request::repo_path;
+
+    use std::path::Path;
+
+    #[test]
+    fn test_repo_path() {
+        let workspace = std::env::current_dir().unwrap();
+        assert_eq!(
+            repo_path(Path::new("tests/flake.dirty.0.lock"), &workspace).unwrap(),
+            "tests/flake.dirty.0.lock"
+        );
+        assert_eq!(
+            repo_path(&workspace.join("flake.lock"), &workspace).unwrap(),
+            "flake.lock"
+        );
+        assert!(repo_path(Path::new("/elsewhere/flake.lock"), &workspace).is_err());
+    }
+}
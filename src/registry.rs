@@ -0,0 +1,333 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::ALLOWED_REFS;
+use crate::http::{self, NetworkConfig};
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use parse_flake_lock::{FlakeLock, Forge, IndirectNode, Node, RepoNode, RepoOriginal};
+use serde::Deserialize;
+
+// The system-wide flake registry
+const SYSTEM_REGISTRY: &str = "/etc/nix/registry.json";
+// The global flake registry, which Nix consults after the local ones
+const GLOBAL_REGISTRY: &str = "https://channels.nixos.org/flake-registry.json";
+
+#[derive(Debug, Deserialize)]
+struct Registry {
+    flakes: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    from: serde_json::Value,
+    to: serde_json::Value,
+}
+
+/// What registry entries may point to. Empty lists allow anything.
+#[derive(Debug, Default)]
+pub(crate) struct RegistryPolicy {
+    /// Allowed owners of GitHub, GitLab, and SourceHut repositories (matched case-insensitively).
+    pub allowed_owners: Vec<String>,
+    /// Allowed flake reference types, like `github` or `path`.
+    pub allowed_types: Vec<String>,
+    /// Allowed hosts, for entries that are fetched from one.
+    pub allowed_hosts: Vec<String>,
+}
+
+/// A registry entry that points somewhere the policy doesn't allow.
+#[derive(Debug)]
+pub(crate) struct RegistryIssue {
+    /// The ID that the entry redirects, like `nixpkgs`.
+    pub id: String,
+    /// The flake reference that the ID is redirected to.
+    pub target: String,
+    pub problem: String,
+}
+
+impl std::fmt::Display for RegistryIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}: {}", self.id, self.target, self.problem)
+    }
+}
+
+/// The user and system registries, where they exist. The user registry is in
+/// `$XDG_CONFIG_HOME/nix` (or `~/.config/nix`).
+pub(crate) fn default_paths() -> Vec<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let user = config_home.map(|config_home| config_home.join("nix/registry.json"));
+
+    user.into_iter()
+        .chain([PathBuf::from(SYSTEM_REGISTRY)])
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// The entries of several flake registries, in the order that Nix consults them, for resolving
+/// indirect inputs like `inputs.nixpkgs.url = "nixpkgs";` to what they point to.
+#[derive(Debug, Default)]
+pub(crate) struct Registries {
+    entries: Vec<Entry>,
+}
+
+impl Registries {
+    /// Load registry files, followed by the global registry unless there's no `network` to fetch
+    /// it with (when offline).
+    pub(crate) fn load(
+        paths: &[PathBuf],
+        network: Option<&NetworkConfig>,
+    ) -> Result<Self, FlakeCheckerError> {
+        let mut entries = Vec::new();
+        for path in paths {
+            let registry: Registry = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            entries.extend(registry.flakes);
+        }
+        if let Some(network) = network {
+            let source = http::send(network, |client| {
+                client
+                    .get(GLOBAL_REGISTRY)
+                    .header("User-Agent", "flake-checker")
+            })?
+            .text()?;
+            let registry: Registry = serde_json::from_str(&source)?;
+            entries.extend(registry.flakes);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Replace each indirect node with the repository that its ID resolves to, so that the checks
+    /// hold it to the source policies rather than skipping it. Returns the names of the indirect
+    /// nodes that don't resolve to a repository on a forge, which are left as they are.
+    pub(crate) fn resolve(&self, flake_lock: &mut FlakeLock) -> Vec<String> {
+        let mut unresolved = Vec::new();
+        for (key, node) in flake_lock.nodes.iter_mut() {
+            if let Node::Indirect(indirect) = node {
+                match self.resolve_node(indirect) {
+                    Some(repo) => *node = Node::Repo(Box::new(repo)),
+                    None => unresolved.push(key.clone()),
+                }
+            }
+        }
+        for (name, node) in flake_lock.root.iter_mut() {
+            let resolved = flake_lock
+                .root_node_names
+                .get(name)
+                .and_then(|key| flake_lock.nodes.get(key));
+            if let (Node::Indirect(_), Some(resolved)) = (&node, resolved) {
+                *node = resolved.clone();
+            }
+        }
+        unresolved.sort();
+        unresolved
+    }
+
+    // The repository that the first matching entry points an indirect node to. The node keeps
+    // its locked attributes, and its own ref and rev override the entry's, as in Nix.
+    fn resolve_node(&self, node: &IndirectNode) -> Option<RepoNode> {
+        let git_ref = node.original.git_ref.as_deref();
+        let entry = self.entries.iter().find(|entry| {
+            entry.from["id"].as_str() == Some(node.original.id.as_str())
+                && entry.from["ref"]
+                    .as_str()
+                    .is_none_or(|from_ref| Some(from_ref) == git_ref)
+        })?;
+
+        let mut original: RepoOriginal = serde_json::from_value(entry.to.clone()).ok()?;
+        Forge::from_type(&original.node_type)?;
+        if let Some(git_ref) = git_ref {
+            original.git_ref = Some(git_ref.to_string());
+        }
+        if let Some(rev) = &node.original.rev {
+            original.rev = Some(rev.clone());
+        }
+        original.unknown = BTreeMap::new();
+        Some(RepoNode {
+            flake: node.flake,
+            inputs: node.inputs.clone(),
+            locked: node.locked.clone(),
+            original,
+            unknown: node.unknown.clone(),
+        })
+    }
+}
+
+/// Check each of a registry's entries against the policy. Regardless of the policy, the `nixpkgs`
+/// entry must point to upstream Nixpkgs on a supported branch, as for flake inputs.
+pub(crate) fn check(
+    source: &str,
+    policy: &RegistryPolicy,
+) -> Result<Vec<RegistryIssue>, FlakeCheckerError> {
+    let registry: Registry = serde_json::from_str(source)?;
+    let mut issues = Vec::new();
+
+    for entry in registry.flakes.iter() {
+        let id = entry.from["id"].as_str().unwrap_or_default().to_string();
+        let to = |field: &str| entry.to[field].as_str();
+        let target = describe(&entry.to);
+        let mut problem = |problem: String| {
+            issues.push(RegistryIssue {
+                id: id.clone(),
+                target: target.clone(),
+                problem,
+            })
+        };
+
+        let ref_type = to("type").unwrap_or_default();
+        if !policy.allowed_types.is_empty() && !policy.allowed_types.iter().any(|t| t == ref_type) {
+            problem(format!("`{ref_type}` references aren't allowed"));
+        }
+
+        if let Some(owner) = to("owner") {
+            if !policy.allowed_owners.is_empty()
+                && !policy
+                    .allowed_owners
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(owner))
+            {
+                problem(format!("the owner `{owner}` isn't allowed"));
+            }
+        }
+
+        if let Some(host) = host(&entry.to) {
+            if !policy.allowed_hosts.is_empty() && !policy.allowed_hosts.contains(&host) {
+                problem(format!("the host `{host}` isn't allowed"));
+            }
+        }
+
+        if id == "nixpkgs" {
+            let upstream = ref_type == "github"
+                && to("owner").is_some_and(|owner| owner.eq_ignore_ascii_case("nixos"))
+                && to("repo") == Some("nixpkgs")
+                && to("host").is_none();
+            if !upstream {
+                problem(String::from("nixpkgs should point to github:NixOS/nixpkgs"));
+            }
+            if let Some(git_ref) = to("ref").filter(|git_ref| !ALLOWED_REFS.contains(git_ref)) {
+                problem(format!("`{git_ref}` isn't a supported Nixpkgs branch"));
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+// Describes a flake reference's attributes in the URL-like syntax, like `github:NixOS/nixpkgs`.
+fn describe(to: &serde_json::Value) -> String {
+    let field = |field: &str| to[field].as_str();
+    let ref_type = field("type").unwrap_or("unknown");
+    match (field("owner"), field("repo"), field("url"), field("path")) {
+        (Some(owner), Some(repo), _, _) => {
+            let suffix = field("ref").map(|r| format!("/{r}")).unwrap_or_default();
+            format!("{ref_type}:{owner}/{repo}{suffix}")
+        }
+        (_, _, Some(url), _) => format!("{ref_type}+{url}"),
+        (_, _, _, Some(path)) => format!("{ref_type}:{path}"),
+        _ => ref_type.to_string(),
+    }
+}
+
+// The host that a flake reference is fetched from, if any.
+fn host(to: &serde_json::Value) -> Option<String> {
+    if let Some(host) = to["host"].as_str() {
+        return Some(host.to_string());
+    }
+    match Forge::from_type(to["type"].as_str()?) {
+        Some(forge) => Some(forge.host().to_string()),
+        None => reqwest::Url::parse(to["url"].as_str()?)
+            .ok()?
+            .host_str()
+            .map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::issue::IssueKind;
+    use crate::registry::{check, Registries, RegistryPolicy};
+
+    use parse_flake_lock::{FlakeLock, Node};
+
+    #[test]
+    fn test_registry() {
+        let registry = r#"{
+            "flakes": [
+                {
+                    "from": { "id": "nixpkgs", "type": "indirect" },
+                    "to": { "owner": "evil-corp", "repo": "nixpkgs", "type": "github" }
+                },
+                {
+                    "from": { "id": "templates", "type": "indirect" },
+                    "to": { "type": "git", "url": "https://git.example.com/templates.git" }
+                },
+                {
+                    "from": { "id": "home-manager", "type": "indirect" },
+                    "to": { "owner": "nix-community", "repo": "home-manager", "type": "github" }
+                }
+            ],
+            "version": 2
+        }"#;
+
+        let issues = check(registry, &RegistryPolicy::default()).unwrap();
+        let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(
+            issues,
+            ["nixpkgs -> github:evil-corp/nixpkgs: nixpkgs should point to github:NixOS/nixpkgs"]
+        );
+
+        let policy = RegistryPolicy {
+            allowed_owners: vec![String::from("NixOS"), String::from("nix-community")],
+            allowed_types: vec![String::from("github")],
+            allowed_hosts: vec![String::from("github.com")],
+        };
+        let issues = check(registry, &policy).unwrap();
+        let ids: Vec<&str> = issues.iter().map(|issue| issue.id.as_str()).collect();
+        assert_eq!(ids, ["nixpkgs", "nixpkgs", "templates", "templates"]);
+    }
+
+    #[test]
+    fn test_resolve_indirect_inputs() {
+        let source = std::fs::read_to_string("tests/flake.clean.0.lock").unwrap();
+        let mut lock: serde_json::Value = serde_json::from_str(&source).unwrap();
+        lock["nodes"]["nixpkgs"]["original"] = serde_json::json!({
+            "id": "nixpkgs",
+            "ref": "nixos-unstable",
+            "type": "indirect"
+        });
+        let mut flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        let config = FlakeCheckConfig::default();
+        // Unresolved, an indirect input's source isn't checked
+        assert!(find_issues(&flake_lock, &config).unwrap().is_empty());
+
+        let registry = serde_json::json!({
+            "flakes": [
+                {
+                    "from": { "id": "nixpkgs", "type": "indirect" },
+                    "to": { "owner": "evil-corp", "repo": "nixpkgs", "type": "github" }
+                }
+            ],
+            "version": 2
+        });
+        let path = std::env::temp_dir().join(format!(
+            "flake-checker-registry-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, registry.to_string()).unwrap();
+        let registries = Registries::load(std::slice::from_ref(&path), None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(registries.resolve(&mut flake_lock), ["flake-utils"]);
+        let Some(Node::Repo(nixpkgs)) = flake_lock.root.get("nixpkgs") else {
+            panic!("expected a repository node");
+        };
+        assert_eq!(nixpkgs.original.owner, "evil-corp");
+        assert_eq!(nixpkgs.original.git_ref.as_deref(), Some("nixos-unstable"));
+
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        assert!(issues
+            .iter()
+            .any(|issue| matches!(issue.kind, IssueKind::NonUpstream(_))));
+    }
+}
@@ -0,0 +1,175 @@
+//! Requirements in policy files: declarative CEL expressions that each Nixpkgs input must satisfy,
+//! like `node.original.ref in allowed_refs`, for requirements that don't need a whole rule.
+//!
+//! An expression sees the input's name as `input`, the input as `node` (with its `locked` and
+//! `original` attributes, as in flake.lock), its age in days as `num_days_old`, and the policy's
+//! `allowed_refs`, `allowed_owners`, `allowed_hosts`, and `max_days`.
+
+use crate::checks::Check;
+use crate::error::FlakeCheckerError;
+use crate::flake::{num_days_old, FlakeCheckConfig};
+use crate::issue::{CheckError, Custom, IssueKind};
+
+use cel_interpreter::{Context, Program, Value};
+use parse_flake_lock::RepoNode;
+use serde::Deserialize;
+use serde_json::json;
+
+/// The source of an expression, which is checked to compile when it's deserialized. (Compiled
+/// programs can't be shared between threads, so requirements compile their expressions when
+/// they're run.)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Expression(String);
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Program::compile(&source).map_err(|err| format!("invalid expression `{source}`: {err}"))?;
+        Ok(Self(source))
+    }
+}
+
+/// A requirement from a policy file, which is a check that reports issues with the requirement's
+/// name as their code.
+pub(crate) struct Requirement {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl Check for Requirement {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn code(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self, _: &FlakeCheckConfig) -> String {
+        format!("the input must satisfy `{}`", self.expression.0)
+    }
+
+    fn run(
+        &self,
+        input: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        let error = |error: String| {
+            Ok(Some(IssueKind::CheckError(CheckError {
+                check: self.name.clone(),
+                error,
+            })))
+        };
+        let program = match Program::compile(&self.expression.0) {
+            Ok(program) => program,
+            Err(err) => return error(err.to_string()),
+        };
+
+        let mut context = Context::default();
+        context.add_variable_from_value("input", input);
+        let variables = [
+            ("node", node(repo)),
+            (
+                "num_days_old",
+                json!(num_days_old(repo.locked.last_modified, config.now())),
+            ),
+            ("allowed_refs", json!(config.allowed_refs)),
+            ("allowed_owners", json!(config.allowed_owners)),
+            ("allowed_hosts", json!(config.allowed_hosts)),
+            ("max_days", json!(config.max_days)),
+        ];
+        for (name, value) in variables {
+            if let Err(err) = context.add_variable(name, value) {
+                return error(err.to_string());
+            }
+        }
+
+        // An expression that can't be evaluated is reported rather than failing the run, like a
+        // networked check
+        match program.execute(&context) {
+            Ok(Value::Bool(true)) => Ok(None),
+            Ok(Value::Bool(false)) => Ok(Some(IssueKind::Custom(Custom {
+                code: self.name.clone(),
+                message: format!(
+                    "the `{input}` input doesn't satisfy the `{}` requirement (`{}`)",
+                    self.name, self.expression.0
+                ),
+            }))),
+            Ok(value) => error(format!(
+                "the expression evaluated to {value:?} rather than true or false"
+            )),
+            Err(err) => error(err.to_string()),
+        }
+    }
+}
+
+// The input's attributes, as they're written in flake.lock.
+fn node(repo: &RepoNode) -> serde_json::Value {
+    let locked = &repo.locked;
+    let original = &repo.original;
+    json!({
+        "flake": repo.flake,
+        "locked": {
+            "dir": locked.dir,
+            "host": locked.host,
+            "lastModified": locked.last_modified,
+            "narHash": locked.nar_hash,
+            "owner": locked.owner,
+            "repo": locked.repo,
+            "rev": locked.rev,
+            "type": locked.node_type,
+        },
+        "original": {
+            "dir": original.dir,
+            "host": original.host,
+            "owner": original.owner,
+            "ref": original.git_ref,
+            "repo": original.repo,
+            "type": original.node_type,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::policy::Policy;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_requirements() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+  "check_outdated": false,
+  "allowed_refs": ["nixos-unstable"],
+  "requirements": {
+    "github": "node.locked.type == 'github' && node.original.ref in allowed_refs",
+    "recent": "num_days_old <= max_days",
+    "not-boolean": "input"
+  }
+}"#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let config = policy.apply(&FlakeCheckConfig::default());
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let kinds: Vec<&str> = issues.iter().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["check-error", "recent"]);
+
+        assert!(serde_json::from_str::<Policy>(r#"{"requirements": {"broken": "a &&"}}"#).is_err());
+        let clashing: Policy = serde_json::from_str(
+            r#"{"rules": {"recent": "()"}, "requirements": {"recent": "true"}}"#,
+        )
+        .unwrap();
+        assert!(clashing.validate().is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! Inline rules in policy files: small Rhai scripts that are run against each Nixpkgs input, for
+//! requirements that are too specific to an organization for a built-in check.
+//!
+//! A rule's script sees the input's name as `input`, the input as `node` (with `type`, `owner`,
+//! `repo`, `dir`, `host`, `url`, `ref`, `rev`, `last_modified`, and `num_days_old`), and the policy
+//! as `policy` (with `allowed_refs`, `allowed_owners`, `allowed_hosts`, and `max_days`). It reports an issue by evaluating to a
+//! message, and passes by evaluating to `()`, as an `if` without an `else` does when its
+//! condition is false.
+
+use crate::checks::Check;
+use crate::error::FlakeCheckerError;
+use crate::flake::{num_days_old, FlakeCheckConfig};
+use crate::issue::{CheckError, Custom, IssueKind};
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parse_flake_lock::RepoNode;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use serde::Deserialize;
+
+// How much a rule may compute for each input, in Rhai's operations.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A compiled rule script.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Script(Arc<AST>);
+
+impl TryFrom<String> for Script {
+    type Error = String;
+
+    fn try_from(script: String) -> Result<Self, Self::Error> {
+        engine()
+            .compile(script)
+            .map(|ast| Self(Arc::new(ast)))
+            .map_err(|err| format!("invalid rule: {err}"))
+    }
+}
+
+/// A rule from a policy file, which is a check that reports issues with the rule's name as their
+/// code.
+pub(crate) struct Rule {
+    pub name: String,
+    pub script: Script,
+}
+
+impl Check for Rule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn code(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self, _: &FlakeCheckConfig) -> String {
+        format!("the input must pass the policy's `{}` rule", self.name)
+    }
+
+    fn run(
+        &self,
+        input: &str,
+        repo: &RepoNode,
+        config: &FlakeCheckConfig,
+    ) -> Result<Option<IssueKind>, FlakeCheckerError> {
+        let mut scope = Scope::new();
+        scope.push_constant("input", input.to_string());
+        scope.push_constant("node", node(repo, config.now()));
+        scope.push_constant("policy", policy(config));
+
+        // A rule that fails is reported rather than failing the run, like a networked check
+        let error = |error: String| {
+            Ok(Some(IssueKind::CheckError(CheckError {
+                check: self.name.clone(),
+                error,
+            })))
+        };
+        match engine().eval_ast_with_scope::<Dynamic>(&mut scope, &self.script.0) {
+            Ok(value) if value.is_unit() => Ok(None),
+            Ok(value) if value.is_string() => Ok(Some(IssueKind::Custom(Custom {
+                code: self.name.clone(),
+                message: value.to_string(),
+            }))),
+            Ok(value) => error(format!(
+                "the rule evaluated to a {} rather than a message or ()",
+                value.type_name()
+            )),
+            Err(err) => error(err.to_string()),
+        }
+    }
+}
+
+// An engine that limits what scripts can do, so that a rule can't hang or exhaust the memory of
+// the run.
+pub(crate) fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(1 << 16)
+        .set_max_array_size(1 << 12)
+        .set_max_map_size(1 << 12);
+    engine
+}
+
+fn node(repo: &RepoNode, now: DateTime<Utc>) -> Map {
+    let mut node = Map::new();
+    node.insert("type".into(), repo.original.node_type.clone().into());
+    node.insert("owner".into(), repo.original.owner.clone().into());
+    node.insert("repo".into(), repo.original.repo.clone().into());
+    node.insert("host".into(), repo.host().to_string().into());
+    node.insert("url".into(), repo.url().into());
+    node.insert("flake".into(), repo.is_flake().into());
+    node.insert(
+        "dir".into(),
+        repo.dir()
+            .map(|dir| Dynamic::from(dir.to_string()))
+            .unwrap_or(Dynamic::UNIT),
+    );
+    node.insert(
+        "ref".into(),
+        repo.original
+            .git_ref
+            .clone()
+            .map(Dynamic::from)
+            .unwrap_or(Dynamic::UNIT),
+    );
+    node.insert("rev".into(), repo.locked.rev.clone().into());
+    node.insert("last_modified".into(), repo.locked.last_modified.into());
+    node.insert(
+        "num_days_old".into(),
+        num_days_old(repo.locked.last_modified, now).into(),
+    );
+    node
+}
+
+fn policy(config: &FlakeCheckConfig) -> Map {
+    let strings =
+        |strings: &[String]| -> Array { strings.iter().cloned().map(Dynamic::from).collect() };
+    let mut policy = Map::new();
+    policy.insert("allowed_refs".into(), strings(&config.allowed_refs).into());
+    policy.insert(
+        "allowed_owners".into(),
+        strings(&config.allowed_owners).into(),
+    );
+    policy.insert(
+        "allowed_hosts".into(),
+        strings(&config.allowed_hosts).into(),
+    );
+    policy.insert("max_days".into(), config.max_days.into());
+    policy
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::issue::IssueKind;
+    use crate::policy::Policy;
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_rules() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+  "check_outdated": false,
+  "rules": {
+    "unstable-upstream": "if node.ref == \"nixos-unstable\" && node.owner != \"NixOS\" { `${input} is a fork` }",
+    "stable-only": "if node.ref in policy.allowed_refs && node.ref.contains(\"unstable\") { `${input} tracks ${node.ref}` }",
+    "looping": "loop {}"
+  }
+}"#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let config = policy.apply(&FlakeCheckConfig::default());
+
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let kinds: Vec<&str> = issues.iter().map(|issue| issue.kind.name()).collect();
+        assert_eq!(kinds, ["check-error", "stable-only"]);
+        let IssueKind::Custom(custom) = &issues[1].kind else {
+            panic!("expected a custom issue");
+        };
+        assert_eq!(custom.message, "nixpkgs tracks nixos-unstable");
+
+        assert!(serde_json::from_str::<Policy>(r#"{"rules": {"broken": "if {"}}"#).is_err());
+        let shadowing: Policy = serde_json::from_str(r#"{"rules": {"refs": "()"}}"#).unwrap();
+        assert!(shadowing.validate().is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! Selectors for the inputs that checks run against, as an alternative to naming the Nixpkgs
+//! inputs: by type, owner, repository, or name, optionally only among the root's own inputs, so
+//! that renamed Nixpkgs inputs (and Nixpkgs pulled in by other inputs) are checked too.
+
+use crate::graph::InputGraph;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use parse_flake_lock::{FlakeLock, Node, RepoNode};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+
+/// A description of the inputs to check. An input matches if every field that's set matches, so
+/// a selector without any fields set matches every input.
+///
+/// On the command line, and with [FromStr], a selector is written as comma-separated fields like
+/// `type=github,owner=NixOS,repo=nixpkgs` or `name=^nixpkgs,direct`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Selector {
+    /// The input's locked type, like `github`.
+    #[serde(rename = "type")]
+    pub node_type: Option<String>,
+    /// The input's owner (compared case-insensitively).
+    pub owner: Option<String>,
+    /// The input's repository.
+    pub repo: Option<String>,
+    /// A regular expression that the input's name matches: the root's name for the input for
+    /// direct inputs, and the node's key in the lockfile for others.
+    #[serde(default, deserialize_with = "regex")]
+    pub name: Option<Regex>,
+    /// Only match the root's own inputs, rather than any node in the lockfile.
+    #[serde(default)]
+    pub direct: bool,
+}
+
+impl Selector {
+    /// Whether the selector matches an input with the given name, which is one of the root's own
+    /// inputs if `direct` is set.
+    pub fn matches(&self, name: &str, repo: &RepoNode, direct: bool) -> bool {
+        let matches = |expected: &Option<String>, actual: &str| {
+            expected.as_ref().is_none_or(|expected| expected == actual)
+        };
+        (direct || !self.direct)
+            && matches(&self.node_type, &repo.locked.node_type)
+            && self
+                .owner
+                .as_ref()
+                .is_none_or(|owner| owner.eq_ignore_ascii_case(&repo.original.owner))
+            && matches(&self.repo, &repo.original.repo)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(name))
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        let mut parsed = Self::default();
+        for field in selector.split(',').map(str::trim) {
+            match field.split_once('=') {
+                Some(("type", value)) => parsed.node_type = Some(value.to_string()),
+                Some(("owner", value)) => parsed.owner = Some(value.to_string()),
+                Some(("repo", value)) => parsed.repo = Some(value.to_string()),
+                Some(("name", value)) => {
+                    parsed.name = Some(Regex::new(value).map_err(|err| err.to_string())?)
+                }
+                None if field == "direct" => parsed.direct = true,
+                _ => {
+                    return Err(format!(
+                        "expected type=, owner=, repo=, name=, or direct, got `{field}`"
+                    ))
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = Vec::new();
+        fields.extend(self.node_type.as_ref().map(|value| format!("type={value}")));
+        fields.extend(self.owner.as_ref().map(|value| format!("owner={value}")));
+        fields.extend(self.repo.as_ref().map(|value| format!("repo={value}")));
+        fields.extend(self.name.as_ref().map(|value| format!("name={value}")));
+        if self.direct {
+            fields.push(String::from("direct"));
+        }
+        if fields.is_empty() {
+            write!(f, "any input")
+        } else {
+            write!(f, "{}", fields.join(","))
+        }
+    }
+}
+
+/// Which inputs a check runs against, by whether they're the root's own inputs or inputs of other
+/// inputs.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Only the root's own inputs.
+    Direct,
+    /// Only inputs of other inputs.
+    Transitive,
+    /// Every input.
+    #[default]
+    All,
+}
+
+impl Scope {
+    /// Whether the scope includes an input that is one of the root's own inputs if `direct` is
+    /// set.
+    pub fn includes(self, direct: bool) -> bool {
+        match self {
+            Self::Direct => direct,
+            Self::Transitive => !direct,
+            Self::All => true,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Direct => "direct",
+            Self::Transitive => "transitive",
+            Self::All => "all",
+        }
+    }
+}
+
+/// Parse a `check=scope` check scope from the command line.
+pub(crate) fn parse_check_scope(value: &str) -> Result<(String, Scope), String> {
+    let (check, scope) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected check=scope, got `{value}`"))?;
+    let scope = [Scope::Direct, Scope::Transitive, Scope::All]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == scope)
+        .ok_or_else(|| format!("unknown scope `{scope}` (expected direct, transitive, or all)"))?;
+    Ok((check.to_string(), scope))
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&pattern).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// The repository inputs that any of the selectors match: the root's own inputs by their names,
+/// and other nodes that are reachable from the root by their keys.
+pub(crate) fn select(flake_lock: &FlakeLock, selectors: &[Selector]) -> BTreeMap<String, Node> {
+    let mut selected = BTreeMap::new();
+    let mut matches = |name: &str, node: &Node, direct: bool| {
+        if let Node::Repo(repo) = node {
+            if selectors
+                .iter()
+                .any(|selector| selector.matches(name, repo, direct))
+            {
+                selected.insert(name.to_string(), node.clone());
+            }
+        }
+    };
+
+    for (name, node) in flake_lock.root.iter() {
+        matches(name, node, true);
+    }
+    // Nodes left over in the lockfile aren't used, so they aren't inputs
+    let direct: HashSet<&String> = flake_lock.root_node_names.values().collect();
+    let reachable: HashSet<String> = InputGraph::new(flake_lock)
+        .map(|graph| graph.reachable().into_iter().map(String::from).collect())
+        .unwrap_or_default();
+    for (key, node) in flake_lock.nodes.iter() {
+        if !direct.contains(key) && reachable.contains(key) {
+            matches(key, node, false);
+        }
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::selector::{parse_check_scope, select, Scope, Selector};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_select() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let names = |selectors: &[&str]| -> Vec<String> {
+            let selectors: Vec<Selector> = selectors.iter().map(|s| s.parse().unwrap()).collect();
+            select(&flake_lock, &selectors).into_keys().collect()
+        };
+
+        // nixpkgs_3 isn't reachable from the root
+        assert_eq!(names(&["repo=nixpkgs"]), ["nixpkgs", "nixpkgs_2"]);
+        assert_eq!(names(&["repo=nixpkgs,direct"]), ["nixpkgs"]);
+        assert_eq!(names(&["name=^nixpkgs_[0-9]$,owner=nixos"]), ["nixpkgs_2"]);
+        assert_eq!(names(&["type=github,name=^rust"]), ["rust-overlay"]);
+        assert!(names(&["owner=nobody"]).is_empty());
+
+        assert!("name=(".parse::<Selector>().is_err());
+        assert!("branch=main".parse::<Selector>().is_err());
+        let selector: Selector =
+            serde_json::from_str(r#"{"owner": "NixOS", "direct": true}"#).unwrap();
+        assert_eq!(selector.to_string(), "owner=NixOS,direct");
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut config = FlakeCheckConfig {
+            selectors: vec!["repo=nixpkgs".parse().unwrap()],
+            ..FlakeCheckConfig::default()
+        };
+        let inputs = |config: &FlakeCheckConfig, kind: &str| -> Vec<String> {
+            find_issues(&flake_lock, config)
+                .unwrap()
+                .into_iter()
+                .filter(|issue| issue.kind.name() == kind)
+                .map(|issue| issue.input)
+                .collect()
+        };
+        assert_eq!(inputs(&config, "outdated"), ["nixpkgs", "nixpkgs_2"]);
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        let provenance: Vec<Option<String>> =
+            issues.iter().map(|issue| issue.provenance_path()).collect();
+        assert!(provenance.contains(&Some(String::from("root → rust-overlay → nixpkgs"))));
+        assert!(issues
+            .iter()
+            .filter(|issue| issue.input == "nixpkgs")
+            .all(|issue| issue.provenance.is_none()));
+
+        config.check_scopes = [
+            (String::from("max-age"), Scope::Transitive),
+            (String::from("owner"), Scope::Direct),
+        ]
+        .into();
+        assert_eq!(inputs(&config, "outdated"), ["nixpkgs_2"]);
+        assert_eq!(inputs(&config, "non-upstream"), ["nixpkgs"]);
+
+        assert_eq!(
+            parse_check_scope("refs=direct").unwrap(),
+            (String::from("refs"), Scope::Direct)
+        );
+        assert!(parse_check_scope("refs=indirect").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::{find_issues, FlakeCheckConfig};
+use crate::i18n::Lang;
+use crate::policy::Policy;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use parse_flake_lock::source::SourceMap;
+use parse_flake_lock::FlakeLock;
+use serde::Deserialize;
+use serde_json::json;
+
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+// Shared by the request line and the headers
+const MAX_HEADER_BYTES: u64 = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The body of a `POST /check` request.
+#[derive(Deserialize)]
+struct CheckRequest {
+    /// The `flake.lock` contents, either as a JSON object or as a string.
+    lock: serde_json::Value,
+    /// Per-request overrides of the server's check configuration.
+    #[serde(default)]
+    policy: Policy,
+}
+
+pub(crate) struct Response {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl Response {
+    pub(crate) fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+/// An HTTP/1.1 request, as read by [`read_request`].
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the named header (case-insensitively), if present.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Serve the HTTP API on the given address until the process is terminated, handling each
+/// connection on its own thread.
+pub(crate) fn serve(
+    address: &str,
+    defaults: FlakeCheckConfig,
+    lang: Lang,
+) -> Result<(), FlakeCheckerError> {
+    let listener = TcpListener::bind(address)?;
+    println!("listening on http://{}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let defaults = defaults.clone();
+        std::thread::spawn(move || {
+            // There's nobody to report a failure to write the response to
+            let _ = handle_connection(stream, &defaults, lang);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    defaults: &FlakeCheckConfig,
+    lang: Lang,
+) -> Result<(), FlakeCheckerError> {
+    let response = match read_request(&stream) {
+        Ok(request) => route(
+            &request.method,
+            &request.path,
+            &request.body,
+            defaults,
+            lang,
+        ),
+        Err(response) => response,
+    };
+
+    write_response(&mut stream, &response)
+}
+
+/// Read an HTTP/1.1 request from the stream, or the error response to send if it's malformed.
+pub(crate) fn read_request(stream: &TcpStream) -> Result<Request, Response> {
+    let bad_request = |message: &str| Response::error(400, message);
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|_| bad_request("couldn't configure connection"))?;
+    let mut reader = BufReader::new(stream);
+
+    // Reads a line without going past what's left of MAX_HEADER_BYTES
+    let mut remaining = MAX_HEADER_BYTES;
+    let mut read_line = |line: &mut String, error: &str| {
+        let read = (&mut reader)
+            .take(remaining)
+            .read_line(line)
+            .map_err(|_| bad_request(error))?;
+        remaining -= read as u64;
+        if remaining == 0 && !line.ends_with('\n') {
+            return Err(Response::error(431, "request headers too large"));
+        }
+        Ok(())
+    };
+
+    let mut request_line = String::new();
+    read_line(&mut request_line, "couldn't read request")?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(bad_request("malformed request line"));
+    };
+
+    let mut headers = Vec::new();
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        read_line(&mut header, "couldn't read headers")?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .parse()
+                    .map_err(|_| bad_request("invalid Content-Length"))?;
+            }
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("couldn't read body"))?;
+
+    // Ignore any query string
+    let path = path.split('?').next().unwrap_or_default();
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body,
+    })
+}
+
+/// Write a JSON response and close the connection.
+pub(crate) fn write_response(
+    stream: &mut TcpStream,
+    response: &Response,
+) -> Result<(), FlakeCheckerError> {
+    let body = serde_json::to_string(&response.body)?;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+fn route(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    defaults: &FlakeCheckConfig,
+    lang: Lang,
+) -> Response {
+    match (method, path) {
+        ("GET", "/health") => Response {
+            status: 200,
+            body: json!({ "status": "ok" }),
+        },
+        ("POST", "/check") => match check(body, defaults, lang) {
+            Ok(body) => Response { status: 200, body },
+            Err(err) => Response::error(422, err),
+        },
+        (_, "/check" | "/health") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+fn check(
+    body: &[u8],
+    defaults: &FlakeCheckConfig,
+    lang: Lang,
+) -> Result<serde_json::Value, FlakeCheckerError> {
+    let request: CheckRequest = serde_json::from_slice(body)?;
+    request
+        .policy
+        .validate()
+        .map_err(FlakeCheckerError::Invalid)?;
+    // Requests can't make the server run executables
+    if request.policy.plugins.is_some() {
+        return Err(FlakeCheckerError::Invalid(String::from(
+            "plugins can't be set by requests",
+        )));
+    }
+    let config = request.policy.apply(defaults);
+
+    // Only locks sent as strings have lines and columns that mean something to the client
+    let (flake_lock, source_map): (FlakeLock, _) = match request.lock {
+        serde_json::Value::String(source) => (source.parse()?, SourceMap::new(&source).ok()),
+        lock => (serde_json::to_string(&lock)?.parse()?, None),
+    };
+
+    let mut issues = find_issues(&flake_lock, &config)?;
+    if let Some(source_map) = &source_map {
+        for issue in issues.iter_mut() {
+            issue.locate(Path::new("flake.lock"), &flake_lock, source_map);
+        }
+    }
+    let budget_violations = match config.budget.has_limits() {
+        true => config.budget.violations(&issues),
+        false => vec![],
+    };
+    let issues: Vec<serde_json::Value> = issues
+        .iter()
+        .map(|issue| {
+            json!({
+                "input": issue.input,
+                "kind": issue.kind.name(),
+                "severity": issue.severity,
+                "details": issue.kind,
+                "message": issue.message(lang),
+                "location": issue.location,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "severity": config.severity(),
+        "issues": issues,
+        "budget_violations": budget_violations,
+    }))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::flake::FlakeCheckConfig;
+    use crate::i18n::Lang;
+    use crate::serve::{read_request, route};
+
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_check_route() {
+        let lock = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let body = serde_json::json!({ "lock": lock, "policy": { "check_outdated": false } });
+        let body = serde_json::to_vec(&body).unwrap();
+
+        let response = route(
+            "POST",
+            "/check",
+            &body,
+            &FlakeCheckConfig::default(),
+            Lang::En,
+        );
+
+        assert_eq!(response.status, 200);
+        let kinds: Vec<&str> = response.body["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|issue| issue["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, ["disallowed", "non-upstream"]);
+
+        assert_eq!(
+            route("GET", "/nope", &[], &FlakeCheckConfig::default(), Lang::En).status,
+            404
+        );
+    }
+
+    #[test]
+    fn test_read_request_header_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let read = |request: String| {
+            let mut client = TcpStream::connect(address).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            read_request(&stream)
+        };
+
+        let request = read(String::from(
+            "POST /check HTTP/1.1\r\nContent-Length: 2\r\nX-Test: yes\r\n\r\n{}",
+        ))
+        .ok()
+        .unwrap();
+        assert_eq!(request.header("x-test"), Some("yes"));
+        assert_eq!(request.body, b"{}");
+
+        let padding = "a".repeat(100);
+        let headers: String = (0..100)
+            .map(|i| format!("X-Padding-{i}: {padding}\r\n"))
+            .collect();
+        let response = read(format!("GET /health HTTP/1.1\r\n{headers}\r\n")).err();
+        assert_eq!(response.map(|response| response.status), Some(431));
+
+        let response = read(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000))).err();
+        assert_eq!(response.map(|response| response.status), Some(431));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::issue::Issue;
+
+use parse_flake_lock::source::{escape_pointer_token, SourceMap};
+use parse_flake_lock::FlakeLock;
+
+/// An excerpt of the `flake.lock` source showing the block responsible for an issue, with the
+/// problematic field highlighted.
+#[derive(Clone, Debug)]
+pub(crate) struct Snippet {
+    /// The 1-based number of the first line in the excerpt.
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    /// The 1-based number of the highlighted line.
+    pub highlight: usize,
+}
+
+impl Snippet {
+    pub(crate) fn for_issue(
+        issue: &Issue,
+        flake_lock: &FlakeLock,
+        source: &str,
+        source_map: &SourceMap,
+    ) -> Option<Self> {
+        let node_name = match issue.provenance {
+            Some(_) => flake_lock.nodes.get_key_value(&issue.input)?.0,
+            None => flake_lock.root_node_names.get(&issue.input)?,
+        };
+        let (section, field) = issue.kind.field();
+
+        let block = format!("/nodes/{}/{section}", escape_pointer_token(node_name));
+        let lines = source_map.lines(&block)?;
+        let (highlight, _) =
+            source_map.line_col(source_map.span(&format!("{block}/{field}"))?.start);
+
+        Some(Self {
+            start_line: lines.start,
+            lines: source
+                .lines()
+                .skip(lines.start - 1)
+                .take(lines.end - lines.start)
+                .map(String::from)
+                .collect(),
+            highlight,
+        })
+    }
+
+    /// Render with line numbers and a `>` marker on the highlighted line, for terminal output.
+    pub(crate) fn to_text(&self) -> String {
+        let width = (self.start_line + self.lines.len()).to_string().len();
+        self.numbered_lines()
+            .map(|(number, line)| {
+                let marker = if number == self.highlight { '>' } else { ' ' };
+                format!("{marker} {number:>width$} | {line}")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Render as a fenced `diff` code block in which the highlighted line is marked as removed
+    /// (and thus rendered in red).
+    pub(crate) fn to_markdown(&self) -> String {
+        let mut markdown = String::from("```diff\n");
+        for (number, line) in self.numbered_lines() {
+            let marker = if number == self.highlight { '-' } else { ' ' };
+            markdown.push_str(&format!("{marker} {line}\n"));
+        }
+        markdown.push_str("```");
+        markdown
+    }
+
+    fn numbered_lines(&self) -> impl Iterator<Item = (usize, &String)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (self.start_line + i, line))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use parse_flake_lock::source::SourceMap;
+    use parse_flake_lock::FlakeLock;
+
+    use crate::issue::{Disallowed, Issue, IssueKind, Severity};
+    use crate::snippet::Snippet;
+
+    #[test]
+    fn test_snippet_highlights_field() {
+        let path = PathBuf::from("tests/flake.dirty.0.lock");
+        let source = std::fs::read_to_string(&path).expect("couldn't read flake.lock");
+        let source_map = SourceMap::new(&source).expect("couldn't map flake.lock source");
+        let flake_lock = FlakeLock::new(&path).expect("couldn't create flake.lock");
+
+        let issue = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("this-should-fail"),
+            }),
+            location: None,
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+
+        let snippet = Snippet::for_issue(&issue, &flake_lock, &source, &source_map)
+            .expect("couldn't create snippet");
+
+        assert_eq!(snippet.start_line, 30);
+        assert_eq!(snippet.highlight, 32);
+        assert_eq!(snippet.lines.len(), 6);
+        assert!(snippet.lines[2].contains("\"ref\": \"this-should-fail\""));
+    }
+}
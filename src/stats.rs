@@ -0,0 +1,204 @@
+use crate::error::FlakeCheckerError;
+use crate::flake::InputDetails;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use chrono::Utc;
+use parse_flake_lock::FlakeLock;
+use serde::Serialize;
+
+/// Output formats for `flake-checker stats`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub(crate) enum StatsFormat {
+    /// An aligned, human-readable table.
+    Table,
+    /// A JSON object.
+    Json,
+}
+
+/// The composition of a lockfile, counting every locked node (not just the checked inputs).
+#[derive(Debug, Serialize)]
+pub(crate) struct LockStats {
+    pub nodes: usize,
+    /// The number of nodes of each type (like `github` or `path`).
+    pub nodes_by_type: BTreeMap<String, usize>,
+    /// The distinct owners of the nodes that have one, in name order.
+    pub owners: Vec<String>,
+    /// The nodes that the root flake depends on directly.
+    pub direct_inputs: usize,
+    /// The nodes that are only dependencies of other inputs.
+    pub transitive_inputs: usize,
+    /// The number of distinct revisions of Nixpkgs that are locked.
+    pub nixpkgs_revisions: usize,
+    /// The distribution of the nodes' ages, if any node records when it was last modified.
+    pub age_days: Option<AgePercentiles>,
+}
+
+/// Percentiles of node ages, in days.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct AgePercentiles {
+    pub min: i64,
+    pub p50: i64,
+    pub p75: i64,
+    pub p90: i64,
+    pub max: i64,
+}
+
+impl AgePercentiles {
+    fn new(mut ages: Vec<i64>) -> Option<Self> {
+        ages.sort_unstable();
+        // The nearest-rank percentile
+        let percentile = |p: usize| ages[(p * ages.len()).div_ceil(100).max(1) - 1];
+        Some(Self {
+            min: *ages.first()?,
+            p50: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max: *ages.last()?,
+        })
+    }
+}
+
+pub(crate) fn stats(flake_lock: &FlakeLock) -> LockStats {
+    let direct: HashSet<&String> = flake_lock.root_node_names.values().collect();
+    let nodes: Vec<(&String, InputDetails)> = flake_lock
+        .nodes
+        .iter()
+        .filter_map(|(key, node)| {
+            InputDetails::new(key, node, Utc::now()).map(|details| (key, details))
+        })
+        .collect();
+
+    let mut nodes_by_type = BTreeMap::new();
+    for (_, details) in nodes.iter() {
+        let node_type = match details.node_type.as_str() {
+            "" => "unknown",
+            node_type => node_type,
+        };
+        *nodes_by_type.entry(node_type.to_string()).or_insert(0) += 1;
+    }
+
+    let owners: BTreeSet<&String> = nodes
+        .iter()
+        .filter_map(|(_, details)| details.owner.as_ref())
+        .collect();
+    let nixpkgs_revisions: HashSet<&String> = nodes
+        .iter()
+        .filter(|(_, details)| details.repo.as_deref() == Some("nixpkgs"))
+        .filter_map(|(_, details)| details.rev.as_ref())
+        .collect();
+    let direct_inputs = nodes.iter().filter(|(key, _)| direct.contains(key)).count();
+
+    LockStats {
+        nodes: nodes.len(),
+        nodes_by_type,
+        owners: owners.into_iter().cloned().collect(),
+        direct_inputs,
+        transitive_inputs: nodes.len() - direct_inputs,
+        nixpkgs_revisions: nixpkgs_revisions.len(),
+        age_days: AgePercentiles::new(
+            nodes
+                .iter()
+                .filter_map(|(_, details)| details.num_days_old)
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) fn generate(
+    stats: &LockStats,
+    format: StatsFormat,
+) -> Result<String, FlakeCheckerError> {
+    Ok(match format {
+        StatsFormat::Table => table(stats),
+        StatsFormat::Json => serde_json::to_string_pretty(stats)? + "\n",
+    })
+}
+
+fn table(stats: &LockStats) -> String {
+    let mut rows = vec![(String::from("nodes"), stats.nodes.to_string())];
+    rows.extend(
+        stats
+            .nodes_by_type
+            .iter()
+            .map(|(node_type, count)| (format!("  {node_type}"), count.to_string())),
+    );
+    rows.push((
+        String::from("direct inputs"),
+        stats.direct_inputs.to_string(),
+    ));
+    rows.push((
+        String::from("transitive inputs"),
+        stats.transitive_inputs.to_string(),
+    ));
+    rows.push((
+        String::from("owners"),
+        format!("{} ({})", stats.owners.len(), stats.owners.join(", ")),
+    ));
+    rows.push((
+        String::from("nixpkgs revisions"),
+        stats.nixpkgs_revisions.to_string(),
+    ));
+    if let Some(age) = &stats.age_days {
+        rows.push((
+            String::from("age (days)"),
+            format!(
+                "min {}, p50 {}, p75 {}, p90 {}, max {}",
+                age.min, age.p50, age.p75, age.p90, age.max
+            ),
+        ));
+    }
+
+    let width = rows
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or_default();
+    rows.iter()
+        .map(|(label, value)| format!("{label:<width$}  {value}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::{stats, AgePercentiles};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_stats() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let stats = stats(&flake_lock);
+        assert_eq!(stats.nodes, 6);
+        assert_eq!(stats.nodes_by_type.get("github"), Some(&6));
+        assert_eq!(stats.direct_inputs, 2);
+        assert_eq!(stats.transitive_inputs, 4);
+        assert_eq!(
+            stats.owners,
+            [
+                "NixOS",
+                "bitcoin-miner-org",
+                "nix-systems",
+                "numtide",
+                "oxalica"
+            ]
+        );
+        assert_eq!(stats.nixpkgs_revisions, 2);
+
+        assert_eq!(
+            AgePercentiles::new((1..=10).collect()),
+            Some(AgePercentiles {
+                min: 1,
+                p50: 5,
+                p75: 8,
+                p90: 9,
+                max: 10,
+            })
+        );
+        assert_eq!(AgePercentiles::new(Vec::new()), None);
+    }
+}
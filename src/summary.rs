@@ -1,39 +1,218 @@
+use crate::checker::{CheckStats, SkipReason};
+use crate::compare::Comparison;
 use crate::error::FlakeCheckerError;
-use crate::flake::{ALLOWED_REFS, MAX_DAYS};
-use crate::issue::{Issue, IssueKind};
+use crate::exemption::Exemption;
+use crate::fix::Fix;
+use crate::flake::{input_details, iso8601, InputDetails};
+use crate::i18n::Lang;
+use crate::issue::{Issue, IssueKind, Severity};
+use crate::policy;
+use crate::snippet::Snippet;
 use crate::FlakeCheckConfig;
 
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use chrono::Utc;
 use handlebars::Handlebars;
+use parse_flake_lock::source::SourceMap;
+use parse_flake_lock::FlakeLock;
 use serde_json::json;
 
+/// Machine-readable output formats selectable via `--format`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// A [shields.io endpoint](https://shields.io/badges/endpoint-badge) JSON document.
+    Badge,
+    /// A standalone HTML report page.
+    Html,
+    /// Comma-separated values with one row per input.
+    Csv,
+    /// Metrics in the Prometheus textfile collector format.
+    Prometheus,
+    /// One JSON object per issue per line, streamed as issues are found.
+    Jsonl,
+}
+
 pub(crate) struct Summary {
     pub issues: Vec<Issue>,
+    pub inputs: Vec<InputDetails>,
+    pub stats: CheckStats,
+    pub flake_lock_path: PathBuf,
+    pub flake_check_config: FlakeCheckConfig,
+    pub lang: Lang,
     data: serde_json::Value,
-    flake_lock_path: PathBuf,
-    flake_check_config: FlakeCheckConfig,
 }
 
 impl Summary {
     pub(crate) fn new(
         issues: &Vec<Issue>,
+        flake_lock: &FlakeLock,
+        source: &str,
         flake_lock_path: PathBuf,
         flake_check_config: FlakeCheckConfig,
+        lang: Lang,
+        max_issues: Option<usize>,
     ) -> Self {
-        let disallowed: Vec<&Issue> = issues.iter().filter(|i| i.kind.is_disallowed()).collect();
-        let outdated: Vec<&Issue> = issues.iter().filter(|i| i.kind.is_outdated()).collect();
-        let non_upstream: Vec<&Issue> =
-            issues.iter().filter(|i| i.kind.is_non_upstream()).collect();
+        // The source map is only used for snippets, so it's fine if it can't be built
+        let source_map = SourceMap::new(source).ok();
+        let with_snippet = |issue: &Issue| {
+            let snippet = source_map
+                .as_ref()
+                .and_then(|source_map| Snippet::for_issue(issue, flake_lock, source, source_map));
+            let age = match &issue.kind {
+                IssueKind::Outdated(outdated) => Some(lang.humanize_days(outdated.num_days_old)),
+                _ => None,
+            };
+            json!({
+                "input": issue.input,
+                "kind": issue.kind,
+                "severity": issue.severity,
+                "grace": flake_check_config
+                    .escalation(issue)
+                    .map(|escalation| escalation.message(lang)),
+                "age": age,
+                "location": issue.location,
+                "provenance": issue.provenance_path(),
+                "snippet": snippet.as_ref().map(Snippet::to_text),
+                "snippet_md": snippet.as_ref().map(Snippet::to_markdown),
+            })
+        };
+
+        // Only the first `max_issues` issues are rendered in summaries
+        let shown: Vec<&Issue> = issues
+            .iter()
+            .take(max_issues.unwrap_or(usize::MAX))
+            .collect();
+        let num_hidden = issues.len() - shown.len();
+
+        let disallowed: Vec<serde_json::Value> = shown
+            .iter()
+            .copied()
+            .filter(|i| i.kind.is_disallowed())
+            .map(with_snippet)
+            .collect();
+        let outdated: Vec<serde_json::Value> = shown
+            .iter()
+            .copied()
+            .filter(|i| i.kind.is_outdated())
+            .map(with_snippet)
+            .collect();
+        let non_upstream: Vec<serde_json::Value> = shown
+            .iter()
+            .copied()
+            .filter(|i| i.kind.is_non_upstream())
+            .map(with_snippet)
+            .collect();
+
+        let check_errors: Vec<serde_json::Value> = shown
+            .iter()
+            .copied()
+            .filter(|i| i.kind.is_check_error())
+            .map(|i| json!({ "input": i.input, "message": i.message(lang) }))
+            .collect();
+        let custom: Vec<serde_json::Value> = shown
+            .iter()
+            .copied()
+            .filter(|i| i.kind.is_custom())
+            .map(|i| json!({ "input": i.input, "provenance": i.provenance_path(), "message": i.message(lang) }))
+            .collect();
+        let missing = shown
+            .iter()
+            .find(|i| i.kind.is_missing())
+            .map(|i| i.message(lang));
+        let non_canonical = shown
+            .iter()
+            .find(|i| i.kind.is_non_canonical())
+            .map(|i| i.message(lang));
+        let unknown_fields: Vec<String> = shown
+            .iter()
+            .filter(|i| i.kind.is_unknown_fields())
+            .map(|i| i.message(lang))
+            .collect();
+
+        let inputs = input_details(flake_lock, flake_check_config.now());
+        let input_rows: Vec<serde_json::Value> = inputs
+            .iter()
+            .map(|input| {
+                let input_issues: Vec<serde_json::Value> = issues
+                    .iter()
+                    .filter(|i| i.input == input.name)
+                    .map(|i| json!({ "kind": i.kind.name(), "message": i.message(lang) }))
+                    .collect();
+                json!({
+                    "details": input,
+                    "issues": input_issues,
+                    "clean": input_issues.is_empty(),
+                })
+            })
+            .collect();
+        let issue_rows: Vec<serde_json::Value> = shown
+            .iter()
+            .map(|i| {
+                json!({
+                    "input": i.input,
+                    "kind": i.kind.name(),
+                    "severity": i.severity,
+                    "message": i.message(lang),
+                    "provenance": i.provenance_path(),
+                })
+            })
+            .collect();
+
+        let stats = CheckStats::new(flake_lock, &flake_check_config, issues);
+        let issues_by_kind: Vec<serde_json::Value> = stats
+            .issues_by_kind
+            .iter()
+            .map(|(kind, count)| json!({ "kind": kind, "count": count }))
+            .collect();
+        let skipped: Vec<serde_json::Value> = stats
+            .skipped
+            .iter()
+            .map(|skipped| {
+                json!({
+                    "check": skipped.check,
+                    "disabled": skipped.reason == SkipReason::Disabled,
+                })
+            })
+            .collect();
+
+        let num_failed_inputs = input_rows
+            .iter()
+            .filter(|row| row["clean"] == json!(false))
+            .count();
 
         let data = json!({
+            // Run metadata
+            "version": env!("CARGO_PKG_VERSION"),
+            "run_timestamp": iso8601(Utc::now().timestamp()),
+            "policy": policy::describe(&flake_check_config),
+            "num_inputs": input_rows.len(),
+            "num_passed_inputs": input_rows.len() - num_failed_inputs,
+            "num_failed_inputs": num_failed_inputs,
             "issues": issues,
+            "issue_rows": issue_rows,
+            "inputs": input_rows,
+            "flake_lock_path": flake_lock_path.to_string_lossy(),
             "num_issues": issues.len(),
             "clean": issues.is_empty(),
             "dirty": !issues.is_empty(),
-            "issue_word": if issues.len() == 1 { "issue" } else { "issues" },
+            "issue_word": lang.tr(if issues.len() == 1 { "issue" } else { "issues" }, &[]),
+            // Issue budget
+            "budget": flake_check_config.budget.usage(issues),
+            // Totals
+            "totals": {
+                "num_inputs_checked": stats.inputs_checked.len(),
+                "inputs_checked": stats.inputs_checked.join(", "),
+                "errors": stats.errors,
+                "warnings": stats.warnings,
+                "issues_by_kind": issues_by_kind,
+                "skipped": skipped,
+            },
+            // Truncation
+            "truncated": num_hidden > 0,
+            "num_hidden": num_hidden,
             // Disallowed refs
             "has_disallowed": !disallowed.is_empty(),
             "disallowed": disallowed,
@@ -43,66 +222,242 @@ impl Summary {
             // Non-upstream refs
             "has_non_upstream": !non_upstream.is_empty(),
             "non_upstream": non_upstream,
+            // Checks that couldn't be completed
+            "has_check_errors": !check_errors.is_empty(),
+            "check_errors": check_errors,
+            // Issues reported by custom checks
+            "has_custom": !custom.is_empty(),
+            "custom": custom,
+            // Missing lockfiles
+            "missing": missing,
+            // Non-canonical formatting
+            "non_canonical": non_canonical,
+            // Unrecognized fields
+            "has_unknown_fields": !unknown_fields.is_empty(),
+            "unknown_fields": unknown_fields,
             // Constants
-            "max_days": MAX_DAYS,
-            "supported_ref_names": ALLOWED_REFS,
+            "max_days": flake_check_config.max_days,
+            "supported_ref_names": flake_check_config.allowed_refs,
         });
 
         Self {
             issues: issues.to_vec(),
+            inputs,
+            stats,
             data,
             flake_lock_path,
             flake_check_config,
+            lang,
         }
     }
 
+    /// The severity of the most severe issue found in this run.
+    pub fn severity(&self) -> Severity {
+        self.issues
+            .iter()
+            .map(|issue| issue.severity)
+            .max()
+            .unwrap_or(Severity::Warning)
+    }
+
+    /// Report issues from the baseline as known, separately from the summary's (new) issues.
+    pub(crate) fn set_known_issues(&mut self, known_issues: &[Issue]) {
+        let messages: Vec<String> = known_issues.iter().map(|i| i.message(self.lang)).collect();
+        self.data["num_known"] = json!(messages.len());
+        self.data["known_issues"] = json!(messages);
+    }
+
+    /// Report issues that flake.nix exempts, along with the reasons and expiry dates given for them.
+    pub(crate) fn set_exempted(&mut self, exempted: &[(&Issue, &Exemption)]) {
+        let exempted: Vec<serde_json::Value> = exempted
+            .iter()
+            .map(|(issue, exemption)| {
+                json!({
+                    "message": issue.message(self.lang),
+                    "reason": exemption.reason,
+                    "until": exemption.until.map(|until| until.to_string()),
+                })
+            })
+            .collect();
+        self.data["exempted"] = json!(exempted);
+    }
+
+    /// Report the age issues of inputs that were added within the grace period for new inputs.
+    pub(crate) fn set_in_grace(&mut self, in_grace: &[Issue], days: i64) {
+        let messages: Vec<String> = in_grace.iter().map(|i| i.message(self.lang)).collect();
+        self.data["in_grace"] = json!(messages);
+        self.data["grace_days"] = json!(days);
+    }
+
+    /// Highlight the issues that are new since a previous run and the previous run's issues that
+    /// have since been fixed.
+    pub(crate) fn set_comparison(&mut self, comparison: &Comparison) {
+        let regressions: Vec<String> = comparison
+            .regressions
+            .iter()
+            .map(|i| i.message(self.lang))
+            .collect();
+        let fixes: Vec<&str> = comparison
+            .fixes
+            .iter()
+            .map(|fix| fix.message.as_str())
+            .collect();
+        self.data["comparison"] = json!({
+            "regressions": regressions,
+            "fixes": fixes,
+            "unchanged": regressions.is_empty() && fixes.is_empty(),
+        });
+    }
+
+    /// Suggest the command that would resolve each issue that has one.
+    pub(crate) fn set_suggestions(&mut self, suggestions: &[(&Issue, Fix)]) {
+        let suggestions: Vec<serde_json::Value> = suggestions
+            .iter()
+            .map(|(issue, fix)| {
+                json!({
+                    "message": issue.message(self.lang),
+                    "command": fix.to_string(),
+                })
+            })
+            .collect();
+        self.data["suggestions"] = json!(suggestions);
+    }
+
+    /// Record the policy file that governed this lockfile's checks.
+    pub(crate) fn set_policy_path(&mut self, policy_path: &Path) {
+        self.data["policy_path"] = json!(policy_path.to_string_lossy());
+    }
+
+    /// Show one entry per input with all of its findings, instead of a section per kind of issue.
+    /// Findings with the same message (like a rule that repeats a built-in check) are shown once.
+    pub(crate) fn group_by_input(&mut self) {
+        let num_shown = self.issues.len() - self.data["num_hidden"].as_u64().unwrap_or(0) as usize;
+        let mut groups: Vec<(&Issue, Vec<&Issue>)> = Vec::new();
+        for issue in self.issues.iter().take(num_shown) {
+            // Issues with the whole lockfile keep their own sections
+            if issue.input.is_empty() {
+                continue;
+            }
+            match groups
+                .iter_mut()
+                .find(|(first, _)| first.input == issue.input)
+            {
+                Some((_, findings)) => findings.push(issue),
+                None => groups.push((issue, vec![issue])),
+            }
+        }
+
+        let groups: Vec<serde_json::Value> = groups
+            .into_iter()
+            .map(|(first, mut findings)| {
+                findings.sort_by_key(|issue| std::cmp::Reverse(issue.severity));
+                let mut messages: Vec<String> = Vec::new();
+                let findings: Vec<serde_json::Value> = findings
+                    .into_iter()
+                    .filter_map(|issue| {
+                        let message = issue.message(self.lang);
+                        if messages.contains(&message) {
+                            return None;
+                        }
+                        messages.push(message.clone());
+                        Some(json!({
+                            "kind": issue.kind.name(),
+                            "severity": issue.severity,
+                            "message": message,
+                        }))
+                    })
+                    .collect();
+                let kinds: Vec<&str> = findings
+                    .iter()
+                    .filter_map(|finding| finding["kind"].as_str())
+                    .collect();
+                json!({
+                    "input": first.input,
+                    "provenance": first.provenance_path(),
+                    "severity": findings.first().map(|finding| finding["severity"].clone()),
+                    "kind": kinds.join(", "),
+                    "message": messages.join("; "),
+                    "findings": findings,
+                })
+            })
+            .collect();
+
+        for section in [
+            "has_disallowed",
+            "has_outdated",
+            "has_non_upstream",
+            "has_check_errors",
+            "has_custom",
+        ] {
+            self.data[section] = json!(false);
+        }
+        self.data["issue_rows"] = json!(groups);
+        self.data["grouped"] = json!(groups);
+    }
+
+    /// List the `top` oldest inputs with their ages and where they come from, along with a
+    /// histogram of all of the inputs' ages, whether or not they exceed the maximum age.
+    pub(crate) fn set_oldest_inputs(&mut self, top: usize) {
+        let mut inputs: Vec<&InputDetails> = self
+            .inputs
+            .iter()
+            .filter(|input| input.num_days_old.is_some())
+            .collect();
+        inputs.sort_by_key(|input| std::cmp::Reverse(input.num_days_old));
+
+        let oldest: Vec<serde_json::Value> = inputs
+            .iter()
+            .take(top)
+            .map(|input| {
+                let num_days_old = input.num_days_old.unwrap_or_default();
+                json!({
+                    "input": input.name,
+                    "num_days_old": num_days_old,
+                    "age": self.lang.humanize_days(num_days_old),
+                    "locked_at": input.locked_at,
+                    "source": source(input),
+                })
+            })
+            .collect();
+
+        let ages: Vec<i64> = inputs
+            .iter()
+            .filter_map(|input| input.num_days_old)
+            .collect();
+
+        self.data["oldest_inputs"] = json!(oldest);
+        self.data["age_histogram"] = json!(age_histogram(&ages));
+    }
+
+    /// The policy that the lockfile was checked against, as rendered in reports.
+    pub(crate) fn policy(&self) -> &serde_json::Value {
+        &self.data["policy"]
+    }
+
+    // Whether to show the lockfile path in the summary (when reporting on several lockfiles).
+    fn show_path(&mut self, show_path: bool) {
+        self.data["show_path"] = json!(show_path);
+    }
+
     pub fn console_log_errors(&self) -> Result<(), FlakeCheckerError> {
         let file = self.flake_lock_path.to_string_lossy();
 
         if self.issues.is_empty() {
-            println!("The Determinate Nix Flake Checker scanned {file} and found no issues");
+            println!("{}", self.lang.tr("no-issues", &[("file", &file)]));
         } else {
-            let level = if self.flake_check_config.fail_mode {
-                "error"
-            } else {
-                "warning"
-            };
-
             for issue in self.issues.iter() {
-                let input = &issue.input;
-
-                let message: Option<String> = match &issue.kind {
-                    IssueKind::Disallowed(disallowed) => {
-                        if self.flake_check_config.check_supported {
-                            let reference = &disallowed.reference;
-                            Some(format!(
-                                "the `{input}` input uses the non-supported Git branch `{reference}` for Nixpkgs"
-                             ))
-                        } else {
-                            None
-                        }
-                    }
-                    IssueKind::Outdated(outdated) => {
-                        if self.flake_check_config.check_outdated {
-                            let num_days_old = outdated.num_days_old;
-                            Some(format!(
-                                "the `{input}` input is {num_days_old} days old (the max allowed is {MAX_DAYS})"
-                            ))
-                        } else {
-                            None
-                        }
-                    }
-                    IssueKind::NonUpstream(non_upstream) => {
-                        if self.flake_check_config.check_owner {
-                            let owner = &non_upstream.owner;
-                            Some(format!(
-                                "the `{input}` input has the non-upstream owner `{owner}` rather than `NixOS` (upstream)"
-                            ))
-                        } else {
-                            None
-                        }
-                    }
+                let level = issue.severity.as_str();
+                let enabled = match &issue.kind {
+                    IssueKind::Disallowed(_) => self.flake_check_config.check_supported,
+                    IssueKind::Outdated(_) => self.flake_check_config.check_outdated,
+                    IssueKind::NonUpstream(_) => self.flake_check_config.check_owner,
+                    IssueKind::CheckError(_) => true,
+                    IssueKind::NonCanonical(_) => self.flake_check_config.check_format,
+                    IssueKind::Missing(_) => true,
+                    IssueKind::UnknownFields(_) => self.flake_check_config.strict_fields,
+                    IssueKind::Custom(_) => true,
                 };
+                let message = enabled.then(|| issue.message(self.lang));
 
                 if let Some(message) = message {
                     println!("{}: {}", level.to_uppercase(), message);
@@ -112,15 +467,402 @@ impl Summary {
         Ok(())
     }
 
-    pub fn generate_markdown(&self) -> Result<(), FlakeCheckerError> {
+    fn azure_log_issues(&self) {
+        let sourcepath = azure_property(&self.flake_lock_path.to_string_lossy());
+
+        for issue in self.issues.iter() {
+            let level = issue.severity.as_str();
+            let position = match &issue.location {
+                Some(location) => format!(
+                    "linenumber={};columnnumber={};",
+                    location.line, location.column
+                ),
+                None => String::new(),
+            };
+            println!(
+                "##vso[task.logissue type={level};sourcepath={sourcepath};{position}code={}]{}",
+                issue.kind.name(),
+                azure_message(&issue.message(self.lang)),
+            );
+        }
+    }
+
+    fn csv_rows(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .map(|input| {
+                let issues: Vec<&str> = self
+                    .issues
+                    .iter()
+                    .filter(|i| i.input == input.name)
+                    .map(|i| i.kind.name())
+                    .collect();
+
+                let row = [
+                    self.flake_lock_path.to_string_lossy().to_string(),
+                    input.name.clone(),
+                    input.node_type.clone(),
+                    input.owner.clone().unwrap_or_default(),
+                    input.repo.clone().unwrap_or_default(),
+                    input.git_ref.clone().unwrap_or_default(),
+                    input.rev.clone().unwrap_or_default(),
+                    input
+                        .num_days_old
+                        .map(|days| days.to_string())
+                        .unwrap_or_default(),
+                    issues.join(";"),
+                ];
+
+                let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                row.join(",")
+            })
+            .chain([self.csv_totals_row()])
+            .collect()
+    }
+
+    // The lockfile's totals, with `totals` as the type and the counts in the issues column, like
+    // `inputs_checked=1;errors=0;warnings=2;skipped=owner:disabled`.
+    fn csv_totals_row(&self) -> String {
+        let mut totals = vec![
+            format!("inputs_checked={}", self.stats.inputs_checked.len()),
+            format!("errors={}", self.stats.errors),
+            format!("warnings={}", self.stats.warnings),
+        ];
+        for skipped in self.stats.skipped.iter() {
+            totals.push(format!(
+                "skipped={}:{}",
+                skipped.check,
+                skipped.reason.as_str()
+            ));
+        }
+        let path = self.flake_lock_path.to_string_lossy();
+        let row = [&*path, "", "totals", "", "", "", "", "", &totals.join(";")];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        row.join(",")
+    }
+
+    /// The lockfile's totals as a line of the `jsonl` output, which is told apart from the issues
+    /// by its `record` of `totals`.
+    pub(crate) fn totals_json_line(&self) -> Result<String, FlakeCheckerError> {
+        let line = json!({
+            "schema_version": SCHEMA_VERSION,
+            "record": "totals",
+            "path": self.flake_lock_path.to_string_lossy(),
+            "inputs_checked": self.stats.inputs_checked,
+            "issues_by_kind": self.stats.issues_by_kind,
+            "errors": self.stats.errors,
+            "warnings": self.stats.warnings,
+            "skipped": self.stats.skipped,
+        });
+        Ok(serde_json::to_string(&line)?)
+    }
+
+    pub(crate) fn markdown(&self) -> Result<String, FlakeCheckerError> {
+        let mut handlebars = Handlebars::new();
+
+        handlebars
+            .register_template_string("summary.md", self.lang.summary_md_template())
+            .map_err(Box::new)?;
+        self.flake_check_config
+            .template_dir
+            .register(&mut handlebars)?;
+        Ok(handlebars.render("summary.md", &self.data)?)
+    }
+
+    fn text(&self) -> Result<String, FlakeCheckerError> {
         let mut handlebars = Handlebars::new();
+        handlebars
+            .register_template_string("summary.txt", self.lang.summary_txt_template())
+            .map_err(Box::new)?;
+        self.flake_check_config
+            .template_dir
+            .register(&mut handlebars)?;
+
+        Ok(handlebars.render("summary.txt", &self.data)?)
+    }
+}
+
+/// The results of checking one or more lockfiles, with a [Summary] per lockfile.
+pub(crate) struct Report {
+    pub summaries: Vec<Summary>,
+}
+
+impl Report {
+    pub(crate) fn new(mut summaries: Vec<Summary>) -> Self {
+        let show_path = summaries.len() > 1;
+        for summary in summaries.iter_mut() {
+            summary.show_path(show_path);
+        }
+        Self { summaries }
+    }
+
+    pub fn num_issues(&self) -> usize {
+        self.summaries.iter().map(|s| s.issues.len()).sum()
+    }
+
+    /// The severity of the issues found in this run.
+    pub fn severity(&self) -> Severity {
+        self.summaries
+            .iter()
+            .map(Summary::severity)
+            .max()
+            .unwrap_or(Severity::Warning)
+    }
+
+    pub fn console_log_errors(&self) -> Result<(), FlakeCheckerError> {
+        for summary in self.summaries.iter() {
+            summary.console_log_errors()?;
+        }
+        Ok(())
+    }
+
+    /// Emit Azure Pipelines [logging commands] for each issue and set the task result.
+    ///
+    /// [logging commands]: https://learn.microsoft.com/en-us/azure/devops/pipelines/scripts/logging-commands
+    pub fn azure_log_issues(&self) -> Result<(), FlakeCheckerError> {
+        for summary in self.summaries.iter() {
+            summary.azure_log_issues();
+        }
+
+        if self.num_issues() > 0 {
+            let result = match self.severity() {
+                Severity::Error => "Failed",
+                Severity::Warning => "SucceededWithIssues",
+            };
+            println!("##vso[task.complete result={result};]");
+        }
+
+        Ok(())
+    }
+
+    pub fn generate(&self, format: OutputFormat) -> Result<(), FlakeCheckerError> {
+        match format {
+            OutputFormat::Badge => self.generate_badge(),
+            OutputFormat::Html => self.generate_html(),
+            OutputFormat::Csv => self.generate_csv(),
+            OutputFormat::Prometheus => self.generate_prometheus(),
+            // Issues are streamed by the checker as they're found (see `issue_json_line`), so
+            // only the totals are left
+            OutputFormat::Jsonl => {
+                for summary in self.summaries.iter() {
+                    println!("{}", summary.totals_json_line()?);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn generate_badge(&self) -> Result<(), FlakeCheckerError> {
+        println!("{}", serde_json::to_string(&self.badge())?);
+        Ok(())
+    }
+
+    /// The shields.io endpoint document, with the issues by severity and the inputs checked (but
+    /// not the skipped checks, which don't fit on a badge).
+    pub(crate) fn badge(&self) -> serde_json::Value {
+        let num_issues = self.num_issues();
+        let plural = |count: usize, word: &str| {
+            format!("{count} {word}{}", if count == 1 { "" } else { "s" })
+        };
+        let errors: usize = self.summaries.iter().map(|s| s.stats.errors).sum();
+        let warnings: usize = self.summaries.iter().map(|s| s.stats.warnings).sum();
+        let inputs_checked: usize = self
+            .summaries
+            .iter()
+            .map(|s| s.stats.inputs_checked.len())
+            .sum();
 
+        let issues = match (errors, warnings) {
+            (0, 0) => String::from("no issues"),
+            (0, warnings) => plural(warnings, "warning"),
+            (errors, 0) => plural(errors, "error"),
+            (errors, warnings) => {
+                format!(
+                    "{}, {}",
+                    plural(errors, "error"),
+                    plural(warnings, "warning")
+                )
+            }
+        };
+        let message = format!("{issues} in {}", plural(inputs_checked, "input"));
+
+        let color = if num_issues == 0 {
+            "brightgreen"
+        } else if self.severity() == Severity::Error {
+            "red"
+        } else {
+            "yellow"
+        };
+
+        json!({
+            "schemaVersion": 1,
+            "label": "flake freshness",
+            "message": message,
+            "color": color,
+        })
+    }
+
+    pub fn generate_html(&self) -> Result<(), FlakeCheckerError> {
+        let mut handlebars = Handlebars::new();
         handlebars
-            .register_template_string("summary.md", include_str!("templates/summary_md.hbs"))
+            .register_template_string("summary.html", include_str!("templates/summary_html.hbs"))
             .map_err(Box::new)?;
-        let summary_md = handlebars.render("summary.md", &self.data)?;
+        // The template directory comes from the command line, so it's the same for every lockfile
+        if let Some(summary) = self.summaries.first() {
+            summary
+                .flake_check_config
+                .template_dir
+                .register(&mut handlebars)?;
+        }
+
+        let data = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "run_timestamp": iso8601(Utc::now().timestamp()),
+            "files": self.summaries.iter().map(|s| &s.data).collect::<Vec<_>>(),
+        });
+        let summary_html = handlebars.render("summary.html", &data)?;
+
+        print!("{}", summary_html);
+
+        Ok(())
+    }
+
+    pub fn generate_csv(&self) -> Result<(), FlakeCheckerError> {
+        print!("{}", self.csv());
+        Ok(())
+    }
+
+    /// A row per input, followed by a `totals` row per lockfile.
+    pub(crate) fn csv(&self) -> String {
+        let mut csv = String::from("path,input,type,owner,repo,ref,rev,age_days,issues\n");
+
+        for summary in self.summaries.iter() {
+            for row in summary.csv_rows() {
+                csv.push_str(&row);
+                csv.push('\n');
+            }
+        }
+
+        csv
+    }
+
+    pub fn generate_prometheus(&self) -> Result<(), FlakeCheckerError> {
+        print!("{}", self.prometheus());
+        Ok(())
+    }
 
-        let summary_md_filepath = std::env::var("GITHUB_STEP_SUMMARY")?;
+    /// The metrics in the Prometheus textfile collector format.
+    pub(crate) fn prometheus(&self) -> String {
+        let mut metrics = String::new();
+
+        metrics.push_str("# HELP flake_input_age_days Age in days of each locked flake input.\n");
+        metrics.push_str("# TYPE flake_input_age_days gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            for input in summary.inputs.iter() {
+                if let Some(num_days_old) = input.num_days_old {
+                    metrics.push_str(&format!(
+                        "flake_input_age_days{{input=\"{}\",lockfile=\"{lockfile}\"}} {num_days_old}\n",
+                        prometheus_label(&input.name)
+                    ));
+                }
+            }
+        }
+
+        metrics.push_str(
+            "# HELP flake_input_last_modified_seconds Unix timestamp at which each locked flake input was last modified.\n",
+        );
+        metrics.push_str("# TYPE flake_input_last_modified_seconds gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            for input in summary.inputs.iter() {
+                if let Some(last_modified) = input.last_modified {
+                    metrics.push_str(&format!(
+                        "flake_input_last_modified_seconds{{input=\"{}\",lockfile=\"{lockfile}\"}} {last_modified}\n",
+                        prometheus_label(&input.name)
+                    ));
+                }
+            }
+        }
+
+        metrics.push_str(
+            "# HELP flake_checker_issues_total Number of issues found by the flake checker.\n",
+        );
+        metrics.push_str("# TYPE flake_checker_issues_total gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            for kind in ["disallowed", "outdated", "non-upstream"] {
+                let count = summary
+                    .issues
+                    .iter()
+                    .filter(|i| i.kind.name() == kind)
+                    .count();
+                metrics.push_str(&format!(
+                    "flake_checker_issues_total{{kind=\"{kind}\",lockfile=\"{lockfile}\"}} {count}\n"
+                ));
+            }
+        }
+
+        metrics.push_str(
+            "# HELP flake_checker_issues_by_severity Number of issues found by the flake checker, by severity.\n",
+        );
+        metrics.push_str("# TYPE flake_checker_issues_by_severity gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            for (severity, count) in [
+                ("error", summary.stats.errors),
+                ("warning", summary.stats.warnings),
+            ] {
+                metrics.push_str(&format!(
+                    "flake_checker_issues_by_severity{{severity=\"{severity}\",lockfile=\"{lockfile}\"}} {count}\n"
+                ));
+            }
+        }
+
+        metrics.push_str(
+            "# HELP flake_checker_inputs_checked Number of inputs that at least one check ran against.\n",
+        );
+        metrics.push_str("# TYPE flake_checker_inputs_checked gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            metrics.push_str(&format!(
+                "flake_checker_inputs_checked{{lockfile=\"{lockfile}\"}} {}\n",
+                summary.stats.inputs_checked.len()
+            ));
+        }
+
+        metrics.push_str(
+            "# HELP flake_checker_checks_skipped Whether each check that didn't run was disabled or had no inputs.\n",
+        );
+        metrics.push_str("# TYPE flake_checker_checks_skipped gauge\n");
+        for summary in self.summaries.iter() {
+            let lockfile = prometheus_label(&summary.flake_lock_path.to_string_lossy());
+            for skipped in summary.stats.skipped.iter() {
+                metrics.push_str(&format!(
+                    "flake_checker_checks_skipped{{check=\"{}\",reason=\"{}\",lockfile=\"{lockfile}\"}} 1\n",
+                    prometheus_label(&skipped.check),
+                    skipped.reason.as_str()
+                ));
+            }
+        }
+
+        metrics
+    }
+
+    pub fn generate_markdown(&self) -> Result<(), FlakeCheckerError> {
+        let mut summary_md = String::new();
+        for summary in self.summaries.iter() {
+            summary_md.push_str(&summary.markdown()?);
+        }
+
+        // The path may not be UTF-8, and a runner may not set it at all, in which case the
+        // summary is skipped rather than failing the run
+        let Some(summary_md_filepath) = std::env::var_os("GITHUB_STEP_SUMMARY") else {
+            eprintln!(
+                "warning: GITHUB_STEP_SUMMARY isn't set, so the Markdown summary wasn't written"
+            );
+            return Ok(());
+        };
         let mut summary_md_file = OpenOptions::new()
             .append(true)
             .create(true)
@@ -131,15 +873,300 @@ impl Summary {
     }
 
     pub fn generate_text(&self) -> Result<(), FlakeCheckerError> {
-        let mut handlebars = Handlebars::new();
-        handlebars
-            .register_template_string("summary.txt", include_str!("templates/summary_txt.hbs"))
-            .map_err(Box::new)?;
+        for (i, summary) in self.summaries.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print!("{}", summary.text()?);
+        }
 
-        let summary_txt = handlebars.render("summary.txt", &self.data)?;
+        Ok(())
+    }
+}
 
-        print!("{}", summary_txt);
+/// The version of the schema of the `jsonl` output's issues, which changes only when a field is
+/// removed or changes its meaning or type (fields may be added within a version).
+pub(crate) const SCHEMA_VERSION: u32 = 1;
 
-        Ok(())
+/// The JSON Schema of the `jsonl` output's issues.
+pub(crate) const SCHEMA: &str = include_str!("../schemas/issue-v1.schema.json");
+
+/// A single-line JSON representation of an issue, for the `jsonl` output format.
+pub(crate) fn issue_json_line(
+    issue: &Issue,
+    flake_lock_path: &Path,
+    lang: Lang,
+    known: bool,
+    suggestion: Option<&Fix>,
+) -> Result<String, FlakeCheckerError> {
+    let mut line = json!({
+        "schema_version": SCHEMA_VERSION,
+        "path": flake_lock_path.to_string_lossy(),
+        "input": issue.input,
+        "kind": issue.kind.name(),
+        "severity": issue.severity,
+        "details": issue.kind,
+        "message": issue.message(lang),
+        "known": known,
+    });
+    if let Some(location) = &issue.location {
+        line["location"] = json!(location);
+    }
+    if let Some(provenance) = &issue.provenance {
+        line["provenance"] = json!(provenance);
+    }
+    if let Some(fix) = suggestion {
+        line["suggestion"] = json!(fix.to_string());
+    }
+    Ok(serde_json::to_string(&line)?)
+}
+
+// Escape the message of an Azure Pipelines logging command.
+fn azure_message(value: &str) -> String {
+    value
+        .replace('%', "%AZP25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// Where an input comes from, like `github:NixOS/nixpkgs/nixos-unstable`.
+fn source(input: &InputDetails) -> String {
+    let mut source = input.node_type.clone();
+    if let (Some(owner), Some(repo)) = (&input.owner, &input.repo) {
+        source = format!("{source}:{owner}/{repo}");
+        if let Some(git_ref) = &input.git_ref {
+            source = format!("{source}/{git_ref}");
+        }
+    }
+    source
+}
+
+// The age ranges (in days) of the histogram's buckets.
+const AGE_BUCKETS: &[(i64, Option<i64>)] = &[
+    (0, Some(6)),
+    (7, Some(29)),
+    (30, Some(89)),
+    (90, Some(179)),
+    (180, Some(364)),
+    (365, None),
+];
+const HISTOGRAM_WIDTH: usize = 30;
+
+// A plain-text histogram of ages in days, with one line per bucket.
+fn age_histogram(ages: &[i64]) -> Vec<String> {
+    let counts: Vec<(String, usize)> = AGE_BUCKETS
+        .iter()
+        .map(|(min, max)| {
+            let count = ages
+                .iter()
+                .filter(|age| **age >= *min && max.is_none_or(|max| **age <= max))
+                .count();
+            let label = match max {
+                Some(max) => format!("{min}-{max}"),
+                None => format!("{min}+"),
+            };
+            (label, count)
+        })
+        .collect();
+    let most = counts
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or_default();
+    let count_width = most.to_string().len();
+
+    counts
+        .iter()
+        .map(|(label, count)| {
+            // Scale the bars down to fit, but keep at least one mark for non-empty buckets
+            let width = match most {
+                0 => 0,
+                most => (count * HISTOGRAM_WIDTH).div_ceil(most),
+            };
+            format!("{label:>7}  {count:>count_width$}  {}", "#".repeat(width))
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+// Escape a property value of an Azure Pipelines logging command.
+fn azure_property(value: &str) -> String {
+    azure_message(value).replace(';', "%3B").replace(']', "%5D")
+}
+
+// Quote a CSV field per RFC 4180 if it contains a delimiter, quote, or newline.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Escape a Prometheus label value.
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fix::Fix;
+    use crate::flake::{find_issues, FlakeCheckConfig};
+    use crate::i18n::Lang;
+    use crate::issue::{Disallowed, Issue, IssueKind, Location, Severity};
+    use crate::summary::{issue_json_line, Report, Summary, SCHEMA, SCHEMA_VERSION};
+
+    use std::path::{Path, PathBuf};
+
+    use parse_flake_lock::FlakeLock;
+    use serde_json::Value;
+
+    // The dirty lockfile, checked without the owner check
+    fn dirty_report() -> Report {
+        let source = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        let config = FlakeCheckConfig {
+            check_owner: false,
+            ..FlakeCheckConfig::default()
+        };
+        let issues = find_issues(&flake_lock, &config).unwrap();
+        Report::new(vec![Summary::new(
+            &issues,
+            &flake_lock,
+            &source,
+            PathBuf::from("flake.lock"),
+            config,
+            Lang::En,
+            None,
+        )])
+    }
+
+    #[test]
+    fn test_csv_totals() {
+        let csv = dirty_report().csv();
+        assert_eq!(
+            csv.lines().last().unwrap(),
+            "flake.lock,,totals,,,,,,inputs_checked=1;errors=0;warnings=2;skipped=owner:disabled"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_totals() {
+        let line = dirty_report().summaries[0].totals_json_line().unwrap();
+        let totals: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(totals["record"], "totals");
+        assert_eq!(totals["inputs_checked"], serde_json::json!(["nixpkgs"]));
+        assert_eq!(totals["warnings"], 2);
+        assert_eq!(
+            totals["skipped"],
+            serde_json::json!([{ "check": "owner", "reason": "disabled" }])
+        );
+    }
+
+    #[test]
+    fn test_badge_totals() {
+        let badge = dirty_report().badge();
+        assert_eq!(badge["message"], "2 warnings in 1 input");
+        assert_eq!(badge["color"], "yellow");
+    }
+
+    #[test]
+    fn test_group_by_input() {
+        let source = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let flake_lock: FlakeLock = source.parse().unwrap();
+        let config = FlakeCheckConfig {
+            selectors: vec!["repo=nixpkgs".parse().unwrap()],
+            ..FlakeCheckConfig::default()
+        };
+        let mut issues = find_issues(&flake_lock, &config).unwrap();
+        let grouped_summary = |issues: &Vec<Issue>| {
+            let mut summary = Summary::new(
+                issues,
+                &flake_lock,
+                &source,
+                PathBuf::from("flake.lock"),
+                config.clone(),
+                Lang::En,
+                None,
+            );
+            summary.group_by_input();
+            summary
+        };
+        let summary = grouped_summary(&issues);
+
+        let grouped = summary.data["grouped"].as_array().unwrap();
+        let inputs: Vec<&str> = grouped
+            .iter()
+            .map(|group| group["input"].as_str().unwrap())
+            .collect();
+        assert_eq!(inputs, ["nixpkgs", "nixpkgs_2"]);
+        assert_eq!(grouped[0]["findings"].as_array().unwrap().len(), 3);
+        assert_eq!(grouped[0]["kind"], "disallowed, non-upstream, outdated");
+        assert_eq!(summary.data["has_outdated"], false);
+        assert!(summary.text().unwrap().contains(">>> Findings by input"));
+
+        // Issues with the same input are grouped together even if they aren't next to each other
+        issues.rotate_left(1);
+        assert_eq!(issues[issues.len() - 2].input, "nixpkgs_2");
+        let summary = grouped_summary(&issues);
+        let grouped = summary.data["grouped"].as_array().unwrap();
+        let inputs: Vec<&str> = grouped
+            .iter()
+            .map(|group| group["input"].as_str().unwrap())
+            .collect();
+        assert_eq!(inputs, ["nixpkgs", "nixpkgs_2"]);
+        assert_eq!(grouped[0]["findings"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_issue_json_line_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+
+        let issue = Issue {
+            input: String::from("nixpkgs"),
+            kind: IssueKind::Disallowed(Disallowed {
+                reference: String::from("release-21.05"),
+            }),
+            location: Some(Location {
+                path: PathBuf::from("flake.lock"),
+                line: 32,
+                column: 16,
+                json_pointer: String::from("/nodes/nixpkgs/original/ref"),
+            }),
+            provenance: None,
+            severity: Severity::Warning,
+            message: None,
+        };
+        let fix = Fix {
+            input: String::from("nixpkgs"),
+            args: vec![String::from("flake"), String::from("update")],
+            git_ref: None,
+        };
+        let line =
+            issue_json_line(&issue, Path::new("flake.lock"), Lang::En, false, Some(&fix)).unwrap();
+        let line: Value = serde_json::from_str(&line).unwrap();
+
+        // Every field is in the schema, and every required field is in the line
+        let properties = schema["properties"].as_object().unwrap();
+        for field in line.as_object().unwrap().keys() {
+            assert!(
+                properties.contains_key(field),
+                "{field} isn't in the schema"
+            );
+        }
+        for field in schema["required"].as_array().unwrap() {
+            assert!(
+                line.get(field.as_str().unwrap()).is_some(),
+                "{field} is missing"
+            );
+        }
     }
 }
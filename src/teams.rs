@@ -0,0 +1,93 @@
+use crate::error::FlakeCheckerError;
+use crate::http::{self, NetworkConfig};
+use crate::issue::Severity;
+use crate::summary::Summary;
+
+use serde_json::json;
+
+/// Posts an [Adaptive Card] summarizing the checker's findings to a Microsoft Teams incoming
+/// webhook.
+///
+/// [Adaptive Card]: https://learn.microsoft.com/en-us/microsoftteams/platform/task-modules-and-cards/cards/cards-reference#adaptive-card
+pub(crate) struct TeamsNotifier {
+    webhook_url: String,
+    min_severity: Severity,
+    network: NetworkConfig,
+}
+
+impl TeamsNotifier {
+    pub(crate) fn new(webhook_url: String, min_severity: Severity, network: NetworkConfig) -> Self {
+        Self {
+            webhook_url,
+            min_severity,
+            network,
+        }
+    }
+
+    /// Post the summary card, unless there are no issues or their severity is below the
+    /// configured minimum.
+    pub(crate) fn notify(&self, summary: &Summary) -> Result<(), FlakeCheckerError> {
+        if summary.issues.is_empty() || summary.severity() < self.min_severity {
+            return Ok(());
+        }
+
+        let path = summary.flake_lock_path.to_string_lossy();
+        let num_issues = summary.issues.len();
+
+        let facts: Vec<serde_json::Value> = summary
+            .issues
+            .iter()
+            .map(|issue| {
+                json!({
+                    "title": format!("{} ({})", issue.input, issue.kind.name()),
+                    "value": issue.message(summary.lang),
+                })
+            })
+            .collect();
+
+        let card = json!({
+            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+            "type": "AdaptiveCard",
+            "version": "1.4",
+            "body": [
+                {
+                    "type": "TextBlock",
+                    "size": "Medium",
+                    "weight": "Bolder",
+                    "text": "Nix Flake Checker",
+                },
+                {
+                    "type": "TextBlock",
+                    "wrap": true,
+                    "color": if summary.severity() == Severity::Error { "Attention" } else { "Warning" },
+                    "text": format!(
+                        "Found {num_issues} {} in `{path}`",
+                        if num_issues == 1 { "issue" } else { "issues" }
+                    ),
+                },
+                {
+                    "type": "FactSet",
+                    "facts": facts,
+                },
+            ],
+        });
+
+        let message = json!({
+            "type": "message",
+            "attachments": [{
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "contentUrl": null,
+                "content": card,
+            }],
+        });
+
+        http::client(&self.network)?
+            .post(&self.webhook_url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&message)?)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
+use crate::http::{self, NetworkConfig};
 use crate::issue::Issue;
 
 use std::env;
 
 use sha2::{Digest, Sha256};
 
-const TELEMETRY_ENDPOINT: &str = "https://install.determinate.systems/flake-checker/telemetry";
+pub(crate) const TELEMETRY_ENDPOINT: &str =
+    "https://install.determinate.systems/flake-checker/telemetry";
 
 /// A telemetry report to identify trends in outdated locks against nixpkgs
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
@@ -42,10 +44,12 @@ impl TelemetryReport {
         })
     }
 
-    pub fn make_and_send(issues: &[Issue]) {
+    pub fn make_and_send(issues: &[Issue], network: &NetworkConfig) {
         if let Ok(report) = TelemetryReport::new(issues) {
-            if let Ok(serialized) = serde_json::to_string_pretty(&report) {
-                let _ = reqwest::blocking::Client::new()
+            if let (Ok(serialized), Ok(client)) =
+                (serde_json::to_string_pretty(&report), http::client(network))
+            {
+                let _ = client
                     .post(TELEMETRY_ENDPOINT)
                     .body(serialized)
                     .header("Content-Type", "application/json")
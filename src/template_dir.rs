@@ -0,0 +1,115 @@
+//! Partials and helpers from a `--template-dir`, which are registered alongside the summary
+//! templates and the policy's message templates, for reports that are customized without
+//! recompiling.
+//!
+//! Each `*.hbs` file is a partial named after the file without its extension, like `footer` for
+//! `footer.hbs`, which templates include with `{{> footer}}`. A partial with the name of a
+//! summary template (`summary.md`, `summary.txt`, or `summary.html`) replaces it. Each `*.rhai`
+//! file is a helper, as a Rhai script that sees the helper's arguments as `params` and its named
+//! arguments as `hash`, and evaluates to the helper's output. Helpers need the `cli` feature, as
+//! they pull in a random number generator that doesn't build for `wasm32-unknown-unknown`.
+
+use crate::error::FlakeCheckerError;
+#[cfg(feature = "cli")]
+use crate::rules::engine;
+
+use std::path::Path;
+
+use handlebars::{Handlebars, Template};
+
+/// The partials and helpers loaded from a template directory.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateDir {
+    partials: Vec<(String, String)>,
+    helpers: Vec<(String, String)>,
+}
+
+impl TemplateDir {
+    /// Load the `*.hbs` partials and `*.rhai` helpers in a directory (but not its subdirectories),
+    /// checking that they compile. Other files are ignored.
+    pub fn load(dir: &Path) -> Result<Self, FlakeCheckerError> {
+        let mut paths = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+
+        let mut template_dir = Self::default();
+        for path in paths.iter().filter(|path| path.is_file()) {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let invalid = |err: String| {
+                FlakeCheckerError::Invalid(format!("invalid template {}: {err}", path.display()))
+            };
+            if let Some(name) = file_name.strip_suffix(".hbs") {
+                let source = std::fs::read_to_string(path)?;
+                Template::compile(&source).map_err(|err| invalid(err.to_string()))?;
+                template_dir.partials.push((name.to_string(), source));
+            } else if let Some(name) = file_name.strip_suffix(".rhai") {
+                #[cfg(feature = "cli")]
+                {
+                    let source = std::fs::read_to_string(path)?;
+                    engine()
+                        .compile(&source)
+                        .map_err(|err| invalid(err.to_string()))?;
+                    template_dir.helpers.push((name.to_string(), source));
+                }
+                #[cfg(not(feature = "cli"))]
+                return Err(invalid(format!(
+                    "the {name} helper needs flake-checker to be built with the `cli` feature"
+                )));
+            }
+        }
+        Ok(template_dir)
+    }
+
+    /// Register the partials and helpers, replacing any templates with the same names.
+    pub(crate) fn register(&self, handlebars: &mut Handlebars) -> Result<(), FlakeCheckerError> {
+        for (name, source) in self.partials.iter() {
+            handlebars
+                .register_partial(name, source)
+                .map_err(Box::new)?;
+        }
+        // Helpers are limited like rules, so that one can't hang the run
+        #[cfg(feature = "cli")]
+        handlebars.set_engine(engine());
+        #[cfg(feature = "cli")]
+        for (name, source) in self.helpers.iter() {
+            handlebars
+                .register_script_helper(name, source)
+                .map_err(|err| {
+                    FlakeCheckerError::Invalid(format!("invalid helper {name}: {err}"))
+                })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::template_dir::TemplateDir;
+
+    use handlebars::Handlebars;
+
+    #[test]
+    fn test_template_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("flake-checker-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("footer.hbs"), "-- {{shout team}}").unwrap();
+        std::fs::write(dir.join("shout.rhai"), "params[0].to_upper() + \"!\"").unwrap();
+        std::fs::write(dir.join("README"), "not a template").unwrap();
+
+        let template_dir = TemplateDir::load(&dir).unwrap();
+        let mut handlebars = Handlebars::new();
+        template_dir.register(&mut handlebars).unwrap();
+        let rendered = handlebars
+            .render_template("{{> footer}}", &serde_json::json!({ "team": "platform" }))
+            .unwrap();
+        assert_eq!(rendered, "-- PLATFORM!");
+
+        std::fs::write(dir.join("broken.hbs"), "{{#if}}").unwrap();
+        assert!(TemplateDir::load(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+//! A minimal interactive terminal browser for a lockfile's inputs and issues, drawn with
+//! ratatui.
+
+use crate::error::FlakeCheckerError;
+use crate::flake::{input_details, InputDetails};
+use crate::i18n::Lang;
+use crate::issue::Issue;
+
+use std::io::Write;
+use std::path::Path;
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use parse_flake_lock::{FlakeLock, Input, Node};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Padding, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+// Nested inputs deeper than this aren't shown (lockfiles can be very deep, and even cyclic)
+const MAX_DEPTH: usize = 4;
+
+/// A row in the input tree.
+struct Entry {
+    depth: usize,
+    /// The root-level input that this entry is (or is nested under).
+    root_input: String,
+    name: String,
+    /// The name of the node in the lockfile, or the input path it follows.
+    target: Target,
+    details: Option<InputDetails>,
+    issues: Vec<String>,
+}
+
+enum Target {
+    Node(String),
+    Follows(String),
+}
+
+/// Browse the inputs and issues of a lockfile until the user quits.
+pub(crate) fn browse(
+    flake_lock: &FlakeLock,
+    path: &Path,
+    issues: &[Issue],
+    lang: Lang,
+    now: DateTime<Utc>,
+) -> Result<(), FlakeCheckerError> {
+    let entries = entries(flake_lock, issues, lang, now);
+    if entries.is_empty() {
+        println!("{} has no inputs", path.display());
+        return Ok(());
+    }
+
+    let mut terminal = ratatui::try_init()?;
+    let result = run(&mut terminal, path, &entries);
+    ratatui::try_restore()?;
+    result
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    path: &Path,
+    entries: &[Entry],
+) -> Result<(), FlakeCheckerError> {
+    let mut list_state = ListState::default().with_selected(Some(0));
+    let mut status = String::new();
+
+    loop {
+        terminal.draw(|frame| draw(frame, path, entries, &mut list_state, &status))?;
+        status.clear();
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let selected = list_state.selected().unwrap_or_default();
+        let last = entries.len() - 1;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('j') | KeyCode::Down => list_state.select(Some((selected + 1).min(last))),
+            KeyCode::Char('k') | KeyCode::Up => list_state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Char('g') | KeyCode::Home => list_state.select(Some(0)),
+            KeyCode::Char('G') | KeyCode::End => list_state.select(Some(last)),
+            KeyCode::Char('c') => {
+                let command = update_command(&entries[selected]);
+                copy_to_clipboard(terminal.backend_mut(), &command)?;
+                status = format!("copied `{command}`");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn entries(flake_lock: &FlakeLock, issues: &[Issue], lang: Lang, now: DateTime<Utc>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+
+    for details in input_details(flake_lock, now) {
+        let node_name = flake_lock
+            .root_node_names
+            .get(&details.name)
+            .cloned()
+            .unwrap_or_else(|| details.name.clone());
+        let root_input = details.name.clone();
+
+        entries.push(Entry {
+            depth: 0,
+            root_input: root_input.clone(),
+            name: details.name.clone(),
+            target: Target::Node(node_name.clone()),
+            issues: issues
+                .iter()
+                .filter(|issue| issue.input == details.name)
+                .map(|issue| issue.message(lang))
+                .collect(),
+            details: Some(details),
+        });
+
+        push_children(flake_lock, &node_name, &root_input, 1, &mut entries);
+    }
+
+    entries
+}
+
+fn push_children(
+    flake_lock: &FlakeLock,
+    node_name: &str,
+    root_input: &str,
+    depth: usize,
+    entries: &mut Vec<Entry>,
+) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+
+    let inputs = match flake_lock.nodes.get(node_name) {
+        Some(Node::Repo(repo)) => repo.inputs.clone(),
+        Some(Node::Indirect(indirect)) => indirect.inputs.clone(),
+        Some(Node::Path(path)) => path.inputs.clone(),
+        Some(Node::Tarball(tarball)) => tarball.inputs.clone(),
+        _ => None,
+    };
+    let mut inputs: Vec<(String, Input)> = inputs.unwrap_or_default().into_iter().collect();
+    inputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, input) in inputs {
+        match input {
+            Input::String(child) => {
+                entries.push(Entry {
+                    depth,
+                    root_input: root_input.to_string(),
+                    name,
+                    target: Target::Node(child.clone()),
+                    details: None,
+                    issues: vec![],
+                });
+                push_children(flake_lock, &child, root_input, depth + 1, entries);
+            }
+            Input::List(path) => entries.push(Entry {
+                depth,
+                root_input: root_input.to_string(),
+                name,
+                target: Target::Follows(path.join("/")),
+                details: None,
+                issues: vec![],
+            }),
+        }
+    }
+}
+
+fn update_command(entry: &Entry) -> String {
+    format!("nix flake update {}", entry.root_input)
+}
+
+fn draw(
+    frame: &mut Frame,
+    path: &Path,
+    entries: &[Entry],
+    list_state: &mut ListState,
+    status: &str,
+) {
+    let [title, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let list_width = (body.width / 3).clamp(20, 40);
+    let [list, details] =
+        Layout::horizontal([Constraint::Length(list_width), Constraint::Min(1)]).areas(body);
+
+    frame.render_widget(
+        Paragraph::new(format!("Flake checker: {}", path.display())).bold(),
+        title,
+    );
+
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|entry| {
+            let marker = if !entry.issues.is_empty() { "!" } else { " " };
+            ListItem::new(format!(
+                "{marker} {}{}",
+                "  ".repeat(entry.depth),
+                entry.name
+            ))
+        })
+        .collect();
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list,
+        list_state,
+    );
+
+    let selected = list_state.selected().unwrap_or_default();
+    let lines: Vec<Line> = detail_lines(&entries[selected])
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::new()
+                .borders(Borders::LEFT)
+                .padding(Padding::left(1)),
+        ),
+        details,
+    );
+
+    let help = "↑/↓ or j/k: move · c: copy update command · q: quit";
+    frame.render_widget(
+        Paragraph::new(if status.is_empty() { help } else { status }),
+        footer,
+    );
+}
+
+fn detail_lines(entry: &Entry) -> Vec<String> {
+    let mut lines = vec![format!("Input: {}", entry.name)];
+
+    match &entry.target {
+        Target::Node(node) => lines.push(format!("Node: {node}")),
+        Target::Follows(path) => lines.push(format!("Follows: {path}")),
+    }
+
+    if let Some(details) = &entry.details {
+        lines.push(format!("Type: {}", details.node_type));
+        if let (Some(owner), Some(repo)) = (&details.owner, &details.repo) {
+            lines.push(format!("Repository: {owner}/{repo}"));
+        }
+        if let Some(url) = &details.url {
+            lines.push(format!("URL: {url}"));
+        }
+        if let Some(dir) = &details.dir {
+            lines.push(format!("Directory: {dir}"));
+        }
+        if !details.flake {
+            lines.push(String::from("Flake: no (`flake = false`)"));
+        }
+        if let Some(git_ref) = &details.git_ref {
+            lines.push(format!("Ref: {git_ref}"));
+        }
+        if let Some(rev) = &details.rev {
+            lines.push(format!("Revision: {rev}"));
+        }
+        if let (Some(days), Some(locked_at)) = (details.num_days_old, &details.locked_at) {
+            lines.push(format!("Age: {days} days (locked {locked_at})"));
+        }
+    }
+
+    lines.push(String::new());
+    if entry.issues.is_empty() {
+        lines.push(String::from("No issues"));
+    } else {
+        lines.push(String::from("Issues:"));
+        lines.extend(entry.issues.iter().map(|issue| format!("  • {issue}")));
+    }
+
+    lines.push(String::new());
+    lines.push(format!("Update with: {}", update_command(entry)));
+
+    lines
+}
+
+// Copies text to the system clipboard via the OSC 52 escape sequence, which most terminal
+// emulators (and tmux, with `set-clipboard on`) support.
+fn copy_to_clipboard(out: &mut impl Write, text: &str) -> Result<(), FlakeCheckerError> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    write!(out, "\x1b]52;c;{encoded}\x07")?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{draw, entries};
+    use crate::i18n::Lang;
+
+    use std::path::Path;
+
+    use chrono::Utc;
+    use parse_flake_lock::FlakeLock;
+    use ratatui::backend::TestBackend;
+    use ratatui::widgets::ListState;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_draw() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.clean.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let entries = entries(&flake_lock, &[], Lang::En, Utc::now());
+        let selected = entries
+            .iter()
+            .position(|entry| entry.depth == 0 && entry.name == "nixpkgs")
+            .unwrap();
+        let mut list_state = ListState::default().with_selected(Some(selected));
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal
+            .draw(|frame| {
+                draw(
+                    frame,
+                    Path::new("flake.lock"),
+                    &entries,
+                    &mut list_state,
+                    "",
+                )
+            })
+            .unwrap();
+
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(100)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+        assert!(screen[0].starts_with("Flake checker: flake.lock"));
+        assert!(screen.iter().any(|row| row.contains("Input: nixpkgs")));
+        assert!(screen
+            .iter()
+            .any(|row| row.contains("Update with: nix flake update nixpkgs")));
+        assert!(screen[19].starts_with("↑/↓ or j/k: move"));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::canonical;
+use crate::error::FlakeCheckerError;
+use crate::fix::Fix;
+use crate::http::{self, NetworkConfig};
+use crate::pins;
+
+use std::path::Path;
+
+use base64::Engine;
+use flate2::read::GzDecoder;
+use parse_flake_lock::{FlakeLock, Forge, Node};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// Apply fixes by updating the lockfile directly, without the Nix CLI: each input is moved to
+/// the tip of its (new) ref on GitHub, with the `rev`, `lastModified`, and `narHash` that
+/// `nix flake lock` would have locked.
+pub(crate) fn apply(
+    flake_lock_path: &Path,
+    source: &str,
+    flake_lock: &FlakeLock,
+    fixes: &[Fix],
+    network: &NetworkConfig,
+) -> Result<(), FlakeCheckerError> {
+    let mut lock: serde_json::Value = serde_json::from_str(source)?;
+
+    for fix in fixes.iter() {
+        let node = flake_lock.root.get(&fix.input);
+        let node_name = flake_lock.root_node_names.get(&fix.input);
+        let (Some(Node::Repo(repo)), Some(node_name)) = (node, node_name) else {
+            continue;
+        };
+        let original = &repo.original;
+        if repo.forge() != Some(Forge::GitHub) {
+            return Err(FlakeCheckerError::Invalid(format!(
+                "can't update the `{}` input in place: only GitHub inputs can be updated without the Nix CLI",
+                fix.input
+            )));
+        }
+
+        let git_ref = fix
+            .git_ref
+            .as_deref()
+            .or(original.git_ref.as_deref())
+            .unwrap_or("HEAD");
+        let (rev, last_modified) = pins::commit(&original.owner, &original.repo, git_ref, network)?;
+        println!(
+            "updating `{}` to {}/{}@{rev} ({git_ref})",
+            fix.input, original.owner, original.repo
+        );
+        let nar_hash = if rev == repo.locked.rev {
+            repo.locked.nar_hash.clone()
+        } else {
+            tarball_nar_hash(&original.owner, &original.repo, &rev, network)?
+        };
+
+        let node = &mut lock["nodes"][node_name];
+        node["locked"]["rev"] = json!(rev);
+        node["locked"]["lastModified"] = json!(last_modified);
+        node["locked"]["narHash"] = json!(nar_hash);
+        if let Some(git_ref) = &fix.git_ref {
+            node["original"]["ref"] = json!(git_ref);
+        }
+    }
+
+    let updated = canonical::canonicalize(&serde_json::to_string(&lock)?)?;
+    std::fs::write(flake_lock_path, updated)?;
+    Ok(())
+}
+
+// The NAR hash of a GitHub repository as of a revision, which is the hash of the contents of its
+// tarball (without the tarball's top-level directory).
+fn tarball_nar_hash(
+    owner: &str,
+    repo: &str,
+    rev: &str,
+    network: &NetworkConfig,
+) -> Result<String, FlakeCheckerError> {
+    let response = http::send(network, |client| {
+        network.authorize_github(
+            client
+                .get(format!(
+                    "https://api.github.com/repos/{owner}/{repo}/tarball/{rev}"
+                ))
+                .header("User-Agent", "flake-checker"),
+        )
+    })?;
+
+    let dir = std::env::temp_dir().join(format!("flake-checker-{}-{rev}", std::process::id()));
+    let unpacked = tar::Archive::new(GzDecoder::new(response))
+        .unpack(&dir)
+        .map_err(FlakeCheckerError::from)
+        .and_then(|()| {
+            // GitHub's tarballs have a single top-level directory, like `owner-repo-rev`
+            let top = std::fs::read_dir(&dir)?
+                .next()
+                .transpose()?
+                .ok_or_else(|| {
+                    FlakeCheckerError::Invalid(format!("the tarball of {owner}/{repo} is empty"))
+                })?;
+            nar_hash(&top.path())
+        });
+    let _ = std::fs::remove_dir_all(&dir);
+    unpacked
+}
+
+/// The hash of a path's NAR serialization, in the SRI format that lockfiles use for `narHash`.
+pub(crate) fn nar_hash(path: &Path) -> Result<String, FlakeCheckerError> {
+    let mut hasher = Sha256::new();
+    hasher.update(nar_str(b"nix-archive-1"));
+    nar_node(path, &mut hasher)?;
+    Ok(format!(
+        "sha256-{}",
+        base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+    ))
+}
+
+// Serialize a file, symlink, or directory (with its entries sorted by name) in the NAR format.
+fn nar_node(path: &Path, hasher: &mut Sha256) -> Result<(), FlakeCheckerError> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let write = |hasher: &mut Sha256, tokens: &[&[u8]]| {
+        for token in tokens {
+            hasher.update(nar_str(token));
+        }
+    };
+
+    write(hasher, &[b"(", b"type"]);
+    if metadata.is_symlink() {
+        let target = std::fs::read_link(path)?;
+        write(
+            hasher,
+            &[b"symlink", b"target", target.as_os_str().as_encoded_bytes()],
+        );
+    } else if metadata.is_dir() {
+        write(hasher, &[b"directory"]);
+        let mut entries = std::fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            write(
+                hasher,
+                &[
+                    b"entry",
+                    b"(",
+                    b"name",
+                    entry.file_name().as_encoded_bytes(),
+                    b"node",
+                ],
+            );
+            nar_node(&entry.path(), hasher)?;
+            write(hasher, &[b")"]);
+        }
+    } else {
+        write(hasher, &[b"regular"]);
+        if is_executable(&metadata) {
+            write(hasher, &[b"executable", b""]);
+        }
+        write(hasher, &[b"contents", &std::fs::read(path)?]);
+    }
+    write(hasher, &[b")"]);
+    Ok(())
+}
+
+// A string in the NAR format: its length, then its bytes padded to a multiple of 8 bytes.
+fn nar_str(bytes: &[u8]) -> Vec<u8> {
+    let mut serialized = (bytes.len() as u64).to_le_bytes().to_vec();
+    serialized.extend_from_slice(bytes);
+    serialized.resize(serialized.len().next_multiple_of(8), 0);
+    serialized
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use crate::update::{nar_hash, nar_str};
+
+    #[test]
+    fn test_nar_hash() {
+        assert_eq!(nar_str(b"type"), b"\x04\0\0\0\0\0\0\0type\0\0\0\0");
+        assert_eq!(nar_str(b"").len(), 8);
+
+        // Entries are serialized by name, regardless of the order that they were created in
+        let root = std::env::temp_dir().join(format!("flake-checker-nar-{}", std::process::id()));
+        let tree = |names: [&str; 2]| {
+            let dir = root.join(names.join("-"));
+            std::fs::create_dir_all(dir.join("lib")).unwrap();
+            for name in names {
+                std::fs::write(dir.join("lib").join(name), name).unwrap();
+            }
+            nar_hash(&dir).unwrap()
+        };
+        let hash = tree(["a.nix", "b.nix"]);
+        assert_eq!(hash, tree(["b.nix", "a.nix"]));
+        assert!(hash.starts_with("sha256-") && hash.len() == 51);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let file = root.join("a.nix-b.nix").join("lib").join("a.nix");
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+            assert_ne!(nar_hash(&root.join("a.nix-b.nix")).unwrap(), hash);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
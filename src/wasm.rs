@@ -0,0 +1,127 @@
+//! Checks compiled to WebAssembly, which run sandboxed, so that third-party rules can be run
+//! without trusting them.
+//!
+//! A module can't import anything (so it has no access to the filesystem, the network, or the
+//! clock), and its memory and running time are limited. It exports its `memory`, an
+//! `alloc(len: i32) -> i32` function that returns where the host should write its input, and a
+//! `check(ptr: i32, len: i32) -> i64` function that takes the input and returns where its output
+//! is, as the output's offset in the upper 32 bits and its length in the lower 32 bits. The input
+//! and output are the same JSON as for executable plugins.
+
+use std::path::Path;
+
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+// How much a module may compute for each lockfile, in wasmtime's fuel units (about one per
+// instruction).
+const FUEL: u64 = 1_000_000_000;
+
+// How much memory a module may use, in bytes.
+const MAX_MEMORY: usize = 64 << 20;
+
+/// Run a module's check on an input, returning its output.
+pub(crate) fn run(plugin: &Path, input: &str) -> Result<Vec<u8>, String> {
+    let module = std::fs::read(plugin).map_err(|err| format!("couldn't read it: {err}"))?;
+    invoke(&module, input)
+}
+
+fn invoke(module: &[u8], input: &str) -> Result<Vec<u8>, String> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).map_err(|err| format!("{err:#}"))?;
+    let module = Module::new(&engine, module).map_err(|err| format!("invalid module: {err:#}"))?;
+    if let Some(import) = module.imports().next() {
+        return Err(format!(
+            "it imports `{}::{}`, but modules can't import anything",
+            import.module(),
+            import.name()
+        ));
+    }
+
+    let mut store = Store::new(
+        &engine,
+        StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+    );
+    store.limiter(|limits: &mut StoreLimits| limits);
+    store.set_fuel(FUEL).map_err(|err| format!("{err:#}"))?;
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .map_err(|err| format!("{err:#}"))?;
+
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or_else(|| String::from("it doesn't export its `memory`"))?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut store, "alloc")
+        .map_err(|err| format!("{err:#}"))?;
+    let check = instance
+        .get_typed_func::<(i32, i32), i64>(&mut store, "check")
+        .map_err(|err| format!("{err:#}"))?;
+
+    let len = i32::try_from(input.len()).map_err(|_| String::from("the input is too large"))?;
+    let ptr = alloc
+        .call(&mut store, len)
+        .map_err(|err| trap(&store, err))?;
+    memory
+        .write(&mut store, ptr as u32 as usize, input.as_bytes())
+        .map_err(|_| String::from("`alloc` returned memory that's out of bounds"))?;
+
+    let output = check
+        .call(&mut store, (ptr, len))
+        .map_err(|err| trap(&store, err))?;
+    let (ptr, len) = ((output as u64 >> 32) as usize, output as u32 as usize);
+    memory
+        .data(&store)
+        .get(ptr..ptr + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| String::from("`check` returned output that's out of bounds"))
+}
+
+// Describe a module's failure, distinguishing running out of fuel from other traps.
+fn trap(store: &Store<StoreLimits>, err: wasmtime::Error) -> String {
+    match store.get_fuel() {
+        Ok(0) => String::from("it ran for too long"),
+        _ => format!("it failed: {err:#}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wasm::invoke;
+
+    // A module that reports an issue if its input is a JSON object, with its output at offset 0
+    // and its input written at offset 1024.
+    const CHECK: &str = r#"(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"issues\": [{\"input\": \"nixpkgs\", \"code\": \"unpinned\", \"message\": \"not pinned\"}]}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "check") (param i32 i32) (result i64)
+    (if (result i64) (i32.eq (i32.load8_u (local.get 0)) (i32.const 123))
+      (then (i64.const 79))
+      (else (i64.const 0)))))"#;
+
+    #[test]
+    fn test_wasm_plugins() {
+        let output = invoke(CHECK.as_bytes(), r#"{"version": 1}"#).unwrap();
+        let output: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(output["issues"][0]["code"], "unpinned");
+        assert_eq!(invoke(CHECK.as_bytes(), "[]").unwrap(), b"");
+
+        // Modules are sandboxed
+        let importing = r#"(module (import "wasi_snapshot_preview1" "fd_write" (func)))"#;
+        let err = invoke(importing.as_bytes(), "{}").unwrap_err();
+        assert!(err.contains("can't import"), "{err}");
+
+        let looping = CHECK.replace(
+            r#"(func (export "check") (param i32 i32) (result i64)"#,
+            r#"(func (export "check") (param i32 i32) (result i64) (loop (br 0))"#,
+        );
+        assert_eq!(
+            invoke(looping.as_bytes(), "{}").unwrap_err(),
+            "it ran for too long"
+        );
+
+        let out_of_bounds = CHECK.replace("(i64.const 79)", "(i64.const 0x1000000000010)");
+        assert!(invoke(out_of_bounds.as_bytes(), "{}").is_err());
+    }
+}
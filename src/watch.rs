@@ -0,0 +1,58 @@
+use crate::error::FlakeCheckerError;
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, ExitCode};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Re-run the checker (with the same arguments, minus `--watch`) whenever one of the watched files
+/// changes, until interrupted.
+pub(crate) fn watch(paths: Vec<PathBuf>) -> Result<ExitCode, FlakeCheckerError> {
+    let exe = std::env::current_exe()?;
+    let args: Vec<_> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--watch")
+        .collect();
+
+    let mut first_run = true;
+    loop {
+        let modified = modification_times(&paths);
+
+        // Clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        std::io::stdout().flush()?;
+
+        let mut command = Command::new(&exe);
+        command.args(&args).env_remove("NIX_FLAKE_CHECKER_WATCH");
+        // Only report telemetry for the initial run rather than every change
+        if !first_run {
+            command.env("NIX_FLAKE_CHECKER_NO_TELEMETRY", "true");
+        }
+        command.status()?;
+        first_run = false;
+
+        println!(
+            "\nWatching {} for changes (press Ctrl-C to exit)",
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        while modification_times(&paths) == modified {
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+// A deleted file shows up as `None`, which also counts as a change.
+fn modification_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
@@ -0,0 +1,156 @@
+use crate::error::FlakeCheckerError;
+use crate::graph::InputGraph;
+
+use parse_flake_lock::FlakeLock;
+
+// Guards against cycles in malformed lockfiles
+const MAX_DEPTH: usize = 32;
+
+/// One step along a chain of `inputs` edges: the name of the input and the node it refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Step {
+    pub input: String,
+    pub node: String,
+}
+
+/// Find every chain of `inputs` edges from the root that leads to a node, which may be named by its
+/// key in the lockfile's `nodes` or by the name of a root input. Inputs that `follows` another
+/// input don't add nodes to the lock, so they aren't part of any chain.
+pub(crate) fn why(flake_lock: &FlakeLock, name: &str) -> Result<Vec<Vec<Step>>, FlakeCheckerError> {
+    let target = match flake_lock.nodes.get(name) {
+        Some(_) => name,
+        None => flake_lock.root_node_names.get(name).ok_or_else(|| {
+            FlakeCheckerError::Invalid(format!("no node named {name} in the lock"))
+        })?,
+    };
+
+    let graph = InputGraph::new(flake_lock)?;
+    let mut chains = Vec::new();
+    walk(&graph, graph.root(), target, &mut Vec::new(), &mut chains);
+    Ok(chains)
+}
+
+fn walk(
+    graph: &InputGraph,
+    node_name: &str,
+    target: &str,
+    chain: &mut Vec<Step>,
+    chains: &mut Vec<Vec<Step>>,
+) {
+    if chain.len() > MAX_DEPTH {
+        return;
+    }
+
+    for (edge, child) in graph.inputs(node_name) {
+        if edge.follows.is_some() {
+            continue;
+        }
+        chain.push(Step {
+            input: edge.input.clone(),
+            node: child.to_string(),
+        });
+        if child == target {
+            chains.push(chain.clone());
+        } else {
+            walk(graph, child, target, chain, chains);
+        }
+        chain.pop();
+    }
+}
+
+/// Describe the chains that lead to a node, with one line per chain, and which root inputs to
+/// update or add a `follows` to.
+pub(crate) fn explain(flake_lock: &FlakeLock, name: &str, chains: &[Vec<Step>]) -> String {
+    if chains.is_empty() {
+        return format!(
+            "{name} isn't reachable from the root; it's left over in the lockfile and `nix flake lock` will remove it\n"
+        );
+    }
+
+    let mut out = String::new();
+    for chain in chains {
+        let steps: Vec<String> = chain
+            .iter()
+            .map(|step| {
+                if step.input == step.node {
+                    step.input.clone()
+                } else {
+                    format!("{} ({})", step.input, step.node)
+                }
+            })
+            .collect();
+        out.push_str(&format!("root -> {}\n", steps.join(" -> ")));
+    }
+
+    let mut roots: Vec<&str> = chains.iter().map(|chain| chain[0].input.as_str()).collect();
+    roots.dedup();
+    out.push_str(&format!(
+        "\n{name} is locked because of the root input(s) {}\n",
+        roots.join(", ")
+    ));
+
+    // Transitive inputs can be deduplicated with a `follows` on the root input that pulls them in,
+    // but an input with `flake = false` is used as a plain source tree, so it can only follow
+    // another one
+    let node = chains[0].last().map(|step| step.node.as_str());
+    let is_flake = node
+        .and_then(|node| flake_lock.nodes.get(node))
+        .is_none_or(|node| node.is_flake());
+    let follow = if is_flake {
+        "one of your own inputs"
+    } else {
+        "one of your own `flake = false` inputs"
+    };
+    for chain in chains.iter().filter(|chain| chain.len() > 1) {
+        let path: Vec<&str> = chain.iter().map(|step| step.input.as_str()).collect();
+        out.push_str(&format!(
+            "to reuse {follow} instead, add `inputs.{}.follows = \"...\";`\n",
+            path.join(".inputs.")
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::why::{explain, why};
+
+    use parse_flake_lock::FlakeLock;
+
+    #[test]
+    fn test_why() {
+        let flake_lock: FlakeLock = std::fs::read_to_string("tests/flake.dirty.0.lock")
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let chains = why(&flake_lock, "systems").unwrap();
+        assert_eq!(chains.len(), 1);
+        let inputs: Vec<&str> = chains[0].iter().map(|step| step.input.as_str()).collect();
+        assert_eq!(inputs, ["rust-overlay", "flake-utils", "systems"]);
+
+        let chains = why(&flake_lock, "nixpkgs_2").unwrap();
+        assert_eq!(chains[0][1].input, "nixpkgs");
+
+        assert!(why(&flake_lock, "nixpkgs_3").unwrap().is_empty());
+        assert!(why(&flake_lock, "nonexistent").is_err());
+
+        let chains = why(&flake_lock, "systems").unwrap();
+        assert!(explain(&flake_lock, "systems", &chains)
+            .contains("to reuse one of your own inputs instead, add `inputs.rust-overlay.inputs.flake-utils.inputs.systems.follows"));
+    }
+
+    #[test]
+    fn test_why_non_flake() {
+        let source = std::fs::read_to_string("tests/flake.dirty.0.lock").unwrap();
+        let mut lock: serde_json::Value = serde_json::from_str(&source).unwrap();
+        lock["nodes"]["systems"]["flake"] = false.into();
+        let flake_lock: FlakeLock = lock.to_string().parse().unwrap();
+        assert!(!flake_lock.nodes["systems"].is_flake());
+
+        let chains = why(&flake_lock, "systems").unwrap();
+        assert!(explain(&flake_lock, "systems", &chains)
+            .contains("to reuse one of your own `flake = false` inputs instead"));
+    }
+}